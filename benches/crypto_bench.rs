@@ -0,0 +1,4 @@
+use criterion::{criterion_group, criterion_main};
+
+criterion_group!(benches, ironoxide::bench::all);
+criterion_main!(benches);
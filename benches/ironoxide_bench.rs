@@ -1,6 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use ironoxide::prelude::*;
 use lazy_static::*;
+use std::convert::TryFrom;
 use tokio::runtime::Runtime;
 
 lazy_static! {
@@ -185,9 +186,24 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 }
 
+/// Benchmarks that don't make requests, so don't need a device
+fn local_benchmark(c: &mut Criterion) {
+    // IDs are validated for every user and group of a grant
+    let ids: Vec<String> = (0..1000)
+        .map(|i| format!("bulk-grant-user-{}", i))
+        .collect();
+    c.bench_function("user id validation [x 1000]", |b| {
+        b.iter(|| {
+            for id in &ids {
+                black_box(UserId::try_from(black_box(id.as_str())).expect("id is valid"));
+            }
+        })
+    });
+}
+
 criterion_group!(
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = criterion_benchmark
+    targets = local_benchmark, criterion_benchmark
 );
 criterion_main!(benches);
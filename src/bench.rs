@@ -0,0 +1,156 @@
+//! Benchmarks of the SDK's cryptographic hot paths
+//!
+//! Criterion benchmarks of symmetric encryption, password-based key derivation, device add signatures,
+//! and encrypting a document key to many users, none of which need a device or network access. Add them
+//! to an application's own benchmark harness to measure how upgrades of the SDK, `ring`, or `recrypt`
+//! change its performance.
+//!
+//! # Examples
+//! ```no_run
+//! use criterion::{criterion_group, criterion_main};
+//!
+//! criterion_group!(benches, ironoxide::bench::all);
+//! criterion_main!(benches);
+//! ```
+//!
+//! # Optional
+//! This requires the optional `bench` feature to be enabled.
+
+#[cfg(not(feature = "disable-password-escrow"))]
+use crate::{config::PasswordKdf, crypto::aes};
+use crate::{
+    crypto::{cipher::CipherSuite, transform},
+    document::UserOrGroup,
+    internal::{user_api, TransformKey, WithKey},
+    user::{KeyPair, UserId},
+};
+use chrono::Utc;
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput};
+use recrypt::api::{CryptoOps, Ed25519Ops, KeyGenOps, Recrypt};
+use std::sync::Mutex;
+
+/// Sizes of the data encrypted and decrypted by the symmetric encryption benchmarks
+const DATA_SIZES: [usize; 3] = [1024, 100 * 1024, 1024 * 1024];
+/// Numbers of users a document key is encrypted to by the grant fan-out benchmarks
+const GRANT_COUNTS: [usize; 3] = [1, 10, 100];
+
+/// Runs every benchmark in this module
+pub fn all(c: &mut Criterion) {
+    symmetric_encryption(c);
+    #[cfg(not(feature = "disable-password-escrow"))]
+    password_key_derivation(c);
+    device_add_signature(c);
+    grant_fan_out(c);
+}
+
+/// Encrypting and decrypting document data of several sizes with each cipher suite that is available
+pub fn symmetric_encryption(c: &mut Criterion) {
+    let rng = Mutex::new(rand::thread_rng());
+    let key = [7u8; 32];
+    let mut suites = vec![CipherSuite::Aes256Gcm];
+    if !crate::crypto::provider::FIPS_MODE {
+        suites.push(CipherSuite::XChaCha20Poly1305);
+        suites.push(CipherSuite::Aes256GcmSiv);
+    }
+    let mut group = c.benchmark_group("symmetric encryption");
+    for &suite in &suites {
+        for &size in &DATA_SIZES {
+            let data = vec![42u8; size];
+            let encrypted = suite
+                .encrypt(&rng, &data, &key, &[])
+                .expect("encryption failed");
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?} encrypt", suite), size),
+                &data,
+                |b, data| b.iter(|| suite.encrypt(&rng, data, &key, &[])),
+            );
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?} decrypt", suite), size),
+                &encrypted,
+                |b, encrypted| b.iter(|| suite.decrypt(encrypted, &key, &[])),
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Escrowing a user's private key with a password, which derives a key with PBKDF2 at the default
+/// iteration count
+#[cfg(not(feature = "disable-password-escrow"))]
+pub fn password_key_derivation(c: &mut Criterion) {
+    let rng = Mutex::new(rand::thread_rng());
+    let mut group = c.benchmark_group("password key derivation");
+    // each derivation takes long enough that the default sample size would take minutes
+    group.sample_size(10);
+    group.bench_function("PBKDF2-HMAC-SHA256", |b| {
+        b.iter(|| {
+            aes::encrypt_user_master_key(&rng, "password", &[1u8; 32], PasswordKdf::default())
+        })
+    });
+    group.finish();
+}
+
+/// Signing the request that adds a device to a user
+pub fn device_add_signature(c: &mut Criterion) {
+    let recrypt = Recrypt::new();
+    let (user_priv, user_pub) = recrypt.generate_key_pair().expect("key generation failed");
+    let (_, device_pub) = recrypt.generate_key_pair().expect("key generation failed");
+    let transform_key: TransformKey = recrypt
+        .generate_transform_key(
+            &user_priv,
+            &device_pub,
+            &recrypt.generate_ed25519_key_pair(),
+        )
+        .expect("transform key generation failed")
+        .into();
+    let user_keypair = KeyPair::new(user_pub, user_priv);
+    let signing_ts = Utc::now();
+    c.bench_function("device add signature", |b| {
+        b.iter(|| {
+            user_api::gen_device_add_signature(
+                &recrypt,
+                None,
+                &user_keypair,
+                &transform_key,
+                &signing_ts,
+            )
+        })
+    });
+}
+
+/// Encrypting a document's key to each of the users it is granted to
+pub fn grant_fan_out(c: &mut Criterion) {
+    let recrypt = Recrypt::new();
+    let signing_keys = recrypt.generate_ed25519_key_pair();
+    let dek = recrypt.gen_plaintext();
+    let mut group = c.benchmark_group("grant fan-out");
+    for &count in &GRANT_COUNTS {
+        let grantees: Vec<WithKey<UserOrGroup>> = (0..count)
+            .map(|i| {
+                let (_, public_key) = recrypt.generate_key_pair().expect("key generation failed");
+                WithKey::new(
+                    UserOrGroup::User {
+                        id: UserId::unsafe_from_string(format!("user-{}", i)),
+                    },
+                    public_key.into(),
+                )
+            })
+            .collect();
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &grantees,
+            |b, grantees| {
+                b.iter_batched(
+                    || grantees.clone(),
+                    |grantees| {
+                        transform::encrypt_to_with_key(&recrypt, &dek, &signing_keys, grantees)
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
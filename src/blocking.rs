@@ -4,6 +4,10 @@
 //! of returning futures that need to be executed on a runtime. In every other way,
 //! they are identical to their asynchronous counterparts.
 //!
+//! Each `BlockingIronOxide` owns a Tokio runtime that it runs its operations on, so applications don't
+//! need to set one up. Don't call these functions from within an asynchronous runtime; use `IronOxide`
+//! there instead.
+//!
 //! # Optional
 //! This requires the optional `blocking` feature to be enabled.
 
@@ -39,6 +43,40 @@ impl BlockingIronOxide {
         self.ironoxide.clear_policy_cache()
     }
 
+    /// See [ironoxide::IronOxide::clear_group_key_cache](../struct.IronOxide.html#method.clear_group_key_cache)
+    pub fn clear_group_key_cache(&self) -> usize {
+        self.ironoxide.clear_group_key_cache()
+    }
+
+    /// See [ironoxide::IronOxide::clear_user_key_cache](../struct.IronOxide.html#method.clear_user_key_cache)
+    pub fn clear_user_key_cache(&self) -> usize {
+        self.ironoxide.clear_user_key_cache()
+    }
+
+    /// See [ironoxide::IronOxide::invalidate_user_key](../struct.IronOxide.html#method.invalidate_user_key)
+    pub fn invalidate_user_key(&self, user: &UserId) -> bool {
+        self.ironoxide.invalidate_user_key(user)
+    }
+
+    /// See [ironoxide::IronOxide::fork_for_core](../struct.IronOxide.html#method.fork_for_core). The new
+    /// instance has its own runtime.
+    pub fn fork_for_core(&self) -> BlockingIronOxide {
+        BlockingIronOxide {
+            ironoxide: self.ironoxide.fork_for_core(),
+            runtime: create_runtime(),
+        }
+    }
+
+    /// See [ironoxide::IronOxide::export_policy_cache](../struct.IronOxide.html#method.export_policy_cache)
+    pub fn export_policy_cache(&self) -> Result<Vec<u8>> {
+        self.ironoxide.export_policy_cache()
+    }
+
+    /// See [ironoxide::IronOxide::import_policy_cache](../struct.IronOxide.html#method.import_policy_cache)
+    pub fn import_policy_cache(&self, exported: &[u8], ttl: std::time::Duration) -> Result<usize> {
+        self.ironoxide.import_policy_cache(exported, ttl)
+    }
+
     /// See [ironoxide::IronOxide::create_blind_index](../struct.IronOxide.html#method.create_blind_index)
     #[cfg(feature = "beta")]
     pub fn create_blind_index(&self, group_id: &GroupId) -> Result<EncryptedBlindIndexSalt> {
@@ -47,6 +85,7 @@ impl BlockingIronOxide {
     }
 
     /// See [ironoxide::IronOxide::rotate_all](../struct.IronOxide.html#method.rotate_all)
+    #[cfg(not(feature = "disable-password-escrow"))]
     pub fn rotate_all(
         &self,
         rotations: &PrivateKeyRotationCheckResult,
@@ -60,6 +99,24 @@ impl BlockingIronOxide {
             .block_on(self.ironoxide.rotate_all(rotations, password, timeout))
     }
 
+    /// See [ironoxide::IronOxide::resume_rotation](../struct.IronOxide.html#method.resume_rotation)
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn resume_rotation(&self) -> Result<Option<UserUpdatePrivateKeyResult>> {
+        self.runtime.block_on(self.ironoxide.resume_rotation())
+    }
+
+    /// See [ironoxide::IronOxide::export_pending_rotation](../struct.IronOxide.html#method.export_pending_rotation)
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn export_pending_rotation(&self) -> Option<Vec<u8>> {
+        self.ironoxide.export_pending_rotation()
+    }
+
+    /// See [ironoxide::IronOxide::import_pending_rotation](../struct.IronOxide.html#method.import_pending_rotation)
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn import_pending_rotation(&self, exported: &[u8]) -> Result<()> {
+        self.ironoxide.import_pending_rotation(exported)
+    }
+
     /// See [ironoxide::document::DocumentOps::document_list](trait.DocumentOps.html#tymethod.document_list)
     pub fn document_list(&self) -> Result<DocumentListResult> {
         self.runtime.block_on(self.ironoxide.document_list())
@@ -69,6 +126,11 @@ impl BlockingIronOxide {
         self.runtime
             .block_on(self.ironoxide.document_get_metadata(id))
     }
+    /// See [ironoxide::document::DocumentOps::document_access_via](trait.DocumentOps.html#tymethod.document_access_via)
+    pub fn document_access_via(&self, id: &DocumentId) -> Result<DocumentAccessViaResult> {
+        self.runtime
+            .block_on(self.ironoxide.document_access_via(id))
+    }
     /// See [ironoxide::document::DocumentOps::document_get_id_from_bytes](trait.DocumentOps.html#tymethod.document_get_id_from_bytes)
     pub fn document_get_id_from_bytes(&self, encrypted_document: &[u8]) -> Result<DocumentId> {
         self.ironoxide
@@ -83,6 +145,17 @@ impl BlockingIronOxide {
         self.runtime
             .block_on(self.ironoxide.document_encrypt(document_data, encrypt_opts))
     }
+    /// See [ironoxide::document::DocumentOps::document_encrypt_unique](trait.DocumentOps.html#tymethod.document_encrypt_unique)
+    pub fn document_encrypt_unique(
+        &self,
+        document_data: &[u8],
+        encrypt_opts: &DocumentEncryptOpts,
+    ) -> Result<DocumentEncryptUniqueResult> {
+        self.runtime.block_on(
+            self.ironoxide
+                .document_encrypt_unique(document_data, encrypt_opts),
+        )
+    }
     /// See [ironoxide::document::DocumentOps::document_update_bytes](trait.DocumentOps.html#tymethod.document_update_bytes)
     pub fn document_update_bytes(
         &self,
@@ -97,6 +170,11 @@ impl BlockingIronOxide {
         self.runtime
             .block_on(self.ironoxide.document_decrypt(encrypted_document))
     }
+    /// See [ironoxide::document::DocumentOps::document_verify](trait.DocumentOps.html#tymethod.document_verify)
+    pub fn document_verify(&self, encrypted_document: &[u8]) -> Result<bool> {
+        self.runtime
+            .block_on(self.ironoxide.document_verify(encrypted_document))
+    }
     /// See [ironoxide::document::DocumentOps::document_update_name](trait.DocumentOps.html#tymethod.document_update_name)
     pub fn document_update_name(
         &self,
@@ -146,6 +224,27 @@ impl BlockingIronOxide {
                 .document_decrypt_unmanaged(encrypted_data, encrypted_deks),
         )
     }
+    /// See [ironoxide::document::audit::DocumentAuditOps::document_verify_access_log](trait.DocumentAuditOps.html#tymethod.document_verify_access_log)
+    pub fn document_verify_access_log(
+        &self,
+        entries: &[AccessLogEntry],
+    ) -> Result<Vec<AccessDivergence>> {
+        self.runtime
+            .block_on(self.ironoxide.document_verify_access_log(entries))
+    }
+    /// See [ironoxide::document::audit::DocumentAuditOps::document_access_snapshot](trait.DocumentAuditOps.html#tymethod.document_access_snapshot)
+    pub fn document_access_snapshot(&self, id: &DocumentId) -> Result<DocumentAccessSnapshot> {
+        self.runtime
+            .block_on(self.ironoxide.document_access_snapshot(id))
+    }
+    /// See [ironoxide::document::audit::DocumentAuditOps::document_access_restore](trait.DocumentAuditOps.html#tymethod.document_access_restore)
+    pub fn document_access_restore(
+        &self,
+        snapshot: &DocumentAccessSnapshot,
+    ) -> Result<DocumentAccessRestoreResult> {
+        self.runtime
+            .block_on(self.ironoxide.document_access_restore(snapshot))
+    }
     /// See [ironoxide::group::GroupOps::group_list](trait.GroupOps.html#tymethod.group_list)
     pub fn group_list(&self) -> Result<GroupListResult> {
         self.runtime.block_on(self.ironoxide.group_list())
@@ -162,6 +261,20 @@ impl BlockingIronOxide {
     pub fn group_delete(&self, id: &GroupId) -> Result<GroupId> {
         self.runtime.block_on(self.ironoxide.group_delete(id))
     }
+    /// See [ironoxide::group::GroupOps::group_export](trait.GroupOps.html#tymethod.group_export)
+    pub fn group_export(&self, id: &GroupId) -> Result<GroupSnapshot> {
+        self.runtime.block_on(self.ironoxide.group_export(id))
+    }
+    /// See [ironoxide::group::GroupOps::group_list_documents](trait.GroupOps.html#tymethod.group_list_documents)
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub fn group_list_documents(&self, id: &GroupId) -> Result<GroupDocumentListResult> {
+        self.runtime
+            .block_on(self.ironoxide.group_list_documents(id))
+    }
+    /// See [ironoxide::group::GroupOps::group_import](trait.GroupOps.html#tymethod.group_import)
+    pub fn group_import(&self, snapshot: &GroupSnapshot) -> Result<GroupImportResult> {
+        self.runtime.block_on(self.ironoxide.group_import(snapshot))
+    }
     /// See [ironoxide::group::GroupOps::group_update_name](trait.GroupOps.html#tymethod.group_update_name)
     pub fn group_update_name(
         &self,
@@ -171,6 +284,36 @@ impl BlockingIronOxide {
         self.runtime
             .block_on(self.ironoxide.group_update_name(id, name))
     }
+    /// See [ironoxide::group::GroupOps::group_update_description](trait.GroupOps.html#tymethod.group_update_description)
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub fn group_update_description(
+        &self,
+        id: &GroupId,
+        description: Option<&GroupDescription>,
+    ) -> Result<GroupMetaResult> {
+        self.runtime
+            .block_on(self.ironoxide.group_update_description(id, description))
+    }
+    /// See [ironoxide::group::GroupOps::group_update_attributes](trait.GroupOps.html#tymethod.group_update_attributes)
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub fn group_update_attributes(
+        &self,
+        id: &GroupId,
+        attributes: &GroupAttributes,
+    ) -> Result<GroupMetaResult> {
+        self.runtime
+            .block_on(self.ironoxide.group_update_attributes(id, attributes))
+    }
+    /// See [ironoxide::group::GroupOps::group_transfer_ownership](trait.GroupOps.html#tymethod.group_transfer_ownership)
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub fn group_transfer_ownership(
+        &self,
+        id: &GroupId,
+        new_owner: &UserId,
+    ) -> Result<GroupMetaResult> {
+        self.runtime
+            .block_on(self.ironoxide.group_transfer_ownership(id, new_owner))
+    }
     /// See [ironoxide::group::GroupOps::group_add_members](trait.GroupOps.html#tymethod.group_add_members)
     pub fn group_add_members(
         &self,
@@ -180,6 +323,42 @@ impl BlockingIronOxide {
         self.runtime
             .block_on(self.ironoxide.group_add_members(id, grant_list))
     }
+    /// See [ironoxide::group::GroupOps::group_invite_members](trait.GroupOps.html#tymethod.group_invite_members)
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub fn group_invite_members(
+        &self,
+        id: &GroupId,
+        users: &[UserId],
+    ) -> Result<GroupAccessEditResult> {
+        self.runtime
+            .block_on(self.ironoxide.group_invite_members(id, users))
+    }
+    /// See [ironoxide::group::GroupOps::group_list_invites](trait.GroupOps.html#tymethod.group_list_invites)
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub fn group_list_invites(&self) -> Result<GroupInviteListResult> {
+        self.runtime.block_on(self.ironoxide.group_list_invites())
+    }
+    /// See [ironoxide::group::GroupOps::group_accept_invite](trait.GroupOps.html#tymethod.group_accept_invite)
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub fn group_accept_invite(&self, id: &GroupId) -> Result<GroupMetaResult> {
+        self.runtime
+            .block_on(self.ironoxide.group_accept_invite(id))
+    }
+    /// See [ironoxide::group::GroupOps::group_add_member_groups](trait.GroupOps.html#tymethod.group_add_member_groups)
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub fn group_add_member_groups(
+        &self,
+        id: &GroupId,
+        groups: &[GroupId],
+    ) -> Result<GroupMemberGroupsEditResult> {
+        self.runtime
+            .block_on(self.ironoxide.group_add_member_groups(id, groups))
+    }
+    /// See [ironoxide::group::GroupOps::group_effective_members](trait.GroupOps.html#tymethod.group_effective_members)
+    pub fn group_effective_members(&self, id: &GroupId) -> Result<GroupEffectiveMembersResult> {
+        self.runtime
+            .block_on(self.ironoxide.group_effective_members(id))
+    }
     /// See [ironoxide::group::GroupOps::group_remove_members](trait.GroupOps.html#tymethod.group_remove_members)
     pub fn group_remove_members(
         &self,
@@ -190,11 +369,7 @@ impl BlockingIronOxide {
             .block_on(self.ironoxide.group_remove_members(id, revoke_list))
     }
     /// See [ironoxide::group::GroupOps::group_add_admins](trait.GroupOps.html#tymethod.group_add_admins)
-    pub fn group_add_admins(
-        &self,
-        id: &GroupId,
-        users: &[UserId],
-    ) -> Result<GroupAccessEditResult> {
+    pub fn group_add_admins(&self, id: &GroupId, users: &[UserId]) -> Result<GroupAdminEditResult> {
         self.runtime
             .block_on(self.ironoxide.group_add_admins(id, users))
     }
@@ -203,16 +378,43 @@ impl BlockingIronOxide {
         &self,
         id: &GroupId,
         revoke_list: &[UserId],
-    ) -> Result<GroupAccessEditResult> {
+    ) -> Result<GroupAdminEditResult> {
         self.runtime
             .block_on(self.ironoxide.group_remove_admins(id, revoke_list))
     }
+    /// See [ironoxide::group::GroupOps::group_advisory_approve_admin_removal](trait.GroupOps.html#tymethod.group_advisory_approve_admin_removal)
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn group_advisory_approve_admin_removal(
+        &self,
+        password: &str,
+        id: &GroupId,
+        users: &[UserId],
+    ) -> Result<GroupAdminRemovalApproval> {
+        self.runtime.block_on(
+            self.ironoxide
+                .group_advisory_approve_admin_removal(password, id, users),
+        )
+    }
+    /// See [ironoxide::group::GroupOps::group_advisory_remove_admins](trait.GroupOps.html#tymethod.group_advisory_remove_admins)
+    pub fn group_advisory_remove_admins(
+        &self,
+        id: &GroupId,
+        revoke_list: &[UserId],
+        approval: &GroupAdminRemovalApproval,
+    ) -> Result<GroupAdminEditResult> {
+        self.runtime
+            .block_on(
+                self.ironoxide
+                    .group_advisory_remove_admins(id, revoke_list, approval),
+            )
+    }
     /// See [ironoxide::group::GroupOps::group_rotate_private_key](trait.GroupOps.html#tymethod.group_rotate_private_key)
     pub fn group_rotate_private_key(&self, id: &GroupId) -> Result<GroupUpdatePrivateKeyResult> {
         self.runtime
             .block_on(self.ironoxide.group_rotate_private_key(id))
     }
     /// See [ironoxide::user::UserOps::user_create](trait.UserOps.html#tymethod.user_create)
+    #[cfg(not(feature = "disable-password-escrow"))]
     pub fn user_create(
         jwt: &Jwt,
         password: &str,
@@ -231,7 +433,34 @@ impl BlockingIronOxide {
     pub fn user_list_devices(&self) -> Result<UserDeviceListResult> {
         self.runtime.block_on(self.ironoxide.user_list_devices())
     }
+    /// See [ironoxide::user::UserOps::user_list_devices_with_opts](trait.UserOps.html#tymethod.user_list_devices_with_opts)
+    pub fn user_list_devices_with_opts(
+        &self,
+        opts: &DeviceListOpts,
+    ) -> Result<UserDeviceListResult> {
+        self.runtime
+            .block_on(self.ironoxide.user_list_devices_with_opts(opts))
+    }
+    /// See [ironoxide::user::UserOps::user_create_with_device](trait.UserOps.html#tymethod.user_create_with_device)
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn user_create_with_device(
+        jwt: &Jwt,
+        password: &str,
+        user_create_opts: &UserCreateOpts,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(UserCreateResult, DeviceContext)> {
+        let rt = create_runtime();
+        rt.block_on(IronOxide::user_create_with_device(
+            jwt,
+            password,
+            user_create_opts,
+            device_create_options,
+            timeout,
+        ))
+    }
     /// See [ironoxide::user::UserOps::generate_new_device](trait.UserOps.html#tymethod.generate_new_device)
+    #[cfg(not(feature = "disable-password-escrow"))]
     pub fn generate_new_device(
         jwt: &Jwt,
         password: &str,
@@ -246,6 +475,55 @@ impl BlockingIronOxide {
             timeout,
         ))
     }
+    /// See [ironoxide::user::UserOps::user_create_with_master_key](trait.UserOps.html#tymethod.user_create_with_master_key)
+    pub fn user_create_with_master_key(
+        jwt: &Jwt,
+        key_pair: Option<&KeyPair>,
+        wrapper: &dyn MasterKeyWrapper,
+        user_create_opts: &UserCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserCreateResult> {
+        let rt = create_runtime();
+        rt.block_on(IronOxide::user_create_with_master_key(
+            jwt,
+            key_pair,
+            wrapper,
+            user_create_opts,
+            timeout,
+        ))
+    }
+    /// See [ironoxide::user::UserOps::generate_new_device_with_master_key](trait.UserOps.html#tymethod.generate_new_device_with_master_key)
+    pub fn generate_new_device_with_master_key(
+        jwt: &Jwt,
+        wrapper: &dyn MasterKeyWrapper,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult> {
+        let rt = create_runtime();
+        rt.block_on(IronOxide::generate_new_device_with_master_key(
+            jwt,
+            wrapper,
+            device_create_options,
+            timeout,
+        ))
+    }
+    /// See [ironoxide::user::UserOps::generate_new_device_with_recovery](trait.UserOps.html#tymethod.generate_new_device_with_recovery)
+    pub fn generate_new_device_with_recovery(
+        jwt: &Jwt,
+        escrow: &RecoveryEscrow,
+        recovery_private_key: &PrivateKey,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult> {
+        let rt = create_runtime();
+        rt.block_on(IronOxide::generate_new_device_with_recovery(
+            jwt,
+            escrow,
+            recovery_private_key,
+            device_create_options,
+            timeout,
+        ))
+    }
     /// See [ironoxide::user::UserOps::user_delete_device](trait.UserOps.html#tymethod.user_delete_device)
     pub fn user_delete_device(&self, device_id: Option<&DeviceId>) -> Result<DeviceId> {
         self.runtime
@@ -259,16 +537,66 @@ impl BlockingIronOxide {
         let rt = create_runtime();
         rt.block_on(IronOxide::user_verify(jwt, timeout))
     }
+    /// See [ironoxide::user::UserOps::user_deactivate](trait.UserOps.html#tymethod.user_deactivate)
+    pub fn user_deactivate(
+        jwt: &Jwt,
+        user_id: &UserId,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserDeactivateResult> {
+        let rt = create_runtime();
+        rt.block_on(IronOxide::user_deactivate(jwt, user_id, timeout))
+    }
+    /// See [ironoxide::user::UserOps::user_list_needing_rotation](trait.UserOps.html#tymethod.user_list_needing_rotation)
+    pub fn user_list_needing_rotation(
+        jwt: &Jwt,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserListResult> {
+        let rt = create_runtime();
+        rt.block_on(IronOxide::user_list_needing_rotation(jwt, timeout))
+    }
     /// See [ironoxide::user::UserOps::user_get_public_key](trait.UserOps.html#tymethod.user_get_public_key)
     pub fn user_get_public_key(&self, users: &[UserId]) -> Result<HashMap<UserId, PublicKey>> {
         self.runtime
             .block_on(self.ironoxide.user_get_public_key(users))
     }
     /// See [ironoxide::user::UserOps::user_rotate_private_key](trait.UserOps.html#tymethod.user_rotate_private_key)
+    #[cfg(not(feature = "disable-password-escrow"))]
     pub fn user_rotate_private_key(&self, password: &str) -> Result<UserUpdatePrivateKeyResult> {
         self.runtime
             .block_on(self.ironoxide.user_rotate_private_key(password))
     }
+    /// See [ironoxide::user::UserOps::user_verify_password](trait.UserOps.html#tymethod.user_verify_password)
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn user_verify_password(&self, password: &str) -> Result<bool> {
+        self.runtime
+            .block_on(self.ironoxide.user_verify_password(password))
+    }
+    /// See [ironoxide::user::UserOps::user_rotate_private_key_with_recovery](trait.UserOps.html#tymethod.user_rotate_private_key_with_recovery)
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn user_rotate_private_key_with_recovery(
+        &self,
+        password: &str,
+        recovery_public_key: &PublicKey,
+    ) -> Result<UserUpdatePrivateKeyResult> {
+        self.runtime.block_on(
+            self.ironoxide
+                .user_rotate_private_key_with_recovery(password, recovery_public_key),
+        )
+    }
+    /// See [ironoxide::user::UserOps::device_rotate_keys](trait.UserOps.html#tymethod.device_rotate_keys)
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn device_rotate_keys(&self, password: &str) -> Result<DeviceAddResult> {
+        self.runtime
+            .block_on(self.ironoxide.device_rotate_keys(password))
+    }
+    /// See [ironoxide::offline::OfflineOps::flush](trait.OfflineOps.html#tymethod.flush)
+    pub fn flush(&self) -> Result<FlushResult> {
+        self.runtime.block_on(self.ironoxide.flush())
+    }
+    /// See [ironoxide::offline::OfflineOps::offline_queue](trait.OfflineOps.html#tymethod.offline_queue)
+    pub fn offline_queue(&self) -> Vec<QueuedMutation> {
+        self.runtime.block_on(self.ironoxide.offline_queue())
+    }
 }
 
 /// Creates a tokio runtime with the default number of core threads (num of cores on a machine)
@@ -293,6 +621,22 @@ pub fn initialize(
     })
 }
 
+/// Loads a device from `source` and initializes the BlockingIronOxide SDK with it.
+///
+/// See [ironoxide::secret_source::initialize_from_source](../secret_source/fn.initialize_from_source.html).
+#[cfg(feature = "secret-sources")]
+pub fn initialize_from_source<S: crate::secret_source::DeviceContextSource + ?Sized>(
+    source: &S,
+    config: &IronOxideConfig,
+) -> Result<BlockingIronOxide> {
+    let rt = create_runtime();
+    let maybe_io = rt.block_on(crate::secret_source::initialize_from_source(source, config));
+    maybe_io.map(|io| BlockingIronOxide {
+        ironoxide: io,
+        runtime: rt,
+    })
+}
+
 /// Initialize the BlockingIronOxide SDK and check to see if the user that owns this `DeviceContext` is
 /// marked for private key rotation, or if any of the groups that the user is an admin of are marked
 /// for private key rotation.
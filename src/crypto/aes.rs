@@ -1,86 +1,514 @@
-use std::{fmt, num::NonZeroU32};
+#[cfg(not(feature = "disable-password-escrow"))]
+use crate::config::PasswordKdf;
+#[cfg(not(feature = "disable-password-escrow"))]
+use crate::crypto::{
+    locked::Locked,
+    provider::{hmac, pbkdf2},
+};
+#[cfg(not(feature = "disable-password-escrow"))]
+use std::{
+    collections::HashMap,
+    fmt,
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
 
+use crate::crypto::provider::{aead, aead::BoundKey, error::Unspecified};
 use rand::{self, CryptoRng, RngCore};
-use ring::{aead, aead::BoundKey, digest, error::Unspecified, pbkdf2};
 
 use crate::internal::{take_lock, IronOxideErr};
 use std::{convert::TryFrom, ops::DerefMut, sync::Mutex};
+use zeroize::{Zeroize, Zeroizing};
 
-//There is no way this can fail. Value is most definitely not less than one.
-const PBKDF2_ITERATIONS: NonZeroU32 = unsafe { NonZeroU32::new_unchecked(250_000) };
-const PBKDF2_SALT_LEN: usize = 32;
+/// Fewest PBKDF2 iterations a user's master key can be escrowed or unlocked with. Also the iteration count
+/// of every key escrowed before the count was recorded in the envelope.
+#[cfg(not(feature = "disable-password-escrow"))]
+pub const PBKDF2_MIN_ITERATIONS: u32 = 250_000;
+/// Bounds on the Argon2id parameters a user's master key can be escrowed or unlocked with. The lower bounds
+/// follow the OWASP minimums; the upper bound on memory keeps a malformed key from exhausting memory.
+#[cfg(not(feature = "disable-password-escrow"))]
+pub const ARGON2ID_MIN_MEMORY_KIB: u32 = 19_456;
+#[cfg(not(feature = "disable-password-escrow"))]
+pub const ARGON2ID_MAX_MEMORY_KIB: u32 = 1_048_576;
+#[cfg(not(feature = "disable-password-escrow"))]
+pub const ARGON2ID_MIN_ITERATIONS: u32 = 2;
+#[cfg(not(feature = "disable-password-escrow"))]
+pub const ARGON2ID_MAX_PARALLELISM: u32 = 16;
+#[cfg(not(feature = "disable-password-escrow"))]
+const KDF_SALT_LEN: usize = 32;
+/// Leading byte of an envelope that records its PBKDF2-HMAC-SHA256 iteration count
+#[cfg(not(feature = "disable-password-escrow"))]
+const PBKDF2_SHA256_ENVELOPE: u8 = 1;
+/// Leading byte of an envelope that records its Argon2id memory, iteration, and parallelism parameters
+#[cfg(not(feature = "disable-password-escrow"))]
+const ARGON2ID_ENVELOPE: u8 = 2;
+/// Leading byte of an envelope holding the same master key encrypted under more than one password
+#[cfg(not(feature = "disable-password-escrow"))]
+const MULTI_SLOT_ENVELOPE: u8 = 3;
+#[cfg(not(feature = "disable-password-escrow"))]
+const KDF_PARAM_LEN: usize = 4;
 const AES_GCM_TAG_LEN: usize = 16;
 const AES_IV_LEN: usize = 12;
 const AES_KEY_LEN: usize = 32;
 //The encrypted user master key length will be the size of the encrypted key (32 bytes) plus the size of the GCM auth tag (16 bytes).
+#[cfg(not(feature = "disable-password-escrow"))]
 const ENCRYPTED_KEY_AND_GCM_TAG_LEN: usize = AES_KEY_LEN + AES_GCM_TAG_LEN;
 
+/// Keys derived while unlocking master keys, by key derivation function and salt, kept for `ttl` so
+/// unlocking the same master key with the same password again doesn't derive it again. Held by an SDK
+/// instance when `IronOxideConfig.derived_key_cache_ttl` is set.
+#[cfg(not(feature = "disable-password-escrow"))]
+pub(crate) struct DerivedKeyCache {
+    ttl: Duration,
+    /// Random key for tagging the passwords of cached keys
+    password_tag_key: hmac::Key,
+    entries: Mutex<HashMap<(Kdf, [u8; KDF_SALT_LEN]), CachedDerivedKey>>,
+}
+
+#[cfg(not(feature = "disable-password-escrow"))]
+struct CachedDerivedKey {
+    /// HMAC of the password the key was derived from, so a cached key is only used for the same password
+    password_tag: Vec<u8>,
+    derived_key: Locked<[u8; AES_KEY_LEN]>,
+    expires_at: Instant,
+}
+
+#[cfg(not(feature = "disable-password-escrow"))]
+impl DerivedKeyCache {
+    pub(crate) fn new(ttl: Duration) -> DerivedKeyCache {
+        let mut key = Zeroizing::new([0u8; 32]);
+        rand::thread_rng().fill_bytes(&mut key[..]);
+        DerivedKeyCache {
+            ttl,
+            password_tag_key: hmac::Key::new(hmac::HMAC_SHA256, &key[..]),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached key derived from `password` with `kdf` and `salt`, if there is one that hasn't expired
+    fn get(
+        &self,
+        kdf: Kdf,
+        salt: &[u8; KDF_SALT_LEN],
+        password: &str,
+    ) -> Option<Locked<[u8; AES_KEY_LEN]>> {
+        let mut entries = take_lock(&self.entries);
+        let now = Instant::now();
+        entries.retain(|_, entry| entry.expires_at > now);
+        entries
+            .get(&(kdf, *salt))
+            .filter(|entry| {
+                hmac::verify(
+                    &self.password_tag_key,
+                    password.as_bytes(),
+                    &entry.password_tag,
+                )
+                .is_ok()
+            })
+            .map(|entry| entry.derived_key.clone())
+    }
+
+    /// Cache a key derived from `password`
+    fn insert(
+        &self,
+        kdf: Kdf,
+        salt: &[u8; KDF_SALT_LEN],
+        password: &str,
+        derived_key: &Locked<[u8; AES_KEY_LEN]>,
+    ) {
+        let entry = CachedDerivedKey {
+            password_tag: hmac::sign(&self.password_tag_key, password.as_bytes())
+                .as_ref()
+                .to_vec(),
+            derived_key: derived_key.clone(),
+            expires_at: Instant::now() + self.ttl,
+        };
+        take_lock(&self.entries).insert((kdf, *salt), entry);
+    }
+}
+
+/// Key derivation function and parameters a user's master key was escrowed with
+#[cfg(not(feature = "disable-password-escrow"))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Kdf {
+    Pbkdf2Sha256(NonZeroU32),
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+#[cfg(not(feature = "disable-password-escrow"))]
+impl Kdf {
+    /// The parameters to escrow a new key with
+    fn for_new_key(kdf: PasswordKdf) -> Result<Kdf, IronOxideErr> {
+        match kdf {
+            PasswordKdf::Pbkdf2Sha256 { iterations } => Kdf::pbkdf2_sha256(iterations),
+            PasswordKdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => Kdf::argon2id(memory_kib, iterations, parallelism),
+        }
+    }
+
+    fn pbkdf2_sha256(iterations: u32) -> Result<Kdf, IronOxideErr> {
+        NonZeroU32::new(iterations)
+            .filter(|iterations| iterations.get() >= PBKDF2_MIN_ITERATIONS)
+            .map(Kdf::Pbkdf2Sha256)
+            .ok_or_else(|| {
+                IronOxideErr::ValidationError(
+                    "pbkdf2_iterations".to_string(),
+                    format!(
+                        "{} PBKDF2 iterations is fewer than the minimum of {}.",
+                        iterations, PBKDF2_MIN_ITERATIONS
+                    ),
+                )
+            })
+    }
+
+    fn argon2id(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Kdf, IronOxideErr> {
+        if memory_kib < ARGON2ID_MIN_MEMORY_KIB || memory_kib > ARGON2ID_MAX_MEMORY_KIB {
+            Err(IronOxideErr::ValidationError(
+                "argon2id_memory_kib".to_string(),
+                format!(
+                    "Argon2id memory must be between {} and {} KiB.",
+                    ARGON2ID_MIN_MEMORY_KIB, ARGON2ID_MAX_MEMORY_KIB
+                ),
+            ))
+        } else if iterations < ARGON2ID_MIN_ITERATIONS {
+            Err(IronOxideErr::ValidationError(
+                "argon2id_iterations".to_string(),
+                format!(
+                    "Argon2id iterations must be at least {}.",
+                    ARGON2ID_MIN_ITERATIONS
+                ),
+            ))
+        } else if parallelism == 0 || parallelism > ARGON2ID_MAX_PARALLELISM {
+            Err(IronOxideErr::ValidationError(
+                "argon2id_parallelism".to_string(),
+                format!(
+                    "Argon2id parallelism must be between 1 and {}.",
+                    ARGON2ID_MAX_PARALLELISM
+                ),
+            ))
+        } else {
+            Ok(Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            })
+        }
+    }
+
+    /// Derive a 32 byte key from a string password with the provided salt. The key is cleared when dropped,
+    /// and kept out of swap with the `mlock` feature.
+    fn derive_key(
+        &self,
+        password: &str,
+        salt: &[u8; KDF_SALT_LEN],
+    ) -> Result<Locked<[u8; AES_KEY_LEN]>, IronOxideErr> {
+        let mut derived_key = Locked::<[u8; AES_KEY_LEN]>::default();
+        match *self {
+            Kdf::Pbkdf2Sha256(iterations) => pbkdf2::derive(
+                pbkdf2::PBKDF2_HMAC_SHA256,
+                iterations,
+                salt,
+                password.as_bytes(),
+                &mut derived_key[..],
+            ),
+            #[cfg(feature = "argon2id")]
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                crate::crypto::provider::require_non_fips("Argon2id")?;
+                let config = argon2::Config {
+                    variant: argon2::Variant::Argon2id,
+                    version: argon2::Version::Version13,
+                    mem_cost: memory_kib,
+                    time_cost: iterations,
+                    lanes: parallelism,
+                    thread_mode: argon2::ThreadMode::Sequential,
+                    secret: &[],
+                    ad: &[],
+                    hash_length: AES_KEY_LEN as u32,
+                };
+                let hash = argon2::hash_raw(password.as_bytes(), salt, &config)
+                    .map(Zeroizing::new)
+                    .map_err(|e| {
+                        IronOxideErr::ValidationError("password_kdf".to_string(), e.to_string())
+                    })?;
+                derived_key.copy_from_slice(&hash);
+            }
+            #[cfg(not(feature = "argon2id"))]
+            Kdf::Argon2id { .. } => {
+                return Err(IronOxideErr::ValidationError(
+                    "password_kdf".to_string(),
+                    "Argon2id requires the `argon2id` feature.".to_string(),
+                ))
+            }
+        }
+        Ok(derived_key)
+    }
+
+    /// Parameters recorded in the envelope after its leading byte
+    fn params(&self) -> Vec<u32> {
+        match *self {
+            Kdf::Pbkdf2Sha256(iterations) => vec![iterations.get()],
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => vec![memory_kib, iterations, parallelism],
+        }
+    }
+}
+
+/// Read the big-endian u32 parameters recorded at the start of `bytes`.
+#[cfg(not(feature = "disable-password-escrow"))]
+fn read_kdf_params(bytes: &[u8], count: usize) -> Vec<u32> {
+    bytes[..(count * KDF_PARAM_LEN)]
+        .chunks(KDF_PARAM_LEN)
+        .map(|param| {
+            let mut be_bytes = [0u8; KDF_PARAM_LEN];
+            be_bytes.copy_from_slice(param);
+            u32::from_be_bytes(be_bytes)
+        })
+        .collect()
+}
+
+/// A user's master private key, encrypted with a key derived from their password.
+///
+/// Keys escrowed with PBKDF2 and `PBKDF2_MIN_ITERATIONS` are stored in the original layout of salt, IV
+/// and encrypted key, which every IronCore SDK can read. Other keys are stored with a leading byte naming
+/// the key derivation function (`PBKDF2_SHA256_ENVELOPE` or `ARGON2ID_ENVELOPE`) and its big-endian
+/// parameters before the salt.
+///
+/// A key encrypted under several passwords by `encrypt_user_master_key_slots` is stored as
+/// `MULTI_SLOT_ENVELOPE`, a slot count, each slot as a length byte followed by one of the layouts above,
+/// and the IV and encrypted master key. Each slot holds the key the master key is encrypted with, so any
+/// one of the passwords unlocks it.
+#[cfg(not(feature = "disable-password-escrow"))]
+#[derive(Clone)]
 pub struct EncryptedMasterKey {
-    pbkdf2_salt: [u8; PBKDF2_SALT_LEN],
+    kdf: Kdf,
+    kdf_salt: [u8; KDF_SALT_LEN],
     aes_iv: [u8; AES_IV_LEN],
     encrypted_key: [u8; ENCRYPTED_KEY_AND_GCM_TAG_LEN],
+    /// The same key encrypted under other passwords
+    other_slots: Vec<EncryptedMasterKey>,
+    /// For a key with more than one slot, the master key encrypted with the key the slots hold
+    wrapped_master_key: Option<AesEncryptedValue>,
 }
 
+#[cfg(not(feature = "disable-password-escrow"))]
 impl fmt::Debug for EncryptedMasterKey {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         formatter
             .debug_struct(stringify!(EncryptedMasterKey))
-            .field("pbkdf2_salt", &&self.pbkdf2_salt)
+            .field("kdf", &self.kdf)
+            .field("kdf_salt", &&self.kdf_salt)
             .field("aes_iv", &&self.aes_iv)
             .field("encrypted_key", &&self.encrypted_key[..])
+            .field("other_slots", &self.other_slots)
+            .field("wrapped_master_key", &self.wrapped_master_key)
             .finish()
     }
 }
 
+#[cfg(not(feature = "disable-password-escrow"))]
 impl EncryptedMasterKey {
-    pub const SIZE_BYTES: usize = PBKDF2_SALT_LEN + AES_IV_LEN + ENCRYPTED_KEY_AND_GCM_TAG_LEN;
+    /// Size of a key in the original layout, without a recorded key derivation function
+    pub const SIZE_BYTES: usize = KDF_SALT_LEN + AES_IV_LEN + ENCRYPTED_KEY_AND_GCM_TAG_LEN;
+    /// Size of a key that records its PBKDF2 iteration count
+    pub const PBKDF2_ENVELOPE_SIZE_BYTES: usize =
+        1 + KDF_PARAM_LEN + EncryptedMasterKey::SIZE_BYTES;
+    /// Size of a key that records its Argon2id parameters
+    pub const ARGON2ID_ENVELOPE_SIZE_BYTES: usize =
+        1 + 3 * KDF_PARAM_LEN + EncryptedMasterKey::SIZE_BYTES;
 
+    /// Construct an EncryptedMasterKey escrowed with PBKDF2-HMAC-SHA256 and `pbkdf2_iterations` iterations.
     pub fn new(
-        pbkdf2_salt: [u8; PBKDF2_SALT_LEN],
+        pbkdf2_iterations: NonZeroU32,
+        pbkdf2_salt: [u8; KDF_SALT_LEN],
         aes_iv: [u8; AES_IV_LEN],
         encrypted_key: [u8; ENCRYPTED_KEY_AND_GCM_TAG_LEN],
     ) -> EncryptedMasterKey {
         EncryptedMasterKey {
-            pbkdf2_salt,
+            kdf: Kdf::Pbkdf2Sha256(pbkdf2_iterations),
+            kdf_salt: pbkdf2_salt,
             aes_iv,
             encrypted_key,
+            other_slots: vec![],
+            wrapped_master_key: None,
         }
     }
 
-    /// Construct an EncryptedMasterKey from bytes.
+    /// Construct an EncryptedMasterKey from bytes in any of its layouts.
     /// The reciprocal of `EncryptedMasterKey::bytes`
     pub fn new_from_slice(bytes: &[u8]) -> Result<EncryptedMasterKey, IronOxideErr> {
-        if bytes.len() == EncryptedMasterKey::SIZE_BYTES {
-            let mut pbkdf2_salt = [0u8; PBKDF2_SALT_LEN];
-            let mut aes_iv = [0u8; AES_IV_LEN];
-            let mut encrypted_key = [0u8; ENCRYPTED_KEY_AND_GCM_TAG_LEN];
-            pbkdf2_salt.copy_from_slice(&bytes[..PBKDF2_SALT_LEN]);
-            aes_iv.copy_from_slice(&bytes[PBKDF2_SALT_LEN..(PBKDF2_SALT_LEN + AES_IV_LEN)]);
-            encrypted_key.copy_from_slice(&bytes[(PBKDF2_SALT_LEN + AES_IV_LEN)..]);
-            Ok(EncryptedMasterKey::new(pbkdf2_salt, aes_iv, encrypted_key))
-        } else {
-            Err(IronOxideErr::WrongSizeError(
-                Some(bytes.len()),
-                Some(EncryptedMasterKey::SIZE_BYTES),
-            ))
+        match bytes.split_first() {
+            // a multi-slot envelope holds at least two slots, so it is longer than any single slot
+            Some((&MULTI_SLOT_ENVELOPE, slot_bytes))
+                if bytes.len() > EncryptedMasterKey::ARGON2ID_ENVELOPE_SIZE_BYTES =>
+            {
+                EncryptedMasterKey::slots_from_slice(slot_bytes)
+                    .ok_or_else(|| IronOxideErr::WrongSizeError(Some(bytes.len()), None))?
+            }
+            _ => EncryptedMasterKey::slot_from_slice(bytes),
+        }
+    }
+
+    /// Parse the slot count, slots and encrypted master key of a multi-slot envelope. `None` if the lengths
+    /// don't add up.
+    fn slots_from_slice(bytes: &[u8]) -> Option<Result<EncryptedMasterKey, IronOxideErr>> {
+        let (&count, mut rest) = bytes.split_first()?;
+        let mut slots = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (&len, after_len) = rest.split_first()?;
+            if after_len.len() < len as usize {
+                return None;
+            }
+            let (slot, after_slot) = after_len.split_at(len as usize);
+            match EncryptedMasterKey::slot_from_slice(slot) {
+                Ok(slot) => slots.push(slot),
+                Err(e) => return Some(Err(e)),
+            }
+            rest = after_slot;
+        }
+        if rest.len() != AES_IV_LEN + ENCRYPTED_KEY_AND_GCM_TAG_LEN || slots.len() < 2 {
+            return None;
+        }
+        let mut first = slots.remove(0);
+        first.other_slots = slots;
+        first.wrapped_master_key = Some(AesEncryptedValue::try_from(rest).ok()?);
+        Some(Ok(first))
+    }
+
+    /// Parse a single slot, in any layout but the multi-slot envelope
+    fn slot_from_slice(bytes: &[u8]) -> Result<EncryptedMasterKey, IronOxideErr> {
+        // refuse keys whose parameters are below the floors, so they can't be unlocked with less work
+        let (kdf, key_bytes) = match bytes.len() {
+            EncryptedMasterKey::SIZE_BYTES => (Kdf::pbkdf2_sha256(PBKDF2_MIN_ITERATIONS)?, bytes),
+            EncryptedMasterKey::PBKDF2_ENVELOPE_SIZE_BYTES
+                if bytes[0] == PBKDF2_SHA256_ENVELOPE =>
+            {
+                let params = read_kdf_params(&bytes[1..], 1);
+                (
+                    Kdf::pbkdf2_sha256(params[0])?,
+                    &bytes[(1 + KDF_PARAM_LEN)..],
+                )
+            }
+            EncryptedMasterKey::ARGON2ID_ENVELOPE_SIZE_BYTES if bytes[0] == ARGON2ID_ENVELOPE => {
+                let params = read_kdf_params(&bytes[1..], 3);
+                (
+                    Kdf::argon2id(params[0], params[1], params[2])?,
+                    &bytes[(1 + 3 * KDF_PARAM_LEN)..],
+                )
+            }
+            _ => {
+                return Err(IronOxideErr::WrongSizeError(
+                    Some(bytes.len()),
+                    Some(EncryptedMasterKey::SIZE_BYTES),
+                ))
+            }
+        };
+        let mut kdf_salt = [0u8; KDF_SALT_LEN];
+        let mut aes_iv = [0u8; AES_IV_LEN];
+        let mut encrypted_key = [0u8; ENCRYPTED_KEY_AND_GCM_TAG_LEN];
+        kdf_salt.copy_from_slice(&key_bytes[..KDF_SALT_LEN]);
+        aes_iv.copy_from_slice(&key_bytes[KDF_SALT_LEN..(KDF_SALT_LEN + AES_IV_LEN)]);
+        encrypted_key.copy_from_slice(&key_bytes[(KDF_SALT_LEN + AES_IV_LEN)..]);
+        Ok(EncryptedMasterKey {
+            kdf,
+            kdf_salt,
+            aes_iv,
+            encrypted_key,
+            other_slots: vec![],
+            wrapped_master_key: None,
+        })
+    }
+
+    /// Number of passwords that can unlock the key
+    pub fn slot_count(&self) -> usize {
+        1 + self.other_slots.len()
+    }
+
+    /// Key derivation function the key was escrowed with. For a key with more than one slot, the function
+    /// of the first slot.
+    pub fn password_kdf(&self) -> PasswordKdf {
+        match self.kdf {
+            Kdf::Pbkdf2Sha256(iterations) => PasswordKdf::Pbkdf2Sha256 {
+                iterations: iterations.get(),
+            },
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => PasswordKdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            },
+        }
+    }
+
+    /// Number of PBKDF2 iterations the key derived from the password takes, if it was escrowed with PBKDF2.
+    /// For a key with more than one slot, the count of the first slot.
+    pub fn pbkdf2_iterations(&self) -> Option<u32> {
+        match self.kdf {
+            Kdf::Pbkdf2Sha256(iterations) => Some(iterations.get()),
+            Kdf::Argon2id { .. } => None,
         }
     }
 
     /// A bytes representation of EncryptedMasterKey
     /// The reciprocal of `EncryptedMasterKey::new_from_slice`
-    pub fn bytes(&self) -> [u8; EncryptedMasterKey::SIZE_BYTES] {
-        let mut dest = [0u8; EncryptedMasterKey::SIZE_BYTES];
-        let vec = [
-            &self.pbkdf2_salt[..],
+    pub fn bytes(&self) -> Vec<u8> {
+        let wrapped_master_key = match &self.wrapped_master_key {
+            Some(wrapped_master_key) => wrapped_master_key,
+            None => return self.slot_bytes(),
+        };
+        let mut bytes = vec![MULTI_SLOT_ENVELOPE, self.slot_count() as u8];
+        for slot in self.slots() {
+            let slot_bytes = slot.slot_bytes();
+            bytes.push(slot_bytes.len() as u8);
+            bytes.extend_from_slice(&slot_bytes);
+        }
+        bytes.extend_from_slice(&wrapped_master_key.bytes());
+        bytes
+    }
+
+    /// Every slot, starting with this one
+    fn slots(&self) -> impl Iterator<Item = &EncryptedMasterKey> {
+        std::iter::once(self).chain(self.other_slots.iter())
+    }
+
+    /// Bytes of this slot alone, in the layout for its key derivation function
+    fn slot_bytes(&self) -> Vec<u8> {
+        let key_bytes = [
+            &self.kdf_salt[..],
             &self.aes_iv[..],
             &self.encrypted_key[..],
         ]
         .concat();
-
-        debug_assert!(dest.len() == vec.len());
-
-        dest.copy_from_slice(&vec[..]);
-        dest
+        let envelope = match self.kdf {
+            Kdf::Pbkdf2Sha256(iterations) if iterations.get() == PBKDF2_MIN_ITERATIONS => {
+                return key_bytes
+            }
+            Kdf::Pbkdf2Sha256(_) => PBKDF2_SHA256_ENVELOPE,
+            Kdf::Argon2id { .. } => ARGON2ID_ENVELOPE,
+        };
+        let params: Vec<u8> = self
+            .kdf
+            .params()
+            .iter()
+            .flat_map(|param| param.to_be_bytes().to_vec())
+            .collect();
+        [&[envelope][..], &params[..], &key_bytes[..]].concat()
     }
 }
 #[derive(Clone, Debug)]
@@ -94,6 +522,14 @@ impl AesEncryptedValue {
     }
 }
 
+/// Clears the value, for when it has been decrypted in place and holds a secret
+impl Zeroize for AesEncryptedValue {
+    fn zeroize(&mut self) {
+        self.aes_iv.zeroize();
+        self.ciphertext.zeroize();
+    }
+}
+
 impl TryFrom<&[u8]> for AesEncryptedValue {
     type Error = IronOxideErr;
 
@@ -112,65 +548,193 @@ impl TryFrom<&[u8]> for AesEncryptedValue {
     }
 }
 
-impl From<ring::error::Unspecified> for IronOxideErr {
+impl From<Unspecified> for IronOxideErr {
     fn from(ring_err: Unspecified) -> Self {
         IronOxideErr::AesError(ring_err)
     }
 }
 
-/// Derive a key from a string password. Returns a tuple of salt that was used as part of the deriviation and the
-/// key, both of which are 32 bytes.
-fn derive_key_from_password(password: &str, salt: [u8; PBKDF2_SALT_LEN]) -> [u8; AES_KEY_LEN] {
-    let mut derived_key = [0u8; digest::SHA256_OUTPUT_LEN];
-    pbkdf2::derive(
-        pbkdf2::PBKDF2_HMAC_SHA256,
-        PBKDF2_ITERATIONS,
-        &salt,
-        password.as_bytes(),
-        &mut derived_key,
-    );
-    derived_key
-}
-
+#[cfg(not(feature = "disable-password-escrow"))]
 /// Encrypt a users master private key using the provided password. Uses the password to generate a derived AES key
-/// via PBKDF2 and then AES encrypts the users private key with the derived AES key.
+/// with the provided key derivation function and then AES encrypts the users private key with the derived AES key.
 pub fn encrypt_user_master_key<R: CryptoRng + RngCore>(
     rng: &Mutex<R>,
     password: &str,
     user_master_key: &[u8; 32],
-) -> Result<EncryptedMasterKey, Unspecified> {
-    let mut salt = [0u8; PBKDF2_SALT_LEN];
+    password_kdf: PasswordKdf,
+) -> Result<EncryptedMasterKey, IronOxideErr> {
+    let kdf = Kdf::for_new_key(password_kdf)?;
+    let mut kdf_salt = [0u8; KDF_SALT_LEN];
 
-    take_lock(rng).deref_mut().fill_bytes(&mut salt);
-    let derived_key = derive_key_from_password(password, salt);
+    take_lock(rng).deref_mut().fill_bytes(&mut kdf_salt);
+    let derived_key = kdf.derive_key(password, &kdf_salt)?;
 
-    let encrypted_key = encrypt(rng, &user_master_key.to_vec(), derived_key)?;
+    let encrypted_key = encrypt(rng, &user_master_key[..], &derived_key)?;
     //Convert the AES encrypted ciphertext vector into a fixed size array so that the
     //EncryptedMasterKey struct is all fixed size values
     let mut master_key_ciphertext = [0u8; ENCRYPTED_KEY_AND_GCM_TAG_LEN];
     master_key_ciphertext[..].copy_from_slice(&encrypted_key.ciphertext[..]);
     Ok(EncryptedMasterKey {
-        pbkdf2_salt: salt,
+        kdf,
+        kdf_salt,
         aes_iv: encrypted_key.aes_iv,
         encrypted_key: master_key_ciphertext,
+        other_slots: vec![],
+        wrapped_master_key: None,
     })
 }
 
-/// Decrypts a users encrypted master private key using the provided password. Uses the password and the provided pbkdf2 salt
-/// to generate a derived AES key. Takes that derived AES key and uses it to try and decrypt the provided encrypted user master
-/// key.
+#[cfg(not(feature = "disable-password-escrow"))]
+/// Encrypt a user's master private key under each of `passwords`, like `encrypt_user_master_key`, and combine
+/// them into one multi-slot key that any of the passwords can unlock. Losing one password, for example a
+/// user's login password, still leaves the key recoverable with another, such as a recovery passphrase.
+///
+/// Each slot encrypts a random key, which the master key is encrypted with. Rotating the user's private key
+/// with `reencrypt_user_master_key` only replaces the encrypted master key, so every password keeps working.
+///
+/// Fails with `IronOxideErr::ValidationError` unless there are between 1 and 255 passwords.
+pub fn encrypt_user_master_key_slots<R: CryptoRng + RngCore>(
+    rng: &Mutex<R>,
+    passwords: &[(&str, PasswordKdf)],
+    user_master_key: &[u8; 32],
+) -> Result<EncryptedMasterKey, IronOxideErr> {
+    if passwords.is_empty() || passwords.len() > std::u8::MAX as usize {
+        return Err(IronOxideErr::ValidationError(
+            "passwords".to_string(),
+            format!("must have between 1 and {} passwords", std::u8::MAX),
+        ));
+    }
+    if let [(password, kdf)] = passwords {
+        return encrypt_user_master_key(rng, password, user_master_key, *kdf);
+    }
+    let mut key_encryption_key = Locked::<[u8; AES_KEY_LEN]>::default();
+    take_lock(rng)
+        .deref_mut()
+        .fill_bytes(&mut key_encryption_key[..]);
+    let mut slots = passwords
+        .iter()
+        .map(|&(password, kdf)| encrypt_user_master_key(rng, password, &key_encryption_key, kdf))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut first = slots.remove(0);
+    first.other_slots = slots;
+    first.wrapped_master_key = Some(encrypt(rng, &user_master_key[..], &key_encryption_key)?);
+    Ok(first)
+}
+
+#[cfg(not(feature = "disable-password-escrow"))]
+/// Encrypt `new_master_key`, the rotated master key of `encrypted_master_key`, so the same passwords unlock
+/// it. A key with one slot is encrypted under `password` with `password_kdf`, or with the key derivation
+/// function and parameters it was escrowed with if that is `None`. A key with more than one slot keeps all
+/// of its slots, and `password` must unlock one of them, using `cache` like `decrypt_user_master_key_with`.
+pub(crate) fn reencrypt_user_master_key<R: CryptoRng + RngCore>(
+    rng: &Mutex<R>,
+    password: &str,
+    encrypted_master_key: &EncryptedMasterKey,
+    new_master_key: &[u8; 32],
+    password_kdf: Option<PasswordKdf>,
+    cache: Option<&DerivedKeyCache>,
+) -> Result<EncryptedMasterKey, IronOxideErr> {
+    if encrypted_master_key.wrapped_master_key.is_none() {
+        return encrypt_user_master_key(
+            rng,
+            password,
+            new_master_key,
+            password_kdf.unwrap_or_else(|| encrypted_master_key.password_kdf()),
+        );
+    }
+    let key_encryption_key = decrypt_slots(password, encrypted_master_key, cache)?;
+    Ok(EncryptedMasterKey {
+        wrapped_master_key: Some(encrypt(rng, &new_master_key[..], &key_encryption_key)?),
+        ..encrypted_master_key.clone()
+    })
+}
+
+#[cfg(not(feature = "disable-password-escrow"))]
+/// Decrypts a users encrypted master private key using the provided password. Uses the password and the key derivation
+/// function, parameters, and salt recorded with the key to generate a derived AES key. Takes that derived AES key and uses
+/// it to try and decrypt the provided encrypted user master key. The decrypted key is cleared when dropped,
+/// and kept out of swap with the `mlock` feature.
+///
+/// A key with more than one slot is unlocked by the first slot `password` opens.
 pub fn decrypt_user_master_key(
     password: &str,
     encrypted_master_key: &EncryptedMasterKey,
-) -> Result<[u8; 32], Unspecified> {
-    let derived_key = derive_key_from_password(password, encrypted_master_key.pbkdf2_salt);
-    let mut fixed_decrypted_master_key = [0u8; 32];
+) -> Result<Locked<[u8; 32]>, IronOxideErr> {
+    decrypt_user_master_key_with(password, encrypted_master_key, None)
+}
+
+#[cfg(not(feature = "disable-password-escrow"))]
+/// Like `decrypt_user_master_key`, but reusing the derived key from `cache`, and adding it to `cache` once it
+/// has unlocked the key.
+pub(crate) fn decrypt_user_master_key_with(
+    password: &str,
+    encrypted_master_key: &EncryptedMasterKey,
+    cache: Option<&DerivedKeyCache>,
+) -> Result<Locked<[u8; 32]>, IronOxideErr> {
+    let unlocked = decrypt_slots(password, encrypted_master_key, cache)?;
+    match &encrypted_master_key.wrapped_master_key {
+        None => Ok(unlocked),
+        Some(wrapped_master_key) => {
+            let mut wrapped_master_key = wrapped_master_key.clone();
+            let mut master_key = Locked::<[u8; 32]>::default();
+            let decrypted = decrypt(&mut wrapped_master_key, &unlocked)
+                .map(|decrypted_master_key| master_key.copy_from_slice(decrypted_master_key));
+            // decryption happens in place, so the buffer now holds the master key
+            wrapped_master_key.zeroize();
+            decrypted?;
+            Ok(master_key)
+        }
+    }
+}
+
+#[cfg(not(feature = "disable-password-escrow"))]
+/// Decrypt the first slot of an encrypted master key that `password` opens. For a key with one slot this is
+/// the master key, and otherwise the key the master key is encrypted with.
+fn decrypt_slots(
+    password: &str,
+    encrypted_master_key: &EncryptedMasterKey,
+    cache: Option<&DerivedKeyCache>,
+) -> Result<Locked<[u8; 32]>, IronOxideErr> {
+    let mut result = decrypt_slot(password, encrypted_master_key, cache);
+    for slot in &encrypted_master_key.other_slots {
+        if result.is_ok() {
+            break;
+        }
+        result = decrypt_slot(password, slot, cache);
+    }
+    result
+}
+
+#[cfg(not(feature = "disable-password-escrow"))]
+/// Decrypt a single slot of an encrypted master key
+fn decrypt_slot(
+    password: &str,
+    encrypted_master_key: &EncryptedMasterKey,
+    cache: Option<&DerivedKeyCache>,
+) -> Result<Locked<[u8; 32]>, IronOxideErr> {
+    let kdf = encrypted_master_key.kdf;
+    let salt = &encrypted_master_key.kdf_salt;
+    let cached = cache.and_then(|cache| cache.get(kdf, salt, password));
+    let was_cached = cached.is_some();
+    let derived_key = match cached {
+        Some(derived_key) => derived_key,
+        None => kdf.derive_key(password, salt)?,
+    };
+    let mut fixed_decrypted_master_key = Locked::<[u8; 32]>::default();
     let mut encrypted_key = AesEncryptedValue {
         aes_iv: encrypted_master_key.aes_iv,
         ciphertext: encrypted_master_key.encrypted_key.to_vec(),
     };
-    let decrypted_master_key = decrypt(&mut encrypted_key, derived_key)?;
-    fixed_decrypted_master_key[..].copy_from_slice(decrypted_master_key);
+    let decrypted = decrypt(&mut encrypted_key, &derived_key).map(|decrypted_master_key| {
+        fixed_decrypted_master_key.copy_from_slice(decrypted_master_key)
+    });
+    // decryption happens in place, so the buffer now holds the master key
+    encrypted_key.zeroize();
+    decrypted?;
+    // only cache keys that were derived from the right password
+    if let (Some(cache), false) = (cache, was_cached) {
+        cache.insert(kdf, salt, password, &derived_key);
+    }
     Ok(fixed_decrypted_master_key)
 }
 
@@ -201,7 +765,17 @@ impl aead::NonceSequence for SingleUseNonceGenerator {
 pub fn encrypt<R: CryptoRng + RngCore>(
     rng: &Mutex<R>,
     plaintext: &[u8],
-    key: [u8; AES_KEY_LEN],
+    key: &[u8; AES_KEY_LEN],
+) -> Result<AesEncryptedValue, Unspecified> {
+    encrypt_with_aad(rng, plaintext, key, &[])
+}
+
+/// Like `encrypt`, but also authenticates `aad`. The same `aad` must be provided to `decrypt_with_aad`.
+pub fn encrypt_with_aad<R: CryptoRng + RngCore>(
+    rng: &Mutex<R>,
+    plaintext: &[u8],
+    key: &[u8; AES_KEY_LEN],
+    aad: &[u8],
 ) -> Result<AesEncryptedValue, Unspecified> {
     let algorithm = &aead::AES_256_GCM;
     let mut iv = [0u8; aead::NONCE_LEN];
@@ -212,18 +786,52 @@ pub fn encrypt<R: CryptoRng + RngCore>(
     );
     //Increase the size of the plaintext vector to fit the GCM auth tag
     let mut ciphertext = plaintext.to_owned(); // <-- Not good. We're copying the entire plaintext, which could be large.
-    aes_key.seal_in_place_append_tag(aead::Aad::empty(), &mut ciphertext)?;
+    aes_key.seal_in_place_append_tag(aead::Aad::from(aad), &mut ciphertext)?;
     Ok(AesEncryptedValue {
         ciphertext,
         aes_iv: iv,
     })
 }
 
+/// Like `encrypt_with_aad`, but appends the IV, ciphertext, and auth tag to `out` instead of allocating a
+/// buffer for them. `out` is left as it was if encryption fails.
+pub(crate) fn encrypt_with_aad_into<R: CryptoRng + RngCore>(
+    rng: &Mutex<R>,
+    plaintext: &[u8],
+    key: &[u8; AES_KEY_LEN],
+    aad: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), Unspecified> {
+    let mut iv = [0u8; aead::NONCE_LEN];
+    take_lock(rng).deref_mut().fill_bytes(&mut iv);
+    let mut aes_key = aead::SealingKey::new(
+        aead::UnboundKey::new(&aead::AES_256_GCM, &key[..])?,
+        SingleUseNonceGenerator::new(iv),
+    );
+    // the plaintext is encrypted in place at the end of `out`, and the auth tag appended after it
+    let start = out.len();
+    out.reserve(AES_IV_LEN + plaintext.len() + AES_GCM_TAG_LEN);
+    out.extend_from_slice(&iv);
+    out.extend_from_slice(plaintext);
+    match aes_key.seal_in_place_separate_tag(aead::Aad::from(aad), &mut out[(start + AES_IV_LEN)..])
+    {
+        Ok(tag) => {
+            out.extend_from_slice(tag.as_ref());
+            Ok(())
+        }
+        Err(e) => {
+            out[start..].zeroize();
+            out.truncate(start);
+            Err(e)
+        }
+    }
+}
+
 /// Like `encrypt`, just async for convenience
 pub async fn encrypt_async<R: CryptoRng + RngCore>(
     rng: &Mutex<R>,
     plaintext: &[u8],
-    key: [u8; AES_KEY_LEN],
+    key: &[u8; AES_KEY_LEN],
 ) -> Result<AesEncryptedValue, IronOxideErr> {
     async { encrypt(rng, plaintext, key).map_err(IronOxideErr::from) }.await
 }
@@ -233,44 +841,549 @@ pub async fn encrypt_async<R: CryptoRng + RngCore>(
 /// as an array.
 pub fn decrypt(
     encrypted_doc: &mut AesEncryptedValue,
-    key: [u8; AES_KEY_LEN],
+    key: &[u8; AES_KEY_LEN],
 ) -> Result<&mut [u8], Unspecified> {
+    decrypt_with_aad(encrypted_doc, key, &[])
+}
+
+/// Like `decrypt`, for a value encrypted by `encrypt_with_aad`. Fails if `aad` differs from what it was
+/// encrypted with.
+pub fn decrypt_with_aad<'a>(
+    encrypted_doc: &'a mut AesEncryptedValue,
+    key: &[u8; AES_KEY_LEN],
+    aad: &[u8],
+) -> Result<&'a mut [u8], Unspecified> {
     let mut aes_key = aead::OpeningKey::new(
         aead::UnboundKey::new(&aead::AES_256_GCM, &key[..])?,
         SingleUseNonceGenerator::new(encrypted_doc.aes_iv),
     );
-    let plaintext = aes_key.open_in_place(aead::Aad::empty(), &mut encrypted_doc.ciphertext[..])?;
+    let plaintext =
+        aes_key.open_in_place(aead::Aad::from(aad), &mut encrypted_doc.ciphertext[..])?;
     Ok(plaintext)
 }
 
+/// Like `decrypt_with_aad`, but takes the bytes of an `AesEncryptedValue` and appends the plaintext to `out`
+/// instead of decrypting a copy of them. `out` is left as it was if decryption fails.
+pub(crate) fn decrypt_with_aad_into(
+    encrypted: &[u8],
+    key: &[u8; AES_KEY_LEN],
+    aad: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), IronOxideErr> {
+    if encrypted.len() < (AES_IV_LEN + AES_GCM_TAG_LEN) {
+        return Err(IronOxideErr::AesEncryptedDocSizeError);
+    }
+    let mut iv = [0u8; AES_IV_LEN];
+    iv.copy_from_slice(&encrypted[..AES_IV_LEN]);
+    let mut aes_key = aead::OpeningKey::new(
+        aead::UnboundKey::new(&aead::AES_256_GCM, &key[..])?,
+        SingleUseNonceGenerator::new(iv),
+    );
+    // the ciphertext is decrypted in place at the end of `out`, and the auth tag dropped after
+    let start = out.len();
+    out.extend_from_slice(&encrypted[AES_IV_LEN..]);
+    let opened = aes_key
+        .open_in_place(aead::Aad::from(aad), &mut out[start..])
+        .map(|plaintext| plaintext.len());
+    match opened {
+        Ok(plaintext_len) => {
+            out.truncate(start + plaintext_len);
+            Ok(())
+        }
+        Err(e) => {
+            out[start..].zeroize();
+            out.truncate(start);
+            Err(e.into())
+        }
+    }
+}
+
+/// Size of the plaintext in every frame of a segmented stream but the last
+pub const SEGMENT_PLAINTEXT_LEN: usize = 64 * 1024;
+const SEGMENT_FRAME_LEN: usize = SEGMENT_PLAINTEXT_LEN + AES_GCM_TAG_LEN;
+/// Bytes at the end of the base IV that the frame counter and final frame flag are mixed into
+const SEGMENT_COUNTER_LEN: usize = 4;
+
+/// Nonce for frame `counter` of a segmented stream: the base IV with the big-endian counter XORed into
+/// bytes 7..11 and the final frame flag into byte 11, so no two frames of a stream share a nonce and a
+/// stream can't be truncated or extended at a frame boundary without failing authentication.
+fn segment_nonce(base_iv: &[u8; AES_IV_LEN], counter: u32, is_final: bool) -> aead::Nonce {
+    let mut nonce = *base_iv;
+    let counter_start = AES_IV_LEN - 1 - SEGMENT_COUNTER_LEN;
+    nonce[counter_start..(AES_IV_LEN - 1)]
+        .iter_mut()
+        .zip(counter.to_be_bytes().iter())
+        .for_each(|(nonce_byte, counter_byte)| *nonce_byte ^= counter_byte);
+    nonce[AES_IV_LEN - 1] ^= is_final as u8;
+    aead::Nonce::assume_unique_for_key(nonce)
+}
+
+fn segment_key(key: &[u8; AES_KEY_LEN]) -> aead::LessSafeKey {
+    aead::LessSafeKey::new(
+        aead::UnboundKey::new(&aead::AES_256_GCM, &key[..])
+            .expect("AES-256-GCM accepts 32 byte keys"),
+    )
+}
+
+fn next_counter(counter: u32) -> std::io::Result<u32> {
+    counter.checked_add(1).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Segmented stream has too many frames.",
+        )
+    })
+}
+
+/// Encrypts a stream with AES-256-GCM in fixed-size frames, writing the ciphertext to `W`.
+///
+/// The stream starts with a random 12 byte base IV, followed by frames of `SEGMENT_PLAINTEXT_LEN` bytes of
+/// plaintext plus a GCM auth tag. The last frame may be shorter (or empty) and is sealed with the final
+/// frame flag set. `finish` must be called to write the last frame; a stream that isn't finished can't be
+/// decrypted.
+pub struct AesSegmentWriter<W: std::io::Write> {
+    inner: W,
+    key: aead::LessSafeKey,
+    base_iv: [u8; AES_IV_LEN],
+    counter: u32,
+    /// Plaintext of the frame being filled, cleared when dropped
+    buffer: Zeroizing<Vec<u8>>,
+}
+
+impl<W: std::io::Write> AesSegmentWriter<W> {
+    /// Write the header of a new stream to `inner`, encrypted with `key`.
+    pub fn new<R: CryptoRng + RngCore>(
+        rng: &Mutex<R>,
+        key: &[u8; AES_KEY_LEN],
+        mut inner: W,
+    ) -> std::io::Result<AesSegmentWriter<W>> {
+        let mut base_iv = [0u8; AES_IV_LEN];
+        take_lock(rng).deref_mut().fill_bytes(&mut base_iv);
+        inner.write_all(&base_iv)?;
+        Ok(AesSegmentWriter {
+            inner,
+            key: segment_key(key),
+            base_iv,
+            counter: 0,
+            buffer: Zeroizing::new(Vec::with_capacity(SEGMENT_FRAME_LEN)),
+        })
+    }
+
+    /// Seal the buffered plaintext as the next frame and write it out.
+    fn write_frame(&mut self, is_final: bool) -> std::io::Result<()> {
+        let nonce = segment_nonce(&self.base_iv, self.counter, is_final);
+        self.key
+            .seal_in_place_append_tag(nonce, aead::Aad::empty(), &mut *self.buffer)
+            .map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::Other, "Frame could not be encrypted.")
+            })?;
+        self.inner.write_all(&self.buffer)?;
+        self.buffer.clear();
+        self.counter = next_counter(self.counter)?;
+        Ok(())
+    }
+
+    /// Write the final frame and return the inner writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_frame(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for AesSegmentWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // a full frame is only sealed once more plaintext arrives, since until then it may be the last
+        if self.buffer.len() == SEGMENT_PLAINTEXT_LEN && !buf.is_empty() {
+            self.write_frame(false)?;
+        }
+        let len = buf.len().min(SEGMENT_PLAINTEXT_LEN - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    /// Flushes the inner writer. Buffered plaintext is only written once its frame is full or the
+    /// stream is finished.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts a stream written by [AesSegmentWriter](struct.AesSegmentWriter.html), reading the ciphertext from `R`.
+///
+/// Each frame is authenticated before any of its plaintext is returned. Reads fail with
+/// `std::io::ErrorKind::InvalidData` if a frame has been modified, reordered, or dropped, or if the stream
+/// ends without a final frame.
+pub struct AesSegmentReader<R: std::io::Read> {
+    inner: R,
+    key: aead::LessSafeKey,
+    base_iv: Option<[u8; AES_IV_LEN]>,
+    counter: u32,
+    /// Ciphertext read ahead of the current frame, to tell whether the current frame is the last
+    ciphertext: Vec<u8>,
+    /// Plaintext of the current frame, cleared when dropped
+    plaintext: Zeroizing<Vec<u8>>,
+    plaintext_pos: usize,
+    finished: bool,
+}
+
+impl<R: std::io::Read> AesSegmentReader<R> {
+    /// Decrypt the stream in `inner` with `key`. Nothing is read until the first call to `read`.
+    pub fn new(key: &[u8; AES_KEY_LEN], inner: R) -> AesSegmentReader<R> {
+        AesSegmentReader {
+            inner,
+            key: segment_key(key),
+            base_iv: None,
+            counter: 0,
+            ciphertext: Vec::with_capacity(SEGMENT_FRAME_LEN + 1),
+            plaintext: Zeroizing::new(Vec::with_capacity(SEGMENT_FRAME_LEN)),
+            plaintext_pos: 0,
+            finished: false,
+        }
+    }
+
+    /// Read from `inner` until `buffer` holds `len` bytes or `inner` is exhausted.
+    fn fill(inner: &mut R, buffer: &mut Vec<u8>, len: usize) -> std::io::Result<()> {
+        let start = buffer.len();
+        buffer.resize(len.max(start), 0);
+        let mut filled = start;
+        while filled < buffer.len() {
+            match inner.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        buffer.truncate(filled);
+        Ok(())
+    }
+
+    /// Authenticate and decrypt the next frame into `plaintext`.
+    fn read_frame(&mut self) -> std::io::Result<()> {
+        let invalid =
+            |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+        let base_iv = match self.base_iv {
+            Some(base_iv) => base_iv,
+            None => {
+                let mut header = Vec::with_capacity(AES_IV_LEN);
+                Self::fill(&mut self.inner, &mut header, AES_IV_LEN)?;
+                if header.len() < AES_IV_LEN {
+                    return Err(invalid("Segmented stream is missing its header."));
+                }
+                let mut base_iv = [0u8; AES_IV_LEN];
+                base_iv.copy_from_slice(&header);
+                self.base_iv = Some(base_iv);
+                base_iv
+            }
+        };
+        // one byte past the frame tells whether another frame follows
+        Self::fill(&mut self.inner, &mut self.ciphertext, SEGMENT_FRAME_LEN + 1)?;
+        let is_final = self.ciphertext.len() <= SEGMENT_FRAME_LEN;
+        let frame_len = self.ciphertext.len().min(SEGMENT_FRAME_LEN);
+        if frame_len < AES_GCM_TAG_LEN {
+            return Err(invalid("Segmented stream ended without a final frame."));
+        }
+        self.plaintext.zeroize();
+        self.plaintext
+            .extend_from_slice(&self.ciphertext[..frame_len]);
+        self.ciphertext.drain(..frame_len);
+        let nonce = segment_nonce(&base_iv, self.counter, is_final);
+        let plaintext_len = self
+            .key
+            .open_in_place(nonce, aead::Aad::empty(), &mut self.plaintext[..])
+            .map_err(|_| invalid("Frame of segmented stream failed authentication."))?
+            .len();
+        self.plaintext.truncate(plaintext_len);
+        self.plaintext_pos = 0;
+        self.counter = next_counter(self.counter)?;
+        self.finished = is_final;
+        Ok(())
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for AesSegmentReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.plaintext_pos == self.plaintext.len() {
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+            self.read_frame()?;
+        }
+        let len = buf.len().min(self.plaintext.len() - self.plaintext_pos);
+        buf[..len].copy_from_slice(&self.plaintext[self.plaintext_pos..(self.plaintext_pos + len)]);
+        self.plaintext_pos += len;
+        Ok(len)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(not(feature = "disable-password-escrow"))]
+    use galvanic_assert::*;
     use std::{convert::TryInto, sync::Arc};
 
+    #[cfg(not(feature = "disable-password-escrow"))]
     #[test]
     fn test_encrypt_user_master_key() {
         let user_master_key = [0u8; 32];
         let password = "MyPassword";
         let rng = rand::thread_rng();
-        let encrypted_master_key =
-            encrypt_user_master_key(&Mutex::new(rng), password, &user_master_key).unwrap();
-        assert_eq!(encrypted_master_key.pbkdf2_salt.len(), 32);
+        let encrypted_master_key = encrypt_user_master_key(
+            &Mutex::new(rng),
+            password,
+            &user_master_key,
+            PasswordKdf::default(),
+        )
+        .unwrap();
+        assert_eq!(encrypted_master_key.kdf_salt.len(), 32);
         assert_eq!(encrypted_master_key.aes_iv.len(), 12);
         assert_eq!(encrypted_master_key.encrypted_key.len(), 48);
     }
 
+    #[cfg(not(feature = "disable-password-escrow"))]
     #[test]
     fn test_decrypt_user_master_key() {
         let user_master_key = [0u8; 32];
         let password = "MyPassword";
         let rng = rand::thread_rng();
-        let encrypted_master_key =
-            encrypt_user_master_key(&Mutex::new(rng), password, &user_master_key).unwrap();
+        let encrypted_master_key = encrypt_user_master_key(
+            &Mutex::new(rng),
+            password,
+            &user_master_key,
+            PasswordKdf::default(),
+        )
+        .unwrap();
 
         let decrypted_master_key =
             decrypt_user_master_key(password, &encrypted_master_key).unwrap();
-        assert_eq!(decrypted_master_key, user_master_key);
+        assert_eq!(*decrypted_master_key, user_master_key);
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    #[test]
+    fn derived_key_cache_only_serves_the_same_password() -> Result<(), IronOxideErr> {
+        let user_master_key = [9u8; 32];
+        let password = "MyPassword";
+        let encrypted_master_key = encrypt_user_master_key(
+            &Mutex::new(rand::thread_rng()),
+            password,
+            &user_master_key,
+            PasswordKdf::default(),
+        )?;
+        let (kdf, salt) = (encrypted_master_key.kdf, encrypted_master_key.kdf_salt);
+        let cache = DerivedKeyCache::new(Duration::from_secs(60));
+        // a wrong password is never cached
+        let err =
+            decrypt_user_master_key_with("wrong", &encrypted_master_key, Some(&cache)).unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::AesError));
+        assert!(take_lock(&cache.entries).is_empty());
+
+        assert_eq!(
+            *decrypt_user_master_key_with(password, &encrypted_master_key, Some(&cache))?,
+            user_master_key
+        );
+        assert!(cache.get(kdf, &salt, password).is_some());
+        assert!(cache.get(kdf, &salt, "wrong").is_none());
+        assert_eq!(
+            *decrypt_user_master_key_with(password, &encrypted_master_key, Some(&cache))?,
+            user_master_key
+        );
+        let err =
+            decrypt_user_master_key_with("wrong", &encrypted_master_key, Some(&cache)).unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::AesError));
+
+        // another cache, such as another SDK instance's, starts empty
+        let other = DerivedKeyCache::new(Duration::from_secs(60));
+        assert!(other.get(kdf, &salt, password).is_none());
+        let expired = DerivedKeyCache::new(Duration::from_secs(0));
+        decrypt_user_master_key_with(password, &encrypted_master_key, Some(&expired))?;
+        assert!(expired.get(kdf, &salt, password).is_none());
+        Ok(())
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    #[test]
+    fn encrypted_master_key_records_iteration_count() -> Result<(), IronOxideErr> {
+        let user_master_key = [7u8; 32];
+        let password = "MyPassword";
+        let rng = Mutex::new(rand::thread_rng());
+        let legacy =
+            encrypt_user_master_key(&rng, password, &user_master_key, PasswordKdf::default())?;
+        assert_eq!(legacy.bytes().len(), EncryptedMasterKey::SIZE_BYTES);
+
+        let raised = EncryptedMasterKey {
+            kdf: Kdf::pbkdf2_sha256(PBKDF2_MIN_ITERATIONS + 1)?,
+            ..legacy
+        };
+        let raised_bytes = raised.bytes();
+        assert_eq!(
+            raised_bytes.len(),
+            EncryptedMasterKey::PBKDF2_ENVELOPE_SIZE_BYTES
+        );
+        let round_tripped = EncryptedMasterKey::new_from_slice(&raised_bytes)?;
+        assert_eq!(
+            round_tripped.pbkdf2_iterations(),
+            Some(PBKDF2_MIN_ITERATIONS + 1)
+        );
+        // the key was encrypted with the floor, so the recorded count derives the wrong key
+        assert!(decrypt_user_master_key(password, &round_tripped).is_err());
+
+        // without its leading byte and iteration count, the envelope is the original layout
+        let legacy_round_tripped = EncryptedMasterKey::new_from_slice(&raised_bytes[5..])?;
+        assert_eq!(
+            legacy_round_tripped.pbkdf2_iterations(),
+            Some(PBKDF2_MIN_ITERATIONS)
+        );
+        assert_eq!(
+            *decrypt_user_master_key(password, &legacy_round_tripped)?,
+            user_master_key
+        );
+        Ok(())
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    #[test]
+    fn iteration_count_below_floor_is_rejected() {
+        let err = encrypt_user_master_key(
+            &Mutex::new(rand::thread_rng()),
+            "MyPassword",
+            &[0u8; 32],
+            PasswordKdf::Pbkdf2Sha256 {
+                iterations: PBKDF2_MIN_ITERATIONS - 1,
+            },
+        )
+        .unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+
+        let mut weak = vec![PBKDF2_SHA256_ENVELOPE];
+        weak.extend_from_slice(&1000u32.to_be_bytes());
+        weak.extend_from_slice(&[0u8; EncryptedMasterKey::SIZE_BYTES]);
+        let err = EncryptedMasterKey::new_from_slice(&weak).unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    #[test]
+    fn multi_slot_master_key_unlocks_with_any_password() -> Result<(), IronOxideErr> {
+        let user_master_key = [5u8; 32];
+        let rng = Mutex::new(rand::thread_rng());
+        let encrypted = encrypt_user_master_key_slots(
+            &rng,
+            &[
+                ("MyPassword", PasswordKdf::default()),
+                ("recovery passphrase", PasswordKdf::default()),
+            ],
+            &user_master_key,
+        )?;
+        assert_eq!(encrypted.slot_count(), 2);
+        let bytes = encrypted.bytes();
+        assert_eq!(bytes[..2], [MULTI_SLOT_ENVELOPE, 2]);
+
+        let round_tripped = EncryptedMasterKey::new_from_slice(&bytes)?;
+        assert_eq!(round_tripped.slot_count(), 2);
+        for &password in &["MyPassword", "recovery passphrase"] {
+            assert_eq!(
+                *decrypt_user_master_key(password, &round_tripped)?,
+                user_master_key
+            );
+        }
+        assert!(decrypt_user_master_key("NotMyPassword", &round_tripped).is_err());
+
+        // rotating the key with one password keeps both slots working
+        let rotated_master_key = [6u8; 32];
+        let rotated = EncryptedMasterKey::new_from_slice(
+            &reencrypt_user_master_key(
+                &rng,
+                "recovery passphrase",
+                &round_tripped,
+                &rotated_master_key,
+                None,
+                None,
+            )?
+            .bytes(),
+        )?;
+        assert_eq!(rotated.slot_count(), 2);
+        for &password in &["MyPassword", "recovery passphrase"] {
+            assert_eq!(
+                *decrypt_user_master_key(password, &rotated)?,
+                rotated_master_key
+            );
+        }
+        assert!(reencrypt_user_master_key(
+            &rng,
+            "NotMyPassword",
+            &round_tripped,
+            &rotated_master_key,
+            None,
+            None,
+        )
+        .is_err());
+
+        // a single password is stored in the single slot layout
+        let single = encrypt_user_master_key_slots(
+            &rng,
+            &[("MyPassword", PasswordKdf::default())],
+            &user_master_key,
+        )?;
+        assert_eq!(single.bytes().len(), EncryptedMasterKey::SIZE_BYTES);
+
+        let err = encrypt_user_master_key_slots(&rng, &[], &user_master_key).unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+        let err = EncryptedMasterKey::new_from_slice(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::WrongSizeError));
+        Ok(())
+    }
+
+    #[cfg(all(
+        feature = "argon2id",
+        not(any(feature = "disable-password-escrow", feature = "fips"))
+    ))]
+    #[test]
+    fn argon2id_master_key_roundtrips() -> Result<(), IronOxideErr> {
+        let user_master_key = [3u8; 32];
+        let password = "MyPassword";
+        let kdf = PasswordKdf::Argon2id {
+            memory_kib: ARGON2ID_MIN_MEMORY_KIB,
+            iterations: ARGON2ID_MIN_ITERATIONS,
+            parallelism: 2,
+        };
+        let encrypted = encrypt_user_master_key(
+            &Mutex::new(rand::thread_rng()),
+            password,
+            &user_master_key,
+            kdf,
+        )?;
+        let bytes = encrypted.bytes();
+        assert_eq!(
+            bytes.len(),
+            EncryptedMasterKey::ARGON2ID_ENVELOPE_SIZE_BYTES
+        );
+        assert_eq!(bytes[0], ARGON2ID_ENVELOPE);
+
+        let round_tripped = EncryptedMasterKey::new_from_slice(&bytes)?;
+        assert_eq!(round_tripped.password_kdf(), kdf);
+        assert_eq!(round_tripped.pbkdf2_iterations(), None);
+        assert_eq!(
+            *decrypt_user_master_key(password, &round_tripped)?,
+            user_master_key
+        );
+        assert!(decrypt_user_master_key("NotMyPassword", &round_tripped).is_err());
+
+        let weak = PasswordKdf::Argon2id {
+            memory_kib: ARGON2ID_MIN_MEMORY_KIB - 1,
+            iterations: ARGON2ID_MIN_ITERATIONS,
+            parallelism: 1,
+        };
+        let err = encrypt_user_master_key(
+            &Mutex::new(rand::thread_rng()),
+            password,
+            &user_master_key,
+            weak,
+        )
+        .unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+        Ok(())
     }
 
     #[test]
@@ -280,7 +1393,7 @@ mod tests {
         let mut rng = rand::thread_rng();
         rng.fill_bytes(&mut key);
 
-        let res = encrypt(&Mutex::new(rng), &plaintext, key).unwrap();
+        let res = encrypt(&Mutex::new(rng), &plaintext, &key).unwrap();
         assert_eq!(res.aes_iv.len(), 12);
         assert_eq!(
             res.ciphertext.len(),
@@ -295,13 +1408,25 @@ mod tests {
         let mut rng = rand::thread_rng();
         rng.fill_bytes(&mut key);
 
-        let mut encrypted_result = encrypt(&Mutex::new(rng), &plaintext, key).unwrap();
+        let mut encrypted_result = encrypt(&Mutex::new(rng), &plaintext, &key).unwrap();
 
-        let decrypted_plaintext = decrypt(&mut encrypted_result, key).unwrap();
+        let decrypted_plaintext = decrypt(&mut encrypted_result, &key).unwrap();
 
         assert_eq!(*decrypted_plaintext, plaintext[..]);
     }
 
+    #[test]
+    fn zeroize_clears_decrypted_value() {
+        let plaintext = vec![1, 2, 3, 4, 5, 6, 7];
+        let key = [9u8; 32];
+        let mut encrypted = encrypt(&Mutex::new(rand::thread_rng()), &plaintext, &key).unwrap();
+        decrypt(&mut encrypted, &key).unwrap();
+
+        encrypted.zeroize();
+        assert_eq!(encrypted.aes_iv, [0u8; AES_IV_LEN]);
+        assert!(encrypted.ciphertext.is_empty());
+    }
+
     // Even very small documents of 0 and 1 bytes should roundtrip between AesEncryptedValue and bytes
     #[test]
     fn test_roundtrip_aesencryptedvalue_zero_one_bytes() -> Result<(), IronOxideErr> {
@@ -320,6 +1445,68 @@ mod tests {
         Ok(())
     }
 
+    fn segment_roundtrip(plaintext: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let key = [5u8; 32];
+        let mut writer =
+            AesSegmentWriter::new(&Mutex::new(rand::thread_rng()), &key, vec![]).unwrap();
+        writer.write_all(plaintext).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn segment_decrypt(ciphertext: &[u8]) -> std::io::Result<Vec<u8>> {
+        use std::io::Read;
+        let mut decrypted = vec![];
+        AesSegmentReader::new(&[5u8; 32], ciphertext).read_to_end(&mut decrypted)?;
+        Ok(decrypted)
+    }
+
+    #[test]
+    fn segmented_stream_roundtrips_at_frame_boundaries() {
+        for &len in &[
+            0,
+            1,
+            SEGMENT_PLAINTEXT_LEN - 1,
+            SEGMENT_PLAINTEXT_LEN,
+            SEGMENT_PLAINTEXT_LEN + 1,
+            3 * SEGMENT_PLAINTEXT_LEN,
+        ] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let ciphertext = segment_roundtrip(&plaintext);
+            let frames =
+                std::cmp::max(1, (len + SEGMENT_PLAINTEXT_LEN - 1) / SEGMENT_PLAINTEXT_LEN);
+            assert_eq!(
+                ciphertext.len(),
+                AES_IV_LEN + len + frames * AES_GCM_TAG_LEN
+            );
+            assert_eq!(segment_decrypt(&ciphertext).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn segmented_stream_rejects_truncation_and_reordering() {
+        let plaintext = vec![7u8; 3 * SEGMENT_PLAINTEXT_LEN];
+        let ciphertext = segment_roundtrip(&plaintext);
+        let frame = |i: usize| {
+            &ciphertext
+                [(AES_IV_LEN + i * SEGMENT_FRAME_LEN)..(AES_IV_LEN + (i + 1) * SEGMENT_FRAME_LEN)]
+        };
+
+        // dropping the final frame leaves a stream whose last frame wasn't sealed as final
+        let truncated = &ciphertext[..(AES_IV_LEN + 2 * SEGMENT_FRAME_LEN)];
+        let err = segment_decrypt(truncated).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let reordered = [&ciphertext[..AES_IV_LEN], frame(1), frame(0), frame(2)].concat();
+        let err = segment_decrypt(&reordered).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut tampered = ciphertext.clone();
+        tampered[AES_IV_LEN] ^= 1;
+        let err = segment_decrypt(&tampered).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_parallel_encrypt() {
         use rand::SeedableRng;
@@ -336,7 +1523,7 @@ mod tests {
             let rng_ref = a_rng.clone();
             let pt = plaintext.clone();
             threads.push(std::thread::spawn(move || {
-                let _res = encrypt(&rng_ref, &pt, key).unwrap();
+                let _res = encrypt(&rng_ref, &pt, &key).unwrap();
             }));
         }
 
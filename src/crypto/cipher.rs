@@ -0,0 +1,291 @@
+//! Symmetric ciphers that document data can be encrypted with.
+//!
+//! Documents are encrypted with AES-256-GCM unless another [CipherSuite](enum.CipherSuite.html) is chosen
+//! with [DocumentEncryptOpts::with_cipher_suite](../../document/struct.DocumentEncryptOpts.html#method.with_cipher_suite).
+//! XChaCha20-Poly1305 is much faster than AES on platforms without AES hardware acceleration. AES-256-GCM-SIV
+//! is nonce misuse resistant: encrypting under a key whose random nonces collide only reveals whether the
+//! two plaintexts were equal, where with AES-256-GCM it gives away the authentication key. The cipher
+//! suite is recorded in the document header, so decryption picks it up automatically.
+
+use crate::{
+    crypto::{
+        aes,
+        provider::{self, error::Unspecified},
+    },
+    internal::{take_lock, IronOxideErr},
+};
+use aes_gcm_siv::Aes256GcmSiv;
+use chacha20poly1305::{
+    aead::{
+        generic_array::{typenum::Unsigned, GenericArray},
+        AeadInPlace, NewAead,
+    },
+    XChaCha20Poly1305,
+};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::{ops::DerefMut, sync::Mutex};
+use zeroize::Zeroize;
+
+/// Authenticated cipher used to encrypt a document's data with its symmetric key
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum CipherSuite {
+    /// AES-256 in GCM mode with a 12 byte IV. Readable by every IronCore SDK.
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    /// XChaCha20-Poly1305 with a 24 byte nonce. Only readable by SDKs that support it. Unavailable with
+    /// the `fips` feature.
+    #[serde(rename = "xchacha20-poly1305")]
+    XChaCha20Poly1305,
+    /// AES-256 in GCM-SIV mode with a 12 byte nonce. Only readable by SDKs that support it. Unavailable
+    /// with the `fips` feature.
+    #[serde(rename = "aes-256-gcm-siv")]
+    Aes256GcmSiv,
+}
+
+impl Default for CipherSuite {
+    fn default() -> Self {
+        CipherSuite::Aes256Gcm
+    }
+}
+
+impl CipherSuite {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == CipherSuite::default()
+    }
+
+    /// Encrypt `plaintext` with the 32 byte `key`, authenticating `aad` along with it. Returns the random
+    /// nonce followed by the ciphertext and auth tag.
+    pub fn encrypt<R: CryptoRng + RngCore>(
+        self,
+        rng: &Mutex<R>,
+        plaintext: &[u8],
+        key: &[u8; 32],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, IronOxideErr> {
+        let mut encrypted = vec![];
+        self.encrypt_into(rng, plaintext, key, aad, &mut encrypted)?;
+        Ok(encrypted)
+    }
+
+    /// Like `encrypt`, but appends the nonce, ciphertext, and auth tag to `out`, so one buffer can be reused
+    /// across many encryptions instead of allocating one for each. `out` is left as it was if encryption
+    /// fails.
+    pub fn encrypt_into<R: CryptoRng + RngCore>(
+        self,
+        rng: &Mutex<R>,
+        plaintext: &[u8],
+        key: &[u8; 32],
+        aad: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), IronOxideErr> {
+        match self {
+            CipherSuite::Aes256Gcm => {
+                Ok(aes::encrypt_with_aad_into(rng, plaintext, key, aad, out)?)
+            }
+            CipherSuite::XChaCha20Poly1305 => {
+                provider::require_non_fips("XChaCha20-Poly1305")?;
+                seal_into::<XChaCha20Poly1305, _>(rng, plaintext, key, aad, out)
+            }
+            CipherSuite::Aes256GcmSiv => {
+                provider::require_non_fips("AES-256-GCM-SIV")?;
+                seal_into::<Aes256GcmSiv, _>(rng, plaintext, key, aad, out)
+            }
+        }
+    }
+
+    /// Like `encrypt`, just async for convenience
+    pub async fn encrypt_async<R: CryptoRng + RngCore>(
+        self,
+        rng: &Mutex<R>,
+        plaintext: &[u8],
+        key: &[u8; 32],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, IronOxideErr> {
+        async { self.encrypt(rng, plaintext, key, aad) }.await
+    }
+
+    /// Decrypt the output of `encrypt` with the same key and `aad`. Fails with `IronOxideErr::AesError` if the
+    /// ciphertext doesn't authenticate under `key` and `aad`.
+    pub fn decrypt(
+        self,
+        encrypted: &[u8],
+        key: &[u8; 32],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, IronOxideErr> {
+        let mut decrypted = vec![];
+        self.decrypt_into(encrypted, key, aad, &mut decrypted)?;
+        Ok(decrypted)
+    }
+
+    /// Like `decrypt`, but appends the plaintext to `out`, so one buffer can be reused across many
+    /// decryptions instead of allocating one for each. `out` is left as it was if decryption fails.
+    pub fn decrypt_into(
+        self,
+        encrypted: &[u8],
+        key: &[u8; 32],
+        aad: &[u8],
+        out: &mut Vec<u8>,
+    ) -> Result<(), IronOxideErr> {
+        match self {
+            CipherSuite::Aes256Gcm => aes::decrypt_with_aad_into(encrypted, key, aad, out),
+            CipherSuite::XChaCha20Poly1305 => {
+                provider::require_non_fips("XChaCha20-Poly1305")?;
+                open_into::<XChaCha20Poly1305>(encrypted, key, aad, out)
+            }
+            CipherSuite::Aes256GcmSiv => {
+                provider::require_non_fips("AES-256-GCM-SIV")?;
+                open_into::<Aes256GcmSiv>(encrypted, key, aad, out)
+            }
+        }
+    }
+}
+
+/// Encrypt with a RustCrypto AEAD under a random nonce, appending the nonce, ciphertext, and auth tag to `out`
+fn seal_into<A: NewAead + AeadInPlace, R: CryptoRng + RngCore>(
+    rng: &Mutex<R>,
+    plaintext: &[u8],
+    key: &[u8; 32],
+    aad: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), IronOxideErr> {
+    let mut nonce = GenericArray::<u8, A::NonceSize>::default();
+    take_lock(rng).deref_mut().fill_bytes(&mut nonce);
+    let start = out.len();
+    out.reserve(nonce.len() + plaintext.len() + A::TagSize::to_usize());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(plaintext);
+    let sealed = A::new(GenericArray::from_slice(key)).encrypt_in_place_detached(
+        &nonce,
+        aad,
+        &mut out[(start + nonce.len())..],
+    );
+    match sealed {
+        Ok(tag) => {
+            out.extend_from_slice(&tag);
+            Ok(())
+        }
+        Err(_) => {
+            out[start..].zeroize();
+            out.truncate(start);
+            Err(IronOxideErr::AesError(Unspecified))
+        }
+    }
+}
+
+/// Decrypt the output of `seal_into`, appending the plaintext to `out`
+fn open_into<A: NewAead + AeadInPlace>(
+    encrypted: &[u8],
+    key: &[u8; 32],
+    aad: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), IronOxideErr> {
+    let nonce_len = A::NonceSize::to_usize();
+    let tag_len = A::TagSize::to_usize();
+    if encrypted.len() < nonce_len + tag_len {
+        return Err(IronOxideErr::AesEncryptedDocSizeError);
+    }
+    let (nonce, rest) = encrypted.split_at(nonce_len);
+    let (ciphertext, tag) = rest.split_at(rest.len() - tag_len);
+    let start = out.len();
+    out.extend_from_slice(ciphertext);
+    let opened = A::new(GenericArray::from_slice(key)).decrypt_in_place_detached(
+        GenericArray::from_slice(nonce),
+        aad,
+        &mut out[start..],
+        GenericArray::from_slice(tag),
+    );
+    if opened.is_err() {
+        out[start..].zeroize();
+        out.truncate(start);
+        return Err(IronOxideErr::AesError(Unspecified));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::*;
+
+    #[cfg(not(feature = "fips"))]
+    #[test]
+    fn each_cipher_suite_roundtrips_and_authenticates() -> Result<(), IronOxideErr> {
+        let plaintext = b"secret data".to_vec();
+        let key = [3u8; 32];
+        for &suite in &[
+            CipherSuite::Aes256Gcm,
+            CipherSuite::XChaCha20Poly1305,
+            CipherSuite::Aes256GcmSiv,
+        ] {
+            let rng = Mutex::new(rand::thread_rng());
+            let mut encrypted = suite.encrypt(&rng, &plaintext, &key, b"header")?;
+            assert_eq!(suite.decrypt(&encrypted, &key, b"header")?, plaintext);
+
+            let err = suite
+                .decrypt(&encrypted, &key, b"other header")
+                .unwrap_err();
+            assert_that!(&err, is_variant!(IronOxideErr::AesError));
+
+            let last = encrypted.len() - 1;
+            encrypted[last] ^= 1;
+            let err = suite.decrypt(&encrypted, &key, b"header").unwrap_err();
+            assert_that!(&err, is_variant!(IronOxideErr::AesError));
+        }
+
+        let too_short = [0u8; 24];
+        for &suite in &[CipherSuite::XChaCha20Poly1305, CipherSuite::Aes256GcmSiv] {
+            let err = suite.decrypt(&too_short, &key, &[]).unwrap_err();
+            assert_that!(&err, is_variant!(IronOxideErr::AesEncryptedDocSizeError));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "fips"))]
+    #[test]
+    fn encrypt_into_and_decrypt_into_append_to_buffers() -> Result<(), IronOxideErr> {
+        let key = [3u8; 32];
+        for &suite in &[
+            CipherSuite::Aes256Gcm,
+            CipherSuite::XChaCha20Poly1305,
+            CipherSuite::Aes256GcmSiv,
+        ] {
+            let rng = Mutex::new(rand::thread_rng());
+            let mut encrypted = b"header".to_vec();
+            suite.encrypt_into(&rng, b"secret data", &key, &[], &mut encrypted)?;
+            assert_eq!(&encrypted[..6], b"header");
+            assert_eq!(suite.decrypt(&encrypted[6..], &key, &[])?, b"secret data");
+
+            let mut decrypted = b"earlier data ".to_vec();
+            suite.decrypt_into(&encrypted[6..], &key, &[], &mut decrypted)?;
+            assert_eq!(decrypted, b"earlier data secret data".to_vec());
+
+            // a failed decryption leaves the buffer as it was
+            let err = suite
+                .decrypt_into(&encrypted[6..], &key, b"aad", &mut decrypted)
+                .unwrap_err();
+            assert_that!(&err, is_variant!(IronOxideErr::AesError));
+            assert_eq!(decrypted, b"earlier data secret data".to_vec());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "fips")]
+    #[test]
+    fn fips_refuses_unapproved_cipher_suites() -> Result<(), IronOxideErr> {
+        let rng = Mutex::new(rand::thread_rng());
+        let key = [3u8; 32];
+        let encrypted = CipherSuite::Aes256Gcm.encrypt(&rng, b"secret data", &key, &[])?;
+        assert_eq!(
+            CipherSuite::Aes256Gcm.decrypt(&encrypted, &key, &[])?,
+            b"secret data".to_vec()
+        );
+        for &suite in &[CipherSuite::XChaCha20Poly1305, CipherSuite::Aes256GcmSiv] {
+            let err = suite.encrypt(&rng, b"secret data", &key, &[]).unwrap_err();
+            assert_that!(&err, is_variant!(IronOxideErr::FipsDisallowed));
+            let err = suite.decrypt(&encrypted, &key, &[]).unwrap_err();
+            assert_that!(&err, is_variant!(IronOxideErr::FipsDisallowed));
+        }
+        Ok(())
+    }
+}
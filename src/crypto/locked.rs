@@ -0,0 +1,220 @@
+//! Heap buffers for key material that are cleared when dropped.
+//!
+//! With the `mlock` feature, the memory of each buffer is also locked into RAM with `mlock` (or
+//! `VirtualLock` on Windows) so it is never written to swap. Locking is best effort: if the process is
+//! over its locked memory limit the buffer is still used, unlocked, and a warning is logged. The operating
+//! system locks whole pages and doesn't count how often a page was locked, so the buffers on each page are
+//! counted here and a page is only unlocked when the last buffer on it is dropped.
+
+use std::ops::{Deref, DerefMut};
+use zeroize::Zeroize;
+
+/// Key material on the heap, cleared and unlocked when dropped. Fill it in place with `DerefMut` rather
+/// than moving a key into it, so no copy of the key is left behind.
+pub struct Locked<T: Zeroize>(Box<T>);
+
+impl<T: Zeroize> Locked<T> {
+    /// Move `value` into a locked buffer. Prefer `Locked::default` and filling it in place.
+    pub fn new(value: T) -> Locked<T> {
+        let boxed = Box::new(value);
+        lock(&*boxed);
+        Locked(boxed)
+    }
+}
+
+impl<T: Zeroize + Default> Default for Locked<T> {
+    fn default() -> Self {
+        Locked::new(T::default())
+    }
+}
+
+impl<T: Zeroize + Default + Clone> Clone for Locked<T> {
+    fn clone(&self) -> Self {
+        let mut cloned = Locked::default();
+        (*cloned).clone_from(&*self.0);
+        cloned
+    }
+}
+
+impl<T: Zeroize> Deref for Locked<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> DerefMut for Locked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Zeroize> Drop for Locked<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+        unlock(&*self.0);
+    }
+}
+
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+fn lock<T>(value: &T) {
+    pages::lock(value as *const T as usize, std::mem::size_of::<T>())
+}
+
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+fn unlock<T>(value: &T) {
+    pages::unlock(value as *const T as usize, std::mem::size_of::<T>())
+}
+
+/// Pages holding locked buffers
+#[cfg(all(feature = "mlock", any(unix, windows)))]
+mod pages {
+    use crate::internal::take_lock;
+    use lazy_static::lazy_static;
+    use std::{
+        collections::{hash_map::Entry, HashMap},
+        sync::Mutex,
+    };
+
+    struct PageLock {
+        /// Number of live buffers on the page
+        buffers: usize,
+        /// Whether the operating system locked the page
+        locked: bool,
+    }
+
+    lazy_static! {
+        static ref PAGES: Mutex<HashMap<usize, PageLock>> = Mutex::new(HashMap::new());
+        static ref PAGE_SIZE: usize = sys::page_size();
+    }
+
+    /// Addresses of the pages that `len` bytes at `addr` are on
+    fn spanned(addr: usize, len: usize) -> impl Iterator<Item = usize> {
+        let page_size = *PAGE_SIZE;
+        (addr - addr % page_size..addr + len).step_by(page_size)
+    }
+
+    /// Lock the pages of a new buffer, unless they're locked for other buffers already
+    pub(super) fn lock(addr: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let mut pages = take_lock(&PAGES);
+        let mut failed = false;
+        for page in spanned(addr, len) {
+            let page_lock = pages.entry(page).or_insert(PageLock {
+                buffers: 0,
+                locked: false,
+            });
+            if page_lock.buffers == 0 {
+                page_lock.locked = sys::lock(page, *PAGE_SIZE);
+                failed |= !page_lock.locked;
+            }
+            page_lock.buffers += 1;
+        }
+        if failed {
+            log::warn!("Could not lock key material into memory; it may be swapped to disk.");
+        }
+    }
+
+    /// Unlock the pages of a dropped buffer that no other buffer is on
+    pub(super) fn unlock(addr: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let mut pages = take_lock(&PAGES);
+        for page in spanned(addr, len) {
+            if let Entry::Occupied(mut page_lock) = pages.entry(page) {
+                page_lock.get_mut().buffers -= 1;
+                if page_lock.get().buffers == 0 && page_lock.remove().locked {
+                    sys::unlock(page, *PAGE_SIZE);
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    mod sys {
+        pub(super) fn page_size() -> usize {
+            unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+        }
+
+        pub(super) fn lock(page: usize, len: usize) -> bool {
+            unsafe { libc::mlock(page as *const libc::c_void, len) == 0 }
+        }
+
+        pub(super) fn unlock(page: usize, len: usize) {
+            unsafe { libc::munlock(page as *const libc::c_void, len) };
+        }
+    }
+
+    #[cfg(windows)]
+    mod sys {
+        use winapi::{
+            ctypes::c_void,
+            um::{memoryapi, sysinfoapi},
+        };
+
+        pub(super) fn page_size() -> usize {
+            let mut info: sysinfoapi::SYSTEM_INFO = unsafe { std::mem::zeroed() };
+            unsafe { sysinfoapi::GetSystemInfo(&mut info) };
+            info.dwPageSize as usize
+        }
+
+        pub(super) fn lock(page: usize, len: usize) -> bool {
+            unsafe { memoryapi::VirtualLock(page as *mut c_void, len) != 0 }
+        }
+
+        pub(super) fn unlock(page: usize, len: usize) {
+            unsafe { memoryapi::VirtualUnlock(page as *mut c_void, len) };
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn page_is_unlocked_after_its_last_buffer() {
+            let page_size = *PAGE_SIZE;
+            // two buffers on the same page, far from any the allocator hands out
+            let base = usize::MAX - 4 * page_size + 1;
+            let addr = base - base % page_size;
+            let buffers = |pages: &HashMap<usize, PageLock>| pages.get(&addr).map(|p| p.buffers);
+
+            lock(addr, 32);
+            lock(addr + 64, 32);
+            assert_eq!(buffers(&take_lock(&PAGES)), Some(2));
+            unlock(addr, 32);
+            assert_eq!(buffers(&take_lock(&PAGES)), Some(1));
+            unlock(addr + 64, 32);
+            assert_eq!(buffers(&take_lock(&PAGES)), None);
+
+            // a buffer that straddles a page boundary is counted on both pages
+            assert_eq!(spanned(addr + page_size - 8, 16).count(), 2);
+            assert_eq!(spanned(addr, page_size).count(), 1);
+        }
+    }
+}
+
+#[cfg(not(all(feature = "mlock", any(unix, windows))))]
+fn lock<T>(_value: &T) {}
+
+#[cfg(not(all(feature = "mlock", any(unix, windows))))]
+fn unlock<T>(_value: &T) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_buffer_is_filled_in_place_and_cloned() {
+        let mut key = Locked::<[u8; 32]>::default();
+        assert_eq!(*key, [0u8; 32]);
+        key.copy_from_slice(&[7u8; 32]);
+        let cloned = key.clone();
+        key.zeroize();
+        assert_eq!(*cloned, [7u8; 32]);
+        assert_eq!(*key, [0u8; 32]);
+    }
+}
@@ -0,0 +1,29 @@
+//! Provider of the symmetric encryption, hashing, and key derivation primitives the SDK is built on.
+//!
+//! Everything in the SDK uses these modules instead of naming a provider crate, so the provider can be
+//! swapped with a feature flag. By default they come from `ring`. With the `fips` feature they come from
+//! `aws-lc-rs` built against the FIPS 140-3 validated AWS-LC module, which has the same API, and algorithms
+//! that aren't FIPS approved fail with `IronOxideErr::FipsDisallowed`.
+//!
+//! The `fips` feature does not cover recrypt's proxy re-encryption, which has no FIPS validated
+//! implementation.
+
+#[cfg(feature = "fips")]
+pub(crate) use aws_lc_rs::{aead, digest, error, hkdf, hmac, pbkdf2};
+#[cfg(not(feature = "fips"))]
+pub(crate) use ring::{aead, digest, error, hkdf, hmac, pbkdf2};
+
+/// Whether the SDK was built with the `fips` feature
+pub const FIPS_MODE: bool = cfg!(feature = "fips");
+
+/// Fail with `IronOxideErr::FipsDisallowed` if the SDK was built with the `fips` feature, as `algorithm`
+/// isn't FIPS approved.
+pub(crate) fn require_non_fips(algorithm: &str) -> Result<(), crate::internal::IronOxideErr> {
+    if FIPS_MODE {
+        Err(crate::internal::IronOxideErr::FipsDisallowed(
+            algorithm.to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
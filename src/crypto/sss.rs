@@ -0,0 +1,255 @@
+//! Shamir secret sharing of a user's master private key.
+//!
+//! [split_private_key](fn.split_private_key.html) splits a key into `shares` shares, any `threshold` of which
+//! can rebuild it with [combine_private_key](fn.combine_private_key.html). Fewer than `threshold` shares reveal
+//! nothing about the key, so shares can be handed to separate custodians for key recovery without any one of
+//! them holding the key.
+//!
+//! Each byte of the key is shared with its own random polynomial over GF(2^8).
+
+use crate::internal::{take_lock, IronOxideErr, PrivateKey};
+use rand::{CryptoRng, RngCore};
+use std::{convert::TryFrom, fmt, ops::DerefMut, sync::Mutex};
+use zeroize::{Zeroize, Zeroizing};
+
+const KEY_LEN: usize = 32;
+
+/// One share of a private key. Holds the threshold it was split with, its index, and its share of the key.
+/// The share value is left out of its `Debug` output.
+#[derive(Clone)]
+pub struct PrivateKeyShare {
+    threshold: u8,
+    index: u8,
+    value: [u8; KEY_LEN],
+}
+
+impl PrivateKeyShare {
+    /// Length of the output of `to_bytes`
+    pub const ENCODED_SIZE_BYTES: usize = 2 + KEY_LEN;
+
+    /// Number of shares needed to rebuild the key
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+    /// Index of this share, from 1 to the number of shares the key was split into
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+    /// Serialize the share as the threshold, the index, then the 32 byte share value
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&[self.threshold, self.index][..], &self.value[..]].concat()
+    }
+}
+
+impl fmt::Debug for PrivateKeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrivateKeyShare")
+            .field("threshold", &self.threshold)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl TryFrom<&[u8]> for PrivateKeyShare {
+    type Error = IronOxideErr;
+    fn try_from(bytes: &[u8]) -> Result<PrivateKeyShare, IronOxideErr> {
+        if bytes.len() != PrivateKeyShare::ENCODED_SIZE_BYTES {
+            return Err(IronOxideErr::WrongSizeError(
+                Some(bytes.len()),
+                Some(PrivateKeyShare::ENCODED_SIZE_BYTES),
+            ));
+        }
+        if bytes[0] == 0 || bytes[1] == 0 {
+            return Err(IronOxideErr::ValidationError(
+                "PrivateKeyShare".to_string(),
+                "threshold and index must be nonzero".to_string(),
+            ));
+        }
+        let mut value = [0u8; KEY_LEN];
+        value.copy_from_slice(&bytes[2..]);
+        Ok(PrivateKeyShare {
+            threshold: bytes[0],
+            index: bytes[1],
+            value,
+        })
+    }
+}
+
+impl Drop for PrivateKeyShare {
+    fn drop(&mut self) {
+        self.value.zeroize()
+    }
+}
+
+/// Split `private_key` into `shares` shares, any `threshold` of which can rebuild it.
+///
+/// Fails with `IronOxideErr::ValidationError` unless `1 <= threshold <= shares`.
+pub fn split_private_key<R: CryptoRng + RngCore>(
+    rng: &Mutex<R>,
+    private_key: &PrivateKey,
+    shares: u8,
+    threshold: u8,
+) -> Result<Vec<PrivateKeyShare>, IronOxideErr> {
+    if threshold == 0 || threshold > shares {
+        return Err(IronOxideErr::ValidationError(
+            "threshold".to_string(),
+            format!("must be between 1 and {}", shares),
+        ));
+    }
+    // one polynomial per key byte, with the key byte as the constant term
+    let mut coefficients = Zeroizing::new(vec![0u8; KEY_LEN * threshold as usize]);
+    take_lock(rng)
+        .deref_mut()
+        .fill_bytes(&mut coefficients[KEY_LEN..]);
+    coefficients[..KEY_LEN].copy_from_slice(private_key.as_bytes());
+
+    Ok((1..=shares)
+        .map(|index| {
+            let mut value = [0u8; KEY_LEN];
+            for (i, byte) in value.iter_mut().enumerate() {
+                // Horner's rule, from the highest degree coefficient down
+                *byte = coefficients
+                    .chunks(KEY_LEN)
+                    .rev()
+                    .fold(0, |acc, coeffs| gf_mul(acc, index) ^ coeffs[i]);
+            }
+            PrivateKeyShare {
+                threshold,
+                index,
+                value,
+            }
+        })
+        .collect())
+}
+
+/// Rebuild a private key from shares made by `split_private_key`.
+///
+/// Fails with `IronOxideErr::ValidationError` if there are fewer shares than their threshold, if the shares
+/// were split with different thresholds, or if any index repeats.
+pub fn combine_private_key(shares: &[PrivateKeyShare]) -> Result<PrivateKey, IronOxideErr> {
+    let threshold = shares.first().map(|s| s.threshold).unwrap_or(1);
+    if shares.len() < threshold as usize {
+        return Err(IronOxideErr::ValidationError(
+            "shares".to_string(),
+            format!("at least {} shares are needed", threshold),
+        ));
+    }
+    if shares.iter().any(|s| s.threshold != threshold) {
+        return Err(IronOxideErr::ValidationError(
+            "shares".to_string(),
+            "shares were split with different thresholds".to_string(),
+        ));
+    }
+    let shares = &shares[..threshold as usize];
+    for (i, share) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|s| s.index == share.index) {
+            return Err(IronOxideErr::ValidationError(
+                "shares".to_string(),
+                format!("share {} is repeated", share.index),
+            ));
+        }
+    }
+
+    // Lagrange interpolation at x = 0. Subtraction in GF(2^8) is xor.
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    for share in shares {
+        let basis = shares
+            .iter()
+            .filter(|other| other.index != share.index)
+            .fold(1, |acc, other| {
+                gf_mul(acc, gf_div(other.index, other.index ^ share.index))
+            });
+        for (byte, value) in key.iter_mut().zip(share.value.iter()) {
+            *byte ^= gf_mul(*value, basis);
+        }
+    }
+    Ok(PrivateKey::from(*key))
+}
+
+/// Multiply in GF(2^8) with the AES polynomial, without branching on either operand
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Divide in GF(2^8). `b` must be nonzero; its inverse is b^254.
+fn gf_div(a: u8, b: u8) -> u8 {
+    let b2 = gf_mul(b, b);
+    let b4 = gf_mul(b2, b2);
+    let b8 = gf_mul(b4, b4);
+    let b16 = gf_mul(b8, b8);
+    let b32 = gf_mul(b16, b16);
+    let b64 = gf_mul(b32, b32);
+    let b128 = gf_mul(b64, b64);
+    let inverse = gf_mul(
+        gf_mul(gf_mul(b128, b64), gf_mul(b32, b16)),
+        gf_mul(gf_mul(b8, b4), b2),
+    );
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::*;
+
+    #[test]
+    fn gf_div_inverts_gf_mul() {
+        for a in 0..=255u8 {
+            for b in 1..=255u8 {
+                assert_eq!(gf_div(gf_mul(a, b), b), a);
+            }
+        }
+    }
+
+    #[test]
+    fn any_threshold_shares_rebuild_the_key() -> Result<(), IronOxideErr> {
+        let rng = Mutex::new(rand::thread_rng());
+        let key = PrivateKey::from([7u8; 32]);
+        let shares = split_private_key(&rng, &key, 5, 3)?;
+        assert_eq!(shares.len(), 5);
+
+        for subset in &[[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let chosen: Vec<_> = subset.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(combine_private_key(&chosen)?, key);
+        }
+        let parsed = shares
+            .iter()
+            .map(|s| PrivateKeyShare::try_from(&s.to_bytes()[..]))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(combine_private_key(&parsed)?, key);
+
+        let err = combine_private_key(&shares[..2]).unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+        let repeated = vec![shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        let err = combine_private_key(&repeated).unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+        Ok(())
+    }
+
+    #[test]
+    fn debug_leaves_out_the_share_value() -> Result<(), IronOxideErr> {
+        let share = PrivateKeyShare::try_from(&[&[3u8, 2][..], &[0xab; 32][..]].concat()[..])?;
+        assert_eq!(
+            format!("{:?}", share),
+            "PrivateKeyShare { threshold: 3, index: 2 }"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn split_rejects_bad_threshold() {
+        let rng = Mutex::new(rand::thread_rng());
+        let key = PrivateKey::from([7u8; 32]);
+        for &(shares, threshold) in &[(3, 0), (3, 4)] {
+            let err = split_private_key(&rng, &key, shares, threshold).unwrap_err();
+            assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::internal::{
     document_api::{DocAccessEditErr, UserOrGroup},
-    IronOxideErr, PublicKey, WithKey,
+    DevicePrivateKey, IronOxideErr, PublicKey, WithKey,
 };
 use itertools::{Either, Itertools};
 use recrypt::{
@@ -31,9 +31,9 @@ pub fn gen_group_keys<R: CryptoOps + KeyGenOps>(
 pub fn decrypt_as_symmetric_key<CR: rand::CryptoRng + rand::RngCore>(
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
     encrypted_plaintext: EncryptedValue,
-    user_device_private_key: &PrivateKey,
+    user_device_private_key: &DevicePrivateKey,
 ) -> Result<DerivedSymmetricKey, IronOxideErr> {
-    let plaintext = recrypt.decrypt(encrypted_plaintext, user_device_private_key)?;
+    let plaintext = user_device_private_key.decrypt(recrypt, encrypted_plaintext)?;
     let symmetric_key = recrypt.derive_symmetric_key(&plaintext);
     Ok(symmetric_key)
 }
@@ -43,9 +43,9 @@ pub fn decrypt_as_symmetric_key<CR: rand::CryptoRng + rand::RngCore>(
 pub fn decrypt_as_private_key<CR: rand::CryptoRng + rand::RngCore>(
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
     encrypted_plaintext: EncryptedValue,
-    user_device_private_key: &PrivateKey,
+    user_device_private_key: &DevicePrivateKey,
 ) -> Result<(Plaintext, PrivateKey), IronOxideErr> {
-    let plaintext = recrypt.decrypt(encrypted_plaintext, user_device_private_key)?;
+    let plaintext = user_device_private_key.decrypt(recrypt, encrypted_plaintext)?;
     let private_key = recrypt.derive_private_key(&plaintext);
     Ok((plaintext, private_key))
 }
@@ -2,15 +2,19 @@
 //!
 //! See [DocumentOps](trait.DocumentOps.html) for document functions and key terms.
 
+pub use crate::crypto::cipher::CipherSuite;
 pub use crate::internal::document_api::{
-    AssociationType, DocAccessEditErr, DocumentAccessResult, DocumentDecryptResult,
-    DocumentEncryptResult, DocumentId, DocumentListMeta, DocumentListResult,
-    DocumentMetadataResult, DocumentName, UserOrGroup, VisibleGroup, VisibleUser,
+    AssociationType, DocAccessEditErr, DocumentAccessResult, DocumentAccessViaResult,
+    DocumentDecryptResult, DocumentEncryptResult, DocumentEncryptUniqueResult, DocumentId,
+    DocumentListMeta, DocumentListResult, DocumentMetadataResult, DocumentName, UserOrGroup,
+    VisibleGroup, VisibleUser,
 };
 use crate::{
     common::SdkOperation,
     group::GroupId,
-    internal::{add_optional_timeout, document_api},
+    internal::{self, add_optional_timeout, document_api},
+    metrics,
+    offline::QueuedMutation,
     policy::PolicyGrant,
     user::UserId,
     Result,
@@ -19,6 +23,8 @@ use async_trait::async_trait;
 use itertools::{Either, EitherOrBoth, Itertools};
 
 pub mod advanced;
+pub mod audit;
+pub mod session;
 
 /// List of users and groups that should have access to decrypt a document.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -54,6 +60,9 @@ pub struct DocumentEncryptOpts {
     name: Option<DocumentName>,
     // at least one user/group must be included either explicitly or via a policy
     grants: EitherOrBoth<ExplicitGrant, PolicyGrant>,
+    cipher_suite: CipherSuite,
+    header_aad: bool,
+    validate_only: bool,
 }
 impl DocumentEncryptOpts {
     /// Constructs a new `DocumentEncryptOpts`.
@@ -71,7 +80,14 @@ impl DocumentEncryptOpts {
         name: Option<DocumentName>,
         grants: EitherOrBoth<ExplicitGrant, PolicyGrant>,
     ) -> DocumentEncryptOpts {
-        DocumentEncryptOpts { grants, name, id }
+        DocumentEncryptOpts {
+            grants,
+            name,
+            id,
+            cipher_suite: CipherSuite::default(),
+            header_aad: false,
+            validate_only: false,
+        }
     }
 
     /// Constructs a new `DocumentEncryptOpts` with access explicitly granted to certain users and groups.
@@ -94,6 +110,9 @@ impl DocumentEncryptOpts {
                 grants,
                 grant_to_author,
             }),
+            cipher_suite: CipherSuite::default(),
+            header_aad: false,
+            validate_only: false,
         }
     }
 
@@ -113,8 +132,36 @@ impl DocumentEncryptOpts {
             id,
             name,
             grants: EitherOrBoth::Right(policy),
+            cipher_suite: CipherSuite::default(),
+            header_aad: false,
+            validate_only: false,
         }
     }
+
+    /// Encrypt the document's data with `cipher_suite` rather than AES-256-GCM. See
+    /// [CipherSuite](enum.CipherSuite.html).
+    pub fn with_cipher_suite(mut self, cipher_suite: CipherSuite) -> DocumentEncryptOpts {
+        self.cipher_suite = cipher_suite;
+        self
+    }
+
+    /// If `true`, authenticate the document's header (its version, segment, and ID) along with its data,
+    /// so encrypted data moved under another document's header fails to decrypt instead of decrypting
+    /// as that document. Documents encrypted this way can only be decrypted by SDKs that support it.
+    pub fn with_header_aad(mut self, header_aad: bool) -> DocumentEncryptOpts {
+        self.header_aad = header_aad;
+        self
+    }
+
+    /// If `true`, [document_encrypt](trait.DocumentOps.html#tymethod.document_encrypt) resolves the grants'
+    /// keys and encrypts the data, then stops before the document is created. It fails with
+    /// `IronOxideErr::ValidateOnly` if the document could have been encrypted to every grant, and with a
+    /// `ValidationError` naming the users and groups it couldn't have been otherwise. Has no effect on
+    /// unmanaged encryption, which doesn't change anything in the IronCore service.
+    pub fn with_validate_only(mut self, validate_only: bool) -> DocumentEncryptOpts {
+        self.validate_only = validate_only;
+        self
+    }
 }
 impl Default for DocumentEncryptOpts {
     /// Constructs a `DocumentEncryptOpts` with common values.
@@ -131,6 +178,11 @@ impl Default for DocumentEncryptOpts {
 /// # Key Terms
 /// - ID     - The ID representing a document. It must be unique within the document's segment and will **not** be encrypted.
 /// - Name   - The human-readable name of a document. It does not need to be unique and will **not** be encrypted.
+///
+/// # Document Storage
+/// The IronCore service only stores a document's metadata and its access list. The encrypted bytes are
+/// never sent to it; they're returned to the caller, who stores them wherever the document is kept, so the
+/// size of a document has no effect on the requests made to the service.
 #[async_trait]
 pub trait DocumentOps {
     /// Encrypts the provided document bytes.
@@ -150,14 +202,22 @@ pub trait DocumentOps {
     ///
     /// # Examples
     /// ```
-    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # async fn run(sdk: &ironoxide::IronOxide) -> Result<(), ironoxide::IronOxideErr> {
     /// # use ironoxide::prelude::*;
-    /// # let sdk: IronOxide = unimplemented!();
     /// # use ironoxide::document::DocumentEncryptOpts;
     /// let data = "secret data".as_bytes();
     /// let encrypted = sdk.document_encrypt(data, &DocumentEncryptOpts::default()).await?;
+    /// # assert_eq!(encrypted.grants().len(), 1);
     /// # Ok(())
     /// # }
+    /// # #[cfg(all(feature = "mock", not(feature = "disable-password-escrow")))]
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), ironoxide::IronOxideErr> {
+    /// #     let mock = ironoxide::mock::MockServer::start("foobar").await?;
+    /// #     run(&mock.initialize_sdk().await?).await
+    /// # }
+    /// # #[cfg(not(all(feature = "mock", not(feature = "disable-password-escrow"))))]
+    /// # fn main() {}
     /// ```
     async fn document_encrypt(
         &self,
@@ -165,6 +225,46 @@ pub trait DocumentOps {
         encrypt_opts: &DocumentEncryptOpts,
     ) -> Result<DocumentEncryptResult>;
 
+    /// Encrypts the provided document bytes, unless this SDK has already encrypted the same bytes to
+    /// the same audience.
+    ///
+    /// A keyed hash of the plaintext and the requested grants (explicit users and groups, whether the
+    /// author is included, and the policy) is checked against a local index held by this `IronOxide`
+    /// instance. On a match, no request is made and the ID of the existing document is returned as
+    /// `DocumentEncryptUniqueResult::AlreadyEncrypted`. Otherwise the document is encrypted as with
+    /// [document_encrypt](trait.DocumentOps.html#tymethod.document_encrypt) and, if every grant
+    /// succeeded, recorded in the index. The hash key is derived from the device private key.
+    ///
+    /// The index is shared with instances created by
+    /// [fork_for_core](../struct.IronOxide.html#method.fork_for_core), but not with other SDK instances,
+    /// and isn't persisted. Two concurrent calls with the same document may both encrypt it. Its size and how long entries are used is set by
+    /// [UniqueEncryptionConfig](../config/struct.UniqueEncryptionConfig.html).
+    ///
+    /// # Arguments
+    /// - `document_data` - Bytes of the document to encrypt
+    /// - `encrypt_opts` - Document encryption parameters. Default values provided by
+    ///      [DocumentEncryptOpts::default()](struct.DocumentEncryptOpts.html#method.default).
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # use ironoxide::document::{DocumentEncryptOpts, DocumentEncryptUniqueResult};
+    /// # let sdk: IronOxide = unimplemented!();
+    /// let data = "secret data".as_bytes();
+    /// match sdk.document_encrypt_unique(data, &DocumentEncryptOpts::default()).await? {
+    ///     DocumentEncryptUniqueResult::Encrypted(encrypted) => println!("stored {}", encrypted.id().id()),
+    ///     DocumentEncryptUniqueResult::AlreadyEncrypted(id) => println!("duplicate of {}", id.id()),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn document_encrypt_unique(
+        &self,
+        document_data: &[u8],
+        encrypt_opts: &DocumentEncryptOpts,
+    ) -> Result<DocumentEncryptUniqueResult>;
+
     /// Decrypts an IronCore encrypted document.
     ///
     /// Requires the encrypted data returned from [document_encrypt](trait.DocumentOps.html#tymethod.document_encrypt).
@@ -190,6 +290,35 @@ pub trait DocumentOps {
     /// ```
     async fn document_decrypt(&self, encrypted_document: &[u8]) -> Result<DocumentDecryptResult>;
 
+    /// Checks an IronCore encrypted document for corruption.
+    ///
+    /// The encrypted data is authenticated with the tag of its cipher suite, which needs the document's key,
+    /// so the key is fetched (or taken from the document key cache) as it is for
+    /// [document_decrypt](trait.DocumentOps.html#tymethod.document_decrypt). The decrypted bytes are discarded.
+    ///
+    /// Returns `false` if the document's header is malformed or truncated, or its encrypted data fails
+    /// authentication.
+    ///
+    /// # Arguments
+    /// - `encrypted_document` - Bytes of the encrypted document
+    ///
+    /// # Errors
+    /// Fails if the document's key can't be fetched, such as when the calling user does not have access to the
+    /// document or the request to the service fails. A header corrupted into another document's ID fails
+    /// this way too.
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # let encrypted_data: Vec<u8> = vec![];
+    /// let intact = sdk.document_verify(&encrypted_data).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn document_verify(&self, encrypted_document: &[u8]) -> Result<bool>;
+
     /// Lists metadata for all of the encrypted documents that the calling user can read or decrypt.
     ///
     /// # Examples
@@ -224,6 +353,29 @@ pub trait DocumentOps {
     /// ```
     async fn document_get_metadata(&self, id: &DocumentId) -> Result<DocumentMetadataResult>;
 
+    /// Explains why the calling user can decrypt a document.
+    ///
+    /// Reports whether the calling user has access through a direct grant (including having created
+    /// the document) and which of the groups the document was granted to they are a member of.
+    /// Access can come from both at once.
+    ///
+    /// # Arguments
+    /// - `id` - ID of the document
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// use std::convert::TryFrom;
+    /// let document_id = DocumentId::try_from("test_document")?;
+    /// let access = sdk.document_access_via(&document_id).await?;
+    /// let via_groups: Vec<&GroupId> = access.groups().iter().map(|g| g.id()).collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn document_access_via(&self, id: &DocumentId) -> Result<DocumentAccessViaResult>;
+
     /// Returns the document ID from the bytes of an encrypted document.
     ///
     /// This is the same ID returned by `DocumentEncryptResult.id()`.
@@ -250,7 +402,8 @@ pub trait DocumentOps {
     /// Updates the contents of an existing IronCore encrypted document.
     ///
     /// The new contents will be encrypted, and which users and groups are granted access
-    /// will remain unchanged.
+    /// will remain unchanged. The new contents are always encrypted with AES-256-GCM and without header
+    /// AAD, whichever options the document was first encrypted with.
     ///
     /// # Arguments
     /// - `id` - ID of the document to update
@@ -373,26 +526,11 @@ impl DocumentOps for crate::IronOxide {
         document_data: &[u8],
         encrypt_opts: &DocumentEncryptOpts,
     ) -> Result<DocumentEncryptResult> {
+        self.check_writable(SdkOperation::DocumentEncrypt)?;
         let encrypt_opts = encrypt_opts.clone();
-
         let (explicit_users, explicit_groups, grant_to_author, policy_grants) =
-            match encrypt_opts.grants {
-                EitherOrBoth::Left(explicit_grants) => {
-                    let (users, groups) = partition_user_or_group(&explicit_grants.grants);
-                    (users, groups, explicit_grants.grant_to_author, None)
-                }
-                EitherOrBoth::Right(policy_grant) => (vec![], vec![], false, Some(policy_grant)),
-                EitherOrBoth::Both(explicit_grants, policy_grant) => {
-                    let (users, groups) = partition_user_or_group(&explicit_grants.grants);
-                    (
-                        users,
-                        groups,
-                        explicit_grants.grant_to_author,
-                        Some(policy_grant),
-                    )
-                }
-            };
-        add_optional_timeout(
+            partition_grants(&encrypt_opts.grants);
+        let encrypt = add_optional_timeout(
             document_api::encrypt_document(
                 self.device.auth(),
                 &self.config,
@@ -407,23 +545,83 @@ impl DocumentOps for crate::IronOxide {
                 &explicit_groups,
                 policy_grants.as_ref(),
                 &self.policy_eval_cache,
+                &self.group_key_cache,
+                &self.user_key_cache,
+                encrypt_opts.cipher_suite,
+                encrypt_opts.header_aad,
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::DocumentEncrypt,
-        )
-        .await?
+            self.config.metrics_sink.as_ref(),
+        );
+        let encrypt = metrics::with_bytes_encrypted(document_data.len(), encrypt);
+        internal::with_validate_only(encrypt_opts.validate_only, encrypt).await?
+    }
+
+    async fn document_encrypt_unique(
+        &self,
+        document_data: &[u8],
+        encrypt_opts: &DocumentEncryptOpts,
+    ) -> Result<DocumentEncryptUniqueResult> {
+        let (explicit_users, explicit_groups, grant_to_author, policy_grant) =
+            partition_grants(&encrypt_opts.grants);
+        let hash = document_api::content_hash(
+            self.device.device_key().local()?,
+            document_data,
+            grant_to_author,
+            &explicit_users,
+            &explicit_groups,
+            policy_grant.as_ref(),
+        );
+        let index_config = &self.config.unique_encryption;
+        let indexed =
+            index_config.ttl != std::time::Duration::from_secs(0) && index_config.max_entries != 0;
+        if indexed {
+            if let Some(existing) = self.content_hash_index.get(index_config, &hash) {
+                return Ok(DocumentEncryptUniqueResult::AlreadyEncrypted(existing));
+            }
+        }
+        let encrypted = self.document_encrypt(document_data, encrypt_opts).await?;
+        // a partially granted document doesn't have the requested audience, so it isn't a duplicate
+        if indexed && encrypted.access_errs().is_empty() {
+            self.content_hash_index
+                .insert(index_config, hash, encrypted.id().clone());
+        }
+        Ok(DocumentEncryptUniqueResult::Encrypted(encrypted))
     }
 
     async fn document_decrypt(&self, encrypted_document: &[u8]) -> Result<DocumentDecryptResult> {
         add_optional_timeout(
-            document_api::decrypt_document(
+            document_api::decrypt_document_cached(
                 self.device.auth(),
                 &self.recrypt,
-                self.device.device_private_key(),
+                self.device.device_key(),
                 encrypted_document,
+                &self.config.decryption,
+                &self.config.document_key_caching,
+                &self.document_key_cache,
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::DocumentDecrypt,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    async fn document_verify(&self, encrypted_document: &[u8]) -> Result<bool> {
+        add_optional_timeout(
+            document_api::verify_document_cached(
+                self.device.auth(),
+                &self.recrypt,
+                self.device.device_key(),
+                encrypted_document,
+                &self.config.decryption,
+                &self.config.document_key_caching,
+                &self.document_key_cache,
+            ),
+            self.config.sdk_operation_timeout,
+            SdkOperation::DocumentVerify,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -433,6 +631,7 @@ impl DocumentOps for crate::IronOxide {
             document_api::document_list(self.device.auth()),
             self.config.sdk_operation_timeout,
             SdkOperation::DocumentList,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -442,6 +641,17 @@ impl DocumentOps for crate::IronOxide {
             document_api::document_get_metadata(self.device.auth(), id),
             self.config.sdk_operation_timeout,
             SdkOperation::DocumentGetMetadata,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    async fn document_access_via(&self, id: &DocumentId) -> Result<DocumentAccessViaResult> {
+        add_optional_timeout(
+            document_api::document_access_via(self.device.auth(), id),
+            self.config.sdk_operation_timeout,
+            SdkOperation::DocumentAccessVia,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -455,19 +665,21 @@ impl DocumentOps for crate::IronOxide {
         id: &DocumentId,
         new_document_data: &[u8],
     ) -> Result<DocumentEncryptResult> {
-        add_optional_timeout(
+        self.check_writable(SdkOperation::DocumentUpdateBytes)?;
+        let update = add_optional_timeout(
             document_api::document_update_bytes(
                 self.device.auth(),
                 &self.recrypt,
-                self.device.device_private_key(),
+                self.device.device_key(),
                 &self.rng,
                 id,
                 new_document_data,
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::DocumentUpdateBytes,
-        )
-        .await?
+            self.config.metrics_sink.as_ref(),
+        );
+        metrics::with_bytes_encrypted(new_document_data.len(), update).await?
     }
 
     async fn document_update_name(
@@ -475,12 +687,16 @@ impl DocumentOps for crate::IronOxide {
         id: &DocumentId,
         name: Option<&DocumentName>,
     ) -> Result<DocumentMetadataResult> {
-        add_optional_timeout(
-            document_api::update_document_name(self.device.auth(), id, name),
-            self.config.sdk_operation_timeout,
+        self.check_writable(SdkOperation::DocumentUpdateName)?;
+        self.queue_if_unreachable(
+            update_name(self, id, name),
             SdkOperation::DocumentUpdateName,
+            || QueuedMutation::DocumentUpdateName {
+                id: id.clone(),
+                name: name.cloned(),
+            },
         )
-        .await?
+        .await
     }
 
     async fn document_grant_access(
@@ -488,22 +704,16 @@ impl DocumentOps for crate::IronOxide {
         id: &DocumentId,
         grant_list: &Vec<UserOrGroup>,
     ) -> Result<DocumentAccessResult> {
-        let (users, groups) = partition_user_or_group(grant_list);
-
-        add_optional_timeout(
-            document_api::document_grant_access(
-                self.device.auth(),
-                &self.recrypt,
-                id,
-                &self.user_master_pub_key,
-                self.device.device_private_key(),
-                &users,
-                &groups,
-            ),
-            self.config.sdk_operation_timeout,
+        self.check_writable(SdkOperation::DocumentGrantAccess)?;
+        self.queue_if_unreachable(
+            grant_access(self, id, grant_list),
             SdkOperation::DocumentGrantAccess,
+            || QueuedMutation::DocumentGrantAccess {
+                id: id.clone(),
+                grants: grant_list.clone(),
+            },
         )
-        .await?
+        .await
     }
 
     async fn document_revoke_access(
@@ -511,12 +721,96 @@ impl DocumentOps for crate::IronOxide {
         id: &DocumentId,
         revoke_list: &Vec<UserOrGroup>,
     ) -> Result<DocumentAccessResult> {
-        add_optional_timeout(
-            document_api::document_revoke_access(self.device.auth(), id, revoke_list),
-            self.config.sdk_operation_timeout,
+        self.check_writable(SdkOperation::DocumentRevokeAccess)?;
+        self.queue_if_unreachable(
+            revoke_access(self, id, revoke_list),
             SdkOperation::DocumentRevokeAccess,
+            || QueuedMutation::DocumentRevokeAccess {
+                id: id.clone(),
+                revokes: revoke_list.clone(),
+            },
         )
-        .await?
+        .await
+    }
+}
+
+/// Update a document's name, without queueing it if the service can't be reached
+pub(crate) async fn update_name(
+    sdk: &crate::IronOxide,
+    id: &DocumentId,
+    name: Option<&DocumentName>,
+) -> Result<DocumentMetadataResult> {
+    // the name is returned with a cached key
+    sdk.document_key_cache.remove(id);
+    add_optional_timeout(
+        document_api::update_document_name(sdk.device.auth(), id, name),
+        sdk.config.sdk_operation_timeout,
+        SdkOperation::DocumentUpdateName,
+        sdk.config.metrics_sink.as_ref(),
+    )
+    .await?
+}
+
+/// Grant access to a document, without queueing it if the service can't be reached
+pub(crate) async fn grant_access(
+    sdk: &crate::IronOxide,
+    id: &DocumentId,
+    grant_list: &[UserOrGroup],
+) -> Result<DocumentAccessResult> {
+    let (users, groups) = partition_user_or_group(grant_list);
+
+    add_optional_timeout(
+        document_api::document_grant_access(
+            sdk.device.auth(),
+            &sdk.recrypt,
+            id,
+            &sdk.user_master_pub_key,
+            sdk.device.device_key(),
+            &users,
+            &groups,
+        ),
+        sdk.config.sdk_operation_timeout,
+        SdkOperation::DocumentGrantAccess,
+        sdk.config.metrics_sink.as_ref(),
+    )
+    .await?
+}
+
+/// Revoke access to a document, without queueing it if the service can't be reached
+pub(crate) async fn revoke_access(
+    sdk: &crate::IronOxide,
+    id: &DocumentId,
+    revoke_list: &[UserOrGroup],
+) -> Result<DocumentAccessResult> {
+    add_optional_timeout(
+        document_api::document_revoke_access(sdk.device.auth(), id, revoke_list),
+        sdk.config.sdk_operation_timeout,
+        SdkOperation::DocumentRevokeAccess,
+        sdk.config.metrics_sink.as_ref(),
+    )
+    .await?
+}
+
+/// Split encryption grants into explicit users, explicit groups, whether to grant to the author, and
+/// the policy grant, if any.
+fn partition_grants(
+    grants: &EitherOrBoth<ExplicitGrant, PolicyGrant>,
+) -> (Vec<UserId>, Vec<GroupId>, bool, Option<PolicyGrant>) {
+    match grants {
+        EitherOrBoth::Left(explicit_grants) => {
+            let (users, groups) = partition_user_or_group(&explicit_grants.grants);
+            (users, groups, explicit_grants.grant_to_author, None)
+        }
+        EitherOrBoth::Right(policy_grant) => (vec![], vec![], false, Some(policy_grant.clone())),
+        EitherOrBoth::Both(explicit_grants, policy_grant) => {
+            let (users, groups) = partition_user_or_group(&explicit_grants.grants);
+            (
+                users,
+                groups,
+                explicit_grants.grant_to_author,
+                Some(policy_grant.clone()),
+            )
+        }
     }
 }
 
@@ -9,7 +9,7 @@ use crate::{
     document::{partition_user_or_group, DocumentEncryptOpts},
     internal,
     internal::add_optional_timeout,
-    Result, SdkOperation,
+    metrics, Result, SdkOperation,
 };
 use async_trait::async_trait;
 use itertools::EitherOrBoth;
@@ -79,7 +79,7 @@ impl DocumentAdvancedOps for crate::IronOxide {
                 }
             };
 
-        add_optional_timeout(
+        let encrypt = add_optional_timeout(
             internal::document_api::encrypt_document_unmanaged(
                 self.device.auth(),
                 &self.recrypt,
@@ -91,11 +91,14 @@ impl DocumentAdvancedOps for crate::IronOxide {
                 &explicit_users,
                 &explicit_groups,
                 policy_grants,
+                encrypt_opts.cipher_suite,
+                encrypt_opts.header_aad,
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::DocumentEncryptUnmanaged,
-        )
-        .await?
+            self.config.metrics_sink.as_ref(),
+        );
+        metrics::with_bytes_encrypted(data.len(), encrypt).await?
     }
 
     async fn document_decrypt_unmanaged(
@@ -107,12 +110,14 @@ impl DocumentAdvancedOps for crate::IronOxide {
             internal::document_api::decrypt_document_unmanaged(
                 self.device.auth(),
                 &self.recrypt,
-                self.device().device_private_key(),
+                self.device().device_key(),
                 encrypted_data,
                 encrypted_deks,
+                &self.config.decryption,
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::DocumentDecryptUnmanaged,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
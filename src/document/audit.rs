@@ -0,0 +1,145 @@
+//! Document access audit API
+//!
+//! See [DocumentAuditOps](trait.DocumentAuditOps.html) for functions to verify recorded access changes.
+
+pub use crate::internal::document_api::{
+    AccessDivergence, AccessLogEntry, AccessLogOp, DocumentAccessRestoreResult,
+    DocumentAccessSnapshot,
+};
+use crate::{
+    document::DocumentId,
+    internal::{add_optional_timeout, document_api},
+    Result, SdkOperation,
+};
+use async_trait::async_trait;
+
+/// IronOxide Document Audit Operations
+#[async_trait]
+pub trait DocumentAuditOps {
+    /// Verifies a log of recorded access changes against the current state of the webservice.
+    ///
+    /// The entries are replayed in order to compute the users and groups that each document should
+    /// be shared with. The access list of every document mentioned in the log is then fetched, and
+    /// any differences are reported. An empty result means the log and the webservice agree.
+    ///
+    /// The log itself is supplied by the caller. Verifying its signature, and that it has not been
+    /// truncated, must be done before calling this function.
+    ///
+    /// # Arguments
+    /// - `entries` - Recorded grants and revokes, oldest first. The log should include the grants
+    ///      made when each document was encrypted.
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # let document_id: DocumentId = unimplemented!();
+    /// # let user_id: UserId = unimplemented!();
+    /// let log = vec![AccessLogEntry::new(
+    ///     document_id,
+    ///     UserOrGroup::User { id: user_id },
+    ///     AccessLogOp::Grant,
+    /// )];
+    /// let divergences = sdk.document_verify_access_log(&log).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn document_verify_access_log(
+        &self,
+        entries: &[AccessLogEntry],
+    ) -> Result<Vec<AccessDivergence>>;
+
+    /// Takes a signed snapshot of the users and groups a document is currently shared with.
+    ///
+    /// The snapshot is serializable so it can be stored, and can later be passed to
+    /// [document_access_restore](trait.DocumentAuditOps.html#tymethod.document_access_restore) to undo
+    /// access changes made since it was taken.
+    ///
+    /// # Arguments
+    /// - `id` - ID of the document
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # let document_id: DocumentId = unimplemented!();
+    /// let snapshot = sdk.document_access_snapshot(&document_id).await?;
+    /// let users: Vec<UserId> = snapshot.users().to_vec();
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn document_access_snapshot(&self, id: &DocumentId) -> Result<DocumentAccessSnapshot>;
+
+    /// Grants and revokes access so a document is shared with exactly the users and groups in a snapshot.
+    ///
+    /// Users and groups missing from the document's access list are granted access first, then users
+    /// and groups that are not in the snapshot have their access revoked. The snapshot's signature is
+    /// checked before any changes are made; only the device that took the snapshot can restore it.
+    ///
+    /// # Arguments
+    /// - `snapshot` - Snapshot taken by
+    ///     [document_access_snapshot](trait.DocumentAuditOps.html#tymethod.document_access_snapshot)
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # let snapshot: DocumentAccessSnapshot = unimplemented!();
+    /// let restore_result = sdk.document_access_restore(&snapshot).await?;
+    /// let revoked = restore_result.revoked().succeeded();
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn document_access_restore(
+        &self,
+        snapshot: &DocumentAccessSnapshot,
+    ) -> Result<DocumentAccessRestoreResult>;
+}
+
+#[async_trait]
+impl DocumentAuditOps for crate::IronOxide {
+    async fn document_verify_access_log(
+        &self,
+        entries: &[AccessLogEntry],
+    ) -> Result<Vec<AccessDivergence>> {
+        add_optional_timeout(
+            document_api::verify_access_log(self.device.auth(), entries),
+            self.config.sdk_operation_timeout,
+            SdkOperation::DocumentVerifyAccessLog,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+    async fn document_access_snapshot(&self, id: &DocumentId) -> Result<DocumentAccessSnapshot> {
+        add_optional_timeout(
+            document_api::document_access_snapshot(self.device.auth(), id),
+            self.config.sdk_operation_timeout,
+            SdkOperation::DocumentAccessSnapshot,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    async fn document_access_restore(
+        &self,
+        snapshot: &DocumentAccessSnapshot,
+    ) -> Result<DocumentAccessRestoreResult> {
+        self.check_writable(SdkOperation::DocumentAccessRestore)?;
+        add_optional_timeout(
+            document_api::document_access_restore(
+                self.device.auth(),
+                &self.recrypt,
+                &self.user_master_pub_key,
+                self.device.device_key(),
+                snapshot,
+            ),
+            self.config.sdk_operation_timeout,
+            SdkOperation::DocumentAccessRestore,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+}
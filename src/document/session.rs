@@ -0,0 +1,216 @@
+//! Time-limited document decryption
+//!
+//! See [IronOxide::open_decrypt_session](../../struct.IronOxide.html#method.open_decrypt_session).
+
+use crate::{
+    document::{DocumentDecryptResult, DocumentId},
+    internal::{
+        add_optional_timeout,
+        document_api::{self, DocumentKey},
+        rt::{self, JoinHandle},
+        take_lock,
+    },
+    IronOxide, IronOxideErr, Result, SdkOperation,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Decryption session that keeps the symmetric keys of the documents it has decrypted until it expires.
+///
+/// Created by [IronOxide::open_decrypt_session](../../struct.IronOxide.html#method.open_decrypt_session).
+/// Decrypting a document a second time during the session uses the cached key, so no request is made.
+/// When the session's TTL elapses or the session is dropped, every cached key is overwritten with zeros
+/// and further decryptions through the session fail with `IronOxideErr::DecryptSessionExpired`.
+pub struct DecryptSession<'a> {
+    sdk: &'a IronOxide,
+    /// `None` if the TTL is too large to represent, in which case the session only ends when dropped
+    expires_at: Option<Instant>,
+    keys: Arc<Mutex<HashMap<DocumentId, DocumentKey>>>,
+    expiry: JoinHandle<()>,
+}
+
+impl IronOxide {
+    /// Opens a decryption session that caches document keys for `ttl`.
+    ///
+    /// Use this to bound how long decrypted document keys are resident in memory, for example while a
+    /// user is interacting with a set of documents. Keys can be fetched up front with
+    /// [DecryptSession::preload](document/session/struct.DecryptSession.html#method.preload).
+    ///
+    /// The session's keys are cleared when it expires by a task spawned onto the Tokio runtime this is
+    /// awaited on, so it must be awaited within one unless the `runtime-compat` feature is enabled.
+    ///
+    /// # Arguments
+    /// - `ttl` - How long the session's keys are kept
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # let encrypted_doc: Vec<u8> = unimplemented!();
+    /// let session = sdk
+    ///     .open_decrypt_session(std::time::Duration::from_secs(300))
+    ///     .await;
+    /// let decrypted = session.document_decrypt(&encrypted_doc).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn open_decrypt_session(&self, ttl: Duration) -> DecryptSession<'_> {
+        let keys: Arc<Mutex<HashMap<DocumentId, DocumentKey>>> = Default::default();
+        let expiry = {
+            let keys = keys.clone();
+            rt::spawn(async move {
+                rt::sleep(ttl).await;
+                take_lock(&keys).clear();
+            })
+        };
+        DecryptSession {
+            sdk: self,
+            expires_at: Instant::now().checked_add(ttl),
+            keys,
+            expiry,
+        }
+    }
+}
+
+impl<'a> DecryptSession<'a> {
+    /// Whether the session's TTL has elapsed. An expired session holds no keys.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| Instant::now() >= expires_at)
+    }
+
+    /// Number of document keys the session is holding
+    pub fn len(&self) -> usize {
+        take_lock(&self.keys).len()
+    }
+
+    /// Returns `true` if the session isn't holding any document keys
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fetches and decrypts the keys for documents that will be decrypted during the session.
+    ///
+    /// The keys are fetched a few at a time and decrypted in parallel on a pool of threads, one per core.
+    /// Fails if any document's key can't be decrypted by the calling user, in which case none of the
+    /// keys are kept.
+    ///
+    /// # Arguments
+    /// - `ids` - IDs of the documents to fetch keys for
+    pub async fn preload(&self, ids: &[DocumentId]) -> Result<()> {
+        self.check_expired()?;
+        let missing: Vec<&DocumentId> = {
+            let keys = take_lock(&self.keys);
+            ids.iter().filter(|id| !keys.contains_key(id)).collect()
+        };
+        let fetched = add_optional_timeout(
+            document_api::get_document_keys(
+                self.sdk.device.auth(),
+                self.sdk.device.device_key(),
+                &missing,
+                &self.sdk.config.decryption,
+                self.sdk.config.request_batching.max_concurrency,
+            ),
+            self.sdk.config.sdk_operation_timeout,
+            SdkOperation::DecryptSessionPreload,
+            self.sdk.config.metrics_sink.as_ref(),
+        )
+        .await??;
+        fetched.into_iter().try_for_each(|key| self.insert(key))
+    }
+
+    /// Decrypts a document, using the session's key for it if there is one.
+    ///
+    /// See [document_decrypt](../trait.DocumentOps.html#tymethod.document_decrypt).
+    ///
+    /// # Arguments
+    /// - `encrypted_document` - Encrypted document bytes
+    pub async fn document_decrypt(
+        &self,
+        encrypted_document: &[u8],
+    ) -> Result<DocumentDecryptResult> {
+        self.check_expired()?;
+        let id = document_api::get_id_from_bytes(encrypted_document)?;
+        if let Some(key) = take_lock(&self.keys).get(&id) {
+            return key.decrypt(encrypted_document);
+        }
+        let key = add_optional_timeout(
+            self.fetch_key(&id),
+            self.sdk.config.sdk_operation_timeout,
+            SdkOperation::DocumentDecrypt,
+            self.sdk.config.metrics_sink.as_ref(),
+        )
+        .await??;
+        let decrypted = key.decrypt(encrypted_document)?;
+        self.insert(key)?;
+        Ok(decrypted)
+    }
+
+    /// Ends the session now, overwriting its keys with zeros. Dropping the session does the same.
+    pub fn close(self) {}
+
+    async fn fetch_key(&self, id: &DocumentId) -> Result<DocumentKey> {
+        document_api::get_document_key(
+            self.sdk.device.auth(),
+            &self.sdk.recrypt,
+            self.sdk.device.device_key(),
+            id,
+            &self.sdk.config.decryption,
+        )
+        .await
+    }
+
+    /// Cache `key` unless the session expired while it was being fetched.
+    fn insert(&self, key: DocumentKey) -> Result<()> {
+        let mut keys = take_lock(&self.keys);
+        // checked while holding the lock, so a key can't be added after the expiry task cleared the cache
+        self.check_expired()?;
+        keys.insert(key.id().clone(), key);
+        Ok(())
+    }
+
+    fn check_expired(&self) -> Result<()> {
+        if self.is_expired() {
+            Err(IronOxideErr::DecryptSessionExpired)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for DecryptSession<'_> {
+    fn drop(&mut self) {
+        self.expiry.abort();
+        take_lock(&self.keys).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        internal::{tests::create_test_sdk, IronOxideErr},
+        Result,
+    };
+    use galvanic_assert::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn decrypt_session_expires() -> Result<()> {
+        let sdk = create_test_sdk()?;
+        let session = sdk.open_decrypt_session(Duration::from_millis(10)).await;
+        assert!(!session.is_expired());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(session.is_expired());
+        assert!(session.is_empty());
+        assert_that!(
+            &session.preload(&[]).await.unwrap_err(),
+            is_variant!(IronOxideErr::DecryptSessionExpired)
+        );
+        Ok(())
+    }
+}
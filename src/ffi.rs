@@ -0,0 +1,534 @@
+//! C foreign function interface
+//!
+//! Functions with a C ABI for backends that aren't written in Rust, such as C, Go through cgo, or Swift.
+//! The declarations can be generated with [cbindgen](https://github.com/eqrion/cbindgen) using the
+//! `cbindgen.toml` at the root of the repository.
+//!
+//! An SDK instance is an opaque `IronOxideHandle`, created with `ironoxide_initialize` and freed with
+//! `ironoxide_free`. Each handle owns a runtime that its calls block on, like a
+//! [BlockingIronOxide](../blocking/struct.BlockingIronOxide.html), and can be used from multiple threads
+//! at once. Data is passed in as pointers and lengths, and returned in an `IronOxideBuffer` that must be
+//! freed with `ironoxide_buffer_free`. Structured results, such as lists of devices, are returned as JSON.
+//!
+//! Every function returns an `IronOxideStatus`. When it isn't `Ok`, a message describing the error can be
+//! read with `ironoxide_last_error` on the same thread.
+//!
+//! To build a library that can be linked from other languages, build the crate as a `cdylib` or
+//! `staticlib`, for example with `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! # Optional
+//! This requires the optional `ffi` feature to be enabled, which also enables `blocking`.
+
+use crate::{
+    blocking::{self, BlockingIronOxide},
+    prelude::*,
+    Result,
+};
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    ffi::CStr,
+    os::raw::c_char,
+    panic::{catch_unwind, AssertUnwindSafe},
+    slice,
+};
+use zeroize::Zeroize;
+
+thread_local! {
+    /// Message of the last error returned by an FFI function on this thread
+    static LAST_ERROR: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Outcome of an FFI function
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IronOxideStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// An argument was null, not valid UTF-8, or not a valid ID or JSON document
+    InvalidArgument = 1,
+    /// The SDK returned an error
+    Error = 2,
+    /// The SDK panicked. The handle the call was made with shouldn't be used again.
+    Panic = 3,
+}
+
+/// SDK instance for a device. Opaque to callers.
+pub struct IronOxideHandle(BlockingIronOxide);
+
+/// Bytes returned by the SDK, which must be freed with `ironoxide_buffer_free`
+#[repr(C)]
+#[derive(Debug)]
+pub struct IronOxideBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl IronOxideBuffer {
+    fn new(bytes: Vec<u8>) -> IronOxideBuffer {
+        let len = bytes.len();
+        IronOxideBuffer {
+            data: Box::into_raw(bytes.into_boxed_slice()) as *mut u8,
+            len,
+        }
+    }
+}
+
+/// Failure of an FFI function, before it is reported as a status and the last error
+enum FfiErr {
+    InvalidArgument(String),
+    Sdk(IronOxideErr),
+}
+
+impl From<IronOxideErr> for FfiErr {
+    fn from(e: IronOxideErr) -> Self {
+        FfiErr::Sdk(e)
+    }
+}
+
+type FfiResult<T> = std::result::Result<T, FfiErr>;
+
+fn invalid_argument<T>(name: &str, reason: &str) -> FfiResult<T> {
+    Err(FfiErr::InvalidArgument(format!("{} {}", name, reason)))
+}
+
+/// Run `f`, recording its error as the last error of the thread and converting its outcome to a status
+fn run<F: FnOnce() -> FfiResult<()>>(f: F) -> IronOxideStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => (IronOxideStatus::Ok, None),
+        Ok(Err(FfiErr::InvalidArgument(message))) => {
+            (IronOxideStatus::InvalidArgument, Some(message))
+        }
+        Ok(Err(FfiErr::Sdk(e))) => (IronOxideStatus::Error, Some(e.to_string())),
+        // panics must not unwind into the caller's frames
+        Err(_) => (
+            IronOxideStatus::Panic,
+            Some("the SDK panicked while handling the call".to_string()),
+        ),
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    status
+}
+
+unsafe fn handle<'a>(handle: *const IronOxideHandle) -> FfiResult<&'a BlockingIronOxide> {
+    handle
+        .as_ref()
+        .map(|h| &h.0)
+        .map_or_else(|| invalid_argument("handle", "is null"), Ok)
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize, name: &str) -> FfiResult<&'a [u8]> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        invalid_argument(name, "is null")
+    } else {
+        Ok(slice::from_raw_parts(data, len))
+    }
+}
+
+unsafe fn string<'a>(s: *const c_char, name: &str) -> FfiResult<&'a str> {
+    if s.is_null() {
+        invalid_argument(name, "is null")
+    } else {
+        CStr::from_ptr(s)
+            .to_str()
+            .or_else(|_| invalid_argument(name, "is not valid UTF-8"))
+    }
+}
+
+unsafe fn strings<'a>(
+    array: *const *const c_char,
+    len: usize,
+    name: &str,
+) -> FfiResult<Vec<&'a str>> {
+    if len == 0 {
+        Ok(vec![])
+    } else if array.is_null() {
+        invalid_argument(name, "is null")
+    } else {
+        slice::from_raw_parts(array, len)
+            .iter()
+            .map(|s| string(*s, name))
+            .collect()
+    }
+}
+
+/// Parse an ID, reporting a malformed one as an invalid argument
+fn id<'a, T: TryFrom<&'a str, Error = IronOxideErr>>(s: &'a str, name: &str) -> FfiResult<T> {
+    T::try_from(s).or_else(|e| invalid_argument(name, &e.to_string()))
+}
+
+/// Check that an output pointer can be written to before any work is done
+unsafe fn out<'a, T>(out: *mut T) -> FfiResult<&'a mut T> {
+    out.as_mut()
+        .map_or_else(|| invalid_argument("out", "is null"), Ok)
+}
+
+fn json(value: serde_json::Value) -> Vec<u8> {
+    // serializing a Value can't fail
+    serde_json::to_vec(&value).unwrap_or_default()
+}
+
+/// Initializes the SDK with a device and writes its handle to `out`.
+///
+/// `device_context_json` is a `DeviceContext` serialized as JSON, such as the one returned by
+/// `ironoxide_generate_new_device`. `config_json` is an `IronOxideConfig` serialized as JSON, or null for the
+/// default configuration.
+///
+/// # Safety
+/// Pointers must be null or valid for their lengths. `out` must be valid to write a handle to.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_initialize(
+    device_context_json: *const u8,
+    device_context_json_len: usize,
+    config_json: *const u8,
+    config_json_len: usize,
+    out: *mut *mut IronOxideHandle,
+) -> IronOxideStatus {
+    run(|| {
+        let out = self::out(out)?;
+        let device_context: DeviceContext = serde_json::from_slice(bytes(
+            device_context_json,
+            device_context_json_len,
+            "device_context_json",
+        )?)
+        .or_else(|e| invalid_argument("device_context_json", &e.to_string()))?;
+        let config: IronOxideConfig = if config_json.is_null() {
+            Default::default()
+        } else {
+            serde_json::from_slice(bytes(config_json, config_json_len, "config_json")?)
+                .or_else(|e| invalid_argument("config_json", &e.to_string()))?
+        };
+        let sdk = blocking::initialize(&device_context, &config)?;
+        *out = Box::into_raw(Box::new(IronOxideHandle(sdk)));
+        Ok(())
+    })
+}
+
+/// Frees a handle created by `ironoxide_initialize`. Does nothing if `handle` is null.
+///
+/// # Safety
+/// `handle` must be null or a handle that hasn't been freed, and mustn't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_free(handle: *mut IronOxideHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Frees a buffer returned by the SDK, overwriting its bytes with zeros first. Does nothing if its data
+/// is null.
+///
+/// # Safety
+/// `buffer` must have been returned by the SDK and not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_buffer_free(buffer: IronOxideBuffer) {
+    if !buffer.data.is_null() {
+        let mut bytes =
+            Box::from_raw(slice::from_raw_parts_mut(buffer.data, buffer.len) as *mut [u8]);
+        bytes.zeroize();
+    }
+}
+
+/// Writes the message of the last error returned on this thread to `out`, as UTF-8. The buffer is
+/// empty if the last call succeeded.
+///
+/// # Safety
+/// `out` must be valid to write a buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_last_error(out: *mut IronOxideBuffer) -> IronOxideStatus {
+    // not run through `run`, which would replace the error being read
+    match out.as_mut() {
+        Some(out) => {
+            let message = LAST_ERROR.with(|last| last.borrow().clone().unwrap_or_default());
+            *out = IronOxideBuffer::new(message.into_bytes());
+            IronOxideStatus::Ok
+        }
+        None => IronOxideStatus::InvalidArgument,
+    }
+}
+
+/// Encrypts `data` as a new document that the calling user can decrypt, and writes the encrypted document
+/// to `out`. See [document_encrypt](../document/trait.DocumentOps.html#tymethod.document_encrypt).
+///
+/// # Safety
+/// `handle` must be a live handle, `data` must be null or valid for `data_len` bytes, and `out` must be
+/// valid to write a buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_document_encrypt(
+    handle: *const IronOxideHandle,
+    data: *const u8,
+    data_len: usize,
+    out: *mut IronOxideBuffer,
+) -> IronOxideStatus {
+    run(|| {
+        let out = self::out(out)?;
+        let sdk = self::handle(handle)?;
+        let result = sdk.document_encrypt(bytes(data, data_len, "data")?, &Default::default())?;
+        *out = IronOxideBuffer::new(result.into_encrypted_data());
+        Ok(())
+    })
+}
+
+/// Decrypts an encrypted document and writes its data to `out`. See
+/// [document_decrypt](../document/trait.DocumentOps.html#tymethod.document_decrypt).
+///
+/// # Safety
+/// `handle` must be a live handle, `encrypted` must be null or valid for `encrypted_len` bytes, and `out`
+/// must be valid to write a buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_document_decrypt(
+    handle: *const IronOxideHandle,
+    encrypted: *const u8,
+    encrypted_len: usize,
+    out: *mut IronOxideBuffer,
+) -> IronOxideStatus {
+    run(|| {
+        let out = self::out(out)?;
+        let sdk = self::handle(handle)?;
+        let result = sdk.document_decrypt(bytes(encrypted, encrypted_len, "encrypted")?)?;
+        *out = IronOxideBuffer::new(result.into_decrypted_data());
+        Ok(())
+    })
+}
+
+/// Grants users and groups access to a document. Writes the result to `out` as JSON, with the users and
+/// groups that `succeeded` and those that `failed` and why. See
+/// [document_grant_access](../document/trait.DocumentOps.html#tymethod.document_grant_access).
+///
+/// # Safety
+/// `handle` must be a live handle, `document_id` must be a NUL-terminated string, `user_ids` and
+/// `group_ids` must be null or valid for their lengths of NUL-terminated strings, and `out` must be valid
+/// to write a buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_document_grant_access(
+    handle: *const IronOxideHandle,
+    document_id: *const c_char,
+    user_ids: *const *const c_char,
+    user_ids_len: usize,
+    group_ids: *const *const c_char,
+    group_ids_len: usize,
+    out: *mut IronOxideBuffer,
+) -> IronOxideStatus {
+    run(|| {
+        edit_access(
+            handle,
+            document_id,
+            (user_ids, user_ids_len),
+            (group_ids, group_ids_len),
+            out,
+            BlockingIronOxide::document_grant_access,
+        )
+    })
+}
+
+/// Revokes users' and groups' access to a document. Writes the result to `out` as JSON, like
+/// `ironoxide_document_grant_access`. See
+/// [document_revoke_access](../document/trait.DocumentOps.html#tymethod.document_revoke_access).
+///
+/// # Safety
+/// Same as `ironoxide_document_grant_access`.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_document_revoke_access(
+    handle: *const IronOxideHandle,
+    document_id: *const c_char,
+    user_ids: *const *const c_char,
+    user_ids_len: usize,
+    group_ids: *const *const c_char,
+    group_ids_len: usize,
+    out: *mut IronOxideBuffer,
+) -> IronOxideStatus {
+    run(|| {
+        edit_access(
+            handle,
+            document_id,
+            (user_ids, user_ids_len),
+            (group_ids, group_ids_len),
+            out,
+            BlockingIronOxide::document_revoke_access,
+        )
+    })
+}
+
+unsafe fn edit_access(
+    handle: *const IronOxideHandle,
+    document_id: *const c_char,
+    user_ids: (*const *const c_char, usize),
+    group_ids: (*const *const c_char, usize),
+    out: *mut IronOxideBuffer,
+    edit: fn(&BlockingIronOxide, &DocumentId, &Vec<UserOrGroup>) -> Result<DocumentAccessResult>,
+) -> FfiResult<()> {
+    let out = self::out(out)?;
+    let sdk = self::handle(handle)?;
+    let document_id: DocumentId = id(string(document_id, "document_id")?, "document_id")?;
+    let users = strings(user_ids.0, user_ids.1, "user_ids")?
+        .into_iter()
+        .map(|user| id::<UserId>(user, "user_ids").map(UserOrGroup::from));
+    let groups = strings(group_ids.0, group_ids.1, "group_ids")?
+        .into_iter()
+        .map(|group| id::<GroupId>(group, "group_ids").map(UserOrGroup::from));
+    let users_and_groups = users.chain(groups).collect::<FfiResult<Vec<_>>>()?;
+    let result = edit(sdk, &document_id, &users_and_groups)?;
+    let failed: Vec<_> = result
+        .failed()
+        .iter()
+        .map(|failure| {
+            serde_json::json!({ "userOrGroup": failure.user_or_group, "error": failure.err })
+        })
+        .collect();
+    *out = IronOxideBuffer::new(json(
+        serde_json::json!({ "succeeded": result.succeeded(), "failed": failed }),
+    ));
+    Ok(())
+}
+
+/// Writes the calling user's devices to `out` as a JSON array. See
+/// [user_list_devices](../user/trait.UserOps.html#tymethod.user_list_devices).
+///
+/// # Safety
+/// `handle` must be a live handle and `out` must be valid to write a buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_user_list_devices(
+    handle: *const IronOxideHandle,
+    out: *mut IronOxideBuffer,
+) -> IronOxideStatus {
+    run(|| {
+        let out = self::out(out)?;
+        let sdk = self::handle(handle)?;
+        let devices: Vec<_> = sdk
+            .user_list_devices()?
+            .result()
+            .iter()
+            .map(|device| {
+                serde_json::json!({
+                    "id": device.id(),
+                    "name": device.name(),
+                    "created": device.created(),
+                    "lastUpdated": device.last_updated(),
+                    "isCurrentDevice": device.is_current_device(),
+                })
+            })
+            .collect();
+        *out = IronOxideBuffer::new(json(serde_json::Value::from(devices)));
+        Ok(())
+    })
+}
+
+/// Deletes one of the calling user's devices, or the handle's own device if `device_id` is zero. See
+/// [user_delete_device](../user/trait.UserOps.html#tymethod.user_delete_device).
+///
+/// # Safety
+/// `handle` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_user_delete_device(
+    handle: *const IronOxideHandle,
+    device_id: u64,
+) -> IronOxideStatus {
+    run(|| {
+        let sdk = self::handle(handle)?;
+        let device_id = if device_id == 0 {
+            None
+        } else {
+            Some(
+                DeviceId::try_from(device_id)
+                    .or_else(|e| invalid_argument("device_id", &e.to_string()))?,
+            )
+        };
+        sdk.user_delete_device(device_id.as_ref())?;
+        Ok(())
+    })
+}
+
+/// Generates a new device for the user identified by `jwt`, and writes its `DeviceContext` to `out` as
+/// JSON, which can be passed to `ironoxide_initialize`. `device_name` may be null. See
+/// [generate_new_device](../user/trait.UserOps.html#tymethod.generate_new_device).
+///
+/// # Safety
+/// `jwt` and `password` must be NUL-terminated strings, `device_name` must be null or a NUL-terminated
+/// string, and `out` must be valid to write a buffer to.
+#[cfg(not(feature = "disable-password-escrow"))]
+#[no_mangle]
+pub unsafe extern "C" fn ironoxide_generate_new_device(
+    jwt: *const c_char,
+    password: *const c_char,
+    device_name: *const c_char,
+    out: *mut IronOxideBuffer,
+) -> IronOxideStatus {
+    run(|| {
+        let out = self::out(out)?;
+        let jwt =
+            Jwt::new(string(jwt, "jwt")?).or_else(|e| invalid_argument("jwt", &e.to_string()))?;
+        let device_name = if device_name.is_null() {
+            None
+        } else {
+            Some(id::<DeviceName>(
+                string(device_name, "device_name")?,
+                "device_name",
+            )?)
+        };
+        let device = BlockingIronOxide::generate_new_device(
+            &jwt,
+            string(password, "password")?,
+            &DeviceCreateOpts::default().with_device_name(device_name),
+            None,
+        )?;
+        let device_context = serde_json::to_vec(&DeviceContext::from(device))
+            .map_err(|e| IronOxideErr::DeviceContextSourceError(e.to_string()))?;
+        *out = IronOxideBuffer::new(device_context);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    unsafe fn last_error() -> String {
+        let mut message = IronOxideBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        assert_eq!(ironoxide_last_error(&mut message), IronOxideStatus::Ok);
+        let text =
+            String::from_utf8_lossy(slice::from_raw_parts(message.data, message.len)).into_owned();
+        ironoxide_buffer_free(message);
+        text
+    }
+
+    #[test]
+    fn invalid_arguments_are_reported() {
+        unsafe {
+            let mut out = IronOxideBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            let status = ironoxide_document_decrypt(ptr::null(), ptr::null(), 0, &mut out);
+            assert_eq!(status, IronOxideStatus::InvalidArgument);
+            assert_eq!(last_error(), "handle is null");
+
+            let mut handle = ptr::null_mut();
+            let device = b"not a device";
+            let status =
+                ironoxide_initialize(device.as_ptr(), device.len(), ptr::null(), 0, &mut handle);
+            assert_eq!(status, IronOxideStatus::InvalidArgument);
+            assert!(handle.is_null());
+            assert!(last_error().starts_with("device_context_json"));
+
+            assert_eq!(
+                ironoxide_user_delete_device(ptr::null(), 0),
+                IronOxideStatus::InvalidArgument
+            );
+            // a successful call clears the last error
+            run(|| Ok(()));
+            assert_eq!(last_error(), "");
+        }
+    }
+
+    #[test]
+    fn panics_are_caught() {
+        let status = run(|| panic!("boom"));
+        assert_eq!(status, IronOxideStatus::Panic);
+    }
+}
@@ -3,21 +3,48 @@
 //! See [GroupOps](trait.GroupOps.html) for group functions and key terms.
 
 pub use crate::internal::group_api::{
-    GroupAccessEditErr, GroupAccessEditResult, GroupCreateResult, GroupGetResult, GroupId,
-    GroupListResult, GroupMetaResult, GroupName, GroupUpdatePrivateKeyResult,
+    GroupAccessEditErr, GroupAccessEditErrKind, GroupAccessEditResult, GroupAdminEditResult,
+    GroupAdminRemovalApproval, GroupAttributes, GroupCreateResult, GroupDescription,
+    GroupEffectiveMembersResult, GroupGetResult, GroupId, GroupImportResult, GroupListResult,
+    GroupMemberGroupEditErr, GroupMemberGroupsEditResult, GroupMetaResult, GroupName,
+    GroupSnapshot, GroupUpdatePrivateKeyResult,
+};
+#[cfg(feature = "unstable-group-endpoints")]
+pub use crate::internal::group_api::{
+    GroupDocument, GroupDocumentListResult, GroupInvite, GroupInviteListResult,
 };
 use crate::{
     common::SdkOperation,
-    internal::{add_optional_timeout, group_api, group_api::GroupCreateOptsStd},
+    internal::{
+        self, add_optional_timeout, group_api,
+        group_api::{GroupCreateOptsStd, MemberAddMode},
+    },
+    offline::QueuedMutation,
     user::UserId,
     IronOxideErr, Result,
 };
 use async_trait::async_trait;
+#[cfg(not(feature = "disable-password-escrow"))]
+use std::convert::TryInto;
 use vec1::Vec1;
 
 /// Options for group creation.
 ///
-/// Default values are provided with [GroupCreateOpts::default()](struct.GroupCreateOpts.html#method.default)
+/// Default values are provided with [GroupCreateOpts::default()](struct.GroupCreateOpts.html#method.default).
+/// Individual options can be changed from there with the `with_*` methods, which is easier to read than
+/// the positional [GroupCreateOpts::new](struct.GroupCreateOpts.html#method.new).
+///
+/// ```
+/// # fn run() -> Result<(), ironoxide::IronOxideErr> {
+/// # use ironoxide::prelude::*;
+/// # use std::convert::TryFrom;
+/// let opts = GroupCreateOpts::default()
+///     .with_id(Some(GroupId::try_from("empl412")?))
+///     .with_add_as_member(false)
+///     .with_needs_rotation(true);
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct GroupCreateOpts {
     /// ID of the group. If `None`, the server will assign the ID.
@@ -41,6 +68,13 @@ pub struct GroupCreateOpts {
     /// - `true` - group's private key will be marked for rotation
     /// - `false` (default) - group's private key will not be marked for rotation
     needs_rotation: bool,
+    /// Description of the group.
+    description: Option<GroupDescription>,
+    /// Key/value attributes of the group.
+    attributes: GroupAttributes,
+    /// - `true` - members' and admins' keys will be looked up, but the group will not be created
+    /// - `false` (default) - the group will be created
+    validate_only: bool,
 }
 
 impl GroupCreateOpts {
@@ -88,9 +122,89 @@ impl GroupCreateOpts {
             admins,
             members,
             needs_rotation,
+            description: None,
+            attributes: GroupAttributes::default(),
+            validate_only: false,
         }
     }
 
+    /// Sets the ID of the group. Defaults to `None`, in which case the server will assign the ID.
+    pub fn with_id(mut self, id: Option<GroupId>) -> GroupCreateOpts {
+        self.id = id;
+        self
+    }
+
+    /// Sets the name of the group. Defaults to `None`.
+    pub fn with_name(mut self, name: Option<GroupName>) -> GroupCreateOpts {
+        self.name = name;
+        self
+    }
+
+    /// Sets whether the creating user will be added as a group admin. Defaults to `true`.
+    pub fn with_add_as_admin(mut self, add_as_admin: bool) -> GroupCreateOpts {
+        self.add_as_admin = add_as_admin;
+        self
+    }
+
+    /// Sets whether the creating user will be added as a group member. Defaults to `true`.
+    pub fn with_add_as_member(mut self, add_as_member: bool) -> GroupCreateOpts {
+        self.add_as_member = add_as_member;
+        self
+    }
+
+    /// Sets the owner of the group. Defaults to `None`, in which case the creating user will be the owner.
+    pub fn with_owner(mut self, owner: Option<UserId>) -> GroupCreateOpts {
+        self.owner = owner;
+        self
+    }
+
+    /// Sets the users to add as group admins. Defaults to no users other than the creating user.
+    pub fn with_admins(mut self, admins: Vec<UserId>) -> GroupCreateOpts {
+        self.admins = admins;
+        self
+    }
+
+    /// Sets the users to add as group members. Defaults to no users other than the creating user.
+    pub fn with_members(mut self, members: Vec<UserId>) -> GroupCreateOpts {
+        self.members = members;
+        self
+    }
+
+    /// Sets whether the group's private key will be marked for rotation. Defaults to `false`.
+    pub fn with_needs_rotation(mut self, needs_rotation: bool) -> GroupCreateOpts {
+        self.needs_rotation = needs_rotation;
+        self
+    }
+
+    /// Checks that these options would be accepted by [group_create](trait.GroupOps.html#tymethod.group_create)
+    /// when called by `calling_user`, without making a request.
+    ///
+    /// Fails with a `ValidationError` if the resulting admins list would be empty or would not contain the owner.
+    pub fn validate(&self, calling_user: &UserId) -> Result<()> {
+        self.clone().standardize(calling_user).map(|_| ())
+    }
+
+    /// Sets the description of the group. Defaults to `None`.
+    pub fn with_description(mut self, description: Option<GroupDescription>) -> GroupCreateOpts {
+        self.description = description;
+        self
+    }
+
+    /// Sets the key/value attributes of the group. Defaults to no attributes.
+    pub fn with_attributes(mut self, attributes: GroupAttributes) -> GroupCreateOpts {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Sets whether [group_create](trait.GroupOps.html#tymethod.group_create) only validates these options
+    /// and looks up the keys of the group's admins and members, stopping before the group is created.
+    /// It then fails with `IronOxideErr::ValidateOnly` if the group could have been created. Defaults to
+    /// `false`.
+    pub fn with_validate_only(mut self, validate_only: bool) -> GroupCreateOpts {
+        self.validate_only = validate_only;
+        self
+    }
+
     fn standardize(self, calling_id: &UserId) -> Result<GroupCreateOptsStd> {
         // if `add_as_member`, make sure the calling user is in the `members` list
         let standardized_members = if self.add_as_member && !self.members.contains(calling_id) {
@@ -144,6 +258,8 @@ impl GroupCreateOpts {
                 admins: non_empty_admins,
                 members: standardized_members,
                 needs_rotation: self.needs_rotation,
+                description: self.description,
+                attributes: self.attributes,
             })
         }
     }
@@ -185,15 +301,24 @@ pub trait GroupOps {
     ///
     /// # Examples
     /// ```
-    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # async fn run(sdk: &ironoxide::IronOxide) -> Result<(), ironoxide::IronOxideErr> {
     /// # use ironoxide::prelude::*;
-    /// # let sdk: IronOxide = unimplemented!();
     /// # use std::convert::TryFrom;
     /// let group_id = Some(GroupId::try_from("empl412")?);
-    /// let opts = GroupCreateOpts::new(group_id, None, true, true, None, vec![], vec![], false);
+    /// let opts = GroupCreateOpts::default().with_id(group_id);
     /// let group = sdk.group_create(&opts).await?;
+    /// # assert_eq!(group.id().id(), "empl412");
+    /// # assert!(group.is_admin() && group.is_member());
     /// # Ok(())
     /// # }
+    /// # #[cfg(all(feature = "mock", not(feature = "disable-password-escrow")))]
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), ironoxide::IronOxideErr> {
+    /// #     let mock = ironoxide::mock::MockServer::start("foobar").await?;
+    /// #     run(&mock.initialize_sdk().await?).await
+    /// # }
+    /// # #[cfg(not(all(feature = "mock", not(feature = "disable-password-escrow"))))]
+    /// # fn main() {}
     /// ```
     async fn group_create(&self, group_create_opts: &GroupCreateOpts) -> Result<GroupCreateResult>;
 
@@ -257,6 +382,110 @@ pub trait GroupOps {
         name: Option<&GroupName>,
     ) -> Result<GroupMetaResult>;
 
+    /// Modifies or removes a group's description.
+    ///
+    /// Returns the updated metadata of the group.
+    ///
+    /// Requires the `unstable-group-endpoints` feature, as the IronCore service's support for updating a
+    /// group's description isn't part of its published API. With
+    /// [IronOxideConfig.offline_queue](../config/struct.IronOxideConfig.html#structfield.offline_queue) set,
+    /// an update that fails because the service couldn't be reached is queued.
+    ///
+    /// # Arguments
+    /// - `id` - ID of the group to update
+    /// - `description` - New description for the group. Provide a `Some` to update to a new description or a `None`
+    ///      to clear the group's description
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let description = GroupDescription::try_from("Employees working at headquarters")?;
+    /// let new_metadata = sdk
+    ///     .group_update_description(&group_id, Some(&description))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_update_description(
+        &self,
+        id: &GroupId,
+        description: Option<&GroupDescription>,
+    ) -> Result<GroupMetaResult>;
+
+    /// Replaces all of a group's key/value attributes.
+    ///
+    /// Returns the updated metadata of the group.
+    ///
+    /// Requires the `unstable-group-endpoints` feature, and is queued when the service can't be reached,
+    /// like [group_update_description](trait.GroupOps.html#tymethod.group_update_description).
+    ///
+    /// # Arguments
+    /// - `id` - ID of the group to update
+    /// - `attributes` - New attributes for the group. Provide empty attributes to clear them.
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// use std::collections::BTreeMap;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let mut attributes = BTreeMap::new();
+    /// attributes.insert("location".to_string(), "HQ".to_string());
+    /// let new_metadata = sdk
+    ///     .group_update_attributes(&group_id, &GroupAttributes::try_from(attributes)?)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_update_attributes(
+        &self,
+        id: &GroupId,
+        attributes: &GroupAttributes,
+    ) -> Result<GroupMetaResult>;
+
+    /// Transfers ownership of a group to another of its administrators.
+    ///
+    /// A group always has exactly one owner, who cannot be removed as an administrator. Transferring
+    /// ownership first allows the previous owner to be removed or to leave the group.
+    ///
+    /// Returns the updated metadata of the group.
+    ///
+    /// Requires the `unstable-group-endpoints` feature, as the IronCore service's support for changing a
+    /// group's owner isn't part of its published API. With
+    /// [IronOxideConfig.offline_queue](../config/struct.IronOxideConfig.html#structfield.offline_queue) set,
+    /// a transfer that fails because the service couldn't be reached is queued.
+    ///
+    /// # Arguments
+    /// - `id` - ID of the group to update
+    /// - `new_owner` - ID of the user who will own the group. Must already be an administrator of the group.
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let new_owner = UserId::try_from("colt")?;
+    /// let new_metadata = sdk.group_transfer_ownership(&group_id, &new_owner).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_transfer_ownership(
+        &self,
+        id: &GroupId,
+        new_owner: &UserId,
+    ) -> Result<GroupMetaResult>;
+
     /// Rotates a group's private key while leaving its public key unchanged.
     ///
     /// There's no black magic here! This is accomplished via multi-party computation with the
@@ -283,7 +512,9 @@ pub trait GroupOps {
 
     /// Adds members to a group.
     ///
-    /// Returns successful and failed additions.
+    /// Returns successful and failed additions. Large lists of users are sent to the IronCore service in
+    /// several smaller requests. If one of those requests fails, the users it contained are reported as
+    /// failed additions.
     ///
     /// # Arguments
     /// - `id` - ID of the group to add members to
@@ -313,6 +544,145 @@ pub trait GroupOps {
         users: &[UserId],
     ) -> Result<GroupAccessEditResult>;
 
+    /// Invites users to become members of a group.
+    ///
+    /// Invited users are not members until they accept with [group_accept_invite](trait.GroupOps.html#tymethod.group_accept_invite),
+    /// and can see their pending invitations with [group_list_invites](trait.GroupOps.html#tymethod.group_list_invites).
+    /// Returns successful and failed invitations.
+    ///
+    /// Requires the `unstable-group-endpoints` feature, as the IronCore service's invitation endpoints aren't
+    /// part of its published API.
+    ///
+    /// # Arguments
+    /// - `id` - ID of the group to invite users to
+    /// - `users` - List of users to invite as group members
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let user = UserId::try_from("colt")?;
+    /// let invite_result = sdk.group_invite_members(&group_id, &vec![user]).await?;
+    /// let invited: Vec<UserId> = invite_result.succeeded().to_vec();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// This operation supports partial success. If the request succeeds, then the resulting `GroupAccessEditResult`
+    /// will indicate which invitations succeeded and which failed, and it will provide an explanation for each failure.
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_invite_members(
+        &self,
+        id: &GroupId,
+        users: &[UserId],
+    ) -> Result<GroupAccessEditResult>;
+
+    /// Lists the calling user's pending invitations to become a member of groups.
+    ///
+    /// Requires the `unstable-group-endpoints` feature, like [group_invite_members](trait.GroupOps.html#tymethod.group_invite_members).
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// let invites = sdk.group_list_invites().await?;
+    /// let invited_group_ids: Vec<&GroupId> = invites.result().iter().map(|invite| invite.group_id()).collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_list_invites(&self) -> Result<GroupInviteListResult>;
+
+    /// Accepts the calling user's invitation to become a member of a group.
+    ///
+    /// Requires the `unstable-group-endpoints` feature, like [group_invite_members](trait.GroupOps.html#tymethod.group_invite_members).
+    ///
+    /// # Arguments
+    /// - `id` - ID of the group whose invitation to accept
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let group = sdk.group_accept_invite(&group_id).await?;
+    /// assert!(group.is_member());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the calling user has no pending invitation to the group.
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_accept_invite(&self, id: &GroupId) -> Result<GroupMetaResult>;
+
+    /// Adds groups as members of a group.
+    ///
+    /// Members of an added group become members of the group, so documents shared with the group can be
+    /// decrypted by them. Groups can be nested to any depth. A group that would create a membership cycle,
+    /// because it is the group itself or already contains the group through its own member groups, is
+    /// reported as a failed addition.
+    ///
+    /// Requires the `unstable-group-endpoints` feature, as the IronCore service's endpoint for adding member
+    /// groups isn't part of its published API.
+    ///
+    /// # Arguments
+    /// - `id` - ID of the group to add member groups to
+    /// - `groups` - List of groups to add as members
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("engineering")?;
+    /// let team = GroupId::try_from("platform-team")?;
+    /// let add_result = sdk.group_add_member_groups(&group_id, &vec![team]).await?;
+    /// let new_member_groups: Vec<GroupId> = add_result.succeeded().to_vec();
+    /// let failures: Vec<GroupMemberGroupEditErr> = add_result.failed().to_vec();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// This operation supports partial success. If the request succeeds, then the resulting `GroupMemberGroupsEditResult`
+    /// will indicate which additions succeeded and which failed, and it will provide an explanation for each failure.
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_add_member_groups(
+        &self,
+        id: &GroupId,
+        groups: &[GroupId],
+    ) -> Result<GroupMemberGroupsEditResult>;
+
+    /// Lists the users who are members of a group, either directly or through its nested member groups.
+    ///
+    /// Nested groups whose member lists the calling user can't view are reported separately.
+    ///
+    /// # Arguments
+    /// - `id` - ID of the group to expand
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("engineering")?;
+    /// let effective = sdk.group_effective_members(&group_id).await?;
+    /// let members: Vec<UserId> = effective.members().to_vec();
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn group_effective_members(&self, id: &GroupId) -> Result<GroupEffectiveMembersResult>;
+
     /// Removes members from a group.
     ///
     /// Returns successful and failed removals.
@@ -347,7 +717,7 @@ pub trait GroupOps {
 
     /// Adds administrators to a group.
     ///
-    /// Returns successful and failed additions.
+    /// Returns successful and failed additions, and the group's administrators after the additions.
     ///
     /// # Arguments
     /// - `id` - ID of the group to add administrators to
@@ -364,22 +734,26 @@ pub trait GroupOps {
     /// let add_result = sdk.group_add_admins(&group_id, &vec![user]).await?;
     /// let new_admins: Vec<UserId> = add_result.succeeded().to_vec();
     /// let failures: Vec<GroupAccessEditErr> = add_result.failed().to_vec();
+    /// let all_admins: Vec<UserId> = add_result.admins().to_vec();
     /// # Ok(())
     /// # }
     /// ```
     ///
     /// # Errors
-    /// This operation supports partial success. If the request succeeds, then the resulting `GroupAccessEditResult`
+    /// This operation supports partial success. If the request succeeds, then the resulting `GroupAdminEditResult`
     /// will indicate which additions succeeded and which failed, and it will provide an explanation for each failure.
+    /// Users who are already administrators fail with `GroupAccessEditErrKind::AlreadyAdmin` without being sent
+    /// to the IronCore service.
     async fn group_add_admins(
         &self,
         id: &GroupId,
         users: &[UserId],
-    ) -> Result<GroupAccessEditResult>;
+    ) -> Result<GroupAdminEditResult>;
 
     /// Removes administrators from a group.
     ///
-    /// Returns successful and failed removals.
+    /// Returns successful and failed removals, the group's administrators after the removals, and whether
+    /// the calling user removed themselves as an administrator.
     ///
     /// # Arguments
     /// - `id` - ID of the group to remove administrators from
@@ -396,18 +770,111 @@ pub trait GroupOps {
     /// let remove_result = sdk.group_remove_admins(&group_id, &vec![user]).await?;
     /// let removed_admins: Vec<UserId> = remove_result.succeeded().to_vec();
     /// let failures: Vec<GroupAccessEditErr> = remove_result.failed().to_vec();
+    /// let still_admin: bool = !remove_result.caller_role_changed();
     /// # Ok(())
     /// # }
     /// ```
     ///
     /// # Errors
-    /// This operation supports partial success. If the request succeeds, then the resulting `GroupAccessEditResult`
+    /// Fails with `WouldOrphanGroup` if every administrator of the group would be removed. This check is
+    /// made by the SDK, not the IronCore service. Use
+    /// [group_advisory_remove_admins](trait.GroupOps.html#tymethod.group_advisory_remove_admins) with another
+    /// administrator's approval to remove them anyway.
+    ///
+    /// This operation supports partial success. If the request succeeds, then the resulting `GroupAdminEditResult`
     /// will indicate which removals succeeded and which failed, and it will provide an explanation for each failure.
+    /// Users who aren't administrators fail with `GroupAccessEditErrKind::NotAMember` without being sent
+    /// to the IronCore service.
     async fn group_remove_admins(
         &self,
         id: &GroupId,
         revoke_list: &[UserId],
-    ) -> Result<GroupAccessEditResult>;
+    ) -> Result<GroupAdminEditResult>;
+
+    /// Approves removing administrators from a group even if it would leave the group without any, as part
+    /// of an advisory, SDK-side workflow.
+    ///
+    /// The returned approval is signed with the calling user's own private key and expires after an hour.
+    /// It must be passed to [group_advisory_remove_admins](trait.GroupOps.html#tymethod.group_advisory_remove_admins)
+    /// by a different administrator than the one who made it. The IronCore service doesn't know about
+    /// approvals, so they are not an access control: any administrator can remove the others, and themselves,
+    /// without one by calling the service directly. They only keep well-behaved SDK callers from orphaning a
+    /// group by accident.
+    ///
+    /// Signing needs the calling user's private key, so this requires password escrow and is unavailable
+    /// with the `disable-password-escrow` feature.
+    ///
+    /// # Arguments
+    /// - `password` - Password to unlock the current user's private key, which signs the approval
+    /// - `id` - ID of the group
+    /// - `users` - List of users approved for removal as group administrators
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let user = UserId::try_from("colt")?;
+    /// let approval = sdk
+    ///     .group_advisory_approve_admin_removal("foobar", &group_id, &vec![user])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the calling user is not an administrator of the group, if `password` is wrong, or if the
+    /// calling user's private key has been rotated.
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn group_advisory_approve_admin_removal(
+        &self,
+        password: &str,
+        id: &GroupId,
+        users: &[UserId],
+    ) -> Result<GroupAdminRemovalApproval>;
+
+    /// Removes administrators from a group, even if it leaves the group without any, as part of an advisory,
+    /// SDK-side workflow.
+    ///
+    /// Behaves like [group_remove_admins](trait.GroupOps.html#tymethod.group_remove_admins), except that
+    /// removing every administrator is allowed when `approval` was made by a different administrator of
+    /// the group, covers every user in `revoke_list`, and has not expired.
+    ///
+    /// These checks are made by the SDK, not the IronCore service. They guard against accidentally
+    /// orphaning a group, not against a user who can bypass the SDK, so they must not be relied on as an
+    /// access control.
+    ///
+    /// # Arguments
+    /// - `id` - ID of the group to remove administrators from
+    /// - `revoke_list` - List of users to remove as group administrators
+    /// - `approval` - Approval from [group_advisory_approve_admin_removal](trait.GroupOps.html#tymethod.group_advisory_approve_admin_removal)
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # let approval: GroupAdminRemovalApproval = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let user = UserId::try_from("colt")?;
+    /// let remove_result = sdk
+    ///     .group_advisory_remove_admins(&group_id, &vec![user], &approval)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails with a `ValidationError` if the approval is needed and is not valid.
+    async fn group_advisory_remove_admins(
+        &self,
+        id: &GroupId,
+        revoke_list: &[UserId],
+        approval: &GroupAdminRemovalApproval,
+    ) -> Result<GroupAdminEditResult>;
 
     /// Deletes a group.
     ///
@@ -431,11 +898,95 @@ pub trait GroupOps {
     /// # }
     /// ```
     async fn group_delete(&self, id: &GroupId) -> Result<GroupId>;
+
+    /// Takes a snapshot of a group's name, description, attributes, owner, admins, members, and member groups.
+    ///
+    /// The snapshot is serializable so it can be stored, or passed to [group_import](trait.GroupOps.html#tymethod.group_import)
+    /// by an SDK in another segment or environment to recreate the group.
+    ///
+    /// # Arguments
+    /// `id` - ID of the group to export
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let snapshot = sdk.group_export(&group_id).await?;
+    /// let admins: Vec<UserId> = snapshot.admins().to_vec();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the calling user is not an administrator of the group.
+    async fn group_export(&self, id: &GroupId) -> Result<GroupSnapshot>;
+
+    /// Lists the documents that have been granted to a group.
+    ///
+    /// This shows which documents members would lose access to if the group were deleted or they were
+    /// removed from it. Documents granted to a member directly, or through another group, are not affected.
+    ///
+    /// Requires the `unstable-group-endpoints` feature, as the IronCore service's endpoint for listing a
+    /// group's documents isn't part of its published API.
+    ///
+    /// # Arguments
+    /// `id` - ID of the group
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # use std::convert::TryFrom;
+    /// let group_id = GroupId::try_from("empl412")?;
+    /// let documents = sdk.group_list_documents(&group_id).await?;
+    /// let document_ids: Vec<&DocumentId> = documents.result().iter().map(|doc| doc.id()).collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the calling user is not an administrator of the group.
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_list_documents(&self, id: &GroupId) -> Result<GroupDocumentListResult>;
+
+    /// Recreates a group from a snapshot taken by [group_export](trait.GroupOps.html#tymethod.group_export).
+    ///
+    /// The group is created with the snapshot's ID, name, description, attributes, and owner, with the calling
+    /// user and the owner as its admins. The owner must exist in the segment the SDK is using. The snapshot's
+    /// admins, members, and member groups are then added, and any that can't be added, such as users that
+    /// don't exist in the segment, are reported in the result instead of failing the import. Member groups are
+    /// only added with the `unstable-group-endpoints` feature, and are reported as failed without it. The calling
+    /// user stays an admin of the new group, and is only a member if the snapshot says so; remove them with
+    /// [group_remove_admins](trait.GroupOps.html#tymethod.group_remove_admins) if they shouldn't keep access.
+    ///
+    /// The new group has new keys, so documents encrypted to the original group can't be decrypted through it.
+    ///
+    /// # Arguments
+    /// `snapshot` - Snapshot of the group to create
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// # let snapshot: GroupSnapshot = unimplemented!();
+    /// let import_result = sdk.group_import(&snapshot).await?;
+    /// let group_id = import_result.group().id();
+    /// let missing_members: Vec<GroupAccessEditErr> = import_result.members().failed().to_vec();
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn group_import(&self, snapshot: &GroupSnapshot) -> Result<GroupImportResult>;
 }
 
 #[async_trait]
 impl GroupOps for crate::IronOxide {
     async fn group_create(&self, opts: &GroupCreateOpts) -> Result<GroupCreateResult> {
+        self.check_writable(SdkOperation::GroupCreate)?;
         let standard_opts = opts.clone().standardize(self.device.auth().account_id())?;
         let all_users = &standard_opts.all_users();
         let GroupCreateOptsStd {
@@ -445,9 +996,11 @@ impl GroupOps for crate::IronOxide {
             admins,
             members,
             needs_rotation,
+            description,
+            attributes,
         } = standard_opts;
 
-        add_optional_timeout(
+        let create = add_optional_timeout(
             group_api::group_create(
                 &self.recrypt,
                 self.device.auth(),
@@ -458,11 +1011,14 @@ impl GroupOps for crate::IronOxide {
                 members,
                 all_users,
                 needs_rotation,
+                description,
+                attributes,
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupCreate,
-        )
-        .await?
+            self.config.metrics_sink.as_ref(),
+        );
+        internal::with_validate_only(opts.validate_only, create).await?
     }
 
     async fn group_get_metadata(&self, id: &GroupId) -> Result<GroupGetResult> {
@@ -470,6 +1026,7 @@ impl GroupOps for crate::IronOxide {
             group_api::get_metadata(self.device.auth(), id),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupGetMetadata,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -479,6 +1036,7 @@ impl GroupOps for crate::IronOxide {
             group_api::list(self.device.auth(), None),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupList,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -488,24 +1046,84 @@ impl GroupOps for crate::IronOxide {
         id: &GroupId,
         name: Option<&GroupName>,
     ) -> Result<GroupMetaResult> {
-        add_optional_timeout(
-            group_api::update_group_name(self.device.auth(), id, name),
-            self.config.sdk_operation_timeout,
+        self.check_writable(SdkOperation::GroupUpdateName)?;
+        self.queue_if_unreachable(
+            update_name(self, id, name),
             SdkOperation::GroupUpdateName,
+            || QueuedMutation::GroupUpdateName {
+                id: id.clone(),
+                name: name.cloned(),
+            },
         )
-        .await?
+        .await
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_update_description(
+        &self,
+        id: &GroupId,
+        description: Option<&GroupDescription>,
+    ) -> Result<GroupMetaResult> {
+        self.check_writable(SdkOperation::GroupUpdateDescription)?;
+        self.queue_if_unreachable(
+            update_description(self, id, description),
+            SdkOperation::GroupUpdateDescription,
+            || QueuedMutation::GroupUpdateDescription {
+                id: id.clone(),
+                description: description.cloned(),
+            },
+        )
+        .await
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_update_attributes(
+        &self,
+        id: &GroupId,
+        attributes: &GroupAttributes,
+    ) -> Result<GroupMetaResult> {
+        self.check_writable(SdkOperation::GroupUpdateAttributes)?;
+        self.queue_if_unreachable(
+            update_attributes(self, id, attributes),
+            SdkOperation::GroupUpdateAttributes,
+            || QueuedMutation::GroupUpdateAttributes {
+                id: id.clone(),
+                attributes: attributes.clone(),
+            },
+        )
+        .await
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_transfer_ownership(
+        &self,
+        id: &GroupId,
+        new_owner: &UserId,
+    ) -> Result<GroupMetaResult> {
+        self.check_writable(SdkOperation::GroupTransferOwnership)?;
+        self.queue_if_unreachable(
+            transfer_ownership(self, id, new_owner),
+            SdkOperation::GroupTransferOwnership,
+            || QueuedMutation::GroupTransferOwnership {
+                id: id.clone(),
+                new_owner: new_owner.clone(),
+            },
+        )
+        .await
     }
 
     async fn group_rotate_private_key(&self, id: &GroupId) -> Result<GroupUpdatePrivateKeyResult> {
+        self.check_writable(SdkOperation::GroupRotatePrivateKey)?;
         add_optional_timeout(
             group_api::group_rotate_private_key(
                 &self.recrypt,
                 self.device().auth(),
                 id,
-                self.device().device_private_key(),
+                self.device().device_key(),
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupRotatePrivateKey,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -515,16 +1133,97 @@ impl GroupOps for crate::IronOxide {
         id: &GroupId,
         grant_list: &[UserId],
     ) -> Result<GroupAccessEditResult> {
+        self.check_writable(SdkOperation::GroupAddMembers)?;
         add_optional_timeout(
             group_api::group_add_members(
                 &self.recrypt,
                 self.device.auth(),
-                self.device.device_private_key(),
+                self.device.device_key(),
                 id,
                 &grant_list.to_vec(),
+                MemberAddMode::Add,
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupAddMembers,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_invite_members(
+        &self,
+        id: &GroupId,
+        users: &[UserId],
+    ) -> Result<GroupAccessEditResult> {
+        self.check_writable(SdkOperation::GroupInviteMembers)?;
+        add_optional_timeout(
+            group_api::group_add_members(
+                &self.recrypt,
+                self.device.auth(),
+                self.device.device_key(),
+                id,
+                &users.to_vec(),
+                MemberAddMode::Invite,
+            ),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupInviteMembers,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_list_invites(&self) -> Result<GroupInviteListResult> {
+        add_optional_timeout(
+            group_api::list_invites(self.device.auth()),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupListInvites,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_accept_invite(&self, id: &GroupId) -> Result<GroupMetaResult> {
+        self.check_writable(SdkOperation::GroupAcceptInvite)?;
+        add_optional_timeout(
+            group_api::accept_invite(self.device.auth(), id),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupAcceptInvite,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_add_member_groups(
+        &self,
+        id: &GroupId,
+        groups: &[GroupId],
+    ) -> Result<GroupMemberGroupsEditResult> {
+        self.check_writable(SdkOperation::GroupAddMemberGroups)?;
+        add_optional_timeout(
+            group_api::group_add_member_groups(
+                &self.recrypt,
+                self.device.auth(),
+                self.device.device_key(),
+                id,
+                groups,
+            ),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupAddMemberGroups,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    async fn group_effective_members(&self, id: &GroupId) -> Result<GroupEffectiveMembersResult> {
+        add_optional_timeout(
+            group_api::group_effective_members(self.device.auth(), id),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupEffectiveMembers,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -534,6 +1233,7 @@ impl GroupOps for crate::IronOxide {
         id: &GroupId,
         revoke_list: &[UserId],
     ) -> Result<GroupAccessEditResult> {
+        self.check_writable(SdkOperation::GroupRemoveMembers)?;
         add_optional_timeout(
             group_api::group_remove_entity(
                 self.device.auth(),
@@ -543,6 +1243,7 @@ impl GroupOps for crate::IronOxide {
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupRemoveMembers,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -551,17 +1252,19 @@ impl GroupOps for crate::IronOxide {
         &self,
         id: &GroupId,
         users: &[UserId],
-    ) -> Result<GroupAccessEditResult> {
+    ) -> Result<GroupAdminEditResult> {
+        self.check_writable(SdkOperation::GroupAddAdmins)?;
         add_optional_timeout(
             group_api::group_add_admins(
                 &self.recrypt,
                 self.device.auth(),
-                self.device.device_private_key(),
+                self.device.device_key(),
                 id,
                 &users.to_vec(),
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupAddAdmins,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -570,28 +1273,234 @@ impl GroupOps for crate::IronOxide {
         &self,
         id: &GroupId,
         revoke_list: &[UserId],
-    ) -> Result<GroupAccessEditResult> {
+    ) -> Result<GroupAdminEditResult> {
+        self.check_writable(SdkOperation::GroupRemoveAdmins)?;
         add_optional_timeout(
-            group_api::group_remove_entity(
+            group_api::group_remove_admins(
+                &self.recrypt,
                 self.device.auth(),
                 id,
                 &revoke_list.to_vec(),
-                group_api::GroupEntity::Admin,
+                None,
             ),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupRemoveAdmins,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
 
-    async fn group_delete(&self, id: &GroupId) -> Result<GroupId> {
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn group_advisory_approve_admin_removal(
+        &self,
+        password: &str,
+        id: &GroupId,
+        users: &[UserId],
+    ) -> Result<GroupAdminRemovalApproval> {
+        self.check_writable(SdkOperation::GroupAdvisoryApproveAdminRemoval)?;
         add_optional_timeout(
+            group_api::group_advisory_approve_admin_removal(
+                &self.recrypt,
+                self.device.auth(),
+                password.try_into()?,
+                self.password_derivation(),
+                id,
+                users,
+            ),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupAdvisoryApproveAdminRemoval,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    async fn group_advisory_remove_admins(
+        &self,
+        id: &GroupId,
+        revoke_list: &[UserId],
+        approval: &GroupAdminRemovalApproval,
+    ) -> Result<GroupAdminEditResult> {
+        self.check_writable(SdkOperation::GroupAdvisoryRemoveAdmins)?;
+        add_optional_timeout(
+            group_api::group_remove_admins(
+                &self.recrypt,
+                self.device.auth(),
+                id,
+                &revoke_list.to_vec(),
+                Some(approval),
+            ),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupAdvisoryRemoveAdmins,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    async fn group_delete(&self, id: &GroupId) -> Result<GroupId> {
+        self.check_writable(SdkOperation::GroupDelete)?;
+        let deleted = add_optional_timeout(
             group_api::group_delete(self.device.auth(), id),
             self.config.sdk_operation_timeout,
             SdkOperation::GroupDelete,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await??;
+        self.group_key_cache.remove(&deleted);
+        Ok(deleted)
+    }
+
+    async fn group_export(&self, id: &GroupId) -> Result<GroupSnapshot> {
+        add_optional_timeout(
+            group_api::group_export(self.device.auth(), id),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupExport,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_list_documents(&self, id: &GroupId) -> Result<GroupDocumentListResult> {
+        add_optional_timeout(
+            group_api::group_list_documents(self.device.auth(), id),
+            self.config.sdk_operation_timeout,
+            SdkOperation::GroupListDocuments,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    async fn group_import(&self, snapshot: &GroupSnapshot) -> Result<GroupImportResult> {
+        import_group(self, snapshot).await
+    }
+}
+
+/// Create the group in `snapshot` with the calling user and its owner as admins, then add its admins,
+/// members, and member groups, reporting any that can't be added.
+pub(crate) async fn import_group<G: GroupOps + Sync + ?Sized>(
+    sdk: &G,
+    snapshot: &GroupSnapshot,
+) -> Result<GroupImportResult> {
+    let opts = GroupCreateOpts::new(
+        Some(snapshot.id().clone()),
+        snapshot.name().cloned(),
+        true,
+        false,
+        Some(snapshot.owner().clone()),
+        vec![],
+        vec![],
+        false,
+    )
+    .with_description(snapshot.description().cloned())
+    .with_attributes(snapshot.attributes().clone());
+    let group = sdk.group_create(&opts).await?;
+    let new_admins: Vec<UserId> = snapshot
+        .admins()
+        .iter()
+        .filter(|admin| !group.admins().contains(admin))
+        .cloned()
+        .collect();
+    let admins = if new_admins.is_empty() {
+        GroupAccessEditResult::default()
+    } else {
+        sdk.group_add_admins(group.id(), &new_admins)
+            .await
+            .map(GroupAdminEditResult::into_access_edit_result)
+            .unwrap_or_else(|e| GroupAccessEditResult::all_failed(&new_admins, &e))
+    };
+    let members = if snapshot.members().is_empty() {
+        GroupAccessEditResult::default()
+    } else {
+        sdk.group_add_members(group.id(), snapshot.members())
+            .await
+            .unwrap_or_else(|e| GroupAccessEditResult::all_failed(snapshot.members(), &e))
+    };
+    let member_groups = if snapshot.member_groups().is_empty() {
+        GroupMemberGroupsEditResult::default()
+    } else {
+        #[cfg(feature = "unstable-group-endpoints")]
+        let added = sdk
+            .group_add_member_groups(group.id(), snapshot.member_groups())
+            .await;
+        #[cfg(not(feature = "unstable-group-endpoints"))]
+        let added: Result<GroupMemberGroupsEditResult> = Err(IronOxideErr::ValidationError(
+            "member_groups".to_string(),
+            "Member groups can only be added with the `unstable-group-endpoints` feature."
+                .to_string(),
+        ));
+        added.unwrap_or_else(|e| {
+            GroupMemberGroupsEditResult::all_failed(snapshot.member_groups(), &e)
+        })
+    };
+    Ok(GroupImportResult::new(
+        group,
+        admins,
+        members,
+        member_groups,
+    ))
+}
+
+/// Update a group's name, without queueing it if the service can't be reached
+pub(crate) async fn update_name(
+    sdk: &crate::IronOxide,
+    id: &GroupId,
+    name: Option<&GroupName>,
+) -> Result<GroupMetaResult> {
+    add_optional_timeout(
+        group_api::update_group_name(sdk.device.auth(), id, name),
+        sdk.config.sdk_operation_timeout,
+        SdkOperation::GroupUpdateName,
+        sdk.config.metrics_sink.as_ref(),
+    )
+    .await?
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// Update a group's description, without queueing it if the service can't be reached
+pub(crate) async fn update_description(
+    sdk: &crate::IronOxide,
+    id: &GroupId,
+    description: Option<&GroupDescription>,
+) -> Result<GroupMetaResult> {
+    add_optional_timeout(
+        group_api::update_group_description(sdk.device.auth(), id, description),
+        sdk.config.sdk_operation_timeout,
+        SdkOperation::GroupUpdateDescription,
+        sdk.config.metrics_sink.as_ref(),
+    )
+    .await?
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// Replace a group's attributes, without queueing it if the service can't be reached
+pub(crate) async fn update_attributes(
+    sdk: &crate::IronOxide,
+    id: &GroupId,
+    attributes: &GroupAttributes,
+) -> Result<GroupMetaResult> {
+    add_optional_timeout(
+        group_api::update_group_attributes(sdk.device.auth(), id, attributes),
+        sdk.config.sdk_operation_timeout,
+        SdkOperation::GroupUpdateAttributes,
+        sdk.config.metrics_sink.as_ref(),
+    )
+    .await?
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// Transfer ownership of a group, without queueing it if the service can't be reached
+pub(crate) async fn transfer_ownership(
+    sdk: &crate::IronOxide,
+    id: &GroupId,
+    new_owner: &UserId,
+) -> Result<GroupMetaResult> {
+    add_optional_timeout(
+        group_api::transfer_group_ownership(sdk.device.auth(), id, new_owner),
+        sdk.config.sdk_operation_timeout,
+        SdkOperation::GroupTransferOwnership,
+        sdk.config.metrics_sink.as_ref(),
+    )
+    .await?
 }
 
 #[cfg(test)]
@@ -622,6 +1531,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn group_create_opts_description_and_attributes_standardize() -> Result<(), IronOxideErr> {
+        use crate::group::{GroupAttributes, GroupDescription};
+        use std::{collections::BTreeMap, convert::TryFrom};
+
+        let calling_user_id = UserId::unsafe_from_string("test_user".to_string());
+        let description = GroupDescription::try_from("test description")?;
+        let mut attribute_map = BTreeMap::new();
+        attribute_map.insert("key".to_string(), "value".to_string());
+        let attributes = GroupAttributes::try_from(attribute_map)?;
+
+        let opts = GroupCreateOpts::default()
+            .with_description(Some(description.clone()))
+            .with_attributes(attributes.clone());
+        let std_opts = opts.standardize(&calling_user_id)?;
+        assert_eq!(std_opts.description, Some(description));
+        assert_eq!(std_opts.attributes, attributes);
+        Ok(())
+    }
+
     #[test]
     fn group_create_opts_standardize_non_owner() -> Result<(), IronOxideErr> {
         let calling_user_id = UserId::unsafe_from_string("test_user".to_string());
@@ -645,6 +1574,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn group_create_opts_setters_match_new() {
+        let owner = UserId::unsafe_from_string("owner".to_string());
+        let member = UserId::unsafe_from_string("member".to_string());
+        let positional = GroupCreateOpts::new(
+            None,
+            None,
+            false,
+            false,
+            Some(owner.clone()),
+            vec![owner.clone()],
+            vec![member.clone()],
+            true,
+        );
+        let built = GroupCreateOpts::default()
+            .with_add_as_admin(false)
+            .with_add_as_member(false)
+            .with_owner(Some(owner.clone()))
+            .with_admins(vec![owner])
+            .with_members(vec![member])
+            .with_needs_rotation(true);
+        assert_eq!(built, positional);
+    }
+
+    #[test]
+    fn group_create_opts_validate() {
+        let calling_user_id = UserId::unsafe_from_string("test_user".to_string());
+        assert!(GroupCreateOpts::default()
+            .validate(&calling_user_id)
+            .is_ok());
+        assert!(GroupCreateOpts::default()
+            .with_add_as_admin(false)
+            .validate(&calling_user_id)
+            .is_err());
+        assert!(GroupCreateOpts::default()
+            .with_add_as_admin(false)
+            .with_admins(vec![calling_user_id.clone()])
+            .validate(&calling_user_id)
+            .is_ok());
+    }
+
     #[test]
     fn group_create_opts_standardize_invalid() -> Result<(), IronOxideErr> {
         let calling_user_id = UserId::unsafe_from_string("test_user".to_string());
@@ -2,57 +2,100 @@
 //! If it can be defined in API specific file, it should go there to keep this file's
 //! size to a minimum.
 
+use crate::config::{
+    ApiProxyConfig, ApiResolutionConfig, FailoverConfig, HttpTimeoutConfig, RequestBatchingConfig,
+    RetryConfig,
+};
+use crate::crypto::provider::{digest, hkdf};
 use crate::internal::{
     group_api::GroupId,
     rest::{Authorization, IronCoreRequest, SignatureUrlString},
-    user_api::UserId,
+    user_api::{DeviceKeyProtection, UserId},
 };
 use chrono::{DateTime, Utc};
-use futures::Future;
+use itertools::{Either, Itertools};
 use lazy_static::lazy_static;
 use log::error;
 use protobuf::{self, ProtobufError};
 use quick_error::quick_error;
 use recrypt::api::{
-    CryptoOps, Ed25519, Hashable, KeyGenOps, Plaintext, PrivateKey as RecryptPrivateKey,
-    PublicKey as RecryptPublicKey, RandomBytes, Recrypt, RecryptErr, Sha256,
-    SigningKeypair as RecryptSigningKeypair,
+    CryptoOps, Ed25519, EncryptedValue as RecryptEncryptedValue, Hashable, KeyGenOps, Plaintext,
+    PrivateKey as RecryptPrivateKey, PublicKey as RecryptPublicKey, RandomBytes, Recrypt,
+    RecryptErr, SchnorrOps, Sha256, SigningKeypair as RecryptSigningKeypair,
 };
-use regex::Regex;
 use reqwest::Method;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     convert::{TryFrom, TryInto},
     fmt::{Error, Formatter},
+    future::Future,
     result::Result,
-    sync::{Mutex, MutexGuard},
+    sync::{Arc, Mutex, MutexGuard},
 };
+#[cfg(not(feature = "disable-password-escrow"))]
+use zeroize::Zeroize;
 
 pub mod document_api;
 pub mod group_api;
-mod rest;
+pub(crate) mod rest;
+pub(crate) mod rt;
 pub mod user_api;
+pub(crate) mod validation;
+
+pub(crate) use validation::{validate_id, validate_name};
+
+const PROD_URL: &str = "https://api.ironcorelabs.com/api/1/";
+const STAGING_URL: &str = "https://api-staging.ironcorelabs.com/api/1/";
 
 lazy_static! {
     pub static ref URL_STRING: String = match std::env::var("IRONCORE_ENV") {
         Ok(url) => match url.to_lowercase().as_ref() {
             "dev" => "https://api-dev1.ironcorelabs.com/api/1/",
-            "stage" => "https://api-staging.ironcorelabs.com/api/1/",
-            "prod" => "https://api.ironcorelabs.com/api/1/",
+            "stage" => STAGING_URL,
+            "prod" => PROD_URL,
             url_choice => url_choice,
         }
         .to_string(),
-        _ => "https://api.ironcorelabs.com/api/1/".to_string(),
+        _ => PROD_URL.to_string(),
     };
     pub static ref OUR_REQUEST: IronCoreRequest = IronCoreRequest::new(URL_STRING.as_str());
 }
 
+/// IronCore webservice a `DeviceContext` makes its requests to.
+///
+/// Without one, requests go to the service named by the `IRONCORE_ENV` environment variable, or to
+/// production if it isn't set.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Environment {
+    /// IronCore's production service
+    Prod,
+    /// IronCore's staging service
+    Staging,
+    /// Another deployment of the service, such as an on-premises proxy. The URL is the base of the API,
+    /// for example `https://ironcore.example.com/api/1/`.
+    Custom(url::Url),
+}
+impl Environment {
+    /// Base URL of the service's API, always ending in `/`
+    pub fn base_url(&self) -> String {
+        match self {
+            Environment::Prod => PROD_URL.to_string(),
+            Environment::Staging => STAGING_URL.to_string(),
+            Environment::Custom(url) if url.as_str().ends_with('/') => url.to_string(),
+            Environment::Custom(url) => format!("{}/", url),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum RequestErrorCode {
     UserVerify,
     UserCreate,
+    UserDeactivate,
+    UserList,
     UserDeviceAdd,
     UserDeviceDelete,
+    UserDeviceKeyUpdate,
     UserDeviceList,
     UserKeyList,
     UserKeyUpdate,
@@ -62,6 +105,11 @@ pub enum RequestErrorCode {
     GroupList,
     GroupGet,
     GroupAddMember,
+    GroupAddMemberGroup,
+    GroupInviteMembers,
+    GroupInviteList,
+    GroupInviteAccept,
+    GroupDocumentList,
     GroupUpdate,
     GroupMemberRemove,
     GroupAdminRemove,
@@ -84,33 +132,126 @@ pub enum SdkOperation {
     RotateAll,
     DocumentList,
     DocumentGetMetadata,
+    DocumentAccessVia,
     DocumentEncrypt,
     DocumentUpdateBytes,
     DocumentDecrypt,
+    DocumentVerify,
     DocumentUpdateName,
     DocumentGrantAccess,
     DocumentRevokeAccess,
     DocumentEncryptUnmanaged,
     DocumentDecryptUnmanaged,
+    DocumentVerifyAccessLog,
+    DocumentAccessSnapshot,
+    DocumentAccessRestore,
+    DecryptSessionPreload,
     UserCreate,
+    UserCreateWithDevice,
+    UserCreateWithMasterKey,
+    UserDeactivate,
     UserListDevices,
+    UserListNeedingRotation,
     GenerateNewDevice,
+    GenerateNewDeviceWithMasterKey,
+    GenerateNewDeviceWithRecovery,
     UserDeleteDevice,
+    DeviceRotateKeys,
     UserVerify,
+    UserVerifyPassword,
     UserGetPublicKey,
     UserRotatePrivateKey,
+    UserResumeRotation,
     GroupList,
     GroupCreate,
     GroupGetMetadata,
     GroupDelete,
+    GroupExport,
+    GroupListDocuments,
     GroupUpdateName,
+    GroupUpdateDescription,
+    GroupUpdateAttributes,
+    GroupTransferOwnership,
     GroupAddMembers,
+    GroupAddMemberGroups,
+    GroupInviteMembers,
+    GroupListInvites,
+    GroupAcceptInvite,
+    GroupEffectiveMembers,
     GroupRemoveMembers,
     GroupAddAdmins,
     GroupRemoveAdmins,
+    GroupAdvisoryRemoveAdmins,
+    GroupAdvisoryApproveAdminRemoval,
     GroupRotatePrivateKey,
 }
 
+impl SdkOperation {
+    /// Whether the operation creates, changes, or deletes data in the IronCore service.
+    pub fn is_mutating(&self) -> bool {
+        use SdkOperation::*;
+        match self {
+            RotateAll
+            | DocumentEncrypt
+            | DocumentUpdateBytes
+            | DocumentUpdateName
+            | DocumentGrantAccess
+            | DocumentRevokeAccess
+            | DocumentAccessRestore
+            | UserCreate
+            | UserCreateWithDevice
+            | UserCreateWithMasterKey
+            | UserDeactivate
+            | GenerateNewDevice
+            | GenerateNewDeviceWithMasterKey
+            | GenerateNewDeviceWithRecovery
+            | UserDeleteDevice
+            | DeviceRotateKeys
+            | UserRotatePrivateKey
+            | UserResumeRotation
+            | GroupCreate
+            | GroupDelete
+            | GroupUpdateName
+            | GroupUpdateDescription
+            | GroupUpdateAttributes
+            | GroupTransferOwnership
+            | GroupAddMembers
+            | GroupAddMemberGroups
+            | GroupInviteMembers
+            | GroupAcceptInvite
+            | GroupRemoveMembers
+            | GroupAddAdmins
+            | GroupRemoveAdmins
+            | GroupAdvisoryRemoveAdmins
+            | GroupAdvisoryApproveAdminRemoval
+            | GroupRotatePrivateKey => true,
+            InitializeSdk
+            | InitializeSdkCheckRotation
+            | DocumentList
+            | DocumentGetMetadata
+            | DocumentAccessVia
+            | DocumentDecrypt
+            | DocumentVerify
+            | DocumentEncryptUnmanaged
+            | DocumentDecryptUnmanaged
+            | DocumentVerifyAccessLog
+            | DocumentAccessSnapshot
+            | DecryptSessionPreload
+            | UserListDevices
+            | UserListNeedingRotation
+            | UserVerify
+            | UserVerifyPassword
+            | UserGetPublicKey
+            | GroupList
+            | GroupGetMetadata
+            | GroupExport
+            | GroupListDocuments
+            | GroupListInvites
+            | GroupEffectiveMembers => false,
+        }
+    }
+}
+
 impl std::fmt::Display for SdkOperation {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(f, "'{:?}'", self)
@@ -132,9 +273,12 @@ quick_error! {
         KeyGenerationError {
             display("Key generation failed")
         }
-        AesError(err: ring::error::Unspecified) {
+        AesError(err: crate::crypto::provider::error::Unspecified) {
             source(err)
         }
+        FipsDisallowed(algorithm: String) {
+            display("{} is not FIPS approved, so it can't be used with the `fips` feature.", algorithm)
+        }
         AesEncryptedDocSizeError{
             display("Provided document is not long enough to be an encrypted document.")
         }
@@ -153,12 +297,18 @@ quick_error! {
         InitializeError(cause: String) {
             display("SDK initialization failed. Underlying cause '{}'", cause)
         }
-        RequestError { message: String, code: RequestErrorCode, http_status: Option<u16> } {
-            display("Request failed with HTTP status code '{:?}' message '{}' and code '{:?}'", http_status, message, code)
+        /// `request_id` is the ID the server gave the request, if it gave one. Include it when contacting support.
+        RequestError { message: String, code: RequestErrorCode, http_status: Option<u16>, request_id: Option<String> } {
+            display("Request failed with HTTP status code '{:?}' message '{}' code '{:?}' and request ID '{:?}'", http_status, message, code, request_id)
+        }
+        /// The request was denied due to rate limiting. `retry_after` is how long the server asked us to
+        /// wait before trying again, if it said.
+        RateLimited { retry_after: Option<std::time::Duration>, code: RequestErrorCode } {
+            display("Request was denied due to rate limiting with code '{:?}'. Retry after '{:?}'", code, retry_after)
         }
         ///This is used if the response from the server was an error. In that case we know that the format of the errors will be `ServerError`.
-        RequestServerErrors {errors: Vec<rest::ServerError>, code: RequestErrorCode, http_status: Option<u16> } {
-            display("Request failed with HTTP status code '{:?}' errors list is '{:?}' and code '{:?}'", http_status, errors, code)
+        RequestServerErrors {errors: Vec<rest::ServerError>, code: RequestErrorCode, http_status: Option<u16>, request_id: Option<String> } {
+            display("Request failed with HTTP status code '{:?}' errors list is '{:?}' code '{:?}' and request ID '{:?}'", http_status, errors, code, request_id)
         }
         MissingTransformBlocks {
             display("Expected at least one TransformBlock in transformed value but received none.")
@@ -167,6 +317,10 @@ quick_error! {
         NotGroupAdmin(id: GroupId) {
             display("You are not an administrator of group '{}'", id.id())
         }
+        ///The operation would leave the group without any administrators. This is checked by the SDK, and the operation can be retried with the advisory approval of another admin.
+        WouldOrphanGroup(id: GroupId) {
+            display("Operation would leave group '{}' without any administrators", id.id())
+        }
         /// No policy exists for the segment
         PolicyDoesNotExist {
             display("No policy is defined. Please visit https://admin.ironcorelabs.com/policy to set a policy")
@@ -195,6 +349,49 @@ quick_error! {
         OperationTimedOut{operation: SdkOperation, duration: std::time::Duration} {
             display("Operation {} timed out after {}ms", operation, duration.as_millis())
         }
+        /// The IronCore service couldn't be reached, so the mutation was put in the offline queue to be
+        /// replayed by `OfflineOps::flush`
+        MutationQueued{operation: SdkOperation, cause: String} {
+            display("Operation {} couldn't reach the IronCore service and was queued. Underlying cause '{}'", operation, cause)
+        }
+        /// The document could only be decrypted via a key path that is disallowed by the `DecryptionConfig`
+        DecryptKeyPathNotAllowed(key_path: crate::config::DecryptKeyPath) {
+            display("Decryption via key path '{:?}' is not allowed by the configured DecryptionConfig", key_path)
+        }
+        /// A persisted policy cache could not be written or read back
+        PolicyCacheSerdeError(msg: String) {
+            display("Policy cache serialization failed with '{}'", msg)
+        }
+        /// A `DeviceContext` could not be read from a secret store
+        DeviceContextSourceError(msg: String) {
+            display("Loading the DeviceContext from its secret source failed with '{}'", msg)
+        }
+        /// The decryption session's TTL has elapsed and its keys have been cleared
+        DecryptSessionExpired {
+            display("The decryption session has expired")
+        }
+        /// Too many password key derivations were already running for this one to start within the
+        /// `queue_timeout` of the `PasswordDerivationLimit`
+        PasswordDerivationQueueTimeout(queue_timeout: std::time::Duration) {
+            display("Waited {}ms for other password key derivations to finish", queue_timeout.as_millis())
+        }
+        /// The operation would modify data, but the SDK was configured with `read_only`
+        ReadOnlyMode(operation: SdkOperation) {
+            display("Operation {} is not allowed because the SDK is in read-only mode", operation)
+        }
+        /// The operation passed validation and its key lookups, and was stopped before changing anything
+        /// because it was run with `validate_only`
+        ValidateOnly(operation: SdkOperation) {
+            display("Operation {} passed validation and was not applied because validate_only was set", operation)
+        }
+        /// An identity provider token could not be exchanged for an IronCore JWT
+        TokenExchangeError(msg: String) {
+            display("Exchanging the identity provider token failed with '{}'", msg)
+        }
+        /// The operation needs the device private key in memory, but it's held by a `PrivateKeyProvider`
+        KeyProviderUnsupported {
+            display("Operation needs the device private key, which is held by a PrivateKeyProvider")
+        }
     }
 }
 
@@ -230,44 +427,6 @@ impl From<recrypt::nonemptyvec::NonEmptyVecError> for IronOxideErr {
     }
 }
 
-const NAME_AND_ID_MAX_LEN: usize = 100;
-
-/// Validate that the provided id is valid for our user/document/group IDs. Validates that the
-/// ID has a length and that it matches our restricted set of characters. Also takes the readable
-/// type of ID for usage within any resulting error messages.
-pub fn validate_id(id: &str, id_type: &str) -> Result<String, IronOxideErr> {
-    let id_regex = Regex::new("^[a-zA-Z0-9_.$#|@/:;=+'-]+$").expect("regex is valid");
-    let trimmed_id = id.trim();
-    if trimmed_id.is_empty() || trimmed_id.len() > NAME_AND_ID_MAX_LEN {
-        Err(IronOxideErr::ValidationError(
-            id_type.to_string(),
-            format!("'{}' must have length between 1 and 100", trimmed_id),
-        ))
-    } else if !id_regex.is_match(trimmed_id) {
-        Err(IronOxideErr::ValidationError(
-            id_type.to_string(),
-            format!("'{}' contains invalid characters", trimmed_id),
-        ))
-    } else {
-        Ok(trimmed_id.to_string())
-    }
-}
-
-/// Validate that the provided document/group name is valid. Ensures that the length of
-/// the name is between 1-100 characters. Also takes the readable type of the name for
-/// usage within any resulting error messages.
-pub fn validate_name(name: &str, name_type: &str) -> Result<String, IronOxideErr> {
-    let trimmed_name = name.trim();
-    if trimmed_name.trim().is_empty() || trimmed_name.len() > NAME_AND_ID_MAX_LEN {
-        Err(IronOxideErr::ValidationError(
-            name_type.to_string(),
-            format!("'{}' must have length between 1 and 100", trimmed_name),
-        ))
-    } else {
-        Ok(trimmed_name.trim().to_string())
-    }
-}
-
 pub mod auth_v2 {
     use super::*;
 
@@ -318,7 +477,11 @@ pub struct RequestAuth {
     ///The segment_id for the above user.
     segment_id: usize,
     ///The signing key which was generated for the device. “expanded private key” (both pub/priv)
+    ///If the device keys are held by a `PrivateKeyProvider`, this key only signs encrypted values.
     signing_private_key: DeviceSigningKeyPair,
+    ///Signs requests in place of `signing_private_key` if the device keys are held by a provider
+    #[serde(skip_serializing, skip_deserializing)]
+    key_provider: Option<KeyProvider>,
     #[serde(skip_serializing, skip_deserializing)]
     pub(crate) request: IronCoreRequest,
 }
@@ -337,10 +500,17 @@ impl RequestAuth {
             method,
             sig_url,
             body,
-            &self.signing_private_key,
+            self.request_signer(),
         )
     }
 
+    fn request_signer(&self) -> RequestSigner<'_> {
+        match &self.key_provider {
+            Some(provider) => RequestSigner::Provider(&*provider.0),
+            None => RequestSigner::Device(&self.signing_private_key),
+        }
+    }
+
     pub fn account_id(&self) -> &UserId {
         &self.account_id
     }
@@ -360,13 +530,42 @@ impl RequestAuth {
 ///
 /// Can be generated by calling [generate_new_device](../user/trait.UserOps.html#tymethod.generate_new_device) and
 /// passing the result to `DeviceContext::from`.
-#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+///
+/// Serializing a `DeviceContext` fails unless its [protection](#method.protection) level is
+/// `DeviceKeyProtection::Exportable`. A deserialized `DeviceContext` is always `Exportable`.
+///
+/// # JSON format
+/// A `DeviceContext` serializes to the same JSON object that the other IronCore SDKs read and write,
+/// so a device persisted by one of them can be loaded by another. The shape is stable:
+///
+/// | Field               | Type   | Contents                                                   |
+/// |---------------------|--------|------------------------------------------------------------|
+/// | `accountId`         | string | ID of the device's owner                                   |
+/// | `segmentId`         | number | ID of the owner's segment                                  |
+/// | `signingPrivateKey` | string | Base64 (standard alphabet, padded) of the 64 byte Ed25519 key pair |
+/// | `devicePrivateKey`  | string | Base64 (standard alphabet, padded) of the 32 byte device private key |
+///
+/// Other fields, such as the `deviceId` some SDKs include, are ignored when deserializing.
+///
+/// ```
+/// # fn main() -> Result<(), serde_json::Error> {
+/// # use ironoxide::prelude::*;
+/// let json = r#"{"accountId":"account_id","segmentId":22,"signingPrivateKey":"AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQGKiOPddAnxlf1S2y08ul1yymcJvx2UEhvzdIgBtA9vXA==","devicePrivateKey":"bzb0Rlg0u7gx9wDuk1ppRI77OH/0ferXleenJ3Ag6Jg="}"#;
+/// let device: DeviceContext = serde_json::from_str(json)?;
+/// assert_eq!(device.segment_id(), 22);
+/// assert_eq!(serde_json::to_string(&device)?, json);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceContext {
     #[serde(flatten)]
     auth: RequestAuth,
     /// The private key which was generated for a particular device for the user. Not the user's master private key.
-    device_private_key: PrivateKey,
+    device_private_key: DevicePrivateKey,
+    #[serde(skip)]
+    protection: DeviceKeyProtection,
 }
 impl DeviceContext {
     /// Constructs a `DeviceContext` from its components.
@@ -384,12 +583,158 @@ impl DeviceContext {
                 account_id,
                 segment_id,
                 signing_private_key,
-                request: IronCoreRequest::new(OUR_REQUEST.base_url()),
+                key_provider: None,
+                request: OUR_REQUEST.clone(),
+            },
+            device_private_key: DevicePrivateKey::Local(device_private_key),
+            protection: DeviceKeyProtection::default(),
+        }
+    }
+
+    /// Constructs a `DeviceContext` whose private keys are held by `key_provider` rather than in memory.
+    ///
+    /// Requests are signed and values encrypted to the device are decrypted by the provider. The
+    /// resulting `DeviceContext` can't be serialized, and the operations that derive keys from the
+    /// device private key fail with `IronOxideErr::KeyProviderUnsupported`: `document_encrypt_unique`,
+    /// exporting or importing the policy cache, and rotating the user's private key.
+    ///
+    /// Values the SDK encrypts and document access snapshots are signed with `signing_private_key`,
+    /// which is what [signing_private_key](#method.signing_private_key) returns. Pass the same key pair
+    /// each time the device is loaded to restore snapshots taken with an earlier `DeviceContext`.
+    pub fn with_key_provider(
+        account_id: UserId,
+        segment_id: usize,
+        key_provider: Arc<dyn PrivateKeyProvider>,
+        signing_private_key: DeviceSigningKeyPair,
+    ) -> DeviceContext {
+        let key_provider = KeyProvider(key_provider);
+        DeviceContext {
+            auth: RequestAuth {
+                account_id,
+                segment_id,
+                signing_private_key,
+                key_provider: Some(key_provider.clone()),
+                request: OUR_REQUEST.clone(),
             },
-            device_private_key,
+            device_private_key: DevicePrivateKey::Provider(key_provider),
+            protection: DeviceKeyProtection::NonExportable,
         }
     }
 
+    /// Sends this device's requests to `environment` instead of the service named by `IRONCORE_ENV`.
+    ///
+    /// The environment isn't serialized with the `DeviceContext`, so it has to be set again after
+    /// deserializing.
+    pub fn with_environment(mut self, environment: Environment) -> DeviceContext {
+        self.auth.request = IronCoreRequest::with_base_url(environment.base_url())
+            .with_timeouts(self.auth.request.timeouts());
+        self
+    }
+
+    /// Makes this device's requests with `timeouts`
+    pub(crate) fn with_http_timeouts(mut self, timeouts: HttpTimeoutConfig) -> DeviceContext {
+        self.auth.request = self.auth.request.with_timeouts(timeouts);
+        self
+    }
+
+    /// Sends this device's requests through `proxy`
+    pub(crate) fn with_api_proxy(mut self, proxy: ApiProxyConfig) -> DeviceContext {
+        self.auth.request = self.auth.request.with_api_proxy(proxy);
+        self
+    }
+
+    /// Connects to the addresses chosen by `resolution` for this device's requests
+    pub(crate) fn with_api_resolution(mut self, resolution: ApiResolutionConfig) -> DeviceContext {
+        self.auth.request = self.auth.request.with_api_resolution(resolution);
+        self
+    }
+
+    /// Resends this device's requests that fail for transient reasons as `retry` allows
+    pub(crate) fn with_retry(mut self, retry: RetryConfig) -> DeviceContext {
+        self.auth.request = self.auth.request.with_retry(retry);
+        self
+    }
+
+    /// Splits this device's operations on large lists into requests as `batching` allows
+    pub(crate) fn with_request_batching(
+        mut self,
+        batching: RequestBatchingConfig,
+    ) -> DeviceContext {
+        self.auth.request = self.auth.request.with_batching(batching);
+        self
+    }
+
+    /// Adds `headers` to each of this device's requests
+    pub(crate) fn with_request_headers(
+        mut self,
+        headers: reqwest::header::HeaderMap,
+    ) -> DeviceContext {
+        self.auth.request = self.auth.request.with_headers(headers);
+        self
+    }
+
+    /// Identifies `app_identifier` after the SDK in the User-Agent of this device's requests
+    pub(crate) fn with_app_identifier(
+        mut self,
+        app_identifier: &str,
+    ) -> Result<DeviceContext, IronOxideErr> {
+        self.auth.request = self.auth.request.with_app_identifier(app_identifier)?;
+        Ok(self)
+    }
+
+    /// Keeps responses to this device's requests that rarely change in a new cache, shared with clones of
+    /// the result
+    pub(crate) fn with_response_cache(mut self) -> DeviceContext {
+        self.auth.request = self.auth.request.with_response_cache();
+        self
+    }
+
+    /// Records this device's requests to, or replays them from, `fixtures`
+    #[cfg(feature = "test-fixtures")]
+    pub(crate) fn with_http_fixtures(mut self, fixtures: rest::HttpFixtures) -> DeviceContext {
+        self.auth.request = self.auth.request.with_http_fixtures(fixtures);
+        self
+    }
+
+    /// Fails over to the secondary API of `failover` while this device's API keeps failing. The circuit
+    /// breaker is shared with clones of the result.
+    pub(crate) fn with_failover(
+        mut self,
+        failover: &FailoverConfig,
+    ) -> Result<DeviceContext, IronOxideErr> {
+        self.auth.request = self.auth.request.with_failover(failover)?;
+        Ok(self)
+    }
+
+    /// Sends this device's requests on a new connection pool, shared with clones of the result
+    pub(crate) fn with_connection_pool(mut self) -> DeviceContext {
+        self.auth.request = self.auth.request.with_connection_pool();
+        self
+    }
+
+    /// Sends this device's requests on the connection pool of `request`
+    pub(crate) fn with_connection_pool_of(mut self, request: &IronCoreRequest) -> DeviceContext {
+        self.auth.request = self.auth.request.with_connection_pool_of(request);
+        self
+    }
+
+    /// Base URL of the API this device's requests are sent to
+    pub fn base_url(&self) -> &str {
+        self.auth.request.base_url()
+    }
+
+    /// Sets the protection level of the device keys, which controls whether this `DeviceContext`
+    /// can be serialized.
+    ///
+    /// The protection can only be tightened: a `DeviceContext` that isn't `Exportable` stays that way,
+    /// so passing `DeviceKeyProtection::Exportable` to one leaves its protection unchanged.
+    pub fn with_protection(mut self, protection: DeviceKeyProtection) -> DeviceContext {
+        if !protection.is_exportable() {
+            self.protection = protection;
+        }
+        self
+    }
+
     pub(crate) fn auth(&self) -> &RequestAuth {
         &self.auth
     }
@@ -406,28 +751,269 @@ impl DeviceContext {
         &self.auth.signing_private_key
     }
     /// Private encryption key of the device
+    ///
+    /// # Panics
+    /// If the key is held by a `PrivateKeyProvider`. Check [key_provider](#method.key_provider) first
+    /// for a `DeviceContext` that may have been created with `with_key_provider`.
     pub fn device_private_key(&self) -> &PrivateKey {
+        match &self.device_private_key {
+            DevicePrivateKey::Local(key) => key,
+            DevicePrivateKey::Provider(_) => {
+                panic!("The device private key is held by a PrivateKeyProvider")
+            }
+        }
+    }
+    /// Provider holding the private keys of the device, or `None` if they're in memory
+    pub fn key_provider(&self) -> Option<&Arc<dyn PrivateKeyProvider>> {
+        match &self.device_private_key {
+            DevicePrivateKey::Local(_) => None,
+            DevicePrivateKey::Provider(provider) => Some(&provider.0),
+        }
+    }
+    pub(crate) fn device_key(&self) -> &DevicePrivateKey {
         &self.device_private_key
     }
+    /// How the device keys may be handled
+    pub fn protection(&self) -> DeviceKeyProtection {
+        self.protection
+    }
+}
+impl Serialize for DeviceContext {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ExportedDeviceContext<'a> {
+            #[serde(flatten)]
+            auth: &'a RequestAuth,
+            device_private_key: &'a PrivateKey,
+        }
+
+        match (&self.device_private_key, self.protection.is_exportable()) {
+            (DevicePrivateKey::Local(device_private_key), true) => ExportedDeviceContext {
+                auth: &self.auth,
+                device_private_key,
+            }
+            .serialize(serializer),
+            (DevicePrivateKey::Local(_), false) => {
+                Err(<S::Error as serde::ser::Error>::custom(format!(
+                    "Device keys with protection level {:?} cannot be serialized",
+                    self.protection
+                )))
+            }
+            (DevicePrivateKey::Provider(_), _) => Err(<S::Error as serde::ser::Error>::custom(
+                "Device keys held by a PrivateKeyProvider cannot be serialized",
+            )),
+        }
+    }
+}
+
+/// Holds a device's private keys outside of the SDK, such as in an HSM, a KMS, or a TPM.
+///
+/// Pass an implementation to [DeviceContext::with_key_provider](struct.DeviceContext.html#method.with_key_provider)
+/// to have the SDK delegate signing requests and decrypting values encrypted to the device, so the
+/// device keys never need to be loaded into memory.
+pub trait PrivateKeyProvider: Send + Sync + std::fmt::Debug {
+    /// Public half of the device's Ed25519 signing key pair
+    fn public_signing_key(&self) -> [u8; 32];
+    /// Sign `payload` with the device's Ed25519 signing key
+    fn sign(&self, payload: &[u8]) -> Result<[u8; 64], IronOxideErr>;
+    /// Decrypt a value that was encrypted or transformed to the device's public key
+    fn decrypt(&self, encrypted_value: RecryptEncryptedValue) -> Result<Plaintext, IronOxideErr>;
+}
+
+/// Shared handle to a `PrivateKeyProvider`. Handles are only equal if they refer to the same provider.
+#[derive(Clone, Debug)]
+pub struct KeyProvider(Arc<dyn PrivateKeyProvider>);
+impl KeyProvider {
+    fn addr(&self) -> usize {
+        &*self.0 as *const dyn PrivateKeyProvider as *const u8 as usize
+    }
+}
+impl PartialEq for KeyProvider {
+    fn eq(&self, other: &KeyProvider) -> bool {
+        self.addr() == other.addr()
+    }
+}
+impl Eq for KeyProvider {}
+impl std::hash::Hash for KeyProvider {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.addr().hash(state)
+    }
+}
+
+/// Signs the auth headers of a request to the webservice
+#[derive(Clone, Copy, Debug)]
+pub enum RequestSigner<'a> {
+    Device(&'a DeviceSigningKeyPair),
+    Provider(&'a dyn PrivateKeyProvider),
+}
+impl<'a> RequestSigner<'a> {
+    pub(crate) fn public_key(&self) -> [u8; 32] {
+        match self {
+            RequestSigner::Device(keys) => keys.public_key(),
+            RequestSigner::Provider(provider) => provider.public_signing_key(),
+        }
+    }
+    pub(crate) fn sign(&self, payload: &[u8]) -> Result<[u8; 64], IronOxideErr> {
+        match self {
+            RequestSigner::Device(keys) => Ok(keys.sign(payload)),
+            RequestSigner::Provider(provider) => provider.sign(payload),
+        }
+    }
+}
+impl<'a> From<&'a DeviceSigningKeyPair> for RequestSigner<'a> {
+    fn from(keys: &'a DeviceSigningKeyPair) -> Self {
+        RequestSigner::Device(keys)
+    }
 }
 
-/// Newtype wrapper around Recrypt TransformKey type
+/// A device's private encryption key, either in memory or held by a `PrivateKeyProvider`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum DevicePrivateKey {
+    Local(PrivateKey),
+    Provider(KeyProvider),
+}
+impl DevicePrivateKey {
+    /// Decrypt a value encrypted or transformed to the device
+    pub(crate) fn decrypt<CR: rand::CryptoRng + rand::RngCore>(
+        &self,
+        recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+        encrypted_value: RecryptEncryptedValue,
+    ) -> Result<Plaintext, IronOxideErr> {
+        match self {
+            DevicePrivateKey::Local(key) => {
+                Ok(recrypt.decrypt(encrypted_value, key.recrypt_key())?)
+            }
+            DevicePrivateKey::Provider(provider) => provider.0.decrypt(encrypted_value),
+        }
+    }
+    /// The in-memory private key, for deriving the keys that protect state persisted by this device.
+    /// Fails if the key is held by a provider.
+    pub(crate) fn local(&self) -> Result<&PrivateKey, IronOxideErr> {
+        match self {
+            DevicePrivateKey::Local(key) => Ok(key),
+            DevicePrivateKey::Provider(_) => Err(IronOxideErr::KeyProviderUnsupported),
+        }
+    }
+}
+impl From<PrivateKey> for DevicePrivateKey {
+    fn from(key: PrivateKey) -> Self {
+        DevicePrivateKey::Local(key)
+    }
+}
+impl<'de> Deserialize<'de> for DevicePrivateKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        PrivateKey::deserialize(deserializer).map(DevicePrivateKey::Local)
+    }
+}
+
+/// Transform (re-encryption) key, which lets a holder transform values encrypted to one key so that
+/// they can be decrypted by another without learning what they are.
+///
+/// A `TransformKey` can be persisted or sent elsewhere with [to_bytes](#method.to_bytes) and read back
+/// with `TransformKey::try_from`.
 #[derive(Clone, PartialEq, Debug)]
 pub struct TransformKey(recrypt::api::TransformKey);
+impl TransformKey {
+    const PUBLIC_KEY_LEN: usize = 64;
+    const ENCRYPTED_TEMP_KEY_LEN: usize = 384;
+    const HASHED_TEMP_KEY_LEN: usize = 128;
+    const PUBLIC_SIGNING_KEY_LEN: usize = 32;
+    const SIGNATURE_LEN: usize = 64;
+    /// Length of the output of `to_bytes`
+    pub const ENCODED_SIZE_BYTES: usize = 2 * TransformKey::PUBLIC_KEY_LEN
+        + TransformKey::ENCRYPTED_TEMP_KEY_LEN
+        + TransformKey::HASHED_TEMP_KEY_LEN
+        + TransformKey::PUBLIC_SIGNING_KEY_LEN
+        + TransformKey::SIGNATURE_LEN;
+
+    /// Serialize the transform key. The encoding is a fixed `ENCODED_SIZE_BYTES` long concatenation of the
+    /// ephemeral public key, the public key it transforms to (each as x then y), the encrypted temp key,
+    /// the hashed temp key, the public signing key, and the signature.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_array().to_vec()
+    }
+    /// Same encoding as `to_bytes`, without allocating
+    pub fn to_bytes_array(&self) -> [u8; TransformKey::ENCODED_SIZE_BYTES] {
+        let tk = &self.0;
+        let (eph_x, eph_y) = tk.ephemeral_public_key().bytes_x_y();
+        let (to_x, to_y) = tk.to_public_key().bytes_x_y();
+        let mut bytes = [0u8; TransformKey::ENCODED_SIZE_BYTES];
+        let mut offset = 0;
+        for part in &[
+            &eph_x[..],
+            &eph_y[..],
+            &to_x[..],
+            &to_y[..],
+            &tk.encrypted_temp_key().bytes()[..],
+            &tk.hashed_temp_key().bytes()[..],
+            &tk.public_signing_key().bytes()[..],
+            &tk.signature().bytes()[..],
+        ] {
+            bytes[offset..(offset + part.len())].copy_from_slice(part);
+            offset += part.len();
+        }
+        bytes
+    }
+}
 impl From<recrypt::api::TransformKey> for TransformKey {
     fn from(tk: recrypt::api::TransformKey) -> Self {
         TransformKey(tk)
     }
 }
+impl From<TransformKey> for recrypt::api::TransformKey {
+    fn from(tk: TransformKey) -> Self {
+        tk.0
+    }
+}
 impl Hashable for TransformKey {
     fn to_bytes(&self) -> Vec<u8> {
         self.0.to_bytes()
     }
 }
+impl TryFrom<&[u8]> for TransformKey {
+    type Error = IronOxideErr;
+    fn try_from(bytes: &[u8]) -> Result<TransformKey, Self::Error> {
+        if bytes.len() != TransformKey::ENCODED_SIZE_BYTES {
+            return Err(IronOxideErr::WrongSizeError(
+                Some(bytes.len()),
+                Some(TransformKey::ENCODED_SIZE_BYTES),
+            ));
+        }
+        let public_key = |key: &[u8]| {
+            let (x, y) = key.split_at(TransformKey::PUBLIC_KEY_LEN / 2);
+            RecryptPublicKey::new_from_slice((x, y))
+        };
+        let (ephemeral_public_key, rest) = bytes.split_at(TransformKey::PUBLIC_KEY_LEN);
+        let (to_public_key, rest) = rest.split_at(TransformKey::PUBLIC_KEY_LEN);
+        let (encrypted_temp_key, rest) = rest.split_at(TransformKey::ENCRYPTED_TEMP_KEY_LEN);
+        let (hashed_temp_key, rest) = rest.split_at(TransformKey::HASHED_TEMP_KEY_LEN);
+        let (public_signing_key, signature) = rest.split_at(TransformKey::PUBLIC_SIGNING_KEY_LEN);
+        Ok(TransformKey(recrypt::api::TransformKey::new(
+            public_key(ephemeral_public_key)?,
+            public_key(to_public_key)?,
+            recrypt::api::EncryptedTempKey::new_from_slice(encrypted_temp_key)?,
+            recrypt::api::HashedValue::new_from_slice(hashed_temp_key)?,
+            recrypt::api::PublicSigningKey::new_from_slice(public_signing_key)?,
+            recrypt::api::Ed25519Signature::new_from_slice(signature)?,
+        )))
+    }
+}
 
-/// Newtype wrapper around Recrypt SchnorrSignature type
+/// Schnorr signature made with a user's or group's private key.
+///
+/// Check one with [verify_schnorr](fn.verify_schnorr.html).
 #[derive(Clone, PartialEq, Debug)]
 pub struct SchnorrSignature(recrypt::api::SchnorrSignature);
+impl SchnorrSignature {
+    /// Bytes of the signature
+    pub fn as_bytes(&self) -> &[u8; recrypt::api::SchnorrSignature::ENCODED_SIZE_BYTES] {
+        self.0.bytes()
+    }
+}
 impl From<recrypt::api::SchnorrSignature> for SchnorrSignature {
     fn from(s: recrypt::api::SchnorrSignature) -> Self {
         SchnorrSignature(s)
@@ -438,6 +1024,28 @@ impl From<SchnorrSignature> for Vec<u8> {
         sig.0.bytes().to_vec()
     }
 }
+impl TryFrom<&[u8]> for SchnorrSignature {
+    type Error = IronOxideErr;
+    fn try_from(bytes: &[u8]) -> Result<SchnorrSignature, Self::Error> {
+        recrypt::api::SchnorrSignature::new_from_slice(bytes)
+            .map(SchnorrSignature)
+            .map_err(|e| {
+                IronOxideErr::ValidationError("SchnorrSignature".to_string(), format!("{}", e))
+            })
+    }
+}
+
+/// Check that `signature` is a Schnorr signature over `message` by the private key matching `public_key`.
+///
+/// Verifies signatures made by any IronCore SDK with a user's or group's private key, such as the
+/// signature over a new device's transform key.
+pub fn verify_schnorr(
+    public_key: &PublicKey,
+    message: &[u8],
+    signature: &SchnorrSignature,
+) -> bool {
+    Recrypt::new().schnorr_verify(&public_key.0, None, &message, signature.0.clone())
+}
 
 /// Asymmetric public encryption key.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -451,11 +1059,52 @@ impl PublicKey {
         let re_pub = RecryptPublicKey::new_from_slice(bytes)?;
         Ok(PublicKey(re_pub))
     }
-    /// Bytes of the public key
+    /// Length of the output of `to_bytes_array`
+    pub const ENCODED_SIZE_BYTES: usize = 64;
+    /// Bytes of the public key, x then y. See `to_bytes_array` to get them without allocating.
     pub fn as_bytes(&self) -> Vec<u8> {
-        let (mut x, mut y) = self.to_bytes_x_y();
-        x.append(&mut y);
-        x
+        self.to_bytes_array().to_vec()
+    }
+    /// Bytes of the public key, x then y, without allocating
+    pub fn to_bytes_array(&self) -> [u8; PublicKey::ENCODED_SIZE_BYTES] {
+        let (x, y) = self.0.bytes_x_y();
+        let mut bytes = [0u8; PublicKey::ENCODED_SIZE_BYTES];
+        bytes[..(PublicKey::ENCODED_SIZE_BYTES / 2)].copy_from_slice(x);
+        bytes[(PublicKey::ENCODED_SIZE_BYTES / 2)..].copy_from_slice(y);
+        bytes
+    }
+    /// Short fingerprint of the public key, for users to compare out-of-band when verifying whose key it is
+    pub fn fingerprint(&self) -> PublicKeyFingerprint {
+        let digest = digest::digest(&digest::SHA256, &self.to_bytes_array());
+        let mut fingerprint = [0u8; PublicKeyFingerprint::LEN];
+        fingerprint.copy_from_slice(&digest.as_ref()[..PublicKeyFingerprint::LEN]);
+        PublicKeyFingerprint(fingerprint)
+    }
+}
+
+/// Fingerprint of a [PublicKey](struct.PublicKey.html), returned by [PublicKey::fingerprint](struct.PublicKey.html#method.fingerprint).
+///
+/// The fingerprint is the first 16 bytes of the SHA-256 hash of the key's bytes, so it is the same for a key
+/// across SDK versions. It displays as eight space separated groups of four hex digits, such as
+/// `3f2a 91bc 07de 5a10 c4e8 2b9f 6d03 e71a`, which is easier to read aloud or compare by eye than
+/// [to_hex](#method.to_hex).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PublicKeyFingerprint([u8; PublicKeyFingerprint::LEN]);
+impl PublicKeyFingerprint {
+    const LEN: usize = 16;
+    /// Bytes of the fingerprint
+    pub fn as_bytes(&self) -> &[u8; PublicKeyFingerprint::LEN] {
+        &self.0
+    }
+    /// Lowercase hex encoding of the fingerprint, without separators
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+}
+impl std::fmt::Display for PublicKeyFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let groups: Vec<String> = self.0.chunks(2).map(hex::encode).collect();
+        write!(f, "{}", groups.join(" "))
     }
 }
 impl From<RecryptPublicKey> for PublicKey {
@@ -496,6 +1145,9 @@ impl TryFrom<&[u8]> for PublicKey {
 }
 
 /// Asymmetric private encryption key.
+///
+/// recrypt clears the key from memory when it's dropped. Each clone is another copy of the key, so the SDK
+/// borrows keys rather than cloning them.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct PrivateKey(RecryptPrivateKey);
 impl PrivateKey {
@@ -507,6 +1159,19 @@ impl PrivateKey {
     fn recrypt_key(&self) -> &RecryptPrivateKey {
         &self.0
     }
+    /// Derive a 32 byte key for the purpose named by `label` from this private key with HKDF-SHA256.
+    /// Different labels give independent keys, and none of them reveal the private key.
+    pub(crate) fn derive_subkey(&self, label: &[u8]) -> [u8; 32] {
+        let label = [label];
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, b"ironoxide").extract(self.as_bytes());
+        let okm = prk
+            .expand(&label, hkdf::HKDF_SHA256)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        let mut key = [0u8; 32];
+        okm.fill(&mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        key
+    }
     /// Augment this private key with another, producing a new PrivateKey
     fn augment<F: FnOnce(String) -> IronOxideErr>(
         &self,
@@ -514,19 +1179,21 @@ impl PrivateKey {
         error_fn: F,
     ) -> Result<PrivateKey, IronOxideErr> {
         let zero: RecryptPrivateKey = RecryptPrivateKey::new([0u8; 32]);
-        if RecryptPrivateKey::from(augmenting_key.clone()) == zero {
+        let augmenting_key = (augmenting_key.0).recrypt_key();
+        if *augmenting_key == zero {
             Err(error_fn("Augmenting key cannot be zero".into()))
-        } else if RecryptPrivateKey::from(augmenting_key.clone()) == self.0 {
+        } else if *augmenting_key == self.0 {
             Err(error_fn(
                 "PrivateKey augmentation failed with a zero value".into(),
             ))
         } else {
             // this subtraction needs to be the additive inverse of what the service is doing
-            let augmented_key = self.0.augment_minus(&augmenting_key.clone().into());
+            let augmented_key = self.0.augment_minus(augmenting_key);
             Ok(augmented_key.into())
         }
     }
     /// A convenience function to pass a user rotation error to `augment()`
+    #[cfg_attr(feature = "disable-password-escrow", allow(dead_code))]
     fn augment_user(
         &self,
         augmenting_key: &AugmentationFactor,
@@ -604,6 +1271,8 @@ impl From<AugmentationFactor> for RecryptPrivateKey {
 }
 
 /// Key pair used to sign all requests to the IronCore API endpoints.
+///
+/// Like [PrivateKey](struct.PrivateKey.html), cleared from memory by recrypt when dropped.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct DeviceSigningKeyPair(RecryptSigningKeypair);
 impl DeviceSigningKeyPair {
@@ -617,6 +1286,20 @@ impl DeviceSigningKeyPair {
     pub fn public_key(&self) -> [u8; 32] {
         self.0.public_key().into()
     }
+    /// Borrow the recrypt signing key pair, rather than converting into a copy of it
+    fn recrypt_key(&self) -> &RecryptSigningKeypair {
+        &self.0
+    }
+    /// Fingerprint of the public signing key, as reported by
+    /// [UserDevice::signing_key_fingerprint](../user/struct.UserDevice.html#method.signing_key_fingerprint)
+    pub fn public_key_fingerprint(&self) -> String {
+        signing_key_fingerprint(&self.public_key())
+    }
+}
+
+/// Hex encoded SHA-256 hash of a device's public signing key.
+pub(crate) fn signing_key_fingerprint(public_signing_key: &[u8]) -> String {
+    hex::encode(digest::digest(&digest::SHA256, public_signing_key))
 }
 impl From<&DeviceSigningKeyPair> for RecryptSigningKeypair {
     fn from(dsk: &DeviceSigningKeyPair) -> RecryptSigningKeypair {
@@ -659,9 +1342,18 @@ impl<'de> Deserialize<'de> for DeviceSigningKeyPair {
     }
 }
 
-/// Newtype wrapper around a string which represents the users master private key escrow password
+#[cfg(not(feature = "disable-password-escrow"))]
+/// Newtype wrapper around a string which represents the users master private key escrow password.
+/// The password is cleared from memory when dropped.
 #[derive(Debug, PartialEq)]
 pub struct Password(String);
+#[cfg(not(feature = "disable-password-escrow"))]
+impl Drop for Password {
+    fn drop(&mut self) {
+        self.0.zeroize()
+    }
+}
+#[cfg(not(feature = "disable-password-escrow"))]
 impl TryFrom<&str> for Password {
     type Error = IronOxideErr;
     fn try_from(maybe_password: &str) -> Result<Self, Self::Error> {
@@ -687,6 +1379,93 @@ impl<T> WithKey<T> {
     }
 }
 
+/// Public keys of users or groups, keyed by their IDs, along with when each was cached.
+///
+/// Used for both the user and group public key caches. A cache is created with its `IronOxide` and
+/// shared with the instances created by `fork_for_core`, and an `IronOxideManager` shares one cache
+/// between the instances of each segment.
+pub(crate) struct PublicKeyCache<K: Eq + std::hash::Hash> {
+    entries: dashmap::DashMap<K, CachedPublicKey>,
+}
+
+#[derive(Clone, Debug)]
+struct CachedPublicKey {
+    public_key: PublicKey,
+    cached_at: DateTime<Utc>,
+}
+
+impl<K: Eq + std::hash::Hash + Clone> PublicKeyCache<K> {
+    /// Split `ids` into the keys that are cached and younger than `ttl`, and the IDs whose keys need to
+    /// be fetched.
+    fn partition(&self, ttl: std::time::Duration, ids: &[K]) -> (Vec<WithKey<K>>, Vec<K>) {
+        // a ttl too large to represent never expires
+        let oldest_allowed = chrono::Duration::from_std(ttl)
+            .ok()
+            .and_then(|ttl| Utc::now().checked_sub_signed(ttl));
+        ids.iter().partition_map(|id| match self.entries.get(id) {
+            Some(cached) if oldest_allowed.map_or(true, |oldest| cached.cached_at >= oldest) => {
+                Either::Left(WithKey::new(id.clone(), cached.public_key.clone()))
+            }
+            _ => Either::Right(id.clone()),
+        })
+    }
+
+    /// Uses the keys for `ids` that were cached less than `ttl` ago and fetches the rest with `fetch`,
+    /// caching what it returns. If the cache already has `max_entries` it is cleared before adding new
+    /// entries. A `ttl` or `max_entries` of zero bypasses the cache.
+    pub(crate) async fn get_or_fetch<F, Fut>(
+        &self,
+        ttl: std::time::Duration,
+        max_entries: usize,
+        ids: &Vec<K>,
+        fetch: F,
+    ) -> Result<(Vec<K>, Vec<WithKey<K>>), IronOxideErr>
+    where
+        F: FnOnce(Vec<K>) -> Fut,
+        Fut: Future<Output = Result<(Vec<K>, Vec<WithKey<K>>), IronOxideErr>>,
+    {
+        if ttl == std::time::Duration::from_secs(0) || max_entries == 0 {
+            return fetch(ids.clone()).await;
+        }
+        let (cached, missing) = self.partition(ttl, ids);
+        let (not_found, fetched) = fetch(missing).await?;
+        for WithKey { id, public_key } in &fetched {
+            //if the cache has grown too large, clear it prior to adding new entries
+            if self.entries.len() >= max_entries {
+                self.entries.clear()
+            }
+            self.entries.insert(
+                id.clone(),
+                CachedPublicKey {
+                    public_key: public_key.clone(),
+                    cached_at: Utc::now(),
+                },
+            );
+        }
+        Ok((not_found, [cached, fetched].concat()))
+    }
+
+    /// Remove the key for `id`, returning whether it was cached
+    pub(crate) fn remove(&self, id: &K) -> bool {
+        self.entries.remove(id).is_some()
+    }
+
+    /// Remove every key, returning how many were cached
+    pub(crate) fn clear(&self) -> usize {
+        let size = self.entries.len();
+        self.entries.clear();
+        size
+    }
+}
+
+impl<K: Eq + std::hash::Hash> Default for PublicKeyCache<K> {
+    fn default() -> Self {
+        PublicKeyCache {
+            entries: dashmap::DashMap::new(),
+        }
+    }
+}
+
 /// Acquire mutex in a blocking fashion. If the Mutex is or becomes poisoned, write out an error
 /// message and panic.
 ///
@@ -716,6 +1495,7 @@ pub(crate) fn take_lock<T>(m: &Mutex<T>) -> MutexGuard<T> {
 /// Attempts to augment an existing private key with a newly generated augmentation factor.
 /// There is a very small chance that an augmentation factor could not be compatible with
 /// the given PrivateKey, so we retry once internally before giving the caller an error.
+#[cfg_attr(feature = "disable-password-escrow", allow(dead_code))]
 fn augment_private_key_with_retry<R: KeyGenOps>(
     recrypt: &R,
     priv_key: &PrivateKey,
@@ -747,32 +1527,113 @@ fn gen_plaintext_and_aug_with_retry<R: CryptoOps>(
     aug_private_key().or_else(|_| aug_private_key())
 }
 
+tokio::task_local! {
+    /// When the SDK calls run by `with_call_timeout` must finish, and the timeout it was set with
+    static CALL_DEADLINE: (rt::Instant, std::time::Duration);
+}
+
+/// Run `f`, failing each SDK operation it runs that hasn't finished `timeout` from now, or by the deadline
+/// of an enclosing `with_call_timeout` if that is sooner.
+pub(crate) async fn with_call_timeout<F: Future>(timeout: std::time::Duration, f: F) -> F::Output {
+    let deadline = (rt::Instant::now() + timeout, timeout);
+    let deadline = match CALL_DEADLINE.try_with(|outer| *outer) {
+        Ok(outer) if outer.0 < deadline.0 => outer,
+        _ => deadline,
+    };
+    CALL_DEADLINE.scope(deadline, f).await
+}
+
+tokio::task_local! {
+    /// Whether the SDK calls being run should stop before their mutating requests
+    static VALIDATE_ONLY: bool;
+}
+
+/// Run `f`, stopping each SDK operation it runs before its mutating request if `validate_only` is set or
+/// an enclosing `with_validate_only` set it.
+pub(crate) async fn with_validate_only<F: Future>(validate_only: bool, f: F) -> F::Output {
+    let validate_only = validate_only || VALIDATE_ONLY.try_with(|outer| *outer).unwrap_or(false);
+    VALIDATE_ONLY.scope(validate_only, f).await
+}
+
+/// Stop `operation` before its mutating request if it's being run with `with_validate_only`. Fails with a
+/// `ValidationError` for `field` listing the `failures`, the users or groups the operation wouldn't apply
+/// to, if there are any, and with `ValidateOnly` if there aren't. Otherwise does nothing.
+pub(crate) fn stop_if_validate_only<F>(
+    operation: SdkOperation,
+    field: &str,
+    failures: F,
+) -> Result<(), IronOxideErr>
+where
+    F: FnOnce() -> Vec<String>,
+{
+    if !VALIDATE_ONLY
+        .try_with(|validate_only| *validate_only)
+        .unwrap_or(false)
+    {
+        return Ok(());
+    }
+    let failures = failures();
+    if failures.is_empty() {
+        Err(IronOxideErr::ValidateOnly(operation))
+    } else {
+        Err(IronOxideErr::ValidationError(
+            field.to_string(),
+            failures.join(", "),
+        ))
+    }
+}
+
 /// Runs a future with a timeout or just runs the future, depending on if a timeout is specified.
 ///
 /// If a timeout limit is reached, the result will be an IronOxideErr::OperationTimedOut.
 /// If no timeout is specified, or if the operation finishes before the timeout, the
-/// result is the result of the sdk operation.
-pub async fn add_optional_timeout<F: Future>(
+/// result is the result of the sdk operation. The deadline of an enclosing `with_call_timeout`
+/// applies as well, whichever is sooner. Either way, the operation's metrics are reported to
+/// `metrics_sink`, if there is one.
+pub(crate) async fn add_optional_timeout<F>(
     f: F,
     timeout: Option<std::time::Duration>,
     op: SdkOperation,
-) -> Result<F::Output, IronOxideErr> {
+    metrics_sink: Option<&crate::metrics::SharedMetricsSink>,
+) -> Result<F::Output, IronOxideErr>
+where
+    F: Future,
+    F::Output: crate::metrics::Outcome,
+{
     use futures::future::TryFutureExt;
-    let result = match timeout {
-        Some(d) => {
-            tokio::time::timeout(d, f)
-                .map_err(|_| IronOxideErr::OperationTimedOut {
-                    operation: op,
-                    duration: d,
-                })
-                .await?
-        }
-
-        // no timeout, just run the Future and return
-        None => f.await,
-    };
+    rt::compat(async move {
+        // the time to wait, and the timeout reported if it runs out
+        let call_timeout = CALL_DEADLINE
+            .try_with(|(deadline, duration)| {
+                (
+                    deadline.saturating_duration_since(rt::Instant::now()),
+                    *duration,
+                )
+            })
+            .ok();
+        let timeout = match (timeout, call_timeout) {
+            (Some(d), Some((remaining, _))) if d <= remaining => Some((d, d)),
+            (_, Some(call_timeout)) => Some(call_timeout),
+            (d, None) => d.map(|d| (d, d)),
+        };
+        let started = rt::Instant::now();
+        let result = match timeout {
+            Some((wait, d)) => {
+                rt::timeout(wait, f)
+                    .map_err(|_| IronOxideErr::OperationTimedOut {
+                        operation: op,
+                        duration: d,
+                    })
+                    .await
+            }
 
-    Ok(result)
+            // no timeout, just run the Future and return
+            None => Ok(f.await),
+        };
+        crate::metrics::record(op, started.elapsed(), &result, metrics_sink);
+        result
+    })
+    .await
 }
 
 #[cfg(test)]
@@ -780,6 +1641,7 @@ pub(crate) mod tests {
     use super::*;
     use double::*;
     use galvanic_assert::{matchers::*, *};
+    use recrypt::api::Ed25519Ops;
     use std::fmt::Debug;
     use tokio::time::Duration;
     use vec1::vec1;
@@ -858,96 +1720,202 @@ pub(crate) mod tests {
     }
 
     #[test]
-    fn validate_id_success() {
-        let valid_id = "abcABC012_.$#|@/:;=+'-";
-        let id = validate_id(valid_id, "id_type");
-        assert_that!(&id, is_variant!(Ok));
-        assert_that!(&id.unwrap(), eq(valid_id.to_string()))
-    }
-
-    #[test]
-    fn valid_id_whitespace() {
-        let valid_id = " abc212     ";
-        let id = validate_id(valid_id, "id_type");
-        assert_that!(&id, is_variant!(Ok));
-        assert_that!(&id.unwrap(), eq("abc212".to_string()))
+    fn public_key_fingerprint_is_truncated_sha256() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (_, recrypt_pub) = recrypt.generate_key_pair()?;
+        let (_, other_pub) = recrypt.generate_key_pair()?;
+        let public_key: PublicKey = recrypt_pub.into();
+
+        let fingerprint = public_key.fingerprint();
+        let digest = digest::digest(&digest::SHA256, &public_key.as_bytes());
+        assert_eq!(&public_key.to_bytes_array()[..], &public_key.as_bytes()[..]);
+        assert_eq!(&fingerprint.as_bytes()[..], &digest.as_ref()[..16]);
+        assert_eq!(fingerprint.to_hex(), hex::encode(&digest.as_ref()[..16]));
+        assert_ne!(fingerprint, PublicKey::from(other_pub).fingerprint());
+
+        let displayed = fingerprint.to_string();
+        assert_eq!(displayed.split(' ').count(), 8);
+        assert_eq!(displayed.replace(" ", ""), fingerprint.to_hex());
+        Ok(())
     }
 
     #[test]
-    fn validate_id_failure() {
-        let invalid_id = "with spaces";
-        let id_type = "id_type";
-        let id = validate_id(invalid_id, id_type);
-        assert_that!(&id, is_variant!(Err));
-        let validation_error = id.unwrap_err();
+    fn verify_schnorr_checks_key_message_and_signature() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (priv_key, pub_key) = recrypt.generate_key_pair()?;
+        let (_, other_pub_key) = recrypt.generate_key_pair()?;
+        let message: &[u8] = b"signed payload";
+        let signature: SchnorrSignature =
+            recrypt.schnorr_sign(&priv_key, &pub_key, &message).into();
+        let public_key: PublicKey = pub_key.into();
+
+        let roundtripped = SchnorrSignature::try_from(&signature.as_bytes()[..])?;
+        assert!(verify_schnorr(&public_key, message, &roundtripped));
+        assert!(!verify_schnorr(&public_key, b"other payload", &signature));
+        assert!(!verify_schnorr(&other_pub_key.into(), message, &signature));
         assert_that!(
-            &validation_error,
+            &SchnorrSignature::try_from(&[0u8; 10][..]).unwrap_err(),
             is_variant!(IronOxideErr::ValidationError)
         );
-        assert_that!(&format!("{}", validation_error), contains(id_type));
-        assert_that!(&format!("{}", validation_error), contains(invalid_id));
+        Ok(())
     }
 
     #[test]
-    fn validate_id_all_whitespace() {
-        let invalid_id = "     ";
-        let id_type = "id_type";
-        let id = validate_id(invalid_id, id_type);
-        assert_that!(&id, is_variant!(Err));
-        let validation_error = id.unwrap_err();
+    fn transform_key_roundtrips_through_bytes() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (from_priv, from_pub) = recrypt.generate_key_pair()?;
+        let (to_priv, to_pub) = recrypt.generate_key_pair()?;
+        let signing_keys = recrypt.generate_ed25519_key_pair();
+        let transform_key: TransformKey = recrypt
+            .generate_transform_key(&from_priv, &to_pub, &signing_keys)?
+            .into();
+
+        let bytes = transform_key.to_bytes();
+        assert_eq!(bytes.len(), TransformKey::ENCODED_SIZE_BYTES);
+        assert_eq!(&transform_key.to_bytes_array()[..], &bytes[..]);
+        let parsed = TransformKey::try_from(&bytes[..])?;
+        assert_eq!(parsed, transform_key);
+
+        // the parsed key still transforms values so the other key can decrypt them
+        let plaintext = recrypt.gen_plaintext();
+        let encrypted = recrypt.encrypt(&plaintext, &from_pub, &signing_keys)?;
+        let transformed = recrypt.transform(encrypted, parsed.into(), &signing_keys)?;
+        let decrypted = recrypt.decrypt(transformed, &to_priv)?;
+        assert_eq!(decrypted.bytes()[..], plaintext.bytes()[..]);
+
         assert_that!(
-            &validation_error,
-            is_variant!(IronOxideErr::ValidationError)
+            &TransformKey::try_from(&bytes[1..]).unwrap_err(),
+            is_variant!(IronOxideErr::WrongSizeError)
         );
-        assert_that!(&format!("{}", validation_error), contains(id_type));
+        Ok(())
     }
 
     #[test]
-    fn validate_name_success() {
-        let valid_name = "name with any char _.$#|@/:;=+'-";
-        let id = validate_name(valid_name, "name_type");
-        assert_that!(&id, is_variant!(Ok));
-        assert_that!(&id.unwrap(), eq(valid_name.to_string()))
-    }
+    fn device_context_with_environment() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (priv_key, _) = recrypt.generate_key_pair()?;
+        let context = DeviceContext::new(
+            "account_id".try_into()?,
+            22,
+            priv_key.into(),
+            recrypt.generate_ed25519_key_pair().into(),
+        );
+        assert_eq!(context.base_url(), URL_STRING.as_str());
 
-    #[test]
-    fn validate_name_surrounding_whitespace() {
-        let valid_name = "   a good name    ";
-        let id = validate_name(valid_name, "name_type");
-        assert_that!(&id, is_variant!(Ok));
-        assert_that!(&id.unwrap(), eq("a good name".to_string()))
+        let timeouts = HttpTimeoutConfig {
+            connect_timeout: Some(std::time::Duration::from_secs(5)),
+            request_timeout: None,
+            rate_limit_wait: None,
+        };
+        let staging = context
+            .clone()
+            .with_http_timeouts(timeouts)
+            .with_environment(Environment::Staging);
+        assert_eq!(staging.base_url(), STAGING_URL);
+        assert_eq!(staging.auth().request.timeouts(), timeouts);
+        let custom = context.with_environment(Environment::Custom(
+            url::Url::parse("https://ironcore.example.com/api/1").unwrap(),
+        ));
+        assert_eq!(custom.base_url(), "https://ironcore.example.com/api/1/");
+
+        // the environment isn't serialized
+        let json = serde_json::to_string(&custom).unwrap();
+        let de: DeviceContext = serde_json::from_str(&json).unwrap();
+        assert_eq!(de.base_url(), URL_STRING.as_str());
+        Ok(())
     }
 
     #[test]
-    fn validate_name_failure() {
-        let name_type = "name_type";
-        let invalid_name = "too many chars 012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789";
-        let name = validate_name(invalid_name, name_type);
-        assert_that!(&name, is_variant!(Err));
-        let validation_error = name.unwrap_err();
-        assert_that!(
-            &validation_error,
-            is_variant!(IronOxideErr::ValidationError)
+    fn serde_devicecontext_refuses_non_exportable() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (priv_key, _) = recrypt.generate_key_pair()?;
+        let context = DeviceContext::new(
+            "account_id".try_into()?,
+            22,
+            priv_key.into(),
+            DeviceSigningKeyPair::from(recrypt::api::Ed25519Ops::generate_ed25519_key_pair(
+                &recrypt,
+            )),
         );
-        assert_that!(&format!("{}", validation_error), contains(invalid_name));
-        assert_that!(&format!("{}", validation_error), contains(name_type));
+        assert!(serde_json::to_string(&context).is_ok());
+        for protection in &[
+            DeviceKeyProtection::NonExportable,
+            DeviceKeyProtection::HardwareBacked,
+        ] {
+            let restricted = context.clone().with_protection(*protection);
+            assert!(serde_json::to_string(&restricted).is_err());
+            // a restricted context can't be made exportable again
+            let downgraded = restricted.with_protection(DeviceKeyProtection::Exportable);
+            assert_eq!(downgraded.protection(), *protection);
+            assert!(serde_json::to_string(&downgraded).is_err());
+        }
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct InMemoryKeyProvider {
+        private_key: RecryptPrivateKey,
+        signing_keys: RecryptSigningKeypair,
+    }
+    impl PrivateKeyProvider for InMemoryKeyProvider {
+        fn public_signing_key(&self) -> [u8; 32] {
+            self.signing_keys.public_key().into()
+        }
+        fn sign(&self, payload: &[u8]) -> Result<[u8; 64], IronOxideErr> {
+            Ok(self.signing_keys.sign(&payload).into())
+        }
+        fn decrypt(
+            &self,
+            encrypted_value: RecryptEncryptedValue,
+        ) -> Result<Plaintext, IronOxideErr> {
+            Ok(Recrypt::new().decrypt(encrypted_value, &self.private_key)?)
+        }
     }
 
     #[test]
-    fn validate_name_all_whitespace() {
-        let invalid_name = "        ";
-        let name_type = "name_type";
+    fn device_context_delegates_to_key_provider() -> Result<(), IronOxideErr> {
+        let recrypt = Recrypt::new();
+        let (private_key, public_key) = recrypt.generate_key_pair()?;
+        let signing_keys = recrypt.generate_ed25519_key_pair();
+        let public_signing_key = signing_keys.public_key();
+        let value_signing_keys: DeviceSigningKeyPair = recrypt.generate_ed25519_key_pair().into();
+        let context = DeviceContext::with_key_provider(
+            "account_id".try_into()?,
+            22,
+            Arc::new(InMemoryKeyProvider {
+                private_key,
+                signing_keys,
+            }),
+            value_signing_keys.clone(),
+        );
+        assert_eq!(context.signing_private_key(), &value_signing_keys);
+
+        let signer = context.auth().request_signer();
+        assert_eq!(&signer.public_key(), public_signing_key.bytes());
+        let signature = signer.sign(b"payload")?;
+        assert!(
+            public_signing_key.verify(b"payload", &recrypt::api::Ed25519Signature::new(signature))
+        );
+
+        let plaintext = recrypt.gen_plaintext();
+        let encrypted = recrypt.encrypt(
+            &plaintext,
+            &public_key,
+            context.signing_private_key().recrypt_key(),
+        )?;
+        let decrypted = context.device_key().decrypt(&recrypt, encrypted)?;
+        assert_eq!(decrypted.bytes()[..], plaintext.bytes()[..]);
 
-        let name = validate_name(invalid_name, name_type);
-        assert_that!(&name, is_variant!(Err));
-        let validation_error = name.unwrap_err();
+        assert!(context.key_provider().is_some());
         assert_that!(
-            &validation_error,
-            is_variant!(IronOxideErr::ValidationError)
+            &context.device_key().local().unwrap_err(),
+            is_variant!(IronOxideErr::KeyProviderUnsupported)
         );
-        assert_that!(&format!("{}", validation_error), contains(name_type));
+        assert!(serde_json::to_string(&context).is_err());
+        Ok(())
     }
 
+    #[cfg(not(feature = "disable-password-escrow"))]
     #[test]
     fn passphrase_validation() {
         let result = Password::try_from("");
@@ -1225,6 +2193,119 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    /// SDK for a made-up user and device. Any request it makes will fail.
+    pub(crate) fn create_test_sdk() -> Result<crate::IronOxide, IronOxideErr> {
+        let (user, de) = create_test_user_and_device()?;
+        Ok(crate::IronOxide::create(&user, &de, &Default::default()))
+    }
+
+    /// SDK for a made-up user and device that sends its requests to a closed local port, so they fail
+    /// without a response.
+    pub(crate) fn create_unreachable_test_sdk(
+        config: &crate::config::IronOxideConfig,
+    ) -> Result<crate::IronOxide, IronOxideErr> {
+        let (user, de) = create_test_user_and_device()?;
+        let de = de.with_environment(Environment::Custom(
+            url::Url::parse("http://127.0.0.1:1/api/1/").unwrap(),
+        ));
+        Ok(crate::IronOxide::create(&user, &de, config))
+    }
+
+    fn create_test_user_and_device() -> Result<(user_api::UserResult, DeviceContext), IronOxideErr>
+    {
+        use crate::internal::user_api::tests::create_user_result;
+        let recrypt = recrypt::api::Recrypt::new();
+        let (_, pub_key) = recrypt.generate_key_pair()?;
+        let de_json = r#"{"accountId":"account_id","segmentId":22,"signingPrivateKey":"AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQGKiOPddAnxlf1S2y08ul1yymcJvx2UEhvzdIgBtA9vXA==","devicePrivateKey":"bzb0Rlg0u7gx9wDuk1ppRI77OH/0ferXleenJ3Ag6Jg="}"#;
+        let de: DeviceContext = serde_json::from_str(de_json).unwrap();
+        let user = create_user_result("account_id".try_into()?, 22, pub_key.into(), false);
+        Ok((user, de))
+    }
+
+    #[test]
+    fn derive_subkey_depends_on_label_and_key() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (first, _) = recrypt.generate_key_pair()?;
+        let (second, _) = recrypt.generate_key_pair()?;
+        let (first, second): (PrivateKey, PrivateKey) = (first.into(), second.into());
+        assert_eq!(first.derive_subkey(b"label"), first.derive_subkey(b"label"));
+        assert_ne!(first.derive_subkey(b"label"), first.derive_subkey(b"other"));
+        assert_ne!(
+            first.derive_subkey(b"label"),
+            second.derive_subkey(b"label")
+        );
+        assert_ne!(&first.derive_subkey(b"label"), first.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn public_key_cache_honors_ttl() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (_, user_pub) = recrypt.generate_key_pair()?;
+        let public_key: PublicKey = user_pub.into();
+        let recent = UserId::unsafe_from_string("recent".into());
+        let stale = UserId::unsafe_from_string("stale".into());
+        let uncached = UserId::unsafe_from_string("uncached".into());
+        let cache = PublicKeyCache::default();
+        let insert = |id: &UserId, age: chrono::Duration| {
+            cache.entries.insert(
+                id.clone(),
+                CachedPublicKey {
+                    public_key: public_key.clone(),
+                    cached_at: Utc::now() - age,
+                },
+            )
+        };
+        insert(&recent, chrono::Duration::seconds(1));
+        insert(&stale, chrono::Duration::hours(1));
+        let users = [stale.clone(), recent.clone(), uncached.clone()];
+
+        let (cached, missing) = cache.partition(std::time::Duration::from_secs(300), &users);
+        assert_eq!(
+            cached,
+            vec![WithKey::new(recent.clone(), public_key.clone())]
+        );
+        assert_eq!(missing, vec![stale.clone(), uncached.clone()]);
+
+        // a ttl too large to represent keeps every entry
+        let (cached, missing) = cache.partition(std::time::Duration::from_secs(u64::MAX), &users);
+        assert_eq!(cached.len(), 2);
+        assert_eq!(missing, vec![uncached]);
+        assert!(cache.remove(&stale));
+        assert_eq!(cache.clear(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn fork_for_core_shares_caches() -> Result<(), IronOxideErr> {
+        use std::sync::Arc;
+        let io = create_test_sdk()?;
+        let forked = io.fork_for_core();
+
+        assert!(Arc::ptr_eq(&io.config, &forked.config));
+        assert!(Arc::ptr_eq(
+            &io.policy_eval_cache,
+            &forked.policy_eval_cache
+        ));
+        assert!(Arc::ptr_eq(&io.group_key_cache, &forked.group_key_cache));
+        assert!(Arc::ptr_eq(&io.user_key_cache, &forked.user_key_cache));
+        let doc_id = document_api::DocumentId("doc".to_string());
+        let index_config = crate::config::UniqueEncryptionConfig::default();
+        io.content_hash_index
+            .insert(&index_config, [1u8; 32], doc_id.clone());
+        assert_eq!(
+            forked.content_hash_index.get(&index_config, &[1u8; 32]),
+            Some(doc_id)
+        );
+        Ok(())
+    }
+
+    impl crate::metrics::Outcome for u8 {
+        fn succeeded(&self) -> bool {
+            true
+        }
+    }
+
     #[tokio::test]
     async fn run_maybe_timed_sdk_op_no_timeout() -> Result<(), IronOxideErr> {
         async fn get_42() -> u8 {
@@ -1233,7 +2314,7 @@ pub(crate) mod tests {
         }
         let forty_two = get_42();
         let result =
-            add_optional_timeout(forty_two, None, SdkOperation::DocumentRevokeAccess).await?;
+            add_optional_timeout(forty_two, None, SdkOperation::DocumentRevokeAccess, None).await?;
         assert_eq!(result, 42);
 
         let forty_two = get_42();
@@ -1241,6 +2322,7 @@ pub(crate) mod tests {
             forty_two,
             Some(Duration::from_secs(1)),
             SdkOperation::DocumentRevokeAccess,
+            None,
         )
         .await?;
         assert_eq!(result, 42);
@@ -1251,7 +2333,8 @@ pub(crate) mod tests {
         }
 
         let err_f = get_err();
-        let result = add_optional_timeout(err_f, None, SdkOperation::DocumentRevokeAccess).await?;
+        let result =
+            add_optional_timeout(err_f, None, SdkOperation::DocumentRevokeAccess, None).await?;
         assert!(result.is_err());
         assert_that!(
             &result.unwrap_err(),
@@ -1263,6 +2346,7 @@ pub(crate) mod tests {
             err_f,
             Some(Duration::from_secs(1)),
             SdkOperation::DocumentRevokeAccess,
+            None,
         )
         .await?;
         assert!(result.is_err());
@@ -1288,6 +2372,7 @@ pub(crate) mod tests {
             forty_two,
             Some(Duration::from_nanos(1)),
             SdkOperation::DocumentRevokeAccess,
+            None,
         )
         .await;
         assert!(result.is_err());
@@ -1306,6 +2391,7 @@ pub(crate) mod tests {
             err_f,
             Some(Duration::from_millis(1)),
             SdkOperation::DocumentRevokeAccess,
+            None,
         )
         .await;
         assert!(result.is_err());
@@ -1315,4 +2401,82 @@ pub(crate) mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn validate_only_stops_before_mutating() {
+        let stop = |failures: Vec<String>| {
+            stop_if_validate_only(SdkOperation::DocumentGrantAccess, "grants", || failures)
+        };
+        assert_that!(&stop(vec!["'a' [user]: not found".into()]), is_variant!(Ok));
+        let passed = with_validate_only(true, async { stop(vec![]) }).await;
+        assert_that!(
+            &passed.unwrap_err(),
+            is_variant!(IronOxideErr::ValidateOnly)
+        );
+        // an enclosing call's flag can't be turned off
+        let failed = with_validate_only(
+            true,
+            with_validate_only(false, async { stop(vec!["'a' [user]: not found".into()]) }),
+        )
+        .await;
+        assert_that!(
+            &failed.unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+    }
+
+    #[tokio::test]
+    async fn call_timeout_bounds_sdk_ops() -> Result<(), IronOxideErr> {
+        let pending = || futures::future::pending::<()>();
+        let call_timeout = Duration::from_millis(10);
+
+        let result = with_call_timeout(
+            call_timeout,
+            add_optional_timeout(
+                pending(),
+                Some(Duration::from_secs(60)),
+                SdkOperation::DocumentList,
+                None,
+            ),
+        )
+        .await;
+        match result {
+            Err(IronOxideErr::OperationTimedOut {
+                operation: SdkOperation::DocumentList,
+                duration,
+            }) => assert_eq!(duration, call_timeout),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+
+        // an enclosing deadline that is sooner still applies
+        let result = with_call_timeout(
+            call_timeout,
+            with_call_timeout(
+                Duration::from_secs(60),
+                add_optional_timeout(pending(), None, SdkOperation::DocumentList, None),
+            ),
+        )
+        .await;
+        assert!(result.is_err());
+
+        // a shorter configured timeout is kept
+        let config_timeout = Duration::from_millis(1);
+        let result = with_call_timeout(
+            Duration::from_secs(60),
+            add_optional_timeout(
+                pending(),
+                Some(config_timeout),
+                SdkOperation::DocumentList,
+                None,
+            ),
+        )
+        .await;
+        match result {
+            Err(IronOxideErr::OperationTimedOut { duration, .. }) => {
+                assert_eq!(duration, config_timeout)
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        Ok(())
+    }
 }
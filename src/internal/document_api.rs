@@ -1,7 +1,13 @@
 use crate::{
-    config::{IronOxideConfig, PolicyCachingConfig},
+    config::{
+        DecryptKeyPath, DecryptionConfig, DocumentKeyCachingConfig, IronOxideConfig,
+        PolicyCachingConfig, UniqueEncryptionConfig,
+    },
     crypto::{
         aes::{self, AesEncryptedValue},
+        cipher::CipherSuite,
+        locked::Locked,
+        provider::hmac,
         transform,
     },
     internal::{
@@ -10,21 +16,23 @@ use crate::{
         group_api::{GroupId, GroupName},
         take_lock,
         user_api::UserId,
-        validate_id, validate_name, IronOxideErr, PrivateKey, PublicKey, RequestAuth, WithKey,
+        validate_id, validate_name, DevicePrivateKey, IronOxideErr, PrivateKey, PublicKey,
+        RequestAuth, SdkOperation, WithKey,
     },
     policy::PolicyGrant,
     proto::transform::{
         EncryptedDek as EncryptedDekP, EncryptedDekData as EncryptedDekDataP,
         EncryptedDeks as EncryptedDeksP,
     },
-    DeviceSigningKeyPair, PolicyCache,
+    DeviceSigningKeyPair, GroupKeyCache, PolicyCache, UserKeyCache,
 };
 use chrono::{DateTime, Utc};
-use futures::{try_join, Future};
+use futures::{try_join, StreamExt, TryStreamExt};
 use hex::encode;
 use itertools::{Either, Itertools};
 use protobuf::{Message, RepeatedField};
 use rand::{self, CryptoRng, RngCore};
+use rayon::prelude::*;
 use recrypt::{api::Plaintext, prelude::*};
 use requests::{
     document_create,
@@ -34,10 +42,15 @@ use requests::{
 };
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     convert::{TryFrom, TryInto},
     fmt::Formatter,
+    future::Future,
     ops::DerefMut,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
 
 mod requests;
@@ -119,14 +132,70 @@ struct DocumentHeader {
     document_id: DocumentId,
     #[serde(rename = "_sid_")]
     segment_id: usize,
+    /// Omitted for AES-256-GCM, so those headers are unchanged and readable by every IronCore SDK
+    #[serde(
+        rename = "_cs_",
+        default,
+        skip_serializing_if = "CipherSuite::is_default"
+    )]
+    cipher_suite: CipherSuite,
+    /// If set, the packed header is authenticated as AAD along with the document's data. Omitted when
+    /// unset, for headers written by SDKs without support for it.
+    #[serde(rename = "_aad_", default, skip_serializing_if = "std::ops::Not::not")]
+    header_aad: bool,
 }
 impl DocumentHeader {
     fn new(document_id: DocumentId, segment_id: usize) -> DocumentHeader {
         DocumentHeader {
             document_id,
             segment_id,
+            cipher_suite: CipherSuite::default(),
+            header_aad: false,
+        }
+    }
+    fn with_cipher_suite(mut self, cipher_suite: CipherSuite) -> DocumentHeader {
+        self.cipher_suite = cipher_suite;
+        self
+    }
+    fn with_header_aad(mut self, header_aad: bool) -> DocumentHeader {
+        self.header_aad = header_aad;
+        self
+    }
+    /// Additional authenticated data the document's data is encrypted with. Binding the header this way
+    /// means encrypted data moved under another document's header fails to decrypt.
+    fn aad(&self) -> Vec<u8> {
+        if self.header_aad {
+            self.pack().0
+        } else {
+            vec![]
         }
     }
+    /// Encrypt a document's data with this header's cipher suite and AAD
+    async fn encrypt_data<R: CryptoRng + RngCore>(
+        &self,
+        rng: &Mutex<R>,
+        plaintext: &[u8],
+        key: &[u8; 32],
+    ) -> Result<Vec<u8>, IronOxideErr> {
+        self.cipher_suite
+            .encrypt_async(rng, plaintext, key, &self.aad())
+            .await
+    }
+    /// Like `encrypt_data`, but appends the encrypted data to `out`
+    fn encrypt_data_into<R: CryptoRng + RngCore>(
+        &self,
+        rng: &Mutex<R>,
+        plaintext: &[u8],
+        key: &[u8; 32],
+        out: &mut Vec<u8>,
+    ) -> Result<(), IronOxideErr> {
+        self.cipher_suite
+            .encrypt_into(rng, plaintext, key, &self.aad(), out)
+    }
+    /// Decrypt the data that followed this header in an encrypted document
+    fn decrypt_data(&self, encrypted: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, IronOxideErr> {
+        self.cipher_suite.decrypt(encrypted, key, &self.aad())
+    }
     /// Generate a documents header given its ID and internal segment ID that is is associated with. Generates
     /// a Vec<u8> which includes the document version, header size, and header JSON as bytes.
     fn pack(&self) -> DocHeaderPacked {
@@ -144,24 +213,33 @@ impl DocumentHeader {
     }
 }
 
-/// Take an encrypted document and extract out the header metadata. Return that metadata as well as the AESEncryptedValue
-/// that contains the AES IV and encrypted content. Will fail if the provided document doesn't contain the latest version
-/// which contains the header bytes.
+/// Take an encrypted document and extract out the header metadata. Return that metadata as well as the bytes that
+/// contain the IV and encrypted content, to be decrypted with the header's cipher suite. Will fail if the provided
+/// document doesn't contain the latest version which contains the header bytes.
 fn parse_document_parts(
     encrypted_document: &[u8],
-) -> Result<(DocumentHeader, aes::AesEncryptedValue), IronOxideErr> {
+) -> Result<(DocumentHeader, &[u8]), IronOxideErr> {
     //We're explicitly erroring on version 1 documents since there are so few of them and it seems extremely unlikely
     //that anybody will use them with this SDK which was released after we went to version 2.
-    if encrypted_document[0] != CURRENT_DOCUMENT_ID_VERSION {
+    let header_len_end = DOC_VERSION_HEADER_LENGTH + HEADER_META_LENGTH_LENGTH;
+    if encrypted_document.first() != Some(&CURRENT_DOCUMENT_ID_VERSION) {
         Err(IronOxideErr::DocumentHeaderParseFailure(
             "Document is not a supported version and may not be an encrypted file.".to_string(),
         ))
+    } else if encrypted_document.len() < header_len_end {
+        Err(IronOxideErr::DocumentHeaderParseFailure(
+            "Document is truncated before the end of its header.".to_string(),
+        ))
     } else {
-        let header_len_end = DOC_VERSION_HEADER_LENGTH + HEADER_META_LENGTH_LENGTH;
         //The 2nd and 3rd bytes of the header are a big-endian u16 that tell us how long the subsequent JSON
         //header is in bytes. So we need to convert these two u8s into a single u16.
         let encoded_header_size =
             encrypted_document[1] as usize * 256 + encrypted_document[2] as usize;
+        if encrypted_document.len() < header_len_end + encoded_header_size {
+            return Err(IronOxideErr::DocumentHeaderParseFailure(
+                "Document is truncated before the end of its header.".to_string(),
+            ));
+        }
         serde_json::from_slice(
             &encrypted_document[header_len_end..(header_len_end + encoded_header_size)],
         )
@@ -170,12 +248,11 @@ fn parse_document_parts(
                 "Unable to parse document header. Header value is corrupted.".to_string(),
             )
         })
-        .and_then(|header_json| {
-            //Convert the remaining document bytes into an AesEncryptedValue which splits out the IV/data
-            Ok((
+        .map(|header_json| {
+            (
                 header_json,
-                encrypted_document[(header_len_end + encoded_header_size)..].try_into()?,
-            ))
+                &encrypted_document[(header_len_end + encoded_header_size)..],
+            )
         })
     }
 }
@@ -191,6 +268,15 @@ pub enum AssociationType {
     /// User was granted access to the document via a group they are a member of.
     FromGroup,
 }
+impl AssociationType {
+    /// Key path used to gain access to the document.
+    pub fn key_path(&self) -> DecryptKeyPath {
+        match self {
+            AssociationType::Owner | AssociationType::FromUser => DecryptKeyPath::User,
+            AssociationType::FromGroup => DecryptKeyPath::Group,
+        }
+    }
+}
 
 /// User who is able to access a document.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
@@ -305,6 +391,35 @@ impl DocumentMetadataResult {
     }
 }
 
+/// The grants through which the calling user can access a document.
+///
+/// Result from [document_access_via](trait.DocumentOps.html#tymethod.document_access_via).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DocumentAccessViaResult {
+    id: DocumentId,
+    association_type: AssociationType,
+    direct: bool,
+    groups: Vec<VisibleGroup>,
+}
+impl DocumentAccessViaResult {
+    /// ID of the document
+    pub fn id(&self) -> &DocumentId {
+        &self.id
+    }
+    /// How the webservice chooses to give the calling user access to the document
+    pub fn association_type(&self) -> &AssociationType {
+        &self.association_type
+    }
+    /// Whether the calling user created the document or was granted access to it directly
+    pub fn has_direct_access(&self) -> bool {
+        self.direct
+    }
+    /// Groups the calling user is a member of that have been granted access to the document
+    pub fn groups(&self) -> &Vec<VisibleGroup> {
+        &self.groups
+    }
+}
+
 /// Encrypted document bytes and metadata.
 ///
 /// Unmanaged encryption does not store document access information with the webservice,
@@ -329,7 +444,7 @@ impl DocumentEncryptUnmanagedResult {
         Ok(DocumentEncryptUnmanagedResult {
             id: encryption_result.header.document_id.clone(),
             access_errs,
-            encrypted_data: encryption_result.edoc_bytes().to_vec(),
+            encrypted_data: encryption_result.edoc_bytes(),
             encrypted_deks: edek_bytes,
             grants: encryption_result
                 .value
@@ -344,6 +459,10 @@ impl DocumentEncryptUnmanagedResult {
     pub fn encrypted_data(&self) -> &[u8] {
         &self.encrypted_data
     }
+    /// Takes the bytes of encrypted document data, without copying them
+    pub fn into_encrypted_data(self) -> Vec<u8> {
+        self.encrypted_data
+    }
     /// Bytes of EDEKs of users/groups that have been granted access to `encrypted_data`
     pub fn encrypted_deks(&self) -> &[u8] {
         &self.encrypted_deks
@@ -377,10 +496,35 @@ pub struct DocumentEncryptResult {
     access_errs: Vec<DocAccessEditErr>,
 }
 impl DocumentEncryptResult {
+    /// Result for a [MockIronOxide](../testing/struct.MockIronOxide.html) to return
+    #[cfg(feature = "testing")]
+    pub fn new(
+        id: DocumentId,
+        name: Option<DocumentName>,
+        created: DateTime<Utc>,
+        updated: DateTime<Utc>,
+        encrypted_data: Vec<u8>,
+        grants: Vec<UserOrGroup>,
+        access_errs: Vec<DocAccessEditErr>,
+    ) -> DocumentEncryptResult {
+        DocumentEncryptResult {
+            id,
+            name,
+            updated,
+            created,
+            encrypted_data,
+            grants,
+            access_errs,
+        }
+    }
     /// Bytes of encrypted document data
     pub fn encrypted_data(&self) -> &[u8] {
         &self.encrypted_data
     }
+    /// Takes the bytes of encrypted document data, without copying them
+    pub fn into_encrypted_data(self) -> Vec<u8> {
+        self.encrypted_data
+    }
     /// ID of the document
     pub fn id(&self) -> &DocumentId {
         &self.id
@@ -406,6 +550,14 @@ impl DocumentEncryptResult {
         &self.access_errs
     }
 }
+/// Result of [document_encrypt_unique](trait.DocumentOps.html#tymethod.document_encrypt_unique).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum DocumentEncryptUniqueResult {
+    /// The document was encrypted
+    Encrypted(DocumentEncryptResult),
+    /// This SDK already encrypted the same bytes to the same audience as the document with this ID
+    AlreadyEncrypted(DocumentId),
+}
 /// Decrypted document bytes and metadata.
 ///
 /// Result from [document_decrypt](trait.DocumentOps.html#tymethod.document_decrypt).
@@ -418,10 +570,31 @@ pub struct DocumentDecryptResult {
     decrypted_data: Vec<u8>,
 }
 impl DocumentDecryptResult {
+    /// Result for a [MockIronOxide](../testing/struct.MockIronOxide.html) to return
+    #[cfg(feature = "testing")]
+    pub fn new(
+        id: DocumentId,
+        name: Option<DocumentName>,
+        created: DateTime<Utc>,
+        updated: DateTime<Utc>,
+        decrypted_data: Vec<u8>,
+    ) -> DocumentDecryptResult {
+        DocumentDecryptResult {
+            id,
+            name,
+            updated,
+            created,
+            decrypted_data,
+        }
+    }
     /// Bytes of decrypted document data
     pub fn decrypted_data(&self) -> &[u8] {
         &self.decrypted_data
     }
+    /// Takes the bytes of decrypted document data, without copying them
+    pub fn into_decrypted_data(self) -> Vec<u8> {
+        self.decrypted_data
+    }
     /// ID of the document
     pub fn id(&self) -> &DocumentId {
         &self.id
@@ -486,6 +659,170 @@ impl DocumentAccessResult {
         &self.failed
     }
 }
+/// Kind of access change recorded in an audit log.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum AccessLogOp {
+    /// Access to the document was granted.
+    Grant,
+    /// Access to the document was revoked.
+    Revoke,
+}
+
+/// Single grant or revoke of document access, as recorded in a local audit log.
+///
+/// Entries are replayed in order by
+/// [document_verify_access_log](trait.DocumentAuditOps.html#tymethod.document_verify_access_log).
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct AccessLogEntry {
+    document_id: DocumentId,
+    user_or_group: UserOrGroup,
+    op: AccessLogOp,
+}
+impl AccessLogEntry {
+    /// Constructs a new `AccessLogEntry`.
+    pub fn new(document_id: DocumentId, user_or_group: UserOrGroup, op: AccessLogOp) -> Self {
+        AccessLogEntry {
+            document_id,
+            user_or_group,
+            op,
+        }
+    }
+    /// ID of the document whose access changed
+    pub fn document_id(&self) -> &DocumentId {
+        &self.document_id
+    }
+    /// User or group whose access changed
+    pub fn user_or_group(&self) -> &UserOrGroup {
+        &self.user_or_group
+    }
+    /// Whether access was granted or revoked
+    pub fn op(&self) -> AccessLogOp {
+        self.op
+    }
+}
+
+/// Difference between the access recorded in an audit log and the access reported by the webservice.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum AccessDivergence {
+    /// The user or group has access to the document, but the log never recorded a grant for it.
+    Unrecorded {
+        document_id: DocumentId,
+        user_or_group: UserOrGroup,
+    },
+    /// The log expects the user or group to have access to the document, but it does not.
+    Missing {
+        document_id: DocumentId,
+        user_or_group: UserOrGroup,
+    },
+}
+
+/// Signed record of the users and groups a document was shared with at a point in time.
+///
+/// Result from [document_access_snapshot](trait.DocumentAuditOps.html#tymethod.document_access_snapshot),
+/// and used by [document_access_restore](trait.DocumentAuditOps.html#tymethod.document_access_restore)
+/// to put the document's access back the way it was. The snapshot is signed with the signing key of the
+/// device that took it, and can only be restored by that device.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentAccessSnapshot {
+    document_id: DocumentId,
+    taken: DateTime<Utc>,
+    users: Vec<UserId>,
+    groups: Vec<GroupId>,
+    #[serde(with = "internal::rest::json::Base64Standard")]
+    public_signing_key: Vec<u8>,
+    #[serde(with = "internal::rest::json::Base64Standard")]
+    signature: Vec<u8>,
+}
+impl DocumentAccessSnapshot {
+    fn new(
+        document_id: DocumentId,
+        taken: DateTime<Utc>,
+        users: Vec<UserId>,
+        groups: Vec<GroupId>,
+        signing_keys: &DeviceSigningKeyPair,
+    ) -> DocumentAccessSnapshot {
+        let mut snapshot = DocumentAccessSnapshot {
+            document_id,
+            taken,
+            users,
+            groups,
+            public_signing_key: signing_keys.public_key().to_vec(),
+            signature: vec![],
+        };
+        snapshot.signature = signing_keys.sign(&snapshot.signed_bytes()).to_vec();
+        snapshot
+    }
+
+    /// Bytes covered by the signature: everything but the signature itself.
+    fn signed_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(&(
+            &self.document_id,
+            &self.taken,
+            &self.users,
+            &self.groups,
+            &self.public_signing_key,
+        ))
+        .expect("Serialization of DocumentAccessSnapshot failed.") //Serializing strings and bytes shouldn't fail
+    }
+
+    /// Check that the snapshot was signed by `signing_keys` and has not been modified since.
+    fn verify(&self, signing_keys: &DeviceSigningKeyPair) -> Result<(), IronOxideErr> {
+        let invalid =
+            |err: &str| IronOxideErr::ValidationError("snapshot".to_string(), err.to_string());
+        if self.public_signing_key[..] != signing_keys.public_key()[..] {
+            return Err(invalid("snapshot was not taken by this device"));
+        }
+        let signature = recrypt::api::Ed25519Signature::new_from_slice(&self.signature)
+            .map_err(|_| invalid("snapshot signature is malformed"))?;
+        if recrypt::api::PublicSigningKey::new(signing_keys.public_key())
+            .verify(&self.signed_bytes(), &signature)
+        {
+            Ok(())
+        } else {
+            Err(invalid("snapshot signature does not match its contents"))
+        }
+    }
+
+    /// ID of the document
+    pub fn document_id(&self) -> &DocumentId {
+        &self.document_id
+    }
+    /// When the snapshot was taken
+    pub fn taken(&self) -> &DateTime<Utc> {
+        &self.taken
+    }
+    /// Users the document was shared with
+    pub fn users(&self) -> &[UserId] {
+        &self.users
+    }
+    /// Groups the document was shared with
+    pub fn groups(&self) -> &[GroupId] {
+        &self.groups
+    }
+}
+
+/// Access changes made to put a document's access back to a
+/// [DocumentAccessSnapshot](struct.DocumentAccessSnapshot.html).
+///
+/// Result from [document_access_restore](trait.DocumentAuditOps.html#tymethod.document_access_restore).
+/// Both the grants and the revokes support partial success.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DocumentAccessRestoreResult {
+    granted: DocumentAccessResult,
+    revoked: DocumentAccessResult,
+}
+impl DocumentAccessRestoreResult {
+    /// Users and groups in the snapshot whose access was granted back
+    pub fn granted(&self) -> &DocumentAccessResult {
+        &self.granted
+    }
+    /// Users and groups not in the snapshot whose access was revoked
+    pub fn revoked(&self) -> &DocumentAccessResult {
+        &self.revoked
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 struct DecryptedData(Vec<u8>);
 
@@ -513,6 +850,10 @@ impl DocumentDecryptUnmanagedResult {
     pub fn decrypted_data(&self) -> &[u8] {
         &self.decrypted_data.0
     }
+    /// Takes the bytes of decrypted document data, without copying them
+    pub fn into_decrypted_data(self) -> Vec<u8> {
+        self.decrypted_data.0
+    }
 }
 
 /// A user or a group.
@@ -573,11 +914,123 @@ pub async fn document_get_metadata(
     ))
 }
 
+/// Determine whether the calling user's access to a document comes from a direct grant and which
+/// of the calling user's groups have been granted access to it.
+pub async fn document_access_via(
+    auth: &RequestAuth,
+    id: &DocumentId,
+) -> Result<DocumentAccessViaResult, IronOxideErr> {
+    let DocumentMetadataResult(meta) = document_get_metadata(auth, id).await?;
+    let direct = match meta.association.typ {
+        AssociationType::Owner | AssociationType::FromUser => true,
+        AssociationType::FromGroup => meta
+            .visible_to
+            .users
+            .iter()
+            .any(|user| &user.id == auth.account_id()),
+    };
+    let groups = if meta.visible_to.groups.is_empty() {
+        vec![]
+    } else {
+        let group_ids = meta
+            .visible_to
+            .groups
+            .iter()
+            .map(|group| group.id.clone())
+            .collect();
+        let member_of = internal::group_api::list(auth, Some(&group_ids)).await?;
+        meta.visible_to
+            .groups
+            .into_iter()
+            .filter(|group| {
+                member_of
+                    .result()
+                    .iter()
+                    .any(|g| g.id() == &group.id && g.is_member())
+            })
+            .collect()
+    };
+    Ok(DocumentAccessViaResult {
+        id: meta.id,
+        association_type: meta.association.typ,
+        direct,
+        groups,
+    })
+}
+
 /// Attempt to parse the provided encrypted document header and extract out the ID if present
 pub fn get_id_from_bytes(encrypted_document: &[u8]) -> Result<DocumentId, IronOxideErr> {
     parse_document_parts(encrypted_document).map(|header| header.0.document_id)
 }
 
+/// Header of an encrypted document: which document it is and how its data was encrypted
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DocumentHeaderInfo {
+    id: DocumentId,
+    segment_id: usize,
+    cipher_suite: CipherSuite,
+    header_aad: bool,
+}
+
+impl DocumentHeaderInfo {
+    /// ID of the document
+    pub fn id(&self) -> &DocumentId {
+        &self.id
+    }
+    /// Internal ID of the segment the document was encrypted in
+    pub fn segment_id(&self) -> usize {
+        self.segment_id
+    }
+    /// Cipher the document's data was encrypted with
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+    /// Whether the header is authenticated along with the document's data
+    pub fn header_aad(&self) -> bool {
+        self.header_aad
+    }
+}
+
+/// Parse the header of the provided encrypted document
+pub fn get_header_from_bytes(
+    encrypted_document: &[u8],
+) -> Result<DocumentHeaderInfo, IronOxideErr> {
+    parse_document_parts(encrypted_document).map(|(header, _)| DocumentHeaderInfo {
+        id: header.document_id,
+        segment_id: header.segment_id,
+        cipher_suite: header.cipher_suite,
+        header_aad: header.header_aad,
+    })
+}
+
+/// Parse the EDEKs of an unmanaged document and return the users and groups they were encrypted to. If
+/// `encrypted_doc` is given, fail if the EDEKs are for a different document.
+pub fn get_edek_recipients(
+    encrypted_deks: &[u8],
+    encrypted_doc: Option<&[u8]>,
+) -> Result<Vec<UserOrGroup>, IronOxideErr> {
+    let proto_edeks = EncryptedDeksP::parse_from_bytes(encrypted_deks)?;
+    if let Some(encrypted_doc) = encrypted_doc {
+        edeks_and_header_match_or_err(&proto_edeks, &parse_document_parts(encrypted_doc)?.0)?;
+    }
+    Ok(proto_edeks
+        .get_edeks()
+        .iter()
+        .map(|edek| {
+            let user_or_group = edek.get_userOrGroup();
+            if user_or_group.has_groupId() {
+                UserOrGroup::Group {
+                    id: GroupId::unsafe_from_string(user_or_group.get_groupId().to_string()),
+                }
+            } else {
+                UserOrGroup::User {
+                    id: UserId::unsafe_from_string(user_or_group.get_userId().to_string()),
+                }
+            }
+        })
+        .collect())
+}
+
 /// Encrypt a new document and share it with explicit users/groups and with users/groups specified by a policy
 pub async fn encrypt_document<
     R1: rand::CryptoRng + rand::RngCore,
@@ -596,13 +1049,19 @@ pub async fn encrypt_document<
     group_grants: &Vec<GroupId>,
     policy_grant: Option<&PolicyGrant>,
     policy_cache: &PolicyCache,
+    group_key_cache: &GroupKeyCache,
+    user_key_cache: &UserKeyCache,
+    cipher_suite: CipherSuite,
+    header_aad: bool,
 ) -> Result<DocumentEncryptResult, IronOxideErr> {
     let (dek, doc_sym_key) = transform::generate_new_doc_key(recrypt);
     let doc_id = document_id.unwrap_or_else(|| DocumentId::goo_id(rng));
-    let pt_bytes = plaintext.to_vec();
+    let header = DocumentHeader::new(doc_id.clone(), auth.segment_id)
+        .with_cipher_suite(cipher_suite)
+        .with_header_aad(header_aad);
 
     let (encrypted_doc, (grants, key_errs)) = try_join!(
-        aes::encrypt_async(rng, &pt_bytes, *doc_sym_key.bytes()),
+        header.encrypt_data(rng, plaintext, doc_sym_key.bytes()),
         resolve_keys_for_grants(
             auth,
             config,
@@ -614,7 +1073,9 @@ pub async fn encrypt_document<
             } else {
                 None
             },
-            policy_cache
+            policy_cache,
+            group_key_cache,
+            user_key_cache
         )
     )?;
     let r = recrypt_document(
@@ -625,17 +1086,27 @@ pub async fn encrypt_document<
         &doc_id,
         grants,
     )?;
-    let encryption_errs = r.encryption_errs.clone();
+    let access_errs = [key_errs, r.encryption_errs.clone()].concat();
+    internal::stop_if_validate_only(SdkOperation::DocumentEncrypt, "grants", || {
+        describe_access_errs(&access_errs)
+    })?;
     document_create(
         auth,
-        r.into_edoc(DocumentHeader::new(doc_id.clone(), auth.segment_id)),
+        r.into_edoc(header),
         doc_id,
         &document_name,
-        [key_errs, encryption_errs].concat(),
+        access_errs,
     )
     .await
 }
 
+/// The users and groups of `errs` and why access couldn't be changed for them
+fn describe_access_errs(errs: &[DocAccessEditErr]) -> Vec<String> {
+    errs.iter()
+        .map(|e| format!("{}: {}", e.user_or_group, e.err))
+        .collect()
+}
+
 type UserMasterPublicKey = PublicKey;
 /// Get the public keys for a document grant.
 ///
@@ -659,9 +1130,21 @@ async fn resolve_keys_for_grants(
     policy_grant: Option<&PolicyGrant>,
     maybe_user_master_pub_key: Option<&UserMasterPublicKey>,
     policy_cache: &PolicyCache,
+    group_key_cache: &GroupKeyCache,
+    user_key_cache: &UserKeyCache,
 ) -> Result<(Vec<WithKey<UserOrGroup>>, Vec<DocAccessEditErr>), IronOxideErr> {
-    let get_user_keys_f = internal::user_api::get_user_keys(auth, user_grants);
-    let get_group_keys_f = internal::group_api::get_group_keys(auth, group_grants);
+    let get_user_keys_f = internal::user_api::get_user_keys_cached(
+        auth,
+        &config.user_key_caching,
+        user_key_cache,
+        user_grants,
+    );
+    let get_group_keys_f = internal::group_api::get_group_keys_cached(
+        auth,
+        &config.group_key_caching,
+        group_key_cache,
+        group_grants,
+    );
 
     let maybe_policy_grants_f =
         policy_grant.map(|p| (p, requests::policy_get::policy_get_request(auth, p)));
@@ -711,9 +1194,12 @@ async fn get_cached_policy_or<F>(
 where
     F: Future<Output = Result<PolicyResponse, IronOxideErr>>,
 {
-    // if there's a value in the cache, use it
-    if let Some(cached_policy) = policy_cache.get(grant) {
-        Ok((vec![], cached_policy.clone()))
+    // if there's an unexpired value in the cache, use it
+    if let Some(cached_policy) = policy_cache
+        .get(grant)
+        .filter(|cached| cached.is_fresh(config.ttl))
+    {
+        Ok((vec![], cached_policy.public_keys.clone()))
     } else {
         // otherwise query the webservice and cache the result if there are no errors
         get_policy_f
@@ -725,7 +1211,7 @@ where
                     if policy_cache.len() >= config.max_entries {
                         policy_cache.clear()
                     }
-                    policy_cache.insert(grant.clone(), public_keys.clone());
+                    policy_cache.insert(grant.clone(), CachedPolicy::new(public_keys.clone()));
                 }
                 (errs, public_keys)
             })
@@ -739,8 +1225,264 @@ where
     }
 }
 
+/// Users and groups a policy evaluated to, along with when the evaluation was cached.
+#[derive(Clone, Debug)]
+pub struct CachedPolicy {
+    pub(crate) public_keys: Vec<WithKey<UserOrGroup>>,
+    pub(crate) cached_at: DateTime<Utc>,
+}
+
+impl CachedPolicy {
+    pub(crate) fn new(public_keys: Vec<WithKey<UserOrGroup>>) -> CachedPolicy {
+        CachedPolicy {
+            public_keys,
+            cached_at: Utc::now(),
+        }
+    }
+
+    /// Whether this evaluation was cached less than `ttl` ago. Without a `ttl` it never expires.
+    fn is_fresh(&self, ttl: Option<std::time::Duration>) -> bool {
+        match ttl.map(chrono::Duration::from_std) {
+            Some(Ok(ttl)) => Utc::now() - self.cached_at < ttl,
+            // a ttl too long to represent never expires either
+            Some(Err(_)) | None => true,
+        }
+    }
+}
+
+/// Serialized form of a single policy cache entry.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedPolicy {
+    grant: PolicyGrant,
+    public_keys: Vec<(UserOrGroup, internal::rest::json::PublicKey)>,
+    cached_at: DateTime<Utc>,
+}
+
+/// Keyed hash of document bytes and the audience they are being encrypted to, used to find documents
+/// that were already encrypted by this device. The HMAC key is derived from the device private key, so
+/// the hash reveals nothing about the plaintext to anyone else.
+pub fn content_hash(
+    device_private_key: &PrivateKey,
+    document_data: &[u8],
+    grant_to_author: bool,
+    users: &[UserId],
+    groups: &[GroupId],
+    policy: Option<&PolicyGrant>,
+) -> [u8; 32] {
+    let key = hmac::Key::new(
+        hmac::HMAC_SHA256,
+        &device_private_key.derive_subkey(b"ironoxide-content-hash"),
+    );
+
+    // every field is length prefixed so that different audiences can't produce the same input
+    fn update(context: &mut hmac::Context, bytes: &[u8]) {
+        context.update(&(bytes.len() as u64).to_be_bytes());
+        context.update(bytes);
+    }
+    let mut context = hmac::Context::with_key(&key);
+    update(&mut context, document_data);
+    update(&mut context, &[grant_to_author as u8]);
+    let user_ids: Vec<&str> = users.iter().map(UserId::id).sorted().dedup().collect();
+    update(&mut context, &(user_ids.len() as u64).to_be_bytes());
+    user_ids
+        .iter()
+        .for_each(|id| update(&mut context, id.as_bytes()));
+    let group_ids: Vec<&str> = groups.iter().map(GroupId::id).sorted().dedup().collect();
+    update(&mut context, &(group_ids.len() as u64).to_be_bytes());
+    group_ids
+        .iter()
+        .for_each(|id| update(&mut context, id.as_bytes()));
+    match policy {
+        None => update(&mut context, b"no-policy"),
+        Some(policy) => {
+            update(&mut context, b"policy");
+            let fields = [
+                policy.category().map(|c| c.inner()),
+                policy.sensitivity().map(|s| s.inner()),
+                policy.data_subject().map(|d| d.inner()),
+                policy.substitute_user().map(UserId::id),
+            ];
+            fields.iter().for_each(|field| match field {
+                None => update(&mut context, &[0]),
+                Some(value) => {
+                    update(&mut context, &[1]);
+                    update(&mut context, value.as_bytes())
+                }
+            });
+        }
+    }
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(context.sign().as_ref());
+    hash
+}
+
+/// Bounded index of the content hashes of documents encrypted by `document_encrypt_unique`, mapping
+/// each hash to the ID of the document it was encrypted as. When full, the least recently used entry
+/// is evicted.
+#[derive(Default)]
+pub(crate) struct ContentHashIndex {
+    entries: Mutex<HashMap<[u8; 32], IndexedDocument>>,
+    /// Incremented every time an entry is used
+    clock: AtomicU64,
+}
+
+struct IndexedDocument {
+    id: DocumentId,
+    indexed_at: DateTime<Utc>,
+    /// Value of `ContentHashIndex.clock` when the entry was last used
+    last_used: u64,
+}
+
+impl ContentHashIndex {
+    /// Document that `hash` was encrypted as, if it was indexed less than `config.ttl` ago. Expired
+    /// entries are removed.
+    pub(crate) fn get(
+        &self,
+        config: &UniqueEncryptionConfig,
+        hash: &[u8; 32],
+    ) -> Option<DocumentId> {
+        // a ttl too large to represent never expires
+        let oldest_allowed = chrono::Duration::from_std(config.ttl)
+            .ok()
+            .and_then(|ttl| Utc::now().checked_sub_signed(ttl));
+        let mut entries = take_lock(&self.entries);
+        match entries.get_mut(hash) {
+            Some(indexed) if oldest_allowed.map_or(true, |oldest| indexed.indexed_at >= oldest) => {
+                indexed.last_used = self.clock.fetch_add(1, Ordering::Relaxed);
+                Some(indexed.id.clone())
+            }
+            Some(_) => {
+                entries.remove(hash);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Index `hash` as `id`, first evicting the least recently used entries if the index has
+    /// `config.max_entries`. Nothing is indexed if `config.max_entries` is zero.
+    pub(crate) fn insert(&self, config: &UniqueEncryptionConfig, hash: [u8; 32], id: DocumentId) {
+        let mut entries = take_lock(&self.entries);
+        entries.remove(&hash);
+        if config.max_entries == 0 {
+            return;
+        }
+        while !entries.is_empty() && entries.len() >= config.max_entries {
+            let lru = entries
+                .iter()
+                .min_by_key(|(_, indexed)| indexed.last_used)
+                .map(|(hash, _)| *hash);
+            if let Some(hash) = lru {
+                entries.remove(&hash);
+            }
+        }
+        entries.insert(
+            hash,
+            IndexedDocument {
+                id,
+                indexed_at: Utc::now(),
+                last_used: self.clock.fetch_add(1, Ordering::Relaxed),
+            },
+        );
+    }
+
+    /// Remove every entry for the document `id`, returning whether it was indexed
+    pub(crate) fn remove(&self, id: &DocumentId) -> bool {
+        let mut entries = take_lock(&self.entries);
+        let size = entries.len();
+        entries.retain(|_, indexed| &indexed.id != id);
+        entries.len() != size
+    }
+
+    /// Remove every entry, returning how many were indexed
+    pub(crate) fn clear(&self) -> usize {
+        let mut entries = take_lock(&self.entries);
+        let size = entries.len();
+        entries.clear();
+        size
+    }
+}
+
+/// Serialize every entry in the policy cache and encrypt the result with a key derived from the
+/// device private key, so only an SDK running as the same device can load it back.
+pub fn export_policy_cache<R: CryptoRng + RngCore>(
+    policy_cache: &PolicyCache,
+    device_private_key: &PrivateKey,
+    rng: &Mutex<R>,
+) -> Result<Vec<u8>, IronOxideErr> {
+    let persisted: Vec<PersistedPolicy> = policy_cache
+        .iter()
+        .map(|entry| PersistedPolicy {
+            grant: entry.key().clone(),
+            public_keys: entry
+                .value()
+                .public_keys
+                .iter()
+                .map(|with_key| (with_key.id.clone(), with_key.public_key.clone().into()))
+                .collect(),
+            cached_at: entry.value().cached_at,
+        })
+        .collect();
+    let serialized = serde_json::to_vec(&persisted)
+        .map_err(|e| IronOxideErr::PolicyCacheSerdeError(e.to_string()))?;
+    let encrypted = aes::encrypt(
+        rng,
+        &serialized,
+        &device_private_key.derive_subkey(b"ironoxide-policy-cache"),
+    )?;
+    Ok(encrypted.bytes())
+}
+
+/// Decrypt a policy cache produced by `export_policy_cache` and insert each entry that is younger
+/// than `ttl` into `policy_cache`. Entries keep their original cache time, so a reloaded entry
+/// expires no later than it would have in the process that exported it.
+///
+/// Returns the number of entries inserted.
+pub fn import_policy_cache(
+    config: &PolicyCachingConfig,
+    policy_cache: &PolicyCache,
+    device_private_key: &PrivateKey,
+    exported: &[u8],
+    ttl: std::time::Duration,
+) -> Result<usize, IronOxideErr> {
+    let mut encrypted: AesEncryptedValue = exported.try_into()?;
+    let decrypted = aes::decrypt(
+        &mut encrypted,
+        &device_private_key.derive_subkey(b"ironoxide-policy-cache"),
+    )?;
+    let persisted: Vec<PersistedPolicy> = serde_json::from_slice(decrypted)
+        .map_err(|e| IronOxideErr::PolicyCacheSerdeError(e.to_string()))?;
+    let ttl = chrono::Duration::from_std(ttl)
+        .map_err(|e| IronOxideErr::ValidationError("ttl".to_string(), e.to_string()))?;
+    let oldest_allowed = Utc::now() - ttl;
+    let mut imported = 0;
+    for entry in persisted {
+        if entry.cached_at < oldest_allowed {
+            continue;
+        }
+        if policy_cache.len() >= config.max_entries && !policy_cache.contains_key(&entry.grant) {
+            break;
+        }
+        let public_keys = entry
+            .public_keys
+            .into_iter()
+            .map(|(id, public_key)| Ok(WithKey::new(id, public_key.try_into()?)))
+            .collect::<Result<Vec<_>, IronOxideErr>>()?;
+        policy_cache.insert(
+            entry.grant,
+            CachedPolicy {
+                public_keys,
+                cached_at: entry.cached_at,
+            },
+        );
+        imported += 1;
+    }
+    Ok(imported)
+}
+
 /// Encrypts a document but does not create the document in the IronCore system.
-/// The resultant DocumentDetachedEncryptResult contains both the EncryptedDeks and the AesEncryptedValue
+/// The resultant DocumentDetachedEncryptResult contains both the EncryptedDeks and the encrypted data
 /// Both pieces will be required for decryption.
 pub async fn encrypt_document_unmanaged<R1, R2>(
     auth: &RequestAuth,
@@ -753,20 +1495,26 @@ pub async fn encrypt_document_unmanaged<R1, R2>(
     user_grants: &Vec<UserId>,
     group_grants: &Vec<GroupId>,
     policy_grant: Option<&PolicyGrant>,
+    cipher_suite: CipherSuite,
+    header_aad: bool,
 ) -> Result<DocumentEncryptUnmanagedResult, IronOxideErr>
 where
     R1: rand::CryptoRng + rand::RngCore,
     R2: rand::CryptoRng + rand::RngCore,
 {
     let policy_cache = dashmap::DashMap::new();
+    let group_key_cache = Default::default();
+    let user_key_cache = Default::default();
     let config = IronOxideConfig::default();
 
     let (dek, doc_sym_key) = transform::generate_new_doc_key(recrypt);
     let doc_id = document_id.unwrap_or_else(|| DocumentId::goo_id(rng));
-    let pt_bytes = plaintext.to_vec();
+    let header = DocumentHeader::new(doc_id.clone(), auth.segment_id)
+        .with_cipher_suite(cipher_suite)
+        .with_header_aad(header_aad);
 
     let (encryption_result, (grants, key_errs)) = try_join!(
-        aes::encrypt_async(rng, &pt_bytes, *doc_sym_key.bytes()),
+        header.encrypt_data(rng, plaintext, doc_sym_key.bytes()),
         resolve_keys_for_grants(
             auth,
             &config,
@@ -778,7 +1526,9 @@ where
             } else {
                 None
             },
-            &policy_cache
+            &policy_cache,
+            &group_key_cache,
+            &user_key_cache
         )
     )?;
     let r = recrypt_document(
@@ -789,10 +1539,7 @@ where
         &doc_id,
         grants,
     )?;
-    let enc_result = EncryptedDoc {
-        header: DocumentHeader::new(doc_id.clone(), auth.segment_id),
-        value: r,
-    };
+    let enc_result = EncryptedDoc { header, value: r };
     let access_errs = [&key_errs[..], &enc_result.value.encryption_errs[..]].concat();
     DocumentEncryptUnmanagedResult::new(enc_result, access_errs)
 }
@@ -807,12 +1554,12 @@ fn dedupe_grants(grants: &[WithKey<UserOrGroup>]) -> Vec<WithKey<UserOrGroup>> {
 
 /// Encrypt the document using transform crypto (recrypt).
 /// Can be called once you have public keys for users/groups that should have access as well as the
-/// AES encrypted data.
+/// encrypted data.
 fn recrypt_document<CR: rand::CryptoRng + rand::RngCore>(
     signing_keys: &DeviceSigningKeyPair,
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
     dek: Plaintext,
-    encrypted_doc: AesEncryptedValue,
+    encrypted_doc: Vec<u8>,
     doc_id: &DocumentId,
     grants: Vec<WithKey<UserOrGroup>>,
 ) -> Result<RecryptionResult, IronOxideErr> {
@@ -831,7 +1578,7 @@ fn recrypt_document<CR: rand::CryptoRng + rand::RngCore>(
             let (encrypt_errs, grants) = transform::encrypt_to_with_key(
                 recrypt,
                 &dek,
-                &signing_keys.into(),
+                signing_keys.recrypt_key(),
                 dedupe_grants(&grants),
             );
 
@@ -934,7 +1681,8 @@ impl TryFrom<&EncryptedDek> for EncryptedDekP {
 #[derive(Clone, Debug)]
 struct RecryptionResult {
     edeks: Vec<EncryptedDek>,
-    encrypted_data: AesEncryptedValue,
+    /// IV and encrypted data, without the document header
+    encrypted_data: Vec<u8>,
     encryption_errs: Vec<DocAccessEditErr>,
 }
 
@@ -957,11 +1705,7 @@ struct EncryptedDoc {
 impl EncryptedDoc {
     /// bytes of the encrypted data with the edoc header prepended
     fn edoc_bytes(&self) -> Vec<u8> {
-        [
-            &self.header.pack().0[..],
-            &self.value.encrypted_data.bytes(),
-        ]
-        .concat()
+        [&self.header.pack().0[..], &self.value.encrypted_data].concat()
     }
 
     /// associated EncryptedDeks for this EncryptedDoc
@@ -1010,7 +1754,7 @@ async fn document_create(
         name: api_resp.name,
         created: api_resp.created,
         updated: api_resp.updated,
-        encrypted_data: edoc.edoc_bytes().to_vec(),
+        encrypted_data: edoc.edoc_bytes(),
         grants: api_resp.shared_with.iter().map(|sw| sw.into()).collect(),
         access_errs: [accum_errs, edoc.value.encryption_errs].concat(),
     })
@@ -1024,7 +1768,7 @@ pub async fn document_update_bytes<
 >(
     auth: &RequestAuth,
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<R1>>,
-    device_private_key: &PrivateKey,
+    device_private_key: &DevicePrivateKey,
     rng: &Mutex<R2>,
     document_id: &DocumentId,
     plaintext: &[u8],
@@ -1033,96 +1777,428 @@ pub async fn document_update_bytes<
     let sym_key = transform::decrypt_as_symmetric_key(
         recrypt,
         doc_meta.0.encrypted_symmetric_key.clone().try_into()?,
-        device_private_key.recrypt_key(),
+        device_private_key,
     )?;
-    Ok(
-        aes::encrypt(rng, &plaintext.to_vec(), *sym_key.bytes()).map(move |encrypted_doc| {
-            let mut encrypted_payload =
-                DocumentHeader::new(document_id.clone(), auth.segment_id()).pack();
-            encrypted_payload.0.append(&mut encrypted_doc.bytes());
-            DocumentEncryptResult {
-                id: doc_meta.0.id,
-                name: doc_meta.0.name,
-                created: doc_meta.0.created,
-                updated: doc_meta.0.updated,
-                encrypted_data: encrypted_payload.0,
-                grants: vec![],      // grants can't currently change via update
-                access_errs: vec![], // no grants, no access errs
-            }
-        })?,
-    )
+    // the data is encrypted straight into the buffer holding the header
+    let header = DocumentHeader::new(document_id.clone(), auth.segment_id());
+    let mut encrypted_data = header.pack().0;
+    header.encrypt_data_into(rng, plaintext, sym_key.bytes(), &mut encrypted_data)?;
+    Ok(DocumentEncryptResult {
+        id: doc_meta.0.id,
+        name: doc_meta.0.name,
+        created: doc_meta.0.created,
+        updated: doc_meta.0.updated,
+        encrypted_data,
+        grants: vec![],      // grants can't currently change via update
+        access_errs: vec![], // no grants, no access errs
+    })
 }
 
 /// Decrypt the provided document with the provided device private key. Return metadata about the document
 /// that was decrypted along with its decrypted bytes.
+///
+/// The key path chosen by the webservice must be allowed by `decryption_config`.
 pub async fn decrypt_document<CR: rand::CryptoRng + rand::RngCore>(
     auth: &RequestAuth,
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
-    device_private_key: &PrivateKey,
+    device_private_key: &DevicePrivateKey,
     encrypted_doc: &[u8],
+    decryption_config: &DecryptionConfig,
 ) -> Result<DocumentDecryptResult, IronOxideErr> {
-    let (doc_header, mut enc_doc) = parse_document_parts(encrypted_doc)?;
-    let doc_meta = document_get_metadata(auth, &doc_header.document_id).await?;
-    let sym_key = transform::decrypt_as_symmetric_key(
+    let doc_id = get_id_from_bytes(encrypted_doc)?;
+    get_document_key(
+        auth,
         recrypt,
-        doc_meta.0.encrypted_symmetric_key.clone().try_into()?,
-        device_private_key.recrypt_key(),
-    )?;
-
-    Ok(
-        aes::decrypt(&mut enc_doc, *sym_key.bytes()).map(move |decrypted_doc| {
-            DocumentDecryptResult {
-                id: doc_meta.0.id,
-                name: doc_meta.0.name,
-                created: doc_meta.0.created,
-                updated: doc_meta.0.updated,
-                decrypted_data: decrypted_doc.to_vec(),
-            }
-        })?,
+        device_private_key,
+        &doc_id,
+        decryption_config,
     )
+    .await?
+    .decrypt(encrypted_doc)
 }
 
-/// Decrypt the unmanaged document. The caller must provide both the encrypted data as well as the
-/// encrypted DEKs. Most use cases would want `decrypt_document` instead.
-pub async fn decrypt_document_unmanaged<CR: rand::CryptoRng + rand::RngCore>(
+/// Like `decrypt_document`, but uses the document's key from `document_key_cache` if it is younger than
+/// `config.ttl`, and caches the key if it had to be fetched.
+pub(crate) async fn decrypt_document_cached<CR: rand::CryptoRng + rand::RngCore>(
     auth: &RequestAuth,
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
-    device_private_key: &PrivateKey,
+    device_private_key: &DevicePrivateKey,
     encrypted_doc: &[u8],
-    encrypted_deks: &[u8],
-) -> Result<DocumentDecryptUnmanagedResult, IronOxideErr> {
-    // attempt to parse the proto as fail-fast validation. If it fails decrypt will fail
-
-    let ((proto_edeks, (doc_meta, mut aes_encrypted_value)), transform_resp) = try_join!(
-        async {
-            Ok((
-                EncryptedDeksP::parse_from_bytes(encrypted_deks).map_err(IronOxideErr::from)?,
-                parse_document_parts(encrypted_doc)?,
-            ))
-        },
-        requests::edek_transform::edek_transform(&auth, encrypted_deks,)
-    )?;
-
-    edeks_and_header_match_or_err(&proto_edeks, &doc_meta)?;
-    let requests::edek_transform::EdekTransformResponse {
-        user_or_group,
-        encrypted_symmetric_key,
-    } = transform_resp;
+    decryption_config: &DecryptionConfig,
+    config: &DocumentKeyCachingConfig,
+    document_key_cache: &DocumentKeyCache,
+) -> Result<DocumentDecryptResult, IronOxideErr> {
+    if config.ttl == std::time::Duration::from_secs(0) || config.max_entries == 0 {
+        return decrypt_document(
+            auth,
+            recrypt,
+            device_private_key,
+            encrypted_doc,
+            decryption_config,
+        )
+        .await;
+    }
+    let doc_id = get_id_from_bytes(encrypted_doc)?;
+    let doc_key = match document_key_cache.get(config, &doc_id) {
+        Some(cached) => cached,
+        None => {
+            let fetched = Arc::new(
+                get_document_key(
+                    auth,
+                    recrypt,
+                    device_private_key,
+                    &doc_id,
+                    decryption_config,
+                )
+                .await?,
+            );
+            document_key_cache.insert(config, fetched.clone());
+            fetched
+        }
+    };
+    doc_key.decrypt(encrypted_doc)
+}
 
-    let sym_key = transform::decrypt_as_symmetric_key(
-        recrypt,
+/// Like `decrypt_document_cached`, but discards the decrypted bytes and returns whether the document is
+/// intact. Errors that mean the document is corrupted are `false`; any other error is returned.
+pub(crate) async fn verify_document_cached<CR: rand::CryptoRng + rand::RngCore>(
+    auth: &RequestAuth,
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    device_private_key: &DevicePrivateKey,
+    encrypted_doc: &[u8],
+    decryption_config: &DecryptionConfig,
+    config: &DocumentKeyCachingConfig,
+    document_key_cache: &DocumentKeyCache,
+) -> Result<bool, IronOxideErr> {
+    let decrypted = decrypt_document_cached(
+        auth,
+        recrypt,
+        device_private_key,
+        encrypted_doc,
+        decryption_config,
+        config,
+        document_key_cache,
+    )
+    .await;
+    match decrypted {
+        Ok(_) => Ok(true),
+        Err(e) if is_corruption(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether `err` from decrypting a document means its bytes are corrupted
+fn is_corruption(err: &IronOxideErr) -> bool {
+    match err {
+        IronOxideErr::DocumentHeaderParseFailure(_)
+        | IronOxideErr::AesEncryptedDocSizeError
+        | IronOxideErr::AesError(_) => true,
+        _ => false,
+    }
+}
+
+/// Bounded cache of document keys, keyed by document ID, that evicts the least recently used key when
+/// it is full
+#[derive(Default)]
+pub(crate) struct DocumentKeyCache {
+    entries: Mutex<HashMap<DocumentId, CachedDocumentKey>>,
+    /// Incremented every time a key is used
+    clock: AtomicU64,
+}
+
+struct CachedDocumentKey {
+    key: Arc<DocumentKey>,
+    cached_at: DateTime<Utc>,
+    /// Value of `DocumentKeyCache.clock` when the key was last used
+    last_used: u64,
+}
+
+impl DocumentKeyCache {
+    /// Key for `id`, if it was cached less than `config.ttl` ago. Expired keys are removed.
+    fn get(&self, config: &DocumentKeyCachingConfig, id: &DocumentId) -> Option<Arc<DocumentKey>> {
+        // a ttl too large to represent never expires
+        let oldest_allowed = chrono::Duration::from_std(config.ttl)
+            .ok()
+            .and_then(|ttl| Utc::now().checked_sub_signed(ttl));
+        let mut entries = take_lock(&self.entries);
+        match entries.get_mut(id) {
+            Some(cached) if oldest_allowed.map_or(true, |oldest| cached.cached_at >= oldest) => {
+                cached.last_used = self.clock.fetch_add(1, Ordering::Relaxed);
+                Some(cached.key.clone())
+            }
+            Some(_) => {
+                entries.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache `key`, first evicting the least recently used keys if the cache has `config.max_entries`
+    fn insert(&self, config: &DocumentKeyCachingConfig, key: Arc<DocumentKey>) {
+        let mut entries = take_lock(&self.entries);
+        entries.remove(key.id());
+        while !entries.is_empty() && entries.len() >= config.max_entries {
+            let lru = entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(id, _)| id.clone());
+            if let Some(id) = lru {
+                entries.remove(&id);
+            }
+        }
+        entries.insert(
+            key.id().clone(),
+            CachedDocumentKey {
+                key,
+                cached_at: Utc::now(),
+                last_used: self.clock.fetch_add(1, Ordering::Relaxed),
+            },
+        );
+    }
+
+    /// Remove the key for `id`, returning whether it was cached
+    pub(crate) fn remove(&self, id: &DocumentId) -> bool {
+        take_lock(&self.entries).remove(id).is_some()
+    }
+
+    /// Remove every key, returning how many were cached
+    pub(crate) fn clear(&self) -> usize {
+        let mut entries = take_lock(&self.entries);
+        let size = entries.len();
+        entries.clear();
+        size
+    }
+}
+
+/// Symmetric key of a document, decrypted by the calling device, along with the metadata that is
+/// returned when decrypting the document. The key is overwritten with zeros when this is dropped.
+pub(crate) struct DocumentKey {
+    id: DocumentId,
+    name: Option<DocumentName>,
+    created: DateTime<Utc>,
+    updated: DateTime<Utc>,
+    key: Locked<[u8; 32]>,
+}
+
+impl DocumentKey {
+    /// ID of the document the key is for
+    pub(crate) fn id(&self) -> &DocumentId {
+        &self.id
+    }
+
+    /// Decrypt an encrypted document with this key. Fails if the document has a different ID.
+    pub(crate) fn decrypt(
+        &self,
+        encrypted_doc: &[u8],
+    ) -> Result<DocumentDecryptResult, IronOxideErr> {
+        let (doc_header, enc_doc) = parse_document_parts(encrypted_doc)?;
+        if doc_header.document_id != self.id {
+            return Err(IronOxideErr::DocumentHeaderParseFailure(format!(
+                "Document key is for '{}', but the document is '{}'.",
+                self.id.id(),
+                doc_header.document_id.id()
+            )));
+        }
+        let decrypted_doc = doc_header.decrypt_data(enc_doc, &self.key)?;
+        Ok(DocumentDecryptResult {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            created: self.created,
+            updated: self.updated,
+            decrypted_data: decrypted_doc,
+        })
+    }
+}
+
+/// Fetch a document's metadata and decrypt its symmetric key with the device private key.
+pub(crate) async fn get_document_key<CR: rand::CryptoRng + rand::RngCore>(
+    auth: &RequestAuth,
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    device_private_key: &DevicePrivateKey,
+    id: &DocumentId,
+    decryption_config: &DecryptionConfig,
+) -> Result<DocumentKey, IronOxideErr> {
+    let doc_meta = document_get_metadata(auth, id).await?;
+    decrypt_document_key(recrypt, device_private_key, doc_meta, decryption_config)
+}
+
+/// Like `get_document_key`, for many documents. The metadata of up to `concurrency` documents is
+/// fetched at a time, and then their keys are decrypted in parallel on rayon's thread pool, as the
+/// transform decryption of each key is CPU bound. The async task waits for the pool without blocking
+/// its worker thread.
+pub(crate) async fn get_document_keys(
+    auth: &RequestAuth,
+    device_private_key: &DevicePrivateKey,
+    ids: &[&DocumentId],
+    decryption_config: &DecryptionConfig,
+    concurrency: usize,
+) -> Result<Vec<DocumentKey>, IronOxideErr> {
+    let doc_metas: Vec<DocumentMetadataResult> =
+        futures::stream::iter(ids.iter().map(|id| document_get_metadata(auth, id)))
+            .buffered(concurrency.max(1))
+            .try_collect()
+            .await?;
+    let device_private_key = device_private_key.clone();
+    let decryption_config = decryption_config.clone();
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    rayon::spawn(move || {
+        // decryption doesn't use the random number generator, so a default Recrypt gives the same keys
+        let recrypt = Recrypt::new();
+        let keys = doc_metas
+            .into_par_iter()
+            .map(|doc_meta| {
+                decrypt_document_key(&recrypt, &device_private_key, doc_meta, &decryption_config)
+            })
+            .collect();
+        // the receiver is gone if the caller stopped waiting, such as after a timeout
+        let _ = sender.send(keys);
+    });
+    receiver.await.map_err(|_| {
+        IronOxideErr::RecryptError("document key decryption was interrupted".to_string())
+    })?
+}
+
+/// Decrypt the symmetric key of a document from its metadata with the device private key.
+fn decrypt_document_key<CR: rand::CryptoRng + rand::RngCore>(
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    device_private_key: &DevicePrivateKey,
+    doc_meta: DocumentMetadataResult,
+    decryption_config: &DecryptionConfig,
+) -> Result<DocumentKey, IronOxideErr> {
+    let key_path = doc_meta.association_type().key_path();
+    if !decryption_config.allows(key_path) {
+        return Err(IronOxideErr::DecryptKeyPathNotAllowed(key_path));
+    }
+    let sym_key = transform::decrypt_as_symmetric_key(
+        recrypt,
+        doc_meta.0.encrypted_symmetric_key.clone().try_into()?,
+        device_private_key,
+    )?;
+    let mut key = Locked::<[u8; 32]>::default();
+    key.copy_from_slice(sym_key.bytes());
+    Ok(DocumentKey {
+        id: doc_meta.0.id,
+        name: doc_meta.0.name,
+        created: doc_meta.0.created,
+        updated: doc_meta.0.updated,
+        key,
+    })
+}
+
+/// Decrypt the unmanaged document. The caller must provide both the encrypted data as well as the
+/// encrypted DEKs. Most use cases would want `decrypt_document` instead.
+///
+/// The EDEKs are tried one key path at a time, in the order given by `decryption_config`.
+pub async fn decrypt_document_unmanaged<CR: rand::CryptoRng + rand::RngCore>(
+    auth: &RequestAuth,
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    device_private_key: &DevicePrivateKey,
+    encrypted_doc: &[u8],
+    encrypted_deks: &[u8],
+    decryption_config: &DecryptionConfig,
+) -> Result<DocumentDecryptUnmanagedResult, IronOxideErr> {
+    let proto_edeks = EncryptedDeksP::parse_from_bytes(encrypted_deks)?;
+    let (doc_meta, encrypted_value) = parse_document_parts(encrypted_doc)?;
+    edeks_and_header_match_or_err(&proto_edeks, &doc_meta)?;
+
+    let transform_resp = transform_edeks_by_key_path(auth, &proto_edeks, decryption_config).await?;
+    let requests::edek_transform::EdekTransformResponse {
+        user_or_group,
+        encrypted_symmetric_key,
+    } = transform_resp;
+
+    let sym_key = transform::decrypt_as_symmetric_key(
+        recrypt,
         encrypted_symmetric_key.try_into()?,
-        device_private_key.recrypt_key(),
+        device_private_key,
     )?;
-    aes::decrypt(&mut aes_encrypted_value, *sym_key.bytes())
-        .map_err(|e| e.into())
+    doc_meta
+        .decrypt_data(encrypted_value, sym_key.bytes())
         .map(move |decrypted_doc| DocumentDecryptUnmanagedResult {
             id: doc_meta.document_id,
             access_via: user_or_group,
-            decrypted_data: DecryptedData(decrypted_doc.to_vec()),
+            decrypted_data: DecryptedData(decrypted_doc),
         })
 }
 
+/// The key path a single proto EDEK would be decrypted through.
+fn edek_key_path(edek: &EncryptedDekP) -> DecryptKeyPath {
+    if edek.get_userOrGroup().has_groupId() {
+        DecryptKeyPath::Group
+    } else {
+        DecryptKeyPath::User
+    }
+}
+
+/// Keep only the EDEKs that would be decrypted through `key_path`.
+fn filter_edeks_by_key_path(edeks: &EncryptedDeksP, key_path: DecryptKeyPath) -> EncryptedDeksP {
+    let mut filtered = edeks.clone();
+    filtered.set_edeks(
+        edeks
+            .get_edeks()
+            .iter()
+            .filter(|edek| edek_key_path(edek) == key_path)
+            .cloned()
+            .collect(),
+    );
+    filtered
+}
+
+/// Request a transform of the EDEKs one key path at a time, in the order configured. The first
+/// successful transform is returned. A path is only skipped for the next one if the webservice refuses it
+/// (see `key_path_refused`); any other failure is returned right away. If every allowed path is refused,
+/// the last refusal is returned.
+///
+/// The default config allows every key path in the webservice's own order, so all the EDEKs are sent in a
+/// single request and the webservice chooses the path, as it does for managed documents.
+async fn transform_edeks_by_key_path(
+    auth: &RequestAuth,
+    edeks: &EncryptedDeksP,
+    decryption_config: &DecryptionConfig,
+) -> Result<requests::edek_transform::EdekTransformResponse, IronOxideErr> {
+    if *decryption_config == DecryptionConfig::default() {
+        return requests::edek_transform::edek_transform(auth, &edeks.write_to_bytes()?).await;
+    }
+    let mut last_err = None;
+    for key_path in &decryption_config.key_paths {
+        let filtered = filter_edeks_by_key_path(edeks, *key_path);
+        if filtered.get_edeks().is_empty() {
+            continue;
+        }
+        match requests::edek_transform::edek_transform(auth, &filtered.write_to_bytes()?).await {
+            Ok(resp) => return Ok(resp),
+            Err(e) if key_path_refused(&e) => last_err = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    match (last_err, edeks.get_edeks().first()) {
+        (Some(e), _) => Err(e),
+        // no EDEK exists for any of the allowed key paths
+        (None, Some(edek)) => Err(IronOxideErr::DecryptKeyPathNotAllowed(edek_key_path(edek))),
+        // no EDEKs at all; let the webservice report the failure
+        (None, None) => {
+            requests::edek_transform::edek_transform(auth, &edeks.write_to_bytes()?).await
+        }
+    }
+}
+
+/// Whether a failed transform means the calling user can't decrypt through the key path it was made for,
+/// as opposed to a failure, like a network error or a server error, that the next path would hit too.
+fn key_path_refused(err: &IronOxideErr) -> bool {
+    match err {
+        IronOxideErr::RequestError {
+            http_status: Some(status),
+            ..
+        }
+        | IronOxideErr::RequestServerErrors {
+            http_status: Some(status),
+            ..
+        } => *status == 401 || *status == 403 || *status == 404,
+        _ => false,
+    }
+}
+
 /// Check to see if a set of edeks match a document header
 fn edeks_and_header_match_or_err(
     edeks: &EncryptedDeksP,
@@ -1154,15 +2230,20 @@ pub async fn update_document_name(
         .map(DocumentMetadataResult)
 }
 
+/// Grant access to a document to the provided lists of users and groups
+///
+/// Large lists of users and groups are split into chunks of `chunk_size`, which are sent with at most
+/// `max_concurrency` requests in flight at once, both from the request's `RequestBatchingConfig`.
 pub async fn document_grant_access<CR: rand::CryptoRng + rand::RngCore>(
     auth: &RequestAuth,
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
     id: &DocumentId,
     user_master_pub_key: &PublicKey,
-    priv_device_key: &PrivateKey,
+    priv_device_key: &DevicePrivateKey,
     user_grants: &Vec<UserId>,
     group_grants: &Vec<GroupId>,
 ) -> Result<DocumentAccessResult, IronOxideErr> {
+    let batching = auth.request.batching();
     let (doc_meta, users, groups) = try_join!(
         document_get_metadata(auth, id),
         // and the public keys for the users and groups
@@ -1172,7 +2253,7 @@ pub async fn document_grant_access<CR: rand::CryptoRng + rand::RngCore>(
     let (grants, other_errs) = {
         // decrypt the dek
         let edek = doc_meta.to_encrypted_symmetric_key()?;
-        let dek = recrypt.decrypt(edek, priv_device_key.recrypt_key())?;
+        let dek = priv_device_key.decrypt(recrypt, edek)?;
 
         let (group_errs, groups_with_key) = process_groups(groups);
         let (user_errs, users_with_key) = process_users(users);
@@ -1182,7 +2263,7 @@ pub async fn document_grant_access<CR: rand::CryptoRng + rand::RngCore>(
         let (grant_errs, grants) = transform::encrypt_to_with_key(
             recrypt,
             &dek,
-            &auth.signing_private_key().into(),
+            auth.signing_private_key().recrypt_key(),
             users_and_groups,
         );
 
@@ -1197,17 +2278,88 @@ pub async fn document_grant_access<CR: rand::CryptoRng + rand::RngCore>(
         (grants, other_errs)
     };
 
-    let resp =
-        requests::document_access::grant_access_request(auth, id, user_master_pub_key, grants)
-            .await?;
-    Ok(requests::document_access::resp::document_access_api_resp_to_result(resp, other_errs))
+    internal::stop_if_validate_only(SdkOperation::DocumentGrantAccess, "grants", || {
+        describe_access_errs(&other_errs)
+    })?;
+    let mut grants = grants.into_iter().peekable();
+    let mut chunks = vec![];
+    while grants.peek().is_some() {
+        chunks.push(
+            grants
+                .by_ref()
+                .take(batching.chunk_size.max(1))
+                .collect::<Vec<_>>(),
+        );
+    }
+    let chunk_results = futures::stream::iter(chunks)
+        .map(|chunk| {
+            let chunk_grantees: Vec<UserOrGroup> = chunk
+                .iter()
+                .map(|(grantee, _)| grantee.id.clone())
+                .collect();
+            async move {
+                let response = requests::document_access::grant_access_request(
+                    auth,
+                    id,
+                    user_master_pub_key,
+                    chunk,
+                )
+                .await;
+                (chunk_grantees, response)
+            }
+        })
+        .buffered(batching.max_concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    merge_chunked_grant_results(other_errs, chunk_results)
+}
+
+/// Merge the responses of a document grant that was split across multiple requests.
+///
+/// Users and groups in a chunk whose request failed outright are reported as failed with that request's
+/// error. If every request failed, the first error is returned instead, matching the behavior of an
+/// unchunked grant.
+fn merge_chunked_grant_results(
+    other_errs: Vec<DocAccessEditErr>,
+    chunk_results: Vec<(
+        Vec<UserOrGroup>,
+        Result<requests::document_access::resp::DocumentAccessResponse, IronOxideErr>,
+    )>,
+) -> Result<DocumentAccessResult, IronOxideErr> {
+    let all_failed =
+        !chunk_results.is_empty() && chunk_results.iter().all(|(_, resp)| resp.is_err());
+    chunk_results.into_iter().try_fold(
+        DocumentAccessResult::new(vec![], other_errs),
+        |mut acc, (chunk_grantees, chunk_result)| match chunk_result {
+            Ok(resp) => {
+                let mut chunk_access =
+                    requests::document_access::resp::document_access_api_resp_to_result(
+                        resp,
+                        vec![],
+                    );
+                acc.succeeded.append(&mut chunk_access.succeeded);
+                acc.failed.append(&mut chunk_access.failed);
+                Ok(acc)
+            }
+            Err(e) if all_failed => Err(e),
+            Err(e) => {
+                let error = e.to_string();
+                acc.failed.extend(
+                    chunk_grantees
+                        .into_iter()
+                        .map(|grantee| DocAccessEditErr::new(grantee, error.clone())),
+                );
+                Ok(acc)
+            }
+        },
+    )
 }
 
 /// Remove access to a document from the provided list of users and/or groups
 pub async fn document_revoke_access(
     auth: &RequestAuth,
     id: &DocumentId,
-    revoke_list: &Vec<UserOrGroup>,
+    revoke_list: &[UserOrGroup],
 ) -> Result<DocumentAccessResult, IronOxideErr> {
     use requests::document_access::{self, resp};
 
@@ -1223,6 +2375,169 @@ pub async fn document_revoke_access(
     Ok(resp::document_access_api_resp_to_result(resp, vec![]))
 }
 
+/// Replay the access log entries in order, returning the users and groups each document is expected to
+/// be shared with.
+///
+/// A grant adds its user or group to the document's expected access unless it's already there, and a
+/// revoke removes it, so a later entry for the same user or group overrides an earlier one. A document
+/// whose every grant was revoked is still returned, with no expected access. Documents are returned in
+/// the order they first appear in the log, and each document's users and groups in the order they were
+/// granted.
+fn replay_access_log(entries: &[AccessLogEntry]) -> Vec<(DocumentId, Vec<UserOrGroup>)> {
+    let mut expected: Vec<(DocumentId, Vec<UserOrGroup>)> = vec![];
+    for entry in entries {
+        let idx = match expected
+            .iter()
+            .position(|(doc_id, _)| doc_id == &entry.document_id)
+        {
+            Some(idx) => idx,
+            None => {
+                expected.push((entry.document_id.clone(), vec![]));
+                expected.len() - 1
+            }
+        };
+        let access = &mut expected[idx].1;
+        match entry.op {
+            AccessLogOp::Grant => {
+                if !access.contains(&entry.user_or_group) {
+                    access.push(entry.user_or_group.clone())
+                }
+            }
+            AccessLogOp::Revoke => access.retain(|uog| uog != &entry.user_or_group),
+        }
+    }
+    expected
+}
+
+/// Compare the expected access for a document with the access in its current metadata.
+fn access_divergences(
+    expected: &[UserOrGroup],
+    doc_meta: &DocumentMetadataResult,
+) -> Vec<AccessDivergence> {
+    let actual: Vec<UserOrGroup> = doc_meta
+        .visible_to_users()
+        .iter()
+        .map(|u| UserOrGroup::from(u.id()))
+        .chain(
+            doc_meta
+                .visible_to_groups()
+                .iter()
+                .map(|g| UserOrGroup::from(g.id())),
+        )
+        .collect();
+    let unrecorded = actual
+        .iter()
+        .filter(|uog| !expected.contains(uog))
+        .map(|uog| AccessDivergence::Unrecorded {
+            document_id: doc_meta.id().clone(),
+            user_or_group: uog.clone(),
+        });
+    let missing = expected
+        .iter()
+        .filter(|uog| !actual.contains(uog))
+        .map(|uog| AccessDivergence::Missing {
+            document_id: doc_meta.id().clone(),
+            user_or_group: uog.clone(),
+        });
+    unrecorded.chain(missing).collect()
+}
+
+/// Replay the provided access log and compare the result against the current access list of every
+/// document it mentions. Returns all divergences found, in log order.
+pub async fn verify_access_log(
+    auth: &RequestAuth,
+    entries: &[AccessLogEntry],
+) -> Result<Vec<AccessDivergence>, IronOxideErr> {
+    let expected = replay_access_log(entries);
+    let doc_metas = futures::future::try_join_all(
+        expected
+            .iter()
+            .map(|(doc_id, _)| document_get_metadata(auth, doc_id)),
+    )
+    .await?;
+    Ok(expected
+        .iter()
+        .zip(doc_metas.iter())
+        .flat_map(|((_, access), doc_meta)| access_divergences(access, doc_meta))
+        .collect())
+}
+
+/// Take a signed snapshot of the users and groups a document is currently shared with.
+pub async fn document_access_snapshot(
+    auth: &RequestAuth,
+    id: &DocumentId,
+) -> Result<DocumentAccessSnapshot, IronOxideErr> {
+    let DocumentMetadataResult(meta) = document_get_metadata(auth, id).await?;
+    Ok(DocumentAccessSnapshot::new(
+        meta.id,
+        Utc::now(),
+        meta.visible_to.users.into_iter().map(|u| u.id).collect(),
+        meta.visible_to.groups.into_iter().map(|g| g.id).collect(),
+        auth.signing_private_key(),
+    ))
+}
+
+/// Grant and revoke access so the document is shared with exactly the users and groups in `snapshot`.
+/// Fails without making any changes if the snapshot was not signed by the calling device.
+pub async fn document_access_restore<CR: rand::CryptoRng + rand::RngCore>(
+    auth: &RequestAuth,
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    user_master_pub_key: &PublicKey,
+    priv_device_key: &DevicePrivateKey,
+    snapshot: &DocumentAccessSnapshot,
+) -> Result<DocumentAccessRestoreResult, IronOxideErr> {
+    snapshot.verify(auth.signing_private_key())?;
+    let DocumentMetadataResult(meta) = document_get_metadata(auth, &snapshot.document_id).await?;
+    let current_users: Vec<UserId> = meta.visible_to.users.into_iter().map(|u| u.id).collect();
+    let current_groups: Vec<GroupId> = meta.visible_to.groups.into_iter().map(|g| g.id).collect();
+
+    let grant_users: Vec<UserId> = snapshot
+        .users
+        .iter()
+        .filter(|u| !current_users.contains(u))
+        .cloned()
+        .collect();
+    let grant_groups: Vec<GroupId> = snapshot
+        .groups
+        .iter()
+        .filter(|g| !current_groups.contains(g))
+        .cloned()
+        .collect();
+    let revokes: Vec<UserOrGroup> = current_users
+        .iter()
+        .filter(|u| !snapshot.users.contains(u))
+        .map(UserOrGroup::from)
+        .chain(
+            current_groups
+                .iter()
+                .filter(|g| !snapshot.groups.contains(g))
+                .map(UserOrGroup::from),
+        )
+        .collect();
+
+    // grant before revoking, as the revokes may remove the calling user's own access
+    let granted = if grant_users.is_empty() && grant_groups.is_empty() {
+        DocumentAccessResult::new(vec![], vec![])
+    } else {
+        document_grant_access(
+            auth,
+            recrypt,
+            &snapshot.document_id,
+            user_master_pub_key,
+            priv_device_key,
+            &grant_users,
+            &grant_groups,
+        )
+        .await?
+    };
+    let revoked = if revokes.is_empty() {
+        DocumentAccessResult::new(vec![], vec![])
+    } else {
+        document_revoke_access(auth, &snapshot.document_id, &revokes).await?
+    };
+    Ok(DocumentAccessRestoreResult { granted, revoked })
+}
+
 /// Map the groups that come back from the server into a common value/err structure
 fn process_groups(
     (group_errs, groups_with_key): (Vec<GroupId>, Vec<WithKey<GroupId>>),
@@ -1391,7 +2706,10 @@ mod tests {
 
         // we've now cached a policy and it's the same as the one that was returned
         assert_eq!(1, policy_cache.len());
-        assert_eq!(policy.1, policy_cache.get(&policy_grant).unwrap().clone());
+        assert_eq!(
+            policy.1,
+            policy_cache.get(&policy_grant).unwrap().public_keys.clone()
+        );
 
         // let's get the policy again, but if the policy future executes (cache miss) error
         get_cached_policy_or(&config, &policy_grant, &policy_cache, async {
@@ -1415,6 +2733,7 @@ mod tests {
                 message: "".into(),
                 code: RequestErrorCode::PolicyGet,
                 http_status: Some(404),
+                request_id: None,
             })
         })
         .await;
@@ -1432,7 +2751,10 @@ mod tests {
         let policy_json = r#"{ "usersAndGroups": [ { "type": "group", "id": "data_recovery_abcABC012_.$#|@/:;=+'-f1e11a54-8aa9-4641-aaf3-fb92079499f0", "masterPublicKey": { "x": "GE5XQYcRDRhBcyDpNwlu79x6tshNi111ym1IfxOTIxk=", "y": "amgLgcCEYIPQ4oxinLoAvsO3VG7XTFdRfkG/3tooaZE=" } } ], "invalidUsersAndGroups": [] }"#;
         let policy_grant = PolicyGrant::default();
         let policy_cache = DashMap::new();
-        let config = PolicyCachingConfig { max_entries: 3 };
+        let config = PolicyCachingConfig {
+            max_entries: 3,
+            ..Default::default()
+        };
         let policy_resp: PolicyResponse =
             serde_json::from_str(policy_json).expect("json should parse");
 
@@ -1468,6 +2790,39 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn policy_cache_entries_expire_after_ttl() -> Result<(), IronOxideErr> {
+        let policy_json = r#"{ "usersAndGroups": [ { "type": "group", "id": "data_recovery_abcABC012_.$#|@/:;=+'-f1e11a54-8aa9-4641-aaf3-fb92079499f0", "masterPublicKey": { "x": "GE5XQYcRDRhBcyDpNwlu79x6tshNi111ym1IfxOTIxk=", "y": "amgLgcCEYIPQ4oxinLoAvsO3VG7XTFdRfkG/3tooaZE=" } } ], "invalidUsersAndGroups": [] }"#;
+        let policy_resp: PolicyResponse =
+            serde_json::from_str(policy_json).expect("json should parse");
+        let policy_grant = PolicyGrant::default();
+        let policy_cache = DashMap::new();
+        // a cached evaluation is used without evaluating the policy, which would fail here
+        let (grant, cache) = (&policy_grant, &policy_cache);
+        let lookup = move |config: PolicyCachingConfig| async move {
+            get_cached_policy_or(&config, grant, cache, async {
+                Err(IronOxideErr::PolicyDoesNotExist)
+            })
+            .await
+        };
+
+        let no_ttl = PolicyCachingConfig::default();
+        get_cached_policy_or(&no_ttl, &policy_grant, &policy_cache, async {
+            Ok(policy_resp.clone())
+        })
+        .await?;
+        assert!(lookup(no_ttl).await.is_ok());
+        let expired = PolicyCachingConfig {
+            ttl: Some(std::time::Duration::from_secs(0)),
+            ..Default::default()
+        };
+        assert_that!(
+            &lookup(expired).await.unwrap_err(),
+            is_variant!(IronOxideErr::PolicyDoesNotExist)
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn policy_cache_unclean_entries_not_cached() -> Result<(), IronOxideErr> {
         // policy with 1 "good" group and one "bad" one
@@ -1486,6 +2841,181 @@ mod tests {
 
         Ok(())
     }
+    #[tokio::test]
+    async fn policy_cache_export_import_roundtrip() -> Result<(), IronOxideErr> {
+        let policy_json = r#"{ "usersAndGroups": [ { "type": "group", "id": "data_recovery_abcABC012_.$#|@/:;=+'-f1e11a54-8aa9-4641-aaf3-fb92079499f0", "masterPublicKey": { "x": "GE5XQYcRDRhBcyDpNwlu79x6tshNi111ym1IfxOTIxk=", "y": "amgLgcCEYIPQ4oxinLoAvsO3VG7XTFdRfkG/3tooaZE=" } } ], "invalidUsersAndGroups": [] }"#;
+        let policy_grant = PolicyGrant::new(Some("foo".try_into()?), None, None, None);
+        let policy_cache = DashMap::new();
+        let config = PolicyCachingConfig::default();
+        let policy_resp: PolicyResponse =
+            serde_json::from_str(policy_json).expect("json should parse");
+        let recrypt = recrypt::api::Recrypt::new();
+        let (device_priv, _) = recrypt.generate_key_pair()?;
+        let device_priv: PrivateKey = device_priv.into();
+        let rng = Mutex::new(rand::thread_rng());
+
+        let policy = get_cached_policy_or(&config, &policy_grant, &policy_cache, async {
+            Ok(policy_resp.clone())
+        })
+        .await?;
+        let exported = export_policy_cache(&policy_cache, &device_priv, &rng)?;
+
+        let reloaded_cache = DashMap::new();
+        let imported = import_policy_cache(
+            &config,
+            &reloaded_cache,
+            &device_priv,
+            &exported,
+            std::time::Duration::from_secs(60),
+        )?;
+        assert_eq!(imported, 1);
+        assert_eq!(
+            policy.1,
+            reloaded_cache
+                .get(&policy_grant)
+                .unwrap()
+                .public_keys
+                .clone()
+        );
+        assert_eq!(
+            policy_cache.get(&policy_grant).unwrap().cached_at,
+            reloaded_cache.get(&policy_grant).unwrap().cached_at
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn content_hash_ignores_grant_order() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (device_priv, _) = recrypt.generate_key_pair()?;
+        let device_priv: PrivateKey = device_priv.into();
+        let alice = UserId::unsafe_from_string("alice".into());
+        let bob = UserId::unsafe_from_string("bob".into());
+        let group = GroupId::unsafe_from_string("group".into());
+
+        let hash = content_hash(
+            &device_priv,
+            b"data",
+            true,
+            &[alice.clone(), bob.clone()],
+            &[group.clone()],
+            None,
+        );
+        let reordered = content_hash(
+            &device_priv,
+            b"data",
+            true,
+            &[bob.clone(), alice.clone(), bob],
+            &[group],
+            None,
+        );
+        assert_eq!(hash, reordered);
+        Ok(())
+    }
+
+    #[test]
+    fn content_hash_differs_by_audience_and_device() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (device_priv, _) = recrypt.generate_key_pair()?;
+        let device_priv: PrivateKey = device_priv.into();
+        let (other_device_priv, _) = recrypt.generate_key_pair()?;
+        let other_device_priv: PrivateKey = other_device_priv.into();
+        let alice = UserId::unsafe_from_string("alice".into());
+        let policy = PolicyGrant::new(Some("foo".try_into()?), None, None, None);
+
+        let hash = content_hash(&device_priv, b"data", true, &[alice.clone()], &[], None);
+        let hashes = vec![
+            content_hash(
+                &device_priv,
+                b"other data",
+                true,
+                &[alice.clone()],
+                &[],
+                None,
+            ),
+            content_hash(&device_priv, b"data", false, &[alice.clone()], &[], None),
+            content_hash(&device_priv, b"data", true, &[], &[], None),
+            content_hash(
+                &device_priv,
+                b"data",
+                true,
+                &[],
+                &[GroupId::unsafe_from_string("alice".into())],
+                None,
+            ),
+            content_hash(
+                &device_priv,
+                b"data",
+                true,
+                &[alice.clone()],
+                &[],
+                Some(&policy),
+            ),
+            content_hash(&other_device_priv, b"data", true, &[alice], &[], None),
+        ];
+        assert!(!hashes.contains(&hash));
+        Ok(())
+    }
+
+    #[test]
+    fn policy_cache_import_drops_expired_entries() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (device_priv, _) = recrypt.generate_key_pair()?;
+        let device_priv: PrivateKey = device_priv.into();
+        let (_, group_pub) = recrypt.generate_key_pair()?;
+        let rng = Mutex::new(rand::thread_rng());
+        let config = PolicyCachingConfig::default();
+        let policy_cache = DashMap::new();
+        let cached_policy = |cached_at| CachedPolicy {
+            public_keys: vec![WithKey::new(
+                GroupId::unsafe_from_string("group".into()).into(),
+                group_pub.into(),
+            )],
+            cached_at,
+        };
+        let fresh_grant = PolicyGrant::new(Some("fresh".try_into()?), None, None, None);
+        let stale_grant = PolicyGrant::new(Some("stale".try_into()?), None, None, None);
+        policy_cache.insert(fresh_grant.clone(), cached_policy(Utc::now()));
+        policy_cache.insert(
+            stale_grant.clone(),
+            cached_policy(Utc::now() - chrono::Duration::hours(2)),
+        );
+        let exported = export_policy_cache(&policy_cache, &device_priv, &rng)?;
+
+        let reloaded_cache = DashMap::new();
+        let imported = import_policy_cache(
+            &config,
+            &reloaded_cache,
+            &device_priv,
+            &exported,
+            std::time::Duration::from_secs(3600),
+        )?;
+        assert_eq!(imported, 1);
+        assert!(reloaded_cache.contains_key(&fresh_grant));
+        assert!(!reloaded_cache.contains_key(&stale_grant));
+        Ok(())
+    }
+
+    #[test]
+    fn policy_cache_import_fails_for_other_device() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (device_priv, _) = recrypt.generate_key_pair()?;
+        let (other_device_priv, _) = recrypt.generate_key_pair()?;
+        let rng = Mutex::new(rand::thread_rng());
+        let policy_cache = DashMap::new();
+        let exported = export_policy_cache(&policy_cache, &device_priv.into(), &rng)?;
+
+        let result = import_policy_cache(
+            &PolicyCachingConfig::default(),
+            &DashMap::new(),
+            &other_device_priv.into(),
+            &exported,
+            std::time::Duration::from_secs(60),
+        );
+        assert_that!(&result, is_variant!(Err));
+        Ok(())
+    }
+
     #[test]
     fn document_id_validate_good() {
         let doc_id1 = "an_actual_good_doc_id$";
@@ -1586,6 +3116,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn access_snapshot_signature_detects_changes() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let signing_keys: DeviceSigningKeyPair =
+            recrypt::api::Ed25519Ops::generate_ed25519_key_pair(&recrypt).into();
+        let other_keys: DeviceSigningKeyPair =
+            recrypt::api::Ed25519Ops::generate_ed25519_key_pair(&recrypt).into();
+        let snapshot = DocumentAccessSnapshot::new(
+            DocumentId("doc".to_string()),
+            Utc::now(),
+            vec![UserId::unsafe_from_string("user".to_string())],
+            vec![GroupId::unsafe_from_string("group".to_string())],
+            &signing_keys,
+        );
+        snapshot.verify(&signing_keys)?;
+
+        let reparsed: DocumentAccessSnapshot =
+            serde_json::from_str(&serde_json::to_string(&snapshot).unwrap()).unwrap();
+        assert_eq!(reparsed, snapshot);
+        reparsed.verify(&signing_keys)?;
+
+        let mut tampered = snapshot.clone();
+        tampered
+            .users
+            .push(UserId::unsafe_from_string("mallory".to_string()));
+        assert_that!(
+            &tampered.verify(&signing_keys).unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        assert_that!(
+            &snapshot.verify(&other_keys).unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn only_corrupted_documents_fail_verification() {
+        let enc_doc = decode("AgA4eyJfZGlkXyI6ImNjOTIyZTA3NzRhM2MwZWViZTI2NDM2Yzk2ZjdiYzkzIiwiX3NpZF8iOjYwOH1ciL4su5SPZh4eFGuG+5rJ+/I2gDSZAs+2dXw097gU8fBkMWzRo0dDIW0dOxHg/1mio1yMRdDZDA==").unwrap();
+        let truncated = get_id_from_bytes(&enc_doc[..20]).unwrap_err();
+        assert!(is_corruption(&truncated));
+        assert!(is_corruption(&IronOxideErr::AesError(
+            crate::crypto::provider::error::Unspecified
+        )));
+        assert!(!is_corruption(&IronOxideErr::MissingTransformBlocks));
+    }
+
+    #[test]
+    fn err_on_truncated_doc_header() {
+        let enc_doc = decode("AgA4eyJfZGlkXyI6ImNjOTIyZTA3NzRhM2MwZWViZTI2NDM2Yzk2ZjdiYzkzIiwiX3NpZF8iOjYwOH1ciL4su5SPZh4eFGuG+5rJ+/I2gDSZAs+2dXw097gU8fBkMWzRo0dDIW0dOxHg/1mio1yMRdDZDA==").unwrap();
+
+        for truncated in &[&enc_doc[..0], &enc_doc[..2], &enc_doc[..20]] {
+            assert_that!(
+                &get_id_from_bytes(truncated).unwrap_err(),
+                is_variant!(IronOxideErr::DocumentHeaderParseFailure)
+            );
+        }
+        assert_that!(
+            &get_id_from_bytes(&enc_doc[..(3 + 56 + 12)]).unwrap_err(),
+            is_variant!(IronOxideErr::AesEncryptedDocSizeError)
+        );
+    }
+
     #[test]
     fn read_good_document_header_test() {
         let enc_doc = decode("AgA4eyJfZGlkXyI6ImNjOTIyZTA3NzRhM2MwZWViZTI2NDM2Yzk2ZjdiYzkzIiwiX3NpZF8iOjYwOH1ciL4su5SPZh4eFGuG+5rJ+/I2gDSZAs+2dXw097gU8fBkMWzRo0dDIW0dOxHg/1mio1yMRdDZDA==").unwrap();
@@ -1608,7 +3201,7 @@ mod tests {
         );
 
         assert_that!(
-            &doc_parts.1.bytes(),
+            &doc_parts.1.to_vec(),
             eq(vec![
                 92, 136, 190, 44, 187, 148, 143, 102, 30, 30, 20, 107, 134, 251, 154, 201, 251,
                 242, 54, 128, 52, 153, 2, 207, 182, 117, 124, 52, 247, 184, 20, 241, 240, 100, 49,
@@ -1618,6 +3211,47 @@ mod tests {
         )
     }
 
+    #[test]
+    fn document_header_records_cipher_suite() -> Result<(), IronOxideErr> {
+        let header = DocumentHeader::new("123abc".try_into()?, 18usize)
+            .with_cipher_suite(CipherSuite::XChaCha20Poly1305);
+        let edoc = [&header.pack().0[..], &[1u8; 40][..]].concat();
+
+        let (parsed, encrypted) = parse_document_parts(&edoc)?;
+        assert_eq!(parsed, header);
+        assert_eq!(encrypted, &[1u8; 40][..]);
+        Ok(())
+    }
+
+    #[test]
+    fn header_aad_binds_data_to_its_header() -> Result<(), IronOxideErr> {
+        let rng = Mutex::new(rand::thread_rng());
+        let key = [7u8; 32];
+        let header = DocumentHeader::new("doc-a".try_into()?, 18usize).with_header_aad(true);
+        let encrypted = header
+            .cipher_suite
+            .encrypt(&rng, b"secret", &key, &header.aad())?;
+        let edoc = [&header.pack().0[..], &encrypted[..]].concat();
+        let (parsed, data) = parse_document_parts(&edoc)?;
+        assert_eq!(parsed, header);
+        assert_eq!(parsed.decrypt_data(data, &key)?, b"secret".to_vec());
+
+        let other_headers = vec![
+            DocumentHeader::new("doc-b".try_into()?, 18usize).with_header_aad(true),
+            DocumentHeader::new("doc-a".try_into()?, 19usize).with_header_aad(true),
+            DocumentHeader::new("doc-a".try_into()?, 18usize),
+        ];
+        for other in other_headers {
+            let swapped = [&other.pack().0[..], &encrypted[..]].concat();
+            let (parsed, data) = parse_document_parts(&swapped)?;
+            assert_that!(
+                &parsed.decrypt_data(data, &key).unwrap_err(),
+                is_variant!(IronOxideErr::AesError)
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn generate_document_header_test() {
         let header = DocumentHeader::new("123abc".try_into().unwrap(), 18usize);
@@ -1811,7 +3445,7 @@ mod tests {
             &signingkeys,
             &recr,
             recr.gen_plaintext(),
-            aes_value,
+            aes_value.bytes(),
             &doc_id,
             with_keys,
         )?
@@ -1853,7 +3487,7 @@ mod tests {
             &signingkeys,
             &recr,
             recr.gen_plaintext(),
-            aes_value,
+            aes_value.bytes(),
             &doc_id,
             with_keys,
         )?
@@ -1913,6 +3547,182 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn filter_edeks_by_key_path_keeps_matching() -> Result<(), IronOxideErr> {
+        use recrypt::prelude::*;
+
+        let recr = recrypt::api::Recrypt::new();
+        let signingkeys = DeviceSigningKeyPair::from(recr.generate_ed25519_key_pair());
+        let aes_value = AesEncryptedValue::try_from(&[42u8; 32][..])?;
+        let uid = UserId::unsafe_from_string("userid".into());
+        let gid = GroupId::unsafe_from_string("groupid".into());
+        let (_, pubk) = recr.generate_key_pair()?;
+        let with_keys = vec![
+            WithKey::new(uid.borrow().into(), pubk.clone().into()),
+            WithKey::new(gid.borrow().into(), pubk.into()),
+        ];
+        let doc_id = DocumentId("docid".into());
+
+        let encryption_result = recrypt_document(
+            &signingkeys,
+            &recr,
+            recr.gen_plaintext(),
+            aes_value.bytes(),
+            &doc_id,
+            with_keys,
+        )?
+        .into_edoc(DocumentHeader::new(doc_id, 33));
+        let doc_encrypt_unmanaged_result =
+            DocumentEncryptUnmanagedResult::new(encryption_result, vec![])?;
+        let proto_edeks =
+            EncryptedDeksP::parse_from_bytes(doc_encrypt_unmanaged_result.encrypted_deks())?;
+
+        let user_edeks = filter_edeks_by_key_path(&proto_edeks, DecryptKeyPath::User);
+        assert_eq!(user_edeks.get_edeks().len(), 1);
+        assert_eq!(
+            edek_key_path(&user_edeks.get_edeks()[0]),
+            DecryptKeyPath::User
+        );
+        assert_eq!(user_edeks.get_documentId(), proto_edeks.get_documentId());
+        assert_eq!(user_edeks.get_segmentId(), proto_edeks.get_segmentId());
+
+        let group_edeks = filter_edeks_by_key_path(&proto_edeks, DecryptKeyPath::Group);
+        assert_eq!(group_edeks.get_edeks().len(), 1);
+        assert_eq!(
+            edek_key_path(&group_edeks.get_edeks()[0]),
+            DecryptKeyPath::Group
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn only_refusals_fall_through_to_the_next_key_path() {
+        let with_status = |http_status| IronOxideErr::RequestError {
+            message: "".into(),
+            code: RequestErrorCode::EdekTransform,
+            http_status,
+            request_id: None,
+        };
+        assert!(key_path_refused(&with_status(Some(403))));
+        assert!(key_path_refused(&with_status(Some(404))));
+        assert!(!key_path_refused(&with_status(Some(503))));
+        assert!(!key_path_refused(&with_status(None)));
+        assert!(!key_path_refused(&IronOxideErr::RateLimited {
+            retry_after: None,
+            code: RequestErrorCode::EdekTransform,
+        }));
+    }
+
+    #[test]
+    fn edek_recipients_and_header_are_read_locally() -> Result<(), IronOxideErr> {
+        use recrypt::prelude::*;
+
+        let recr = recrypt::api::Recrypt::new();
+        let signingkeys = DeviceSigningKeyPair::from(recr.generate_ed25519_key_pair());
+        let aes_value = AesEncryptedValue::try_from(&[42u8; 32][..])?;
+        let user: UserOrGroup = UserId::unsafe_from_string("userid".into()).into();
+        let group: UserOrGroup = GroupId::unsafe_from_string("groupid".into()).into();
+        let (_, pubk) = recr.generate_key_pair()?;
+        let with_keys = vec![
+            WithKey::new(user.clone(), pubk.clone().into()),
+            WithKey::new(group.clone(), pubk.into()),
+        ];
+        let doc_id = DocumentId("docid".into());
+        let encryption_result = recrypt_document(
+            &signingkeys,
+            &recr,
+            recr.gen_plaintext(),
+            aes_value.bytes(),
+            &doc_id,
+            with_keys,
+        )?;
+        let edoc = encryption_result.clone().into_edoc(
+            DocumentHeader::new(doc_id.clone(), 33)
+                .with_cipher_suite(CipherSuite::XChaCha20Poly1305),
+        );
+        let other_edoc =
+            encryption_result.into_edoc(DocumentHeader::new(DocumentId("other_docid".into()), 33));
+
+        let header = get_header_from_bytes(&edoc.edoc_bytes())?;
+        assert_eq!(header.id(), &doc_id);
+        assert_eq!(header.segment_id(), 33);
+        assert_eq!(header.cipher_suite(), CipherSuite::XChaCha20Poly1305);
+        assert!(!header.header_aad());
+
+        let edeks = edoc.edek_bytes()?;
+        assert_eq!(get_edek_recipients(&edeks, None)?, vec![user, group]);
+        assert_eq!(
+            get_edek_recipients(&edeks, Some(&edoc.edoc_bytes()))?.len(),
+            2
+        );
+        assert_that!(
+            &get_edek_recipients(&edeks, Some(&other_edoc.edoc_bytes())).unwrap_err(),
+            is_variant!(IronOxideErr::UnmanagedDecryptionError)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn replay_access_log_applies_grants_and_revokes_in_order() {
+        let doc1 = DocumentId("doc1".into());
+        let doc2 = DocumentId("doc2".into());
+        let user: UserOrGroup = UserId::unsafe_from_string("user".into()).into();
+        let group: UserOrGroup = GroupId::unsafe_from_string("group".into()).into();
+        let entries = vec![
+            AccessLogEntry::new(doc1.clone(), user.clone(), AccessLogOp::Grant),
+            AccessLogEntry::new(doc2.clone(), user.clone(), AccessLogOp::Grant),
+            AccessLogEntry::new(doc1.clone(), group.clone(), AccessLogOp::Grant),
+            AccessLogEntry::new(doc1.clone(), user.clone(), AccessLogOp::Grant),
+            AccessLogEntry::new(doc2.clone(), user.clone(), AccessLogOp::Revoke),
+            AccessLogEntry::new(doc1.clone(), user.clone(), AccessLogOp::Revoke),
+        ];
+
+        let expected = replay_access_log(&entries);
+        assert_eq!(expected, vec![(doc1, vec![group]), (doc2, vec![])]);
+    }
+
+    #[test]
+    fn association_type_to_key_path() {
+        assert_eq!(AssociationType::Owner.key_path(), DecryptKeyPath::User);
+        assert_eq!(AssociationType::FromUser.key_path(), DecryptKeyPath::User);
+        assert_eq!(AssociationType::FromGroup.key_path(), DecryptKeyPath::Group);
+    }
+
+    #[test]
+    fn merge_chunked_grant_results_reports_failed_chunks() -> Result<(), IronOxideErr> {
+        let user = |id: &str| UserOrGroup::User {
+            id: UserId::unsafe_from_string(id.to_string()),
+        };
+        let ok_chunk = serde_json::from_value(serde_json::json!({
+            "succeededIds": [{"userOrGroup": {"type": "user", "id": "a"}}],
+            "failedIds": [{"userOrGroup": {"type": "user", "id": "b"}, "errorMessage": "nope"}]
+        }))
+        .unwrap();
+        let result = merge_chunked_grant_results(
+            vec![DocAccessEditErr::new(user("z"), "no key".to_string())],
+            vec![
+                (vec![user("a"), user("b")], Ok(ok_chunk)),
+                (
+                    vec![user("c")],
+                    Err(IronOxideErr::ValidationError("x".into(), "y".into())),
+                ),
+            ],
+        )?;
+        assert_eq!(result.succeeded(), &[user("a")]);
+        let failed: Vec<_> = result.failed().iter().map(|f| &f.user_or_group).collect();
+        assert_eq!(failed, vec![&user("z"), &user("b"), &user("c")]);
+
+        let all_failed = merge_chunked_grant_results(
+            vec![],
+            vec![(
+                vec![user("a")],
+                Err(IronOxideErr::ValidationError("x".into(), "y".into())),
+            )],
+        );
+        assert_that!(&all_failed, is_variant!(Err));
+        Ok(())
+    }
+
     #[test]
     pub fn edek_edoc_no_match() -> Result<(), IronOxideErr> {
         use recrypt::prelude::*;
@@ -1936,7 +3746,7 @@ mod tests {
             &signingkeys,
             &recr,
             recr.gen_plaintext(),
-            aes_value,
+            aes_value.bytes(),
             &doc_id,
             with_keys,
         )?;
@@ -2005,4 +3815,74 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn document_key_cache_evicts_least_recently_used() -> Result<(), IronOxideErr> {
+        let config = DocumentKeyCachingConfig {
+            max_entries: 2,
+            ..Default::default()
+        };
+        let cache = DocumentKeyCache::default();
+        let doc_key = |id: &str| -> Result<Arc<DocumentKey>, IronOxideErr> {
+            Ok(Arc::new(DocumentKey {
+                id: DocumentId::try_from(id)?,
+                name: None,
+                created: Utc::now(),
+                updated: Utc::now(),
+                key: Locked::default(),
+            }))
+        };
+        let (a, b, c) = (
+            DocumentId::try_from("a")?,
+            DocumentId::try_from("b")?,
+            DocumentId::try_from("c")?,
+        );
+        cache.insert(&config, doc_key("a")?);
+        cache.insert(&config, doc_key("b")?);
+        // using "a" makes "b" the least recently used
+        assert!(cache.get(&config, &a).is_some());
+        cache.insert(&config, doc_key("c")?);
+        assert!(cache.get(&config, &b).is_none());
+        assert!(cache.get(&config, &a).is_some());
+        assert!(cache.get(&config, &c).is_some());
+
+        // expired keys aren't used
+        let expired = DocumentKeyCachingConfig {
+            ttl: std::time::Duration::from_nanos(1),
+            ..config
+        };
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert!(cache.get(&expired, &a).is_none());
+        assert!(cache.remove(&c));
+        assert_eq!(cache.clear(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn content_hash_index_is_bounded() -> Result<(), IronOxideErr> {
+        let config = UniqueEncryptionConfig {
+            max_entries: 2,
+            ..Default::default()
+        };
+        let index = ContentHashIndex::default();
+        let (a, b) = (DocumentId::try_from("a")?, DocumentId::try_from("b")?);
+        index.insert(&config, [1u8; 32], a.clone());
+        index.insert(&config, [2u8; 32], b.clone());
+        // using the first hash makes the second the least recently used
+        assert_eq!(index.get(&config, &[1u8; 32]), Some(a.clone()));
+        index.insert(&config, [3u8; 32], a.clone());
+        assert_eq!(index.get(&config, &[2u8; 32]), None);
+
+        // expired entries aren't used
+        let expired = UniqueEncryptionConfig {
+            ttl: std::time::Duration::from_nanos(1),
+            ..config
+        };
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert_eq!(index.get(&expired, &[1u8; 32]), None);
+        assert!(index.remove(&a));
+        assert!(!index.remove(&b));
+        assert_eq!(index.clear(), 0);
+        Ok(())
+    }
 }
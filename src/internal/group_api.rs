@@ -1,4 +1,7 @@
+#[cfg(feature = "unstable-group-endpoints")]
+use crate::internal::document_api::{DocumentId, DocumentName};
 use crate::{
+    config::GroupKeyCachingConfig,
     crypto::transform,
     internal::{
         self,
@@ -6,20 +9,23 @@ use crate::{
             group_get::group_get_request, group_list::GroupListResponse, GroupAdmin,
             GroupUserEditResponse, User,
         },
-        rest::json::{AugmentationFactor, EncryptedOnceValue, TransformedEncryptedValue},
+        rest::json::{
+            AugmentationFactor, Base64Standard, EncryptedOnceValue, TransformedEncryptedValue,
+        },
         user_api::{self, UserId},
-        validate_id, validate_name, DeviceSigningKeyPair, IronOxideErr, PrivateKey, PublicKey,
-        RequestAuth, SchnorrSignature, TransformKey, WithKey,
+        validate_id, validate_name, DevicePrivateKey, DeviceSigningKeyPair, IronOxideErr,
+        PrivateKey, PublicKey, RequestAuth, SchnorrSignature, SdkOperation, TransformKey, WithKey,
     },
+    GroupKeyCache,
 };
 use chrono::{DateTime, Utc};
 use core::convert::identity;
-use futures::try_join;
+use futures::{try_join, FutureExt, StreamExt};
 use itertools::{Either, Itertools};
 use recrypt::{api::EncryptedValue, prelude::*};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     convert::{TryFrom, TryInto},
     iter::FromIterator,
 };
@@ -27,11 +33,20 @@ use vec1::Vec1;
 
 mod requests;
 
+#[derive(Clone, Copy, Debug)]
 pub enum GroupEntity {
     Member,
     Admin,
 }
 
+/// Whether users are added to a group's membership right away, or invited and become members once they accept.
+#[derive(Clone, Copy)]
+pub enum MemberAddMode {
+    Add,
+    #[cfg(feature = "unstable-group-endpoints")]
+    Invite,
+}
+
 /// This is used for GroupCreateOpts that have been standardized with the GroupCreateOpts::standardize function.
 /// `add_as_member` and `add_as_admin` have been removed, with the calling user added to the `members` and `admins` lists.
 #[derive(Clone)]
@@ -42,6 +57,8 @@ pub struct GroupCreateOptsStd {
     pub(crate) admins: Vec1<UserId>,
     pub(crate) members: Vec<UserId>,
     pub(crate) needs_rotation: bool,
+    pub(crate) description: Option<GroupDescription>,
+    pub(crate) attributes: GroupAttributes,
 }
 impl GroupCreateOptsStd {
     /// returns all the users who need their public keys looked up (with duplicates removed).
@@ -116,6 +133,99 @@ impl TryFrom<&str> for GroupName {
     }
 }
 
+/// Free-text description of a group.
+///
+/// The description can be validated from a `String` or `&str` using `GroupDescription::try_from`.
+///
+/// # Requirements
+/// - Must be between 1 and 1000 characters long.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct GroupDescription(pub(crate) String);
+impl GroupDescription {
+    const MAX_LEN: usize = 1000;
+
+    /// Description of the group
+    pub fn description(&self) -> &String {
+        &self.0
+    }
+}
+impl TryFrom<String> for GroupDescription {
+    type Error = IronOxideErr;
+    fn try_from(description: String) -> Result<Self, Self::Error> {
+        description.as_str().try_into()
+    }
+}
+impl TryFrom<&str> for GroupDescription {
+    type Error = IronOxideErr;
+    fn try_from(description: &str) -> Result<Self, Self::Error> {
+        let char_count = description.chars().count();
+        if description.trim().is_empty() || char_count > GroupDescription::MAX_LEN {
+            Err(IronOxideErr::ValidationError(
+                "group_description".to_string(),
+                format!(
+                    "'{}' must be between 1 and {} characters",
+                    description,
+                    GroupDescription::MAX_LEN
+                ),
+            ))
+        } else {
+            Ok(GroupDescription(description.to_string()))
+        }
+    }
+}
+
+/// Small set of key/value attributes attached to a group.
+///
+/// The attributes can be validated from a `BTreeMap<String, String>` using `GroupAttributes::try_from`.
+///
+/// # Requirements
+/// - Must have no more than 16 entries.
+/// - Keys must be between 1 and 64 characters long.
+/// - Values must be no more than 256 characters long.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct GroupAttributes(pub(crate) BTreeMap<String, String>);
+impl GroupAttributes {
+    const MAX_ENTRIES: usize = 16;
+    const MAX_KEY_LEN: usize = 64;
+    const MAX_VALUE_LEN: usize = 256;
+
+    /// Attributes of the group
+    pub fn attributes(&self) -> &BTreeMap<String, String> {
+        &self.0
+    }
+}
+impl TryFrom<BTreeMap<String, String>> for GroupAttributes {
+    type Error = IronOxideErr;
+    fn try_from(attributes: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        let validation_err =
+            |msg: String| IronOxideErr::ValidationError("group_attributes".to_string(), msg);
+        if attributes.len() > GroupAttributes::MAX_ENTRIES {
+            return Err(validation_err(format!(
+                "cannot have more than {} entries",
+                GroupAttributes::MAX_ENTRIES
+            )));
+        }
+        for (key, value) in &attributes {
+            let key_len = key.chars().count();
+            if key_len == 0 || key_len > GroupAttributes::MAX_KEY_LEN {
+                return Err(validation_err(format!(
+                    "key '{}' must be between 1 and {} characters",
+                    key,
+                    GroupAttributes::MAX_KEY_LEN
+                )));
+            }
+            if value.chars().count() > GroupAttributes::MAX_VALUE_LEN {
+                return Err(validation_err(format!(
+                    "value for key '{}' cannot be more than {} characters",
+                    key,
+                    GroupAttributes::MAX_VALUE_LEN
+                )));
+            }
+        }
+        Ok(GroupAttributes(attributes))
+    }
+}
+
 /// Metadata for each group the user is an admin or a member of.
 ///
 /// Result from [group_list](trait.GroupOps.html#tymethod.group_list).
@@ -130,6 +240,101 @@ impl GroupListResult {
     }
 }
 
+#[cfg(feature = "unstable-group-endpoints")]
+/// A pending invitation for the calling user to become a member of a group.
+///
+/// Result from [GroupInviteListResult.result()](struct.GroupInviteListResult.html#method.result).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GroupInvite {
+    group_id: GroupId,
+    group_name: Option<GroupName>,
+    invited_by: UserId,
+    created: DateTime<Utc>,
+}
+impl GroupInvite {
+    /// ID of the group
+    pub fn group_id(&self) -> &GroupId {
+        &self.group_id
+    }
+    /// Name of the group
+    pub fn group_name(&self) -> Option<&GroupName> {
+        self.group_name.as_ref()
+    }
+    /// Admin who made the invitation
+    pub fn invited_by(&self) -> &UserId {
+        &self.invited_by
+    }
+    /// Date and time when the invitation was made
+    pub fn created(&self) -> &DateTime<Utc> {
+        &self.created
+    }
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// Pending invitations for the calling user to become a member of groups.
+///
+/// Result from [group_list_invites](trait.GroupOps.html#tymethod.group_list_invites).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GroupInviteListResult {
+    result: Vec<GroupInvite>,
+}
+impl GroupInviteListResult {
+    /// Each pending invitation for the calling user
+    pub fn result(&self) -> &Vec<GroupInvite> {
+        &self.result
+    }
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// A document that has been granted to a group.
+///
+/// Result from [GroupDocumentListResult.result()](struct.GroupDocumentListResult.html#method.result).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GroupDocument {
+    id: DocumentId,
+    name: Option<DocumentName>,
+    created: DateTime<Utc>,
+    last_updated: DateTime<Utc>,
+}
+impl GroupDocument {
+    /// ID of the document
+    pub fn id(&self) -> &DocumentId {
+        &self.id
+    }
+    /// Name of the document
+    pub fn name(&self) -> Option<&DocumentName> {
+        self.name.as_ref()
+    }
+    /// Date and time when the document was created
+    pub fn created(&self) -> &DateTime<Utc> {
+        &self.created
+    }
+    /// Date and time when the document was last updated
+    pub fn last_updated(&self) -> &DateTime<Utc> {
+        &self.last_updated
+    }
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// Documents that have been granted to a group.
+///
+/// Result from [group_list_documents](trait.GroupOps.html#tymethod.group_list_documents).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GroupDocumentListResult {
+    group_id: GroupId,
+    result: Vec<GroupDocument>,
+}
+impl GroupDocumentListResult {
+    /// ID of the group
+    pub fn group_id(&self) -> &GroupId {
+        &self.group_id
+    }
+    /// Each document that has been granted to the group
+    pub fn result(&self) -> &Vec<GroupDocument> {
+        &self.result
+    }
+}
+
 /// Abbreviated group metadata.
 ///
 /// Result from [GroupListResult.result()](struct.GroupListResult.html#method.result) and
@@ -144,6 +349,8 @@ pub struct GroupMetaResult {
     created: DateTime<Utc>,
     updated: DateTime<Utc>,
     needs_rotation: Option<bool>,
+    description: Option<GroupDescription>,
+    attributes: GroupAttributes,
 }
 impl GroupMetaResult {
     /// ID of the group
@@ -180,6 +387,14 @@ impl GroupMetaResult {
     pub fn needs_rotation(&self) -> Option<bool> {
         self.needs_rotation
     }
+    /// Description of the group
+    pub fn description(&self) -> Option<&GroupDescription> {
+        self.description.as_ref()
+    }
+    /// Key/value attributes of the group
+    pub fn attributes(&self) -> &GroupAttributes {
+        &self.attributes
+    }
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -261,9 +476,12 @@ pub struct GroupGetResult {
     owner: Option<UserId>,
     admin_list: Option<Vec<UserId>>,
     member_list: Option<Vec<UserId>>,
+    member_group_list: Option<Vec<GroupId>>,
     created: DateTime<Utc>,
     updated: DateTime<Utc>,
     needs_rotation: Option<bool>,
+    description: Option<GroupDescription>,
+    attributes: GroupAttributes,
     /// not exposed outside of the module
     encrypted_private_key: Option<TransformedEncryptedValue>,
 }
@@ -310,28 +528,63 @@ impl GroupGetResult {
     pub fn member_list(&self) -> Option<&Vec<UserId>> {
         self.member_list.as_ref()
     }
+    /// List of groups that are members of this group. Members of these groups are also members of this group.
+    pub fn member_group_list(&self) -> Option<&Vec<GroupId>> {
+        self.member_group_list.as_ref()
+    }
     /// Whether the group's private key needs rotation. Can only be accessed by a group administrator.
     /// - `Some(bool)` - Indicates whether the group's private key needs rotation.
     /// - `None` - The calling user does not have permission to view this.
     pub fn needs_rotation(&self) -> Option<bool> {
         self.needs_rotation
     }
+    /// Description of the group
+    pub fn description(&self) -> Option<&GroupDescription> {
+        self.description.as_ref()
+    }
+    /// Key/value attributes of the group
+    pub fn attributes(&self) -> &GroupAttributes {
+        &self.attributes
+    }
+}
+
+/// Reason a user couldn't be added to or removed from a group's member or admin lists.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum GroupAccessEditErrKind {
+    /// The user does not exist in the segment.
+    UserDoesNotExist,
+    /// The user is already an admin of the group, so can't be added as one.
+    AlreadyAdmin,
+    /// The user is not in the list they were being removed from.
+    NotAMember,
+    /// The calling user isn't allowed to make the change.
+    PermissionDenied,
+    /// Any other failure. See the failure's `error` for details.
+    Other,
 }
 
 /// A failure when attempting to change a group's member or admin lists.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct GroupAccessEditErr {
     user: UserId,
+    kind: GroupAccessEditErrKind,
     error: String,
 }
 impl GroupAccessEditErr {
     fn new(user: UserId, error: String) -> GroupAccessEditErr {
-        GroupAccessEditErr { user, error }
+        GroupAccessEditErr::with_kind(user, GroupAccessEditErrKind::Other, error)
+    }
+    fn with_kind(user: UserId, kind: GroupAccessEditErrKind, error: String) -> GroupAccessEditErr {
+        GroupAccessEditErr { user, kind, error }
     }
     /// The user who was unable to be added/removed from the group.
     pub fn user(&self) -> &UserId {
         &self.user
     }
+    /// Why the user was unable to be added/removed from the group.
+    pub fn kind(&self) -> GroupAccessEditErrKind {
+        self.kind
+    }
     /// The error encountered when attempting to add/remove the user from the group.
     pub fn error(&self) -> &String {
         &self.error
@@ -342,15 +595,24 @@ impl GroupAccessEditErr {
 ///
 /// Partial success is supported.
 ///
-/// Result from [group_add_members](trait.GroupOps.html#tymethod.group_add_members), [group_remove_members](trait.GroupOps.html#tymethod.group_remove_members),
-/// [group_add_admins](trait.GroupOps.html#tymethod.group_add_admins), and [group_remove_admins](trait.GroupOps.html#tymethod.group_remove_admins).
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+/// Result from [group_add_members](trait.GroupOps.html#tymethod.group_add_members) and [group_remove_members](trait.GroupOps.html#tymethod.group_remove_members).
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub struct GroupAccessEditResult {
     succeeded: Vec<UserId>,
     failed: Vec<GroupAccessEditErr>,
 }
 
 impl GroupAccessEditResult {
+    /// Record every user in `users` as failed with the same error.
+    pub(crate) fn all_failed(users: &[UserId], error: &IronOxideErr) -> GroupAccessEditResult {
+        GroupAccessEditResult {
+            succeeded: vec![],
+            failed: users
+                .iter()
+                .map(|user| GroupAccessEditErr::new(user.clone(), error.to_string()))
+                .collect(),
+        }
+    }
     /// Users whose access was successfully modified
     pub fn succeeded(&self) -> &Vec<UserId> {
         &self.succeeded
@@ -361,6 +623,286 @@ impl GroupAccessEditResult {
     }
 }
 
+/// Successful and failed changes to a group's admin list, along with the resulting admin list.
+///
+/// Partial success is supported.
+///
+/// Result from [group_add_admins](trait.GroupOps.html#tymethod.group_add_admins), [group_remove_admins](trait.GroupOps.html#tymethod.group_remove_admins),
+/// and [group_advisory_remove_admins](trait.GroupOps.html#tymethod.group_advisory_remove_admins).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GroupAdminEditResult {
+    succeeded: Vec<UserId>,
+    failed: Vec<GroupAccessEditErr>,
+    admins: Vec<UserId>,
+    caller_role_changed: bool,
+}
+
+impl GroupAdminEditResult {
+    /// The users whose change succeeded and failed, without the resulting admin list, as
+    /// `group_add_admins` and `group_remove_admins` returned before 0.26.0
+    pub fn into_access_edit_result(self) -> GroupAccessEditResult {
+        GroupAccessEditResult {
+            succeeded: self.succeeded,
+            failed: self.failed,
+        }
+    }
+    /// Users who were successfully added or removed as admins
+    pub fn succeeded(&self) -> &Vec<UserId> {
+        &self.succeeded
+    }
+    /// Errors resulting from failure to add or remove a user as an admin
+    pub fn failed(&self) -> &Vec<GroupAccessEditErr> {
+        &self.failed
+    }
+    /// Admins of the group after the change
+    pub fn admins(&self) -> &Vec<UserId> {
+        &self.admins
+    }
+    /// `true` if the calling user is no longer an admin of the group because of the change
+    pub fn caller_role_changed(&self) -> bool {
+        self.caller_role_changed
+    }
+}
+
+/// A failure when attempting to add a group as a member of another group.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GroupMemberGroupEditErr {
+    group: GroupId,
+    error: String,
+}
+impl GroupMemberGroupEditErr {
+    fn new(group: GroupId, error: String) -> GroupMemberGroupEditErr {
+        GroupMemberGroupEditErr { group, error }
+    }
+    /// The group that was unable to be added as a member.
+    pub fn group(&self) -> &GroupId {
+        &self.group
+    }
+    /// The error encountered when attempting to add the group as a member.
+    pub fn error(&self) -> &String {
+        &self.error
+    }
+}
+
+/// Successful and failed additions of groups as members of another group.
+///
+/// Partial success is supported.
+///
+/// Result from [group_add_member_groups](trait.GroupOps.html#tymethod.group_add_member_groups).
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct GroupMemberGroupsEditResult {
+    succeeded: Vec<GroupId>,
+    failed: Vec<GroupMemberGroupEditErr>,
+}
+impl GroupMemberGroupsEditResult {
+    /// Record every group in `groups` as failed with the same error.
+    pub(crate) fn all_failed(
+        groups: &[GroupId],
+        error: &IronOxideErr,
+    ) -> GroupMemberGroupsEditResult {
+        GroupMemberGroupsEditResult {
+            succeeded: vec![],
+            failed: groups
+                .iter()
+                .map(|group| GroupMemberGroupEditErr::new(group.clone(), error.to_string()))
+                .collect(),
+        }
+    }
+    /// Groups that were successfully added as members
+    pub fn succeeded(&self) -> &Vec<GroupId> {
+        &self.succeeded
+    }
+    /// Errors resulting from failure to add a group as a member
+    pub fn failed(&self) -> &Vec<GroupMemberGroupEditErr> {
+        &self.failed
+    }
+}
+
+/// Users who are members of a group directly or through nested member groups.
+///
+/// Result from [group_effective_members](trait.GroupOps.html#tymethod.group_effective_members).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GroupEffectiveMembersResult {
+    members: Vec<UserId>,
+    groups: Vec<GroupId>,
+    unlisted_groups: Vec<GroupId>,
+}
+impl GroupEffectiveMembersResult {
+    /// Users who are members of the group or of any group nested in it, sorted and without duplicates
+    pub fn members(&self) -> &Vec<UserId> {
+        &self.members
+    }
+    /// The group and every group nested in it
+    pub fn groups(&self) -> &Vec<GroupId> {
+        &self.groups
+    }
+    /// Groups whose member lists the calling user cannot view. Their members are not included in `members`.
+    pub fn unlisted_groups(&self) -> &Vec<GroupId> {
+        &self.unlisted_groups
+    }
+}
+
+/// Serializable snapshot of a group's metadata and membership.
+///
+/// Result from [group_export](trait.GroupOps.html#tymethod.group_export), and used by
+/// [group_import](trait.GroupOps.html#tymethod.group_import) to recreate the group. The group's keys are
+/// not part of the snapshot, so an imported group has new keys and can't decrypt documents shared
+/// with the original.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupSnapshot {
+    id: GroupId,
+    name: Option<GroupName>,
+    #[serde(default)]
+    description: Option<GroupDescription>,
+    #[serde(default)]
+    attributes: GroupAttributes,
+    owner: UserId,
+    admins: Vec<UserId>,
+    members: Vec<UserId>,
+    #[serde(default)]
+    member_groups: Vec<GroupId>,
+}
+impl GroupSnapshot {
+    /// ID of the group
+    pub fn id(&self) -> &GroupId {
+        &self.id
+    }
+    /// Name of the group
+    pub fn name(&self) -> Option<&GroupName> {
+        self.name.as_ref()
+    }
+    /// Description of the group
+    pub fn description(&self) -> Option<&GroupDescription> {
+        self.description.as_ref()
+    }
+    /// Key/value attributes of the group
+    pub fn attributes(&self) -> &GroupAttributes {
+        &self.attributes
+    }
+    /// The owner of the group
+    pub fn owner(&self) -> &UserId {
+        &self.owner
+    }
+    /// List of all group administrators
+    pub fn admins(&self) -> &Vec<UserId> {
+        &self.admins
+    }
+    /// List of all group members
+    pub fn members(&self) -> &Vec<UserId> {
+        &self.members
+    }
+    /// List of groups that are members of the group
+    pub fn member_groups(&self) -> &Vec<GroupId> {
+        &self.member_groups
+    }
+}
+
+/// A group recreated from a [GroupSnapshot](struct.GroupSnapshot.html).
+///
+/// Result from [group_import](trait.GroupOps.html#tymethod.group_import).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GroupImportResult {
+    group: GroupCreateResult,
+    admins: GroupAccessEditResult,
+    members: GroupAccessEditResult,
+    member_groups: GroupMemberGroupsEditResult,
+}
+impl GroupImportResult {
+    pub(crate) fn new(
+        group: GroupCreateResult,
+        admins: GroupAccessEditResult,
+        members: GroupAccessEditResult,
+        member_groups: GroupMemberGroupsEditResult,
+    ) -> GroupImportResult {
+        GroupImportResult {
+            group,
+            admins,
+            members,
+            member_groups,
+        }
+    }
+    /// The created group, as it was before the snapshot's admins, members, and member groups were added
+    pub fn group(&self) -> &GroupCreateResult {
+        &self.group
+    }
+    /// Admins from the snapshot that were and were not added to the created group. The owner isn't
+    /// included, as the group is created with them as an admin.
+    pub fn admins(&self) -> &GroupAccessEditResult {
+        &self.admins
+    }
+    /// Members from the snapshot that were and were not added to the created group
+    pub fn members(&self) -> &GroupAccessEditResult {
+        &self.members
+    }
+    /// Member groups from the snapshot that were and were not added to the created group
+    pub fn member_groups(&self) -> &GroupMemberGroupsEditResult {
+        &self.member_groups
+    }
+}
+
+/// How long a [GroupAdminRemovalApproval](struct.GroupAdminRemovalApproval.html) can be used after it is made.
+const ADMIN_REMOVAL_APPROVAL_HOURS: i64 = 1;
+
+/// A group admin's approval to remove admins from a group even if no admins would be left. The approval
+/// is advisory: it is checked by the SDK, not the IronCore service.
+///
+/// Made with [group_advisory_approve_admin_removal](trait.GroupOps.html#tymethod.group_advisory_approve_admin_removal) and
+/// passed to [group_advisory_remove_admins](trait.GroupOps.html#tymethod.group_advisory_remove_admins) by a
+/// different user. The approval is signed with the approving admin's own private key, so it can't be made
+/// by anyone else, and expires an hour after it is made. It is serializable so it can be handed between
+/// users.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupAdminRemovalApproval {
+    group_id: GroupId,
+    approver: UserId,
+    users: Vec<UserId>,
+    expires: DateTime<Utc>,
+    #[serde(with = "Base64Standard")]
+    signature: Vec<u8>,
+}
+impl GroupAdminRemovalApproval {
+    /// Group the approval is for
+    pub fn group_id(&self) -> &GroupId {
+        &self.group_id
+    }
+    /// Admin who made the approval
+    pub fn approver(&self) -> &UserId {
+        &self.approver
+    }
+    /// Users approved for removal as admins
+    pub fn users(&self) -> &Vec<UserId> {
+        &self.users
+    }
+    /// Date and time after which the approval can no longer be used
+    pub fn expires(&self) -> &DateTime<Utc> {
+        &self.expires
+    }
+    /// Bytes covered by the signature
+    fn signed_message(&self) -> ApprovalMessage {
+        let mut bytes = vec![];
+        let mut push = |field: &[u8]| {
+            bytes.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(field);
+        };
+        push(self.group_id.id().as_bytes());
+        push(self.approver.id().as_bytes());
+        self.users
+            .iter()
+            .for_each(|user| push(user.id().as_bytes()));
+        push(&self.expires.timestamp().to_be_bytes());
+        ApprovalMessage(bytes)
+    }
+}
+
+struct ApprovalMessage(Vec<u8>);
+impl recrypt::api::Hashable for ApprovalMessage {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
 // List all of the groups that the requesting user is either a member or admin of
 pub async fn list(
     auth: &RequestAuth,
@@ -377,6 +919,35 @@ pub async fn list(
     Ok(GroupListResult { result: group_list })
 }
 
+#[cfg(feature = "unstable-group-endpoints")]
+/// List the calling user's pending group invitations.
+pub async fn list_invites(auth: &RequestAuth) -> Result<GroupInviteListResult, IronOxideErr> {
+    let resp = requests::group_invite_list::group_invite_list_request(auth).await?;
+    Ok(GroupInviteListResult {
+        result: resp
+            .result
+            .into_iter()
+            .map(|invite| GroupInvite {
+                group_id: invite.group_id,
+                group_name: invite.group_name,
+                invited_by: invite.invited_by,
+                created: invite.created,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// Accept the calling user's invitation to become a member of a group.
+pub async fn accept_invite(
+    auth: &RequestAuth,
+    group_id: &GroupId,
+) -> Result<GroupMetaResult, IronOxideErr> {
+    requests::group_accept_invite::group_accept_invite_request(auth, group_id)
+        .await?
+        .try_into()
+}
+
 /// Get the keys for groups. The result should be either a failure for a specific UserId (Left) or the id with their public key (Right).
 /// The resulting lists will have the same combined size as the incoming list.
 /// Calling this with an empty `groups` list will not result in a call to the server.
@@ -411,6 +982,24 @@ pub(crate) async fn get_group_keys(
     }))
 }
 
+/// Like `get_group_keys`, but uses keys from `group_key_cache` that are younger than `config.ttl` and
+/// caches any keys that had to be fetched.
+pub(crate) async fn get_group_keys_cached(
+    auth: &RequestAuth,
+    config: &GroupKeyCachingConfig,
+    group_key_cache: &GroupKeyCache,
+    groups: &Vec<GroupId>,
+) -> Result<(Vec<GroupId>, Vec<WithKey<GroupId>>), IronOxideErr> {
+    group_key_cache
+        .get_or_fetch(
+            config.ttl,
+            config.max_entries,
+            groups,
+            |missing| async move { get_group_keys(auth, &missing).await },
+        )
+        .await
+}
+
 fn check_user_mismatch<T: Eq + std::hash::Hash + std::fmt::Debug, X>(
     desired_users: &[T],
     found_users: HashMap<T, X>,
@@ -454,9 +1043,9 @@ fn collect_admin_and_member_info<CR: rand::CryptoRng + rand::RngCore>(
         .for_each(|(id, user_pub_key)| {
             if members_set.contains(&id) {
                 let maybe_transform_key = recrypt.generate_transform_key(
-                    &group_priv_key.clone(),
+                    &group_priv_key,
                     &user_pub_key.clone().into(),
-                    &signing_key.into(),
+                    signing_key.recrypt_key(),
                 );
                 match maybe_transform_key {
                     Ok(member_trans_key) => member_info.push(Ok((
@@ -496,6 +1085,8 @@ pub async fn group_create<CR: rand::CryptoRng + rand::RngCore>(
     members: Vec<UserId>,
     users_to_lookup: &Vec<UserId>,
     needs_rotation: bool,
+    description: Option<GroupDescription>,
+    attributes: GroupAttributes,
 ) -> Result<GroupCreateResult, IronOxideErr> {
     let user_ids_and_keys = user_api::user_key_list(auth, users_to_lookup).await?;
     // this will occur when one of the UserIds cannot be found
@@ -532,7 +1123,7 @@ pub async fn group_create<CR: rand::CryptoRng + rand::RngCore>(
             let encrypted_group_key = recrypt.encrypt(
                 &plaintext,
                 &admin_pub_key.clone().into(),
-                &auth.signing_private_key().into(),
+                auth.signing_private_key().recrypt_key(),
             );
             encrypted_group_key
                 .map_err(|e| e.into())
@@ -547,6 +1138,7 @@ pub async fn group_create<CR: rand::CryptoRng + rand::RngCore>(
         })
         .collect::<Result<Vec<_>, _>>()?;
 
+    internal::stop_if_validate_only(SdkOperation::GroupCreate, "users", Vec::new)?;
     let resp = requests::group_create::group_create(
         auth,
         group_id,
@@ -556,6 +1148,8 @@ pub async fn group_create<CR: rand::CryptoRng + rand::RngCore>(
         group_admins,
         maybe_group_members,
         needs_rotation,
+        description,
+        attributes,
     )
     .await?;
 
@@ -585,20 +1179,22 @@ impl GroupUpdatePrivateKeyResult {
 fn collect_group_admin_keys(
     admin_info: Vec<(WithKey<UserId>, EncryptedValue)>,
 ) -> Result<Vec<GroupAdmin>, IronOxideErr> {
-    admin_info
-        .into_iter()
-        .map(|(key_and_id, encrypted_admin_key)| {
-            encrypted_admin_key
-                .try_into()
-                .map(|encrypted_msg| GroupAdmin {
-                    user: User {
-                        user_id: key_and_id.id,
-                        user_master_public_key: key_and_id.public_key.into(),
-                    },
-                    encrypted_msg,
-                })
+    admin_info.into_iter().map(to_group_admin).collect()
+}
+
+/// Maps a single successful result of `transform::encrypt_to_with_key()` into a GroupAdmin
+fn to_group_admin(
+    (key_and_id, encrypted_admin_key): (WithKey<UserId>, EncryptedValue),
+) -> Result<GroupAdmin, IronOxideErr> {
+    encrypted_admin_key
+        .try_into()
+        .map(|encrypted_msg| GroupAdmin {
+            user: User {
+                user_id: key_and_id.id,
+                user_master_public_key: key_and_id.public_key.into(),
+            },
+            encrypted_msg,
         })
-        .collect()
 }
 
 /// Decrypts the group's private key, generates a new private key and plaintext,
@@ -607,20 +1203,17 @@ fn generate_aug_and_admins<CR: rand::CryptoRng + rand::RngCore>(
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
     device_signing_key_pair: &DeviceSigningKeyPair,
     encrypted_group_key: EncryptedValue,
-    device_private_key: &PrivateKey,
+    device_private_key: &DevicePrivateKey,
     admin_map: HashMap<UserId, PublicKey>,
 ) -> Result<(AugmentationFactor, Vec<(WithKey<UserId>, EncryptedValue)>), IronOxideErr> {
-    let (_, old_group_private_key) = transform::decrypt_as_private_key(
-        recrypt,
-        encrypted_group_key,
-        device_private_key.recrypt_key(),
-    )?;
+    let (_, old_group_private_key) =
+        transform::decrypt_as_private_key(recrypt, encrypted_group_key, device_private_key)?;
     let (new_plaintext, aug_factor) =
         internal::gen_plaintext_and_aug_with_retry(recrypt, &old_group_private_key.into())?;
     let (errors, updated_group_admins) = transform::encrypt_to_with_key(
         recrypt,
         &new_plaintext,
-        &device_signing_key_pair.into(),
+        device_signing_key_pair.recrypt_key(),
         admin_map
             .into_iter()
             .map(|(id, public_key)| WithKey { id, public_key })
@@ -644,7 +1237,7 @@ pub async fn group_rotate_private_key<CR: rand::CryptoRng + rand::RngCore>(
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
     auth: &RequestAuth,
     group_id: &GroupId,
-    device_private_key: &PrivateKey,
+    device_private_key: &DevicePrivateKey,
 ) -> Result<GroupUpdatePrivateKeyResult, IronOxideErr> {
     let group_info = group_get_request(auth, group_id).await?;
     let encrypted_group_key = group_info
@@ -684,6 +1277,58 @@ pub async fn get_metadata(
     resp.try_into()
 }
 
+/// Take a snapshot of a group's metadata and membership. The calling user must be an admin of the group.
+pub async fn group_export(
+    auth: &RequestAuth,
+    group_id: &GroupId,
+) -> Result<GroupSnapshot, IronOxideErr> {
+    let group = get_metadata(auth, group_id).await?;
+    match (
+        group.is_admin,
+        group.owner,
+        group.admin_list,
+        group.member_list,
+    ) {
+        (true, Some(owner), Some(admins), Some(members)) => Ok(GroupSnapshot {
+            id: group.id,
+            name: group.name,
+            description: group.description,
+            attributes: group.attributes,
+            owner,
+            admins,
+            members,
+            member_groups: group.member_group_list.unwrap_or_default(),
+        }),
+        _ => Err(IronOxideErr::NotGroupAdmin(group_id.clone())),
+    }
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// List the documents that have been granted to a group. The calling user must be an admin of the group.
+pub async fn group_list_documents(
+    auth: &RequestAuth,
+    group_id: &GroupId,
+) -> Result<GroupDocumentListResult, IronOxideErr> {
+    let group = get_metadata(auth, group_id).await?;
+    if !group.is_admin {
+        return Err(IronOxideErr::NotGroupAdmin(group_id.clone()));
+    }
+    let resp = requests::group_document_list::group_document_list_request(auth, group_id).await?;
+    Ok(GroupDocumentListResult {
+        group_id: group.id,
+        result: resp
+            .result
+            .into_iter()
+            .map(|doc| GroupDocument {
+                id: doc.id,
+                name: doc.name,
+                created: doc.created,
+                last_updated: doc.updated,
+            })
+            .collect(),
+    })
+}
+
 ///Delete the provided group given its ID
 pub async fn group_delete(auth: &RequestAuth, group_id: &GroupId) -> Result<GroupId, IronOxideErr> {
     requests::group_delete::group_delete_request(auth, group_id)
@@ -691,13 +1336,23 @@ pub async fn group_delete(auth: &RequestAuth, group_id: &GroupId) -> Result<Grou
         .map(|resp| resp.id)
 }
 
+/// Maximum number of users sent to the webservice in a single request to add or remove group members or admins.
+const GROUP_ACCESS_EDIT_CHUNK_SIZE: usize = 250;
+/// Maximum number of requests in flight at once when adding or removing group members or admins.
+const GROUP_ACCESS_EDIT_CONCURRENCY: usize = 4;
+
 /// Add the users as members of a group.
 ///
+/// Large lists of users are split into chunks of `GROUP_ACCESS_EDIT_CHUNK_SIZE`, which are sent with at most
+/// `GROUP_ACCESS_EDIT_CONCURRENCY` requests in flight at once.
+///
 /// # Arguments
 /// - `recrypt` - recrypt instance to use for cryptographic operations
 /// - `auth` - Auth context details for making API requests. The user associated with this device must be an admin of the group.
 /// - `group_id` - unique id for the group within the segment.
 /// - `users` - The list of users that will be added to the group as members.
+/// - `mode` - Whether the users become members right away or are invited. The transform keys for invited users are
+///   generated now, so no admin needs to be involved when they accept.
 ///
 /// # Returns
 /// GroupAccessEditResult, which contains all the users that were added. It also contains the users that were not added and
@@ -705,9 +1360,10 @@ pub async fn group_delete(auth: &RequestAuth, group_id: &GroupId) -> Result<Grou
 pub async fn group_add_members<CR: rand::CryptoRng + rand::RngCore>(
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
     auth: &RequestAuth,
-    device_private_key: &PrivateKey,
+    device_private_key: &DevicePrivateKey,
     group_id: &GroupId,
     users: &Vec<UserId>,
+    mode: MemberAddMode,
 ) -> Result<GroupAccessEditResult, IronOxideErr> {
     let (group_get, (mut acc_fails, successes)) =
         try_join!(get_metadata(auth, group_id), get_user_keys(auth, users))?;
@@ -715,10 +1371,21 @@ pub async fn group_add_members<CR: rand::CryptoRng + rand::RngCore>(
     let encrypted_group_key = group_get
         .encrypted_private_key
         .ok_or_else(|| IronOxideErr::NotGroupAdmin(group_id.clone()))?;
+    let operation = match mode {
+        MemberAddMode::Add => SdkOperation::GroupAddMembers,
+        #[cfg(feature = "unstable-group-endpoints")]
+        MemberAddMode::Invite => SdkOperation::GroupInviteMembers,
+    };
+    internal::stop_if_validate_only(operation, "users", || {
+        acc_fails
+            .iter()
+            .map(|e| format!("'{}': {}", e.user().id(), e.error()))
+            .collect()
+    })?;
     let (plaintext, _) = transform::decrypt_as_private_key(
         recrypt,
         encrypted_group_key.try_into()?,
-        device_private_key.recrypt_key(),
+        device_private_key,
     )?;
     let group_private_key = recrypt.derive_private_key(&plaintext);
     let recrypt_schnorr_sig = recrypt.schnorr_sign(
@@ -726,35 +1393,221 @@ pub async fn group_add_members<CR: rand::CryptoRng + rand::RngCore>(
         &group_get.group_master_public_key.into(),
         group_id,
     );
-    let (mut transform_fails, transform_success) = generate_transform_for_keys(
+    let schnorr_sig = SchnorrSignature(recrypt_schnorr_sig);
+    //Now actually add the members, generating the transform keys for each chunk just before it's sent, so
+    //no more keys are generated once the caller stops waiting for the result.
+    let chunks: Vec<Vec<_>> = successes
+        .into_iter()
+        .chunks(GROUP_ACCESS_EDIT_CHUNK_SIZE)
+        .into_iter()
+        .map(Iterator::collect)
+        .collect();
+    let sent_chunks = futures::stream::iter(chunks)
+        .map(|chunk: Vec<WithKey<UserId>>| {
+            let (transform_fails, transforms) = generate_transform_for_keys(
+                recrypt,
+                &group_private_key,
+                auth.signing_private_key().recrypt_key(),
+                chunk,
+            );
+            let chunk_users: Vec<UserId> = transforms
+                .iter()
+                .map(|(user_id, _, _)| user_id.clone())
+                .collect();
+            let request = requests::group_add_member::group_add_member_request(
+                auth,
+                group_id,
+                transforms
+                    .into_iter()
+                    .map(|(user_id, pub_key, transform)| {
+                        (user_id, pub_key.into(), transform.into())
+                    })
+                    .collect(),
+                schnorr_sig.clone(),
+                mode,
+            );
+            async move {
+                // a chunk whose transform keys all failed has nothing to send
+                let response = match chunk_users.len() {
+                    0 => None,
+                    _ => Some(request.await),
+                };
+                (transform_fails, chunk_users, response)
+            }
+        })
+        .buffered(GROUP_ACCESS_EDIT_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+    //acc_fails is currently the key fetch failures, to which the transform generation fails are added.
+    let mut chunk_results = Vec::with_capacity(sent_chunks.len());
+    for (mut transform_fails, chunk_users, response) in sent_chunks {
+        acc_fails.append(&mut transform_fails);
+        if let Some(response) = response {
+            chunk_results.push((chunk_users, response));
+        }
+    }
+    merge_chunked_access_edit_results(acc_fails, chunk_results)
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// Add groups as members of a group. Members of the added groups become members of the group, and
+/// documents shared with the group can be decrypted by them.
+///
+/// A group that is the parent group itself, or that already contains the parent group through its own
+/// member groups, is not added, as that would create a membership cycle.
+///
+/// # Arguments
+/// - `recrypt` - recrypt instance to use for cryptographic operations
+/// - `auth` - Auth context details for making API requests. The user associated with this device must be an admin of the group.
+/// - `group_id` - unique id for the group within the segment.
+/// - `groups` - The list of groups that will be added to the group as members.
+pub async fn group_add_member_groups<CR: rand::CryptoRng + rand::RngCore>(
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    auth: &RequestAuth,
+    device_private_key: &DevicePrivateKey,
+    group_id: &GroupId,
+    groups: &[GroupId],
+) -> Result<GroupMemberGroupsEditResult, IronOxideErr> {
+    let group_get = get_metadata(auth, group_id).await?;
+    let encrypted_group_key = group_get
+        .encrypted_private_key
+        .ok_or_else(|| IronOxideErr::NotGroupAdmin(group_id.clone()))?;
+    let (plaintext, _) = transform::decrypt_as_private_key(
         recrypt,
+        encrypted_group_key.try_into()?,
+        device_private_key,
+    )?;
+    let group_private_key = recrypt.derive_private_key(&plaintext);
+    let schnorr_sig = SchnorrSignature(recrypt.schnorr_sign(
         &group_private_key,
-        &auth.signing_private_key().into(),
-        successes,
-    );
-    acc_fails.append(&mut transform_fails);
-    let (schnorr_sig, acc_fails, transforms_to_send) = (
-        SchnorrSignature(recrypt_schnorr_sig),
-        acc_fails,
-        transform_success,
-    );
-    //Now actually add the members that we have transform keys for.
-    //acc_fails is currently the transform generation fails and the key fetch failures.
-    requests::group_add_member::group_add_member_request(
+        &group_get.group_master_public_key.into(),
+        group_id,
+    ));
+
+    let child_trees =
+        futures::future::join_all(groups.iter().unique().map(|child| {
+            walk_member_groups(child, |id| async move { get_metadata(auth, &id).await })
+        }))
+        .await;
+    let (mut failed, member_groups): (Vec<_>, Vec<_>) = groups
+        .iter()
+        .unique()
+        .zip(child_trees)
+        .partition_map(|(child, tree)| match tree {
+            Err(e) => Either::Left(GroupMemberGroupEditErr::new(child.clone(), e.to_string())),
+            Ok(tree) if tree.iter().any(|nested| nested.id() == group_id) => {
+                Either::Left(GroupMemberGroupEditErr::new(
+                    child.clone(),
+                    "Adding this group would create a membership cycle.".to_string(),
+                ))
+            }
+            Ok(tree) => {
+                let child_public_key = tree[0].group_master_public_key.clone();
+                match recrypt.generate_transform_key(
+                    &group_private_key,
+                    &child_public_key.clone().into(),
+                    auth.signing_private_key().recrypt_key(),
+                ) {
+                    Ok(transform_key) => {
+                        Either::Right(requests::group_add_member_group::GroupMemberGroup {
+                            group_id: child.clone(),
+                            transform_key: TransformKey(transform_key).into(),
+                            group_master_public_key: child_public_key.into(),
+                        })
+                    }
+                    Err(_) => Either::Left(GroupMemberGroupEditErr::new(
+                        child.clone(),
+                        "Transform key could not be generated.".to_string(),
+                    )),
+                }
+            }
+        });
+    if member_groups.is_empty() {
+        return Ok(GroupMemberGroupsEditResult {
+            succeeded: vec![],
+            failed,
+        });
+    }
+    let resp = requests::group_add_member_group::group_add_member_group_request(
         auth,
         group_id,
-        transforms_to_send
-            .into_iter()
-            .map(|(user_id, pub_key, transform)| (user_id, pub_key.into(), transform.into()))
-            .collect(),
+        member_groups,
         schnorr_sig,
     )
-    .await
-    .map(|response| group_access_api_response_to_result(acc_fails, response))
+    .await?;
+    failed.extend(
+        resp.failed_ids
+            .into_iter()
+            .map(|fail| GroupMemberGroupEditErr::new(fail.group_id, fail.error_message)),
+    );
+    Ok(GroupMemberGroupsEditResult {
+        succeeded: resp.succeeded_ids.into_iter().map(|s| s.group_id).collect(),
+        failed,
+    })
+}
+
+/// Expand a group's membership through all of its nested member groups.
+///
+/// # Arguments
+/// - `auth` - Auth context details for making API requests.
+/// - `group_id` - unique id for the group within the segment.
+pub async fn group_effective_members(
+    auth: &RequestAuth,
+    group_id: &GroupId,
+) -> Result<GroupEffectiveMembersResult, IronOxideErr> {
+    let tree =
+        walk_member_groups(group_id, |id| async move { get_metadata(auth, &id).await }).await?;
+    Ok(collect_effective_members(&tree))
+}
+
+/// Get `root` and every group nested in it, breadth first. Each group is fetched once, so membership
+/// cycles terminate. The first group returned is always `root`.
+async fn walk_member_groups<F, Fut>(
+    root: &GroupId,
+    get_group: F,
+) -> Result<Vec<GroupGetResult>, IronOxideErr>
+where
+    F: Fn(GroupId) -> Fut,
+    Fut: std::future::Future<Output = Result<GroupGetResult, IronOxideErr>>,
+{
+    let mut visited: HashSet<GroupId> = HashSet::new();
+    let mut walked = vec![];
+    let mut frontier = vec![root.clone()];
+    while !frontier.is_empty() {
+        visited.extend(frontier.iter().cloned());
+        let level = futures::future::try_join_all(frontier.into_iter().map(&get_group)).await?;
+        frontier = level
+            .iter()
+            .flat_map(|group| group.member_group_list().cloned().unwrap_or_default())
+            .filter(|id| !visited.contains(id))
+            .unique()
+            .collect();
+        walked.extend(level);
+    }
+    Ok(walked)
+}
+
+/// Combine the member lists of a walked group tree.
+fn collect_effective_members(tree: &[GroupGetResult]) -> GroupEffectiveMembersResult {
+    let (listed, unlisted): (Vec<_>, Vec<_>) =
+        tree.iter().partition(|group| group.member_list().is_some());
+    GroupEffectiveMembersResult {
+        members: listed
+            .iter()
+            .flat_map(|group| group.member_list().cloned().unwrap_or_default())
+            .sorted_by(|a, b| a.id().cmp(b.id()))
+            .dedup()
+            .collect(),
+        groups: tree.iter().map(|group| group.id().clone()).collect(),
+        unlisted_groups: unlisted.iter().map(|group| group.id().clone()).collect(),
+    }
 }
 
 /// Add the users as admins of a group.
 ///
+/// Large lists of users are split into chunks the same way as in `group_add_members`. A user whose key cannot be
+/// fetched or encrypted to is reported as failed without affecting the others.
+///
 /// # Arguments
 /// - `recrypt` - recrypt instance to use for cryptographic operations
 /// - `auth` - Auth context details for making API requests. The user associated with this device must be an admin of the group.
@@ -762,25 +1615,39 @@ pub async fn group_add_members<CR: rand::CryptoRng + rand::RngCore>(
 /// - `users` - The list of users that will be added to the group as admins.
 ///
 /// # Returns
-/// GroupAccessEditResult, which contains all the users that were added. It also contains the users that were not added and
+/// GroupAdminEditResult, which contains all the users that were added. It also contains the users that were not added and
 ///   the reason they were not.
 pub async fn group_add_admins<CR: rand::CryptoRng + rand::RngCore>(
     recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
     auth: &RequestAuth,
-    device_private_key: &PrivateKey,
+    device_private_key: &DevicePrivateKey,
     group_id: &GroupId,
     users: &Vec<UserId>,
-) -> Result<GroupAccessEditResult, IronOxideErr> {
+) -> Result<GroupAdminEditResult, IronOxideErr> {
     let (group_get, (mut acc_fails, successes)) =
         try_join!(get_metadata(auth, group_id), get_user_keys(auth, users))?;
     //At this point the acc_fails list is just the key fetch failures. We append to it as we go.
     let encrypted_group_key = group_get
         .encrypted_private_key
         .ok_or_else(|| IronOxideErr::NotGroupAdmin(group_id.clone()))?;
+    let admins_before = group_get.admin_list.unwrap_or_default();
+    let (mut already_admins, successes): (Vec<_>, Vec<_>) =
+        successes.into_iter().partition_map(|user_key| {
+            if admins_before.contains(&user_key.id) {
+                Either::Left(GroupAccessEditErr::with_kind(
+                    user_key.id,
+                    GroupAccessEditErrKind::AlreadyAdmin,
+                    "User is already an admin of the group".to_string(),
+                ))
+            } else {
+                Either::Right(user_key)
+            }
+        });
+    acc_fails.append(&mut already_admins);
     let (plaintext, _) = transform::decrypt_as_private_key(
         recrypt,
         encrypted_group_key.try_into()?,
-        device_private_key.recrypt_key(),
+        device_private_key,
     )?;
     let private_group_key = recrypt.derive_private_key(&plaintext);
     let recrypt_schnorr_sig = recrypt.schnorr_sign(
@@ -791,31 +1658,57 @@ pub async fn group_add_admins<CR: rand::CryptoRng + rand::RngCore>(
     let (recrypt_errors, transform_success) = transform::encrypt_to_with_key(
         recrypt,
         &plaintext,
-        &auth.signing_private_key().into(),
+        auth.signing_private_key().recrypt_key(),
         successes,
     );
     let mut transform_fails = recrypt_errors
         .into_iter()
-        .map(|(WithKey { id: user_id, .. }, _)| GroupAccessEditErr {
-            user: user_id,
-            error: "Transform key could not be generated.".to_string(),
+        .map(|(WithKey { id: user_id, .. }, _)| {
+            GroupAccessEditErr::new(user_id, "Transform key could not be generated.".to_string())
         })
         .collect();
     acc_fails.append(&mut transform_fails);
-    let (schnorr_sig, acc_fails, admin_keys_to_send) = (
-        SchnorrSignature(recrypt_schnorr_sig),
-        acc_fails,
-        transform_success,
-    );
-    //acc_fails is currently the transform generation fails and the key fetch failures.
-    requests::group_add_admin::group_add_admin_request(
-        auth,
-        group_id,
-        collect_group_admin_keys(admin_keys_to_send)?,
-        schnorr_sig,
-    )
-    .await
-    .map(|response| group_access_api_response_to_result(acc_fails, response))
+    let (mut encoding_fails, admins_to_send): (Vec<_>, Vec<_>) =
+        transform_success.into_iter().partition_map(|admin_key| {
+            let user = admin_key.0.id.clone();
+            match to_group_admin(admin_key) {
+                Ok(admin) => Either::Right(admin),
+                Err(e) => Either::Left(GroupAccessEditErr::new(user, e.to_string())),
+            }
+        });
+    acc_fails.append(&mut encoding_fails);
+    let schnorr_sig = SchnorrSignature(recrypt_schnorr_sig);
+    //acc_fails is currently the transform generation fails, the key fetch failures, and the encoding failures.
+    let chunks: Vec<Vec<GroupAdmin>> = admins_to_send
+        .into_iter()
+        .chunks(GROUP_ACCESS_EDIT_CHUNK_SIZE)
+        .into_iter()
+        .map(Iterator::collect)
+        .collect();
+    let chunk_results = futures::stream::iter(chunks)
+        .map(|chunk| {
+            let chunk_users: Vec<UserId> = chunk
+                .iter()
+                .map(|admin| admin.user.user_id.clone())
+                .collect();
+            requests::group_add_admin::group_add_admin_request(
+                auth,
+                group_id,
+                chunk,
+                schnorr_sig.clone(),
+            )
+            .map(|response| (chunk_users, response))
+        })
+        .buffered(GROUP_ACCESS_EDIT_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+    let edit = merge_chunked_access_edit_results(acc_fails, chunk_results)?;
+    Ok(admin_edit_result(
+        admins_before,
+        auth.account_id(),
+        edit,
+        true,
+    ))
 }
 
 ///This is a thin wrapper that's just mapping the errors into the type we need for add member and add admin
@@ -826,7 +1719,13 @@ async fn get_user_keys(
     let (failed_ids, succeeded_ids) = user_api::get_user_keys(auth, users).await?;
     let failed_ids_result = failed_ids
         .into_iter()
-        .map(|user| GroupAccessEditErr::new(user, "User does not exist".to_string()))
+        .map(|user| {
+            GroupAccessEditErr::with_kind(
+                user,
+                GroupAccessEditErrKind::UserDoesNotExist,
+                "User does not exist".to_string(),
+            )
+        })
         .collect::<Vec<_>>();
     Ok((failed_ids_result, succeeded_ids))
 }
@@ -852,6 +1751,76 @@ fn group_access_api_response_to_result(
     }
 }
 
+/// Merge the responses of a group access edit that was split across multiple requests.
+///
+/// Users in a chunk whose request failed outright are reported as failed with that request's error. If every
+/// request failed, the first error is returned instead, matching the behavior of an unchunked edit.
+fn merge_chunked_access_edit_results(
+    other_fails: Vec<GroupAccessEditErr>,
+    chunk_results: Vec<(Vec<UserId>, Result<GroupUserEditResponse, IronOxideErr>)>,
+) -> Result<GroupAccessEditResult, IronOxideErr> {
+    let all_failed =
+        !chunk_results.is_empty() && chunk_results.iter().all(|(_, resp)| resp.is_err());
+    chunk_results.into_iter().try_fold(
+        GroupAccessEditResult {
+            succeeded: vec![],
+            failed: other_fails,
+        },
+        |mut acc, (chunk_users, chunk_result)| match chunk_result {
+            Ok(resp) => {
+                let mut chunk_edit = group_access_api_response_to_result(vec![], resp);
+                acc.succeeded.append(&mut chunk_edit.succeeded);
+                acc.failed.append(&mut chunk_edit.failed);
+                Ok(acc)
+            }
+            Err(e) if all_failed => Err(e),
+            Err(e) => {
+                let kind = match e {
+                    IronOxideErr::RequestServerErrors {
+                        http_status: Some(403),
+                        ..
+                    } => GroupAccessEditErrKind::PermissionDenied,
+                    _ => GroupAccessEditErrKind::Other,
+                };
+                let error = e.to_string();
+                acc.failed.extend(
+                    chunk_users
+                        .into_iter()
+                        .map(|user| GroupAccessEditErr::with_kind(user, kind, error.clone())),
+                );
+                Ok(acc)
+            }
+        },
+    )
+}
+
+/// Build the result of an admin list change from the admins before the change and the users whose change succeeded.
+fn admin_edit_result(
+    admins_before: Vec<UserId>,
+    caller: &UserId,
+    edit: GroupAccessEditResult,
+    added: bool,
+) -> GroupAdminEditResult {
+    let admins: Vec<UserId> = if added {
+        admins_before
+            .into_iter()
+            .chain(edit.succeeded.iter().cloned())
+            .unique()
+            .collect()
+    } else {
+        admins_before
+            .into_iter()
+            .filter(|admin| !edit.succeeded.contains(admin))
+            .collect()
+    };
+    GroupAdminEditResult {
+        caller_role_changed: !added && edit.succeeded.contains(caller),
+        succeeded: edit.succeeded,
+        failed: edit.failed,
+        admins,
+    }
+}
+
 // Update a group's name. Value can be updated to either a new name with a Some or the name value can be cleared out
 // by providing a None.
 pub async fn update_group_name(
@@ -863,25 +1832,231 @@ pub async fn update_group_name(
     resp.try_into()
 }
 
+#[cfg(feature = "unstable-group-endpoints")]
+// Update a group's description. Value can be updated to either a new description with a Some or the description
+// can be cleared out by providing a None.
+pub async fn update_group_description(
+    auth: &RequestAuth,
+    id: &GroupId,
+    description: Option<&GroupDescription>,
+) -> Result<GroupMetaResult, IronOxideErr> {
+    let resp =
+        requests::group_update::group_update_description_request(auth, id, description).await?;
+    resp.try_into()
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+// Replace all of a group's attributes. Providing empty attributes clears them.
+pub async fn update_group_attributes(
+    auth: &RequestAuth,
+    id: &GroupId,
+    attributes: &GroupAttributes,
+) -> Result<GroupMetaResult, IronOxideErr> {
+    let resp =
+        requests::group_update::group_update_attributes_request(auth, id, attributes).await?;
+    resp.try_into()
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+/// Transfer ownership of a group to `new_owner`. The calling user must be an admin of the group, and the
+/// new owner must already be an admin.
+pub async fn transfer_group_ownership(
+    auth: &RequestAuth,
+    id: &GroupId,
+    new_owner: &UserId,
+) -> Result<GroupMetaResult, IronOxideErr> {
+    let group = get_metadata(auth, id).await?;
+    let admins = group
+        .admin_list
+        .ok_or_else(|| IronOxideErr::NotGroupAdmin(id.clone()))?;
+    if !admins.contains(new_owner) {
+        return Err(IronOxideErr::ValidationError(
+            "new_owner".to_string(),
+            format!("'{}' must be an admin of the group", new_owner.id()),
+        ));
+    }
+    let resp = requests::group_update::group_update_owner_request(auth, id, new_owner).await?;
+    resp.try_into()
+}
+
+/// Approve removing `users` as admins of a group, even if that would leave it without any. The calling
+/// user must be an admin of the group. The approval is signed with the calling user's master private key,
+/// which is decrypted with `password`.
+#[cfg(not(feature = "disable-password-escrow"))]
+pub async fn group_advisory_approve_admin_removal<CR: rand::CryptoRng + rand::RngCore>(
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    auth: &RequestAuth,
+    password: internal::Password,
+    derivation: user_api::PasswordDerivation<'_>,
+    group_id: &GroupId,
+    users: &[UserId],
+) -> Result<GroupAdminRemovalApproval, IronOxideErr> {
+    let group_get = get_metadata(auth, group_id).await?;
+    let is_admin = group_get
+        .admin_list
+        .map_or(false, |admins| admins.contains(auth.account_id()));
+    if !is_admin {
+        return Err(IronOxideErr::NotGroupAdmin(group_id.clone()));
+    }
+    let user_key_pair = user_api::user_master_key_pair(recrypt, password, derivation, auth).await?;
+    let mut approval = GroupAdminRemovalApproval {
+        group_id: group_id.clone(),
+        approver: auth.account_id().clone(),
+        users: users
+            .iter()
+            .cloned()
+            .sorted_by(|a, b| a.id().cmp(b.id()))
+            .dedup()
+            .collect(),
+        expires: Utc::now() + chrono::Duration::hours(ADMIN_REMOVAL_APPROVAL_HOURS),
+        signature: vec![],
+    };
+    let signature = recrypt.schnorr_sign(
+        user_key_pair.private_key().recrypt_key(),
+        &user_key_pair.public_key().into(),
+        &approval.signed_message(),
+    );
+    approval.signature = signature.bytes().to_vec();
+    Ok(approval)
+}
+
+/// Remove the provided users as admins of the group.
+///
+/// If the group's admin list is visible and none of its admins would be left, this fails with
+/// `WouldOrphanGroup` unless `approval` is a valid approval made by a different admin of the group for
+/// (at least) these users. The approval's signature is checked against the approver's public key.
+pub async fn group_remove_admins<CR: rand::CryptoRng + rand::RngCore>(
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    auth: &RequestAuth,
+    group_id: &GroupId,
+    users: &Vec<UserId>,
+    approval: Option<&GroupAdminRemovalApproval>,
+) -> Result<GroupAdminEditResult, IronOxideErr> {
+    let group_get = get_metadata(auth, group_id).await?;
+    let admins_before = group_get
+        .admin_list()
+        .cloned()
+        .ok_or_else(|| IronOxideErr::NotGroupAdmin(group_id.clone()))?;
+    let would_orphan = admins_before.iter().all(|admin| users.contains(admin));
+    if would_orphan {
+        match approval {
+            None => return Err(IronOxideErr::WouldOrphanGroup(group_id.clone())),
+            Some(approval) => {
+                let (_, approver_keys) =
+                    user_api::get_user_keys(auth, &vec![approval.approver().clone()]).await?;
+                let approver_key = approver_keys
+                    .into_iter()
+                    .next()
+                    .map(|WithKey { public_key, .. }| public_key)
+                    .ok_or_else(|| {
+                        IronOxideErr::ValidationError(
+                            "approval".to_string(),
+                            "approver does not exist".to_string(),
+                        )
+                    })?;
+                verify_admin_removal_approval(
+                    recrypt,
+                    approval,
+                    group_id,
+                    auth.account_id(),
+                    users,
+                    &admins_before,
+                    &approver_key,
+                    Utc::now(),
+                )?
+            }
+        }
+    }
+    let (not_admins, to_remove): (Vec<_>, Vec<_>) = users.iter().cloned().partition_map(|user| {
+        if admins_before.contains(&user) {
+            Either::Right(user)
+        } else {
+            Either::Left(GroupAccessEditErr::with_kind(
+                user,
+                GroupAccessEditErrKind::NotAMember,
+                "User is not an admin of the group".to_string(),
+            ))
+        }
+    });
+    let mut edit = group_remove_entity(auth, group_id, &to_remove, GroupEntity::Admin).await?;
+    edit.failed.splice(0..0, not_admins);
+    Ok(admin_edit_result(
+        admins_before,
+        auth.account_id(),
+        edit,
+        false,
+    ))
+}
+
+/// Check that `approval` allows `caller` to remove `users` as admins of `group_id` at time `now`. The
+/// approval must have been made by one of `admins` other than `caller`, and signed with the private key
+/// of `approver_public_key`, the approver's public key.
+#[allow(clippy::too_many_arguments)]
+fn verify_admin_removal_approval<CR: rand::CryptoRng + rand::RngCore>(
+    recrypt: &Recrypt<Sha256, Ed25519, RandomBytes<CR>>,
+    approval: &GroupAdminRemovalApproval,
+    group_id: &GroupId,
+    caller: &UserId,
+    users: &[UserId],
+    admins: &[UserId],
+    approver_public_key: &PublicKey,
+    now: DateTime<Utc>,
+) -> Result<(), IronOxideErr> {
+    let invalid =
+        |reason: &str| IronOxideErr::ValidationError("approval".to_string(), reason.to_string());
+    if approval.group_id() != group_id {
+        Err(invalid("approval is for a different group"))
+    } else if approval.approver() == caller {
+        Err(invalid("approval must be made by a different admin"))
+    } else if !admins.contains(approval.approver()) {
+        Err(invalid("approval was not made by an admin of the group"))
+    } else if !users.iter().all(|user| approval.users().contains(user)) {
+        Err(invalid("approval does not cover every user being removed"))
+    } else if approval.expires() <= &now {
+        Err(invalid("approval has expired"))
+    } else if approval.signature.len() != recrypt::api::SchnorrSignature::ENCODED_SIZE_BYTES {
+        Err(invalid("approval signature is malformed"))
+    } else {
+        let mut signature = [0u8; recrypt::api::SchnorrSignature::ENCODED_SIZE_BYTES];
+        signature.copy_from_slice(&approval.signature);
+        if recrypt.schnorr_verify(
+            &approver_public_key.clone().into(),
+            None,
+            &approval.signed_message(),
+            recrypt::api::SchnorrSignature::new(signature),
+        ) {
+            Ok(())
+        } else {
+            Err(invalid("approval was not signed by the approver"))
+        }
+    }
+}
+
 /// Remove the provided list of users as either members or admins (based on the entity_type) from the provided group ID. The
 /// request and response format of these two operations are identical which is why we have a single method for it.
+///
+/// Large lists of users are split into chunks the same way as in `group_add_members`.
 pub async fn group_remove_entity(
     auth: &RequestAuth,
     id: &GroupId,
     users: &Vec<UserId>,
     entity_type: GroupEntity,
 ) -> Result<GroupAccessEditResult, IronOxideErr> {
-    let GroupUserEditResponse {
-        succeeded_ids,
-        failed_ids,
-    } = requests::group_remove_entity::remove_entity_request(auth, id, users, entity_type).await?;
-    Ok(GroupAccessEditResult {
-        succeeded: succeeded_ids.into_iter().map(|user| user.user_id).collect(),
-        failed: failed_ids
-            .into_iter()
-            .map(|fail| GroupAccessEditErr::new(fail.user_id, fail.error_message))
-            .collect(),
-    })
+    let chunks: Vec<Vec<UserId>> = users
+        .chunks(GROUP_ACCESS_EDIT_CHUNK_SIZE)
+        .map(<[UserId]>::to_vec)
+        .collect();
+    let chunk_results = futures::stream::iter(chunks)
+        .map(|chunk| async move {
+            let response =
+                requests::group_remove_entity::remove_entity_request(auth, id, &chunk, entity_type)
+                    .await;
+            (chunk, response)
+        })
+        .buffered(GROUP_ACCESS_EDIT_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+    merge_chunked_access_edit_results(vec![], chunk_results)
 }
 
 ///A stripped down version of this could be put in `transform.rs`, but since it was inconvenient to do the type mapping afterwards
@@ -947,9 +2122,194 @@ pub(crate) mod tests {
             created,
             updated,
             needs_rotation,
+            description: None,
+            attributes: GroupAttributes::default(),
+        }
+    }
+
+    fn nested_group(
+        id: &str,
+        members: Option<Vec<&str>>,
+        member_groups: Vec<&str>,
+    ) -> GroupGetResult {
+        let (_, public_key) = recrypt::api::Recrypt::new()
+            .generate_key_pair()
+            .expect("key generation should succeed");
+        GroupGetResult {
+            id: GroupId::unsafe_from_string(id.to_string()),
+            name: None,
+            group_master_public_key: public_key.into(),
+            is_admin: false,
+            is_member: members.is_some(),
+            owner: None,
+            admin_list: None,
+            member_list: members.map(|members| {
+                members
+                    .into_iter()
+                    .map(|m| UserId::unsafe_from_string(m.to_string()))
+                    .collect()
+            }),
+            member_group_list: Some(
+                member_groups
+                    .into_iter()
+                    .map(|g| GroupId::unsafe_from_string(g.to_string()))
+                    .collect(),
+            ),
+            created: Utc::now(),
+            updated: Utc::now(),
+            needs_rotation: None,
+            description: None,
+            attributes: GroupAttributes::default(),
+            encrypted_private_key: None,
         }
     }
 
+    #[test]
+    fn verify_admin_removal_approval_rejects_invalid() -> Result<(), IronOxideErr> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (approver_priv, approver_pub) = recrypt.generate_key_pair()?;
+        let (_, other_pub) = recrypt.generate_key_pair()?;
+        let approver_key: PublicKey = approver_pub.clone().into();
+        let other_key: PublicKey = other_pub.into();
+        let group_id = GroupId::unsafe_from_string("group".into());
+        let approver = UserId::unsafe_from_string("approver".into());
+        let caller = UserId::unsafe_from_string("caller".into());
+        let now = Utc::now();
+        let sign = |users: Vec<UserId>, expires: DateTime<Utc>| {
+            let mut approval = GroupAdminRemovalApproval {
+                group_id: group_id.clone(),
+                approver: approver.clone(),
+                users,
+                expires,
+                signature: vec![],
+            };
+            approval.signature = recrypt
+                .schnorr_sign(&approver_priv, &approver_pub, &approval.signed_message())
+                .bytes()
+                .to_vec();
+            approval
+        };
+        let approval = sign(
+            vec![approver.clone(), caller.clone()],
+            now + chrono::Duration::hours(1),
+        );
+        let removing = vec![caller.clone(), approver.clone()];
+        let verify =
+            |approval: &GroupAdminRemovalApproval, caller: &UserId, approver_key: &PublicKey| {
+                verify_admin_removal_approval(
+                    &recrypt,
+                    approval,
+                    &group_id,
+                    caller,
+                    &removing,
+                    &removing,
+                    approver_key,
+                    now,
+                )
+            };
+
+        assert_that!(&verify(&approval, &caller, &approver_key), is_variant!(Ok));
+        // the admin removing others can't approve it themselves
+        assert_that!(
+            &verify(&approval, &approver, &approver_key),
+            is_variant!(Err)
+        );
+        // signed by some other user's key, such as another admin forging the approver
+        assert_that!(&verify(&approval, &caller, &other_key), is_variant!(Err));
+        // the approver isn't an admin of the group
+        assert_that!(
+            &verify_admin_removal_approval(
+                &recrypt,
+                &approval,
+                &group_id,
+                &caller,
+                &removing,
+                &[caller.clone()],
+                &approver_key,
+                now,
+            ),
+            is_variant!(Err)
+        );
+        // doesn't cover every user being removed
+        let partial = sign(vec![caller.clone()], now + chrono::Duration::hours(1));
+        assert_that!(&verify(&partial, &caller, &approver_key), is_variant!(Err));
+        // expired
+        let expired = sign(removing.clone(), now - chrono::Duration::seconds(1));
+        assert_that!(&verify(&expired, &caller, &approver_key), is_variant!(Err));
+        // tampered with after signing
+        let mut tampered = approval.clone();
+        tampered.expires = now + chrono::Duration::days(30);
+        assert_that!(&verify(&tampered, &caller, &approver_key), is_variant!(Err));
+        Ok(())
+    }
+
+    #[test]
+    fn group_snapshot_roundtrips_and_defaults_optional_fields() {
+        let json = r#"{"id":"empl412","name":null,"owner":"alice","admins":["alice"],"members":["alice","bob"]}"#;
+        let snapshot: GroupSnapshot = serde_json::from_str(json).expect("snapshot should parse");
+        assert_eq!(snapshot.description(), None);
+        assert_eq!(snapshot.attributes(), &GroupAttributes::default());
+        assert!(snapshot.member_groups().is_empty());
+
+        let reparsed: GroupSnapshot = serde_json::from_str(
+            &serde_json::to_string(&snapshot).expect("snapshot should serialize"),
+        )
+        .expect("snapshot should parse");
+        assert_eq!(reparsed, snapshot);
+    }
+
+    #[tokio::test]
+    async fn walk_member_groups_terminates_on_cycles() -> Result<(), IronOxideErr> {
+        let groups: HashMap<GroupId, GroupGetResult> = vec![
+            nested_group("a", Some(vec!["alice"]), vec!["b", "c"]),
+            nested_group("b", Some(vec!["bob", "alice"]), vec!["c"]),
+            nested_group("c", None, vec!["a"]),
+        ]
+        .into_iter()
+        .map(|group| (group.id().clone(), group))
+        .collect();
+        let get_group = |id: GroupId| {
+            let group = groups.get(&id).cloned();
+            async move {
+                group.ok_or_else(|| IronOxideErr::ValidationError("id".into(), "not found".into()))
+            }
+        };
+
+        let tree = walk_member_groups(&GroupId::unsafe_from_string("b".into()), get_group).await?;
+        let walked: Vec<&str> = tree.iter().map(|group| group.id().id()).collect();
+        assert_eq!(walked, vec!["b", "c", "a"]);
+
+        let effective = collect_effective_members(&tree);
+        assert_eq!(
+            effective.members(),
+            &vec![
+                UserId::unsafe_from_string("alice".into()),
+                UserId::unsafe_from_string("bob".into())
+            ]
+        );
+        assert_eq!(
+            effective.unlisted_groups(),
+            &vec![GroupId::unsafe_from_string("c".into())]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn walk_member_groups_fails_for_missing_group() {
+        let get_group = |id: GroupId| async move {
+            if id.id() == "a" {
+                Ok(nested_group("a", Some(vec![]), vec!["missing"]))
+            } else {
+                Err(IronOxideErr::ValidationError(
+                    "id".into(),
+                    "not found".into(),
+                ))
+            }
+        };
+        let result = walk_member_groups(&GroupId::unsafe_from_string("a".into()), get_group).await;
+        assert_that!(&result, is_variant!(Err));
+    }
+
     #[test]
     fn group_id_validate_good() {
         let group_id1 = "a_fo_real_good_group_id$";
@@ -1036,6 +2396,48 @@ pub(crate) mod tests {
         )
     }
 
+    #[test]
+    fn group_description_validation() {
+        assert!(GroupDescription::try_from("What this group is for").is_ok());
+        assert_that!(
+            &GroupDescription::try_from(" ").unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        assert_that!(
+            &GroupDescription::try_from("a".repeat(1001)).unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+    }
+
+    #[test]
+    fn group_attributes_validation() {
+        let mut attributes = BTreeMap::new();
+        attributes.insert("location".to_string(), "HQ".to_string());
+        assert!(GroupAttributes::try_from(attributes.clone()).is_ok());
+
+        let mut empty_key = attributes.clone();
+        empty_key.insert("".to_string(), "value".to_string());
+        assert_that!(
+            &GroupAttributes::try_from(empty_key).unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+
+        let mut long_value = attributes.clone();
+        long_value.insert("key".to_string(), "v".repeat(257));
+        assert_that!(
+            &GroupAttributes::try_from(long_value).unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+
+        let too_many: BTreeMap<String, String> = (0..17)
+            .map(|i| (format!("key{}", i), "value".to_string()))
+            .collect();
+        assert_that!(
+            &GroupAttributes::try_from(too_many).unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+    }
+
     #[test]
     fn check_user_mismatch_test() -> Result<(), String> {
         let user1 = UserId::unsafe_from_string("user1".to_string());
@@ -1136,7 +2538,7 @@ pub(crate) mod tests {
             &recrypt,
             &signing_key.into(),
             encrypted_value,
-            &priv_key.into(),
+            &PrivateKey::from(priv_key).into(),
             admin_map
                 .clone()
                 .into_iter()
@@ -1169,4 +2571,108 @@ pub(crate) mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn merge_chunked_access_edit_results_reports_failed_chunks() {
+        let user = |id: &str| UserId::unsafe_from_string(id.to_string());
+        let key_fetch_fail = GroupAccessEditErr::new(user("missing"), "not found".to_string());
+        let ok_chunk = GroupUserEditResponse {
+            succeeded_ids: vec![requests::SuccessRes { user_id: user("a") }],
+            failed_ids: vec![requests::FailRes {
+                user_id: user("b"),
+                error_message: "already a member".to_string(),
+            }],
+        };
+        let result = merge_chunked_access_edit_results(
+            vec![key_fetch_fail.clone()],
+            vec![
+                (vec![user("a"), user("b")], Ok(ok_chunk)),
+                (
+                    vec![user("c")],
+                    Err(IronOxideErr::ValidationError("x".into(), "y".into())),
+                ),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(result.succeeded, vec![user("a")]);
+        assert_eq!(result.failed.len(), 3);
+        assert_eq!(result.failed[0], key_fetch_fail);
+        assert_eq!(result.failed[1].user(), &user("b"));
+        assert_eq!(result.failed[2].user(), &user("c"));
+    }
+
+    #[test]
+    fn merge_chunked_access_edit_results_classifies_forbidden_chunks() {
+        let user = |id: &str| UserId::unsafe_from_string(id.to_string());
+        let forbidden = IronOxideErr::RequestServerErrors {
+            errors: vec![],
+            code: crate::internal::RequestErrorCode::GroupAddMember,
+            http_status: Some(403),
+            request_id: None,
+        };
+        let result = merge_chunked_access_edit_results(
+            vec![],
+            vec![
+                (vec![user("a")], Err(forbidden)),
+                (
+                    vec![user("b")],
+                    Err(IronOxideErr::ValidationError("x".into(), "y".into())),
+                ),
+                (
+                    vec![user("c")],
+                    Ok(GroupUserEditResponse {
+                        succeeded_ids: vec![requests::SuccessRes { user_id: user("c") }],
+                        failed_ids: vec![],
+                    }),
+                ),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.failed[0].kind(),
+            GroupAccessEditErrKind::PermissionDenied
+        );
+        assert_eq!(result.failed[1].kind(), GroupAccessEditErrKind::Other);
+    }
+
+    #[test]
+    fn admin_edit_result_tracks_admins_and_caller() {
+        let user = |id: &str| UserId::unsafe_from_string(id.to_string());
+        let edit = |succeeded: Vec<UserId>| GroupAccessEditResult {
+            succeeded,
+            failed: vec![],
+        };
+
+        let added = admin_edit_result(
+            vec![user("caller"), user("a")],
+            &user("caller"),
+            edit(vec![user("b")]),
+            true,
+        );
+        assert_eq!(added.admins(), &vec![user("caller"), user("a"), user("b")]);
+        assert!(!added.caller_role_changed());
+
+        let removed = admin_edit_result(
+            vec![user("caller"), user("a")],
+            &user("caller"),
+            edit(vec![user("caller")]),
+            false,
+        );
+        assert_eq!(removed.admins(), &vec![user("a")]);
+        assert!(removed.caller_role_changed());
+    }
+
+    #[test]
+    fn merge_chunked_access_edit_results_errors_when_all_chunks_fail() {
+        let result = merge_chunked_access_edit_results(
+            vec![],
+            vec![(
+                vec![UserId::unsafe_from_string("a".to_string())],
+                Err(IronOxideErr::ValidationError("x".into(), "y".into())),
+            )],
+        );
+        assert_that!(&result, is_variant!(Err));
+    }
 }
@@ -2,7 +2,8 @@ use crate::internal::{
     self,
     auth_v2::AuthV2Builder,
     group_api::{
-        GroupCreateResult, GroupEntity, GroupGetResult, GroupId, GroupMetaResult, GroupName, UserId,
+        GroupAttributes, GroupCreateResult, GroupDescription, GroupEntity, GroupGetResult, GroupId,
+        GroupMetaResult, GroupName, MemberAddMode, UserId,
     },
     rest::{
         self,
@@ -38,6 +39,10 @@ pub struct GroupBasicApiResponse {
     pub(crate) created: DateTime<Utc>,
     pub(crate) group_master_public_key: PublicKey,
     pub(crate) needs_rotation: Option<bool>,
+    #[serde(default)]
+    pub(crate) description: Option<GroupDescription>,
+    #[serde(default)]
+    pub(crate) attributes: GroupAttributes,
 }
 impl TryFrom<GroupBasicApiResponse> for GroupMetaResult {
     type Error = IronOxideErr;
@@ -53,6 +58,8 @@ impl TryFrom<GroupBasicApiResponse> for GroupMetaResult {
             created: resp.created,
             updated: resp.updated,
             needs_rotation: resp.needs_rotation,
+            description: resp.description,
+            attributes: resp.attributes,
         })
     }
 }
@@ -70,9 +77,15 @@ pub struct GroupGetApiResponse {
     pub(crate) owner: Option<UserId>,
     pub(crate) admin_ids: Option<Vec<UserId>>,
     pub(crate) member_ids: Option<Vec<UserId>>,
+    #[serde(default)]
+    pub(crate) member_group_ids: Option<Vec<GroupId>>,
     pub(crate) group_master_public_key: PublicKey,
     pub(crate) encrypted_private_key: Option<TransformedEncryptedValue>,
     pub(crate) needs_rotation: Option<bool>,
+    #[serde(default)]
+    pub(crate) description: Option<GroupDescription>,
+    #[serde(default)]
+    pub(crate) attributes: GroupAttributes,
 }
 impl TryFrom<GroupGetApiResponse> for GroupGetResult {
     type Error = IronOxideErr;
@@ -89,9 +102,12 @@ impl TryFrom<GroupGetApiResponse> for GroupGetResult {
             owner: resp.owner,
             admin_list: resp.admin_ids,
             member_list: resp.member_ids,
+            member_group_list: resp.member_group_ids,
             created: resp.created,
             updated: resp.updated,
             needs_rotation: resp.needs_rotation,
+            description: resp.description,
+            attributes: resp.attributes,
         })
     }
 }
@@ -222,6 +238,8 @@ pub mod group_create {
         pub(in crate::internal) members: Option<Vec<GroupMember>>,
         pub(in crate::internal) group_public_key: PublicKey,
         pub(in crate::internal) needs_rotation: bool,
+        pub(in crate::internal) description: Option<GroupDescription>,
+        pub(in crate::internal) attributes: GroupAttributes,
     }
 
     pub async fn group_create(
@@ -233,6 +251,8 @@ pub mod group_create {
         admins: Vec<GroupAdmin>,
         members: Option<Vec<GroupMember>>,
         needs_rotation: bool,
+        description: Option<GroupDescription>,
+        attributes: GroupAttributes,
     ) -> Result<GroupCreateApiResponse, IronOxideErr> {
         let req = GroupCreateReq {
             id,
@@ -242,6 +262,8 @@ pub mod group_create {
             group_public_key: group_pub_key.into(),
             members,
             needs_rotation,
+            description,
+            attributes,
         };
 
         auth.request
@@ -371,6 +393,72 @@ pub mod group_update {
             )
             .await
     }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    struct GroupUpdateDescriptionRequest<'a> {
+        description: Option<&'a GroupDescription>,
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub async fn group_update_description_request(
+        auth: &RequestAuth,
+        id: &GroupId,
+        description: Option<&GroupDescription>,
+    ) -> Result<GroupBasicApiResponse, IronOxideErr> {
+        auth.request
+            .put(
+                &format!("groups/{}", rest::url_encode(&id.0)),
+                &GroupUpdateDescriptionRequest { description },
+                RequestErrorCode::GroupUpdate,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    struct GroupUpdateAttributesRequest<'a> {
+        attributes: &'a GroupAttributes,
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub async fn group_update_attributes_request(
+        auth: &RequestAuth,
+        id: &GroupId,
+        attributes: &GroupAttributes,
+    ) -> Result<GroupBasicApiResponse, IronOxideErr> {
+        auth.request
+            .put(
+                &format!("groups/{}", rest::url_encode(&id.0)),
+                &GroupUpdateAttributesRequest { attributes },
+                RequestErrorCode::GroupUpdate,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    struct GroupUpdateOwnerRequest<'a> {
+        owner: &'a UserId,
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    pub async fn group_update_owner_request(
+        auth: &RequestAuth,
+        id: &GroupId,
+        owner: &UserId,
+    ) -> Result<GroupBasicApiResponse, IronOxideErr> {
+        auth.request
+            .put(
+                &format!("groups/{}", rest::url_encode(&id.0)),
+                &GroupUpdateOwnerRequest { owner },
+                RequestErrorCode::GroupUpdate,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
 }
 
 pub mod group_add_member {
@@ -389,8 +477,14 @@ pub mod group_add_member {
         id: &GroupId,
         users: Vec<(UserId, PublicKey, TransformKey)>,
         signature: SchnorrSignature,
+        mode: MemberAddMode,
     ) -> Result<GroupUserEditResponse, IronOxideErr> {
         let encoded_id = rest::url_encode(&id.0).to_string();
+        let (url_path, error_code) = match mode {
+            MemberAddMode::Add => ("users", RequestErrorCode::GroupAddMember),
+            #[cfg(feature = "unstable-group-endpoints")]
+            MemberAddMode::Invite => ("invites", RequestErrorCode::GroupInviteMembers),
+        };
         let users = users
             .into_iter()
             .map(|(user_id, pk, tkey)| GroupMember {
@@ -401,12 +495,160 @@ pub mod group_add_member {
             .collect();
         auth.request
             .post(
-                &format!("groups/{}/users", encoded_id),
+                &format!("groups/{}/{}", encoded_id, url_path),
                 &GroupAddMembersReq {
                     users,
                     signature: signature.into(),
                 },
-                RequestErrorCode::GroupAddMember,
+                error_code,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+pub mod group_add_member_group {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GroupMemberGroup {
+        pub(in crate::internal) group_id: GroupId,
+        pub(in crate::internal) transform_key: TransformKey,
+        pub(in crate::internal) group_master_public_key: PublicKey,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GroupAddMemberGroupsReq {
+        pub groups: Vec<GroupMemberGroup>,
+        #[serde(with = "Base64Standard")]
+        pub signature: Vec<u8>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GroupSuccessRes {
+        pub(crate) group_id: GroupId,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GroupFailRes {
+        pub(crate) group_id: GroupId,
+        pub(crate) error_message: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GroupGroupEditResponse {
+        pub(crate) succeeded_ids: Vec<GroupSuccessRes>,
+        pub(crate) failed_ids: Vec<GroupFailRes>,
+    }
+
+    pub async fn group_add_member_group_request(
+        auth: &RequestAuth,
+        id: &GroupId,
+        groups: Vec<GroupMemberGroup>,
+        signature: SchnorrSignature,
+    ) -> Result<GroupGroupEditResponse, IronOxideErr> {
+        let encoded_id = rest::url_encode(&id.0).to_string();
+        auth.request
+            .post(
+                &format!("groups/{}/groups", encoded_id),
+                &GroupAddMemberGroupsReq {
+                    groups,
+                    signature: signature.into(),
+                },
+                RequestErrorCode::GroupAddMemberGroup,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+pub mod group_invite_list {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GroupInviteApiResponse {
+        pub(crate) group_id: GroupId,
+        pub(crate) group_name: Option<GroupName>,
+        pub(crate) invited_by: UserId,
+        pub(crate) created: DateTime<Utc>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct GroupInviteListResponse {
+        pub result: Vec<GroupInviteApiResponse>,
+    }
+
+    /// List the pending group invitations for the calling user.
+    pub async fn group_invite_list_request(
+        auth: &RequestAuth,
+    ) -> Result<GroupInviteListResponse, IronOxideErr> {
+        auth.request
+            .get(
+                "users/current/invites",
+                RequestErrorCode::GroupInviteList,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+pub mod group_document_list {
+    use super::*;
+    use crate::internal::document_api::{DocumentId, DocumentName};
+
+    #[derive(Debug, Deserialize)]
+    pub struct GroupDocumentApiResponse {
+        pub(crate) id: DocumentId,
+        pub(crate) name: Option<DocumentName>,
+        pub(crate) created: DateTime<Utc>,
+        pub(crate) updated: DateTime<Utc>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct GroupDocumentListResponse {
+        pub result: Vec<GroupDocumentApiResponse>,
+    }
+
+    /// List the documents that have been granted to a group.
+    pub async fn group_document_list_request(
+        auth: &RequestAuth,
+        id: &GroupId,
+    ) -> Result<GroupDocumentListResponse, IronOxideErr> {
+        auth.request
+            .get(
+                &format!("groups/{}/documents", rest::url_encode(&id.0)),
+                RequestErrorCode::GroupDocumentList,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+pub mod group_accept_invite {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct GroupAcceptInviteReq {}
+
+    pub async fn group_accept_invite_request(
+        auth: &RequestAuth,
+        id: &GroupId,
+    ) -> Result<GroupBasicApiResponse, IronOxideErr> {
+        auth.request
+            .post(
+                &format!("groups/{}/invites/accept", rest::url_encode(&id.0)),
+                &GroupAcceptInviteReq {},
+                RequestErrorCode::GroupInviteAccept,
                 AuthV2Builder::new(auth, Utc::now()),
             )
             .await
@@ -518,6 +760,8 @@ mod tests {
             created,
             updated,
             needs_rotation: Some(true),
+            description: Some(GroupDescription("my description".to_string())),
+            attributes: Default::default(),
         };
         let result = serde_json::to_string(&item).unwrap();
         assert!(
@@ -1,26 +1,47 @@
 //! Helpers for talking to the ironcore service.
 
-use crate::internal::{
-    auth_v2::AuthV2Builder,
-    user_api::{Jwt, UserId},
-    DeviceSigningKeyPair, IronOxideErr, RequestErrorCode, OUR_REQUEST,
+use crate::{
+    config::{
+        ApiProxyConfig, ApiResolutionConfig, FailoverConfig, HttpTimeoutConfig,
+        RequestBatchingConfig, RetryConfig,
+    },
+    internal::{
+        auth_v2::AuthV2Builder,
+        rt,
+        user_api::{Jwt, UserId},
+        IronOxideErr, RequestErrorCode, RequestSigner, OUR_REQUEST,
+    },
 };
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
 use percent_encoding::{AsciiSet, CONTROLS};
 use reqwest::{
-    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
-    Client, Method, Request, RequestBuilder, StatusCode, Url,
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, ETAG, IF_NONE_MATCH, USER_AGENT},
+    Client, Method, Request, Response, StatusCode, Url,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
-    borrow::BorrowMut,
+    borrow::{BorrowMut, Cow},
     fmt::{Display, Error, Formatter},
+    future::Future,
+    hash::{Hash, Hasher},
     marker::PhantomData,
     ops::Deref,
+    time::{Duration, Instant},
 };
 
+mod etag;
+mod failover;
+mod fixtures;
+mod resolve;
+mod trace;
+use etag::ResponseCache;
+use failover::{Endpoint, Failover};
+pub(crate) use fixtures::HttpFixtures;
+pub(crate) use resolve::client_builder;
+use resolve::ClientPool;
+
 lazy_static! {
     static ref DEFAULT_HEADERS: HeaderMap = {
         let mut headers: HeaderMap = Default::default();
@@ -33,12 +54,150 @@ lazy_static! {
         headers.append("Content-Type", "application/octet-stream".parse().unwrap());
         headers
     };
+    static ref SDK_USER_AGENT: String = format!("ironoxide/{}", env!("CARGO_PKG_VERSION"));
+    /// Which SDK made a request, as `key=value` pairs, for the service's logs
+    static ref SDK_METADATA: HeaderValue = HeaderValue::from_str(&format!(
+        "name=ironoxide; version={}; lang=rust",
+        env!("CARGO_PKG_VERSION")
+    ))
+    .unwrap();
+}
+
+/// Header naming the SDK and its version
+const SDK_METADATA_HEADER: &str = "X-IronCore-SDK";
+
+tokio::task_local! {
+    /// Headers added to the requests of the SDK calls run by `with_call_headers`
+    static CALL_HEADERS: HeaderMap;
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// Parse `(name, value)` pairs into headers, failing with a `ValidationError` for `field` if any isn't a
+/// valid header.
+pub(crate) fn parse_headers(
+    field: &str,
+    headers: &[(String, String)],
+) -> Result<HeaderMap, IronOxideErr> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let invalid = |e: &dyn std::error::Error| {
+                IronOxideErr::ValidationError(field.to_string(), format!("'{}': {}", name, e))
+            };
+            Ok((
+                HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?,
+                HeaderValue::from_str(value).map_err(|e| invalid(&e))?,
+            ))
+        })
+        .collect()
+}
+
+/// Run `f`, adding `headers` to each request it makes. They replace headers of the same name given to an
+/// enclosing call.
+pub(crate) async fn with_call_headers<F: Future>(headers: HeaderMap, f: F) -> F::Output {
+    let mut call_headers = CALL_HEADERS.try_with(HeaderMap::clone).unwrap_or_default();
+    replace_headers(&mut call_headers, headers);
+    CALL_HEADERS.scope(call_headers, f).await
+}
+
+/// One of the errors the IronCore service returns in the body of a failed request
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct ServerError {
     message: String,
-    code: u32,
+    code: ApiErrorCode,
+}
+
+impl ServerError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    /// What went wrong, for branching on without matching `message`
+    pub fn code(&self) -> &ApiErrorCode {
+        &self.code
+    }
+}
+
+/// Error code of a [ServerError](struct.ServerError.html)
+///
+/// The service sends codes as numbers, or occasionally as numeric strings. Codes this version of the
+/// SDK doesn't know are kept as `Unknown`, so new codes can still be logged and matched on.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ApiErrorCode {
+    /// The service failed in an unexpected way
+    ServiceUnknown,
+    /// The request was malformed or failed validation
+    InvalidRequest,
+    /// The caller isn't allowed to make the request, for example because they aren't an admin of the group
+    NotAuthorized,
+    UserNotFound,
+    GroupNotFound,
+    DocumentNotFound,
+    /// The user, group, or document to be created already exists
+    AlreadyExists,
+    /// A code this version of the SDK doesn't know, as the service sent it
+    Unknown(String),
+}
+
+impl ApiErrorCode {
+    /// Every known code, with the number the service sends for it
+    const KNOWN: [(u32, ApiErrorCode); 7] = [
+        (0, ApiErrorCode::ServiceUnknown),
+        (1, ApiErrorCode::InvalidRequest),
+        (2, ApiErrorCode::NotAuthorized),
+        (3, ApiErrorCode::UserNotFound),
+        (4, ApiErrorCode::GroupNotFound),
+        (5, ApiErrorCode::DocumentNotFound),
+        (6, ApiErrorCode::AlreadyExists),
+    ];
+
+    fn from_raw(raw: String) -> ApiErrorCode {
+        raw.parse::<u32>()
+            .ok()
+            .and_then(|num| {
+                ApiErrorCode::KNOWN
+                    .iter()
+                    .find(|(known, _)| *known == num)
+                    .map(|(_, code)| code.clone())
+            })
+            .unwrap_or(ApiErrorCode::Unknown(raw))
+    }
+
+    /// The number the service sends for the code, if it's numeric
+    pub fn as_number(&self) -> Option<u32> {
+        match self {
+            ApiErrorCode::Unknown(raw) => raw.parse().ok(),
+            known => ApiErrorCode::KNOWN
+                .iter()
+                .find(|(_, code)| code == known)
+                .map(|(num, _)| *num),
+        }
+    }
+}
+
+impl Serialize for ApiErrorCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match (self.as_number(), self) {
+            (Some(num), _) => serializer.serialize_u32(num),
+            (None, ApiErrorCode::Unknown(raw)) => serializer.serialize_str(raw),
+            (None, known) => unreachable!("{:?} has no number", known),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ApiErrorCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawCode {
+            Number(u64),
+            Text(String),
+        }
+        Ok(ApiErrorCode::from_raw(
+            match RawCode::deserialize(deserializer)? {
+                RawCode::Number(num) => num.to_string(),
+                RawCode::Text(text) => text,
+            },
+        ))
+    }
 }
 
 /// This encode set should be used for path components and query strings.
@@ -118,7 +277,16 @@ pub enum Authorization<'a> {
 
 impl<'a> Authorization<'a> {
     const VERSION_NUM: u8 = 2;
-    pub fn to_auth_header(&self) -> HeaderMap {
+
+    /// Fail before sending a request the IronCore service would reject because its JWT has expired.
+    fn check_unexpired(&self) -> Result<(), IronOxideErr> {
+        match self {
+            Authorization::JwtAuth(jwt) => jwt.check_unexpired(Utc::now().timestamp() as u64),
+            Authorization::Version2 { .. } => Ok(()),
+        }
+    }
+
+    pub fn to_auth_header(&self) -> Result<HeaderMap, IronOxideErr> {
         let auth_value = match self {
             Authorization::JwtAuth(jwt) => format!("jwt {}", jwt.jwt())
                 .parse()
@@ -129,14 +297,14 @@ impl<'a> Authorization<'a> {
             } => format!(
                 "IronCore {}.{}",
                 Authorization::VERSION_NUM,
-                base64::encode(&user_context.signature(request_sig.signing_keys).to_vec())
+                base64::encode(&user_context.signature(request_sig.signer)?.to_vec())
             )
             .parse()
             .expect("Auth v2 headers should only contain ASCII"),
         };
         let mut headers: HeaderMap = Default::default();
         headers.append("authorization", auth_value);
-        headers
+        Ok(headers)
     }
 
     pub fn create_signatures_v2(
@@ -146,18 +314,18 @@ impl<'a> Authorization<'a> {
         method: Method,
         signature_url: SignatureUrlString,
         body: Option<&'a [u8]>,
-        signing_keys: &'a DeviceSigningKeyPair,
+        signer: RequestSigner<'a>,
     ) -> Authorization<'a> {
         let user_context = HeaderIronCoreUserContext {
             timestamp: time,
             segment_id,
             user_id: user_id.clone(),
-            public_signing_key: signing_keys.public_key(),
+            public_signing_key: signer.public_key(),
         };
         Authorization::Version2 {
             user_context: user_context.clone(),
             request_sig: HeaderIronCoreRequestSig {
-                signing_keys,
+                signer,
                 url: signature_url,
                 method,
                 ironcore_user_context: user_context,
@@ -218,8 +386,8 @@ impl HeaderIronCoreUserContext {
     }
 
     /// Signature over the header's payload
-    fn signature(&self, signing_keys: &DeviceSigningKeyPair) -> [u8; 64] {
-        signing_keys.sign(&self.payload().into_bytes())
+    fn signature(&self, signer: RequestSigner<'_>) -> Result<[u8; 64], IronOxideErr> {
+        signer.sign(&self.payload().into_bytes())
     }
 
     /// To a reqwest-compatible header
@@ -234,6 +402,7 @@ impl HeaderIronCoreUserContext {
                 ),
                 code: error_code,
                 http_status: None,
+                request_id: None,
             })
             .map(|url| {
                 headers.append("X-IronCore-User-Context", url);
@@ -249,7 +418,7 @@ pub struct HeaderIronCoreRequestSig<'a> {
     method: Method,
     url: SignatureUrlString,
     body: Option<&'a [u8]>, //serialization of this body has to be identical to that in IronCoreRequest
-    signing_keys: &'a DeviceSigningKeyPair,
+    signer: RequestSigner<'a>,
 }
 
 impl<'a> HeaderIronCoreRequestSig<'a> {
@@ -278,40 +447,274 @@ impl<'a> HeaderIronCoreRequestSig<'a> {
     }
 
     /// Signature over the header's payload
-    fn signature(&self) -> [u8; 64] {
-        self.signing_keys.sign(&self.payload())
+    fn signature(&self) -> Result<[u8; 64], IronOxideErr> {
+        self.signer.sign(&self.payload())
     }
-    fn to_header(&self) -> HeaderMap {
+    fn to_header(&self) -> Result<HeaderMap, IronOxideErr> {
         let mut headers: HeaderMap = Default::default();
         headers.append(
             "X-IronCore-Request-Sig",
-            base64::encode(&self.signature().to_vec())
+            base64::encode(&self.signature()?.to_vec())
                 .parse()
                 .expect("signature as base64 can always be encoded as ASCII"),
         );
-        headers
+        Ok(headers)
     }
 }
 
-///A struct which holds the basic info that will be needed for making requests to an ironcore service. Currently just the base_url.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+///A struct which holds the basic info that will be needed for making requests to an ironcore service: the base_url,
+///the HTTP timeouts, the headers added to every request, and the connection pool, response cache, and failover
+///circuit breaker shared with its clones, if it has them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IronCoreRequest {
-    base_url: &'static str,
+    base_url: Cow<'static, str>,
+    timeouts: HttpTimeoutConfig,
+    #[serde(skip)]
+    retry: RetryConfig,
+    #[serde(skip)]
+    batching: RequestBatchingConfig,
+    #[serde(skip)]
+    headers: Vec<(HeaderName, HeaderValue)>,
+    /// User-Agent with the application's identifier, if it gave one
+    #[serde(skip)]
+    user_agent: Option<HeaderValue>,
+    #[serde(skip)]
+    proxy: ApiProxyConfig,
+    #[serde(skip)]
+    resolution: ApiResolutionConfig,
+    #[serde(skip)]
+    pool: Option<ClientPool>,
+    #[serde(skip)]
+    response_cache: Option<ResponseCache>,
+    #[serde(skip)]
+    failover: Option<Failover>,
+    #[serde(skip)]
+    fixtures: Option<HttpFixtures>,
+}
+
+// the connection pool, response cache, and fixtures don't change where or how requests are made
+impl PartialEq for IronCoreRequest {
+    fn eq(&self, other: &IronCoreRequest) -> bool {
+        self.base_url == other.base_url
+            && self.timeouts == other.timeouts
+            && self.retry == other.retry
+            && self.batching == other.batching
+            && self.headers == other.headers
+            && self.user_agent == other.user_agent
+            && self.proxy == other.proxy
+            && self.resolution == other.resolution
+            && self.failover.as_ref().map(Failover::secondary_base_url)
+                == other.failover.as_ref().map(Failover::secondary_base_url)
+    }
+}
+
+impl Eq for IronCoreRequest {}
+
+impl Hash for IronCoreRequest {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.base_url.hash(state);
+        self.timeouts.hash(state);
+        self.retry.hash(state);
+        self.batching.hash(state);
+        for (name, value) in &self.headers {
+            name.hash(state);
+            value.as_bytes().hash(state);
+        }
+        self.user_agent
+            .as_ref()
+            .map(HeaderValue::as_bytes)
+            .hash(state);
+        self.failover
+            .as_ref()
+            .map(Failover::secondary_base_url)
+            .hash(state);
+    }
 }
 
 impl Default for IronCoreRequest {
     fn default() -> Self {
-        *OUR_REQUEST
+        OUR_REQUEST.clone()
     }
 }
 
 impl IronCoreRequest {
     pub const fn new(base_url: &'static str) -> IronCoreRequest {
-        IronCoreRequest { base_url }
+        IronCoreRequest {
+            base_url: Cow::Borrowed(base_url),
+            timeouts: HttpTimeoutConfig {
+                connect_timeout: None,
+                request_timeout: None,
+                rate_limit_wait: None,
+            },
+            retry: RetryConfig {
+                max_retries: 0,
+                backoff: Duration::from_millis(250),
+            },
+            batching: RequestBatchingConfig {
+                chunk_size: 250,
+                max_concurrency: 4,
+                max_query_len: 4096,
+            },
+            headers: Vec::new(),
+            user_agent: None,
+            proxy: ApiProxyConfig::System,
+            resolution: ApiResolutionConfig {
+                pinned_ips: Vec::new(),
+                #[cfg(feature = "fallback-resolvers")]
+                fallback_resolvers: Vec::new(),
+            },
+            pool: None,
+            response_cache: None,
+            failover: None,
+            fixtures: None,
+        }
+    }
+
+    /// Requests to a base URL chosen at runtime
+    pub fn with_base_url(base_url: String) -> IronCoreRequest {
+        IronCoreRequest {
+            base_url: Cow::Owned(base_url),
+            timeouts: Default::default(),
+            retry: Default::default(),
+            batching: Default::default(),
+            headers: Vec::new(),
+            user_agent: None,
+            proxy: ApiProxyConfig::System,
+            resolution: Default::default(),
+            pool: None,
+            response_cache: None,
+            failover: None,
+            fixtures: None,
+        }
+    }
+
+    /// The same requests, made with `timeouts`
+    pub fn with_timeouts(self, timeouts: HttpTimeoutConfig) -> IronCoreRequest {
+        IronCoreRequest { timeouts, ..self }
+    }
+
+    /// The same requests, resending those that fail for transient reasons as `retry` allows
+    pub fn with_retry(self, retry: RetryConfig) -> IronCoreRequest {
+        IronCoreRequest { retry, ..self }
+    }
+
+    /// The same requests, splitting operations on large lists as `batching` allows
+    pub fn with_batching(self, batching: RequestBatchingConfig) -> IronCoreRequest {
+        IronCoreRequest { batching, ..self }
+    }
+
+    /// How operations on large lists are split into requests
+    pub(crate) fn batching(&self) -> &RequestBatchingConfig {
+        &self.batching
+    }
+
+    /// The same requests, with `headers` added to each of them
+    pub fn with_headers(self, headers: HeaderMap) -> IronCoreRequest {
+        IronCoreRequest {
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            ..self
+        }
+    }
+
+    /// The same requests, going through `proxy`
+    pub fn with_api_proxy(self, proxy: ApiProxyConfig) -> IronCoreRequest {
+        IronCoreRequest { proxy, ..self }
+    }
+
+    /// The same requests, connecting to the addresses chosen by `resolution`
+    pub fn with_api_resolution(self, resolution: ApiResolutionConfig) -> IronCoreRequest {
+        IronCoreRequest { resolution, ..self }
+    }
+
+    /// The same requests, sent on a new pool of connections shared with clones of the result. Without a
+    /// pool, each request makes its own connection.
+    pub fn with_connection_pool(self) -> IronCoreRequest {
+        IronCoreRequest {
+            pool: Some(ClientPool::default()),
+            ..self
+        }
+    }
+
+    /// The same requests, sent on the pool of connections of `other`
+    pub(crate) fn with_connection_pool_of(self, other: &IronCoreRequest) -> IronCoreRequest {
+        IronCoreRequest {
+            pool: other.pool.clone(),
+            ..self
+        }
+    }
+
+    /// The same requests, with responses that rarely change kept in a new cache shared with clones of the
+    /// result and revalidated with their ETags
+    pub fn with_response_cache(self) -> IronCoreRequest {
+        IronCoreRequest {
+            response_cache: Some(ResponseCache::default()),
+            ..self
+        }
+    }
+
+    /// The same requests, recorded to or replayed from `fixtures`, which are shared with clones of the result
+    #[cfg(feature = "test-fixtures")]
+    pub(crate) fn with_http_fixtures(self, fixtures: HttpFixtures) -> IronCoreRequest {
+        IronCoreRequest {
+            fixtures: Some(fixtures),
+            ..self
+        }
+    }
+
+    /// The same requests, sent to the secondary API of `failover` while the API at `base_url` keeps
+    /// failing. The circuit breaker is shared with clones of the result.
+    pub(crate) fn with_failover(
+        self,
+        failover: &FailoverConfig,
+    ) -> Result<IronCoreRequest, IronOxideErr> {
+        Ok(IronCoreRequest {
+            failover: Some(Failover::new(failover)?),
+            ..self
+        })
+    }
+
+    /// The same requests, identifying `app_identifier`, such as `"my-app/1.2.0"`, after the SDK in their
+    /// User-Agent
+    pub(crate) fn with_app_identifier(
+        self,
+        app_identifier: &str,
+    ) -> Result<IronCoreRequest, IronOxideErr> {
+        let user_agent = HeaderValue::from_str(&format!("{} {}", *SDK_USER_AGENT, app_identifier))
+            .map_err(|e| {
+                IronOxideErr::ValidationError("app_identifier".to_string(), e.to_string())
+            })?;
+        Ok(IronCoreRequest {
+            user_agent: Some(user_agent),
+            ..self
+        })
     }
 
     pub fn base_url(&self) -> &str {
-        self.base_url
+        &self.base_url
+    }
+
+    /// URL for a request to `relative_url`, and the API it's sent to
+    fn url(
+        &self,
+        relative_url: &str,
+        error_code: RequestErrorCode,
+    ) -> Result<(Url, Endpoint), IronOxideErr> {
+        let (base_url, endpoint) = match self.failover.as_ref().map(|f| (f, f.endpoint())) {
+            Some((failover, Endpoint::Secondary)) => {
+                (failover.secondary_base_url(), Endpoint::Secondary)
+            }
+            _ => (self.base_url(), Endpoint::Primary),
+        };
+        Url::parse(&format!("{}{}", base_url, relative_url))
+            .map(|url| (url, endpoint))
+            .map_err(|e| IronOxideErr::from((e, error_code)))
+    }
+
+    pub fn timeouts(&self) -> HttpTimeoutConfig {
+        self.timeouts
     }
 
     ///POST body to the resource at relative_url using auth for authorization.
@@ -323,13 +726,14 @@ impl IronCoreRequest {
         error_code: RequestErrorCode,
         auth: &Authorization<'_>,
     ) -> Result<B, IronOxideErr> {
+        auth.check_unexpired()?;
         self.request::<A, _, String, _>(
             relative_url,
             Method::POST,
             Some(body),
             None,
             error_code,
-            auth.to_auth_header(),
+            auth.to_auth_header()?,
             move |server_resp| IronCoreRequest::deserialize_body(server_resp, error_code),
         )
         .await
@@ -362,13 +766,10 @@ impl IronCoreRequest {
         error_code: RequestErrorCode,
         auth_b: AuthV2Builder<'_>,
     ) -> Result<B, IronOxideErr> {
+        let (url, endpoint) = self.url(relative_url, error_code)?;
         let (mut req, body_bytes) = Result::<_, IronOxideErr>::Ok({
             // build up a request...
-            let mut req = Request::new(
-                Method::POST,
-                url::Url::parse(&format!("{}{}", self.base_url(), relative_url))
-                    .map_err(|e| IronOxideErr::from((e, error_code)))?,
-            );
+            let mut req = Request::new(Method::POST, url);
             *req.body_mut() = Some(body.to_vec().into());
             (req, body.to_vec())
         })?;
@@ -386,10 +787,10 @@ impl IronCoreRequest {
             let user_context_header = user_context.to_header(error_code)?;
             replace_headers(req.headers_mut(), user_context_header);
             replace_headers(req.headers_mut(), DEFAULT_HEADERS.clone());
-            replace_headers(req.headers_mut(), auth.to_auth_header());
-            replace_headers(req.headers_mut(), request_sig.to_header());
+            replace_headers(req.headers_mut(), auth.to_auth_header()?);
+            replace_headers(req.headers_mut(), request_sig.to_header()?);
 
-            Self::send_req(req, error_code, move |server_resp| {
+            self.send_req(req, endpoint, error_code, move |server_resp| {
                 IronCoreRequest::deserialize_body(server_resp, error_code)
             })
             .await
@@ -460,6 +861,28 @@ impl IronCoreRequest {
         .await
     }
 
+    ///GET the resource at relative_url using a JWT for authorization.
+    ///If the request fails a RequestError will be raised.
+    pub async fn get_jwt_auth<A: DeserializeOwned>(
+        &self,
+        relative_url: &str,
+        error_code: RequestErrorCode,
+        auth: &Authorization<'_>,
+    ) -> Result<A, IronOxideErr> {
+        auth.check_unexpired()?;
+        //A little lie here, String isn't actually the body type as it's unused
+        self.request::<String, _, String, _>(
+            relative_url,
+            Method::GET,
+            None,
+            None,
+            error_code,
+            auth.to_auth_header()?,
+            move |server_resp| IronCoreRequest::deserialize_body(server_resp, error_code),
+        )
+        .await
+    }
+
     ///This should be used for a GET where the result can be empty. If the result is empty the returned value will be None.
     pub async fn get_with_empty_result_jwt_auth<A: DeserializeOwned>(
         &self,
@@ -467,6 +890,7 @@ impl IronCoreRequest {
         error_code: RequestErrorCode,
         auth: &Authorization<'_>,
     ) -> Result<Option<A>, IronOxideErr> {
+        auth.check_unexpired()?;
         //A little lie here, String isn't actually the body type as it's unused
         self.request::<String, _, String, _>(
             relative_url,
@@ -474,7 +898,7 @@ impl IronCoreRequest {
             None,
             None,
             error_code,
-            auth.to_auth_header(),
+            auth.to_auth_header()?,
             move |server_resp| {
                 if !server_resp.is_empty() {
                     IronCoreRequest::deserialize_body(server_resp, error_code).map(Some)
@@ -525,11 +949,17 @@ impl IronCoreRequest {
         Q: Serialize + ?Sized,
         F: FnOnce(&Bytes) -> Result<B, IronOxideErr>,
     {
-        let client = Client::new();
-        let mut builder = client.request(
-            method,
-            format!("{}{}", self.base_url, relative_url).as_str(),
-        );
+        let (url, endpoint) = self.url(relative_url, error_code)?;
+        let client = resolve::api_client(
+            &url,
+            self.timeouts,
+            &self.proxy,
+            &self.resolution,
+            self.pool.as_ref(),
+            error_code,
+        )
+        .await?;
+        let mut builder = client.request(method, url);
         // add query params, if any
         builder = maybe_query_params
             .iter()
@@ -540,8 +970,13 @@ impl IronCoreRequest {
             .iter()
             .fold(builder, |build, body| build.json(body));
 
-        let req = builder.headers(DEFAULT_HEADERS.clone()).headers(headers);
-        IronCoreRequest::send_req_with_builder(req, error_code, resp_handler).await
+        let req = builder
+            .headers(DEFAULT_HEADERS.clone())
+            .headers(headers)
+            .build()
+            .map_err(|e| IronOxideErr::from((e, error_code)))?;
+        self.send_req_with_client(&client, req, endpoint, error_code, resp_handler)
+            .await
     }
 
     ///Make a request to the url using the specified method. DEFAULT_HEADERS will be used as well as whatever headers are passed
@@ -561,13 +996,10 @@ impl IronCoreRequest {
         B: DeserializeOwned,
         F: FnOnce(&Bytes) -> Result<B, IronOxideErr>,
     {
+        let (url, endpoint) = self.url(relative_url, error_code)?;
         let (mut req, body_bytes) = Result::<_, IronOxideErr>::Ok({
             // build up a request...
-            let mut req = Request::new(
-                method,
-                url::Url::parse(&format!("{}{}", self.base_url(), relative_url))
-                    .map_err(|e| IronOxideErr::from((e, error_code)))?,
-            );
+            let mut req = Request::new(method, url);
 
             // add query params
             if let Some(query) = maybe_query_params {
@@ -603,10 +1035,10 @@ impl IronCoreRequest {
             let user_context_header = user_context.to_header(error_code)?;
             replace_headers(req.headers_mut(), user_context_header);
             replace_headers(req.headers_mut(), DEFAULT_HEADERS.clone());
-            replace_headers(req.headers_mut(), auth.to_auth_header());
-            replace_headers(req.headers_mut(), request_sig.to_header());
+            replace_headers(req.headers_mut(), auth.to_auth_header()?);
+            replace_headers(req.headers_mut(), request_sig.to_header()?);
 
-            Self::send_req(req, error_code, resp_handler).await
+            self.send_req(req, endpoint, error_code, resp_handler).await
         } else {
             panic!("authorized requests must use version 2 of API authentication")
         }
@@ -627,7 +1059,9 @@ impl IronCoreRequest {
     }
 
     async fn send_req<B, F>(
+        &self,
         req: Request,
+        endpoint: Endpoint,
         error_code: RequestErrorCode,
         resp_handler: F,
     ) -> Result<B, IronOxideErr>
@@ -635,29 +1069,24 @@ impl IronCoreRequest {
         B: DeserializeOwned,
         F: FnOnce(&Bytes) -> Result<B, IronOxideErr>,
     {
-        let client = Client::new();
-        let server_res = client.execute(req).await;
-        let res = server_res.map_err(|e| (e, error_code))?;
-        //Parse the body content into bytes
-        let status = res.status();
-        //Now make the error type into the IronOxideErr and run the resp_handler which was passed to us.
-        let server_resp = res.bytes().await.map_err(|err| {
-            //Map the generic error from reqwest to our error type.
-            IronCoreRequest::create_request_err(err.to_string(), error_code, err.status())
-        })?;
-        //If the status code is a 5xx, return a fixed error code message
-        if status.is_server_error() || status.is_client_error() {
-            Err(IronCoreRequest::request_failure_to_error(
-                status,
-                error_code,
-                &server_resp,
-            ))
-        } else {
-            resp_handler(&server_resp)
-        }
+        let client = resolve::api_client(
+            req.url(),
+            self.timeouts,
+            &self.proxy,
+            &self.resolution,
+            self.pool.as_ref(),
+            error_code,
+        )
+        .await?;
+        self.send_req_with_client(&client, req, endpoint, error_code, resp_handler)
+            .await
     }
-    async fn send_req_with_builder<B, F>(
-        req: RequestBuilder,
+
+    async fn send_req_with_client<B, F>(
+        &self,
+        client: &Client,
+        mut req: Request,
+        endpoint: Endpoint,
         error_code: RequestErrorCode,
         resp_handler: F,
     ) -> Result<B, IronOxideErr>
@@ -665,28 +1094,170 @@ impl IronCoreRequest {
         B: DeserializeOwned,
         F: FnOnce(&Bytes) -> Result<B, IronOxideErr>,
     {
-        let res_result = req.send().await;
-        let res = res_result.map_err(|e| (e, error_code))?;
-        //Parse the body content into bytes
-        let status = res.status();
-        //Now make the error type into the IronOxideErr and run the resp_handler which was passed to us.
-        let server_resp = res.bytes().await.map_err(|err| {
-            //Map the generic error from reqwest to our error type.
-
-            IronCoreRequest::create_request_err(err.to_string(), error_code, err.status())
-        })?;
+        let user_agent = match &self.user_agent {
+            Some(user_agent) => user_agent.clone(),
+            None => HeaderValue::from_static(&SDK_USER_AGENT),
+        };
+        req.headers_mut().insert(USER_AGENT, user_agent);
+        req.headers_mut()
+            .insert(SDK_METADATA_HEADER, SDK_METADATA.clone());
+        // headers of the current call win over those of the instance, and neither replaces a header the
+        // request already has
+        let call_headers = CALL_HEADERS.try_with(HeaderMap::clone).unwrap_or_default();
+        let instance_headers = self.headers.iter().map(|(name, value)| (name, value));
+        for (name, value) in call_headers.iter().chain(instance_headers) {
+            if !req.headers().contains_key(name) {
+                req.headers_mut().insert(name, value.clone());
+            }
+        }
+        // revalidate a kept response instead of downloading it again
+        let cache = self
+            .response_cache
+            .as_ref()
+            .filter(|_| etag::is_cacheable(req.method(), error_code))
+            .map(|cache| (cache, req.url().to_string()));
+        let kept = cache.as_ref().and_then(|(cache, url)| cache.get(url));
+        if let Some((etag, _)) = &kept {
+            req.headers_mut().insert(IF_NONE_MATCH, etag.clone());
+        }
+        let timeouts = self.timeouts;
+        let retry = self.retry;
+        let fixture = fixtures::request_key(&req);
+        let span = trace::request_span(&req);
+        let started = Instant::now();
+        let live = trace::in_span(span.clone(), async {
+            let executed =
+                IronCoreRequest::execute(client, req, timeouts.rate_limit_wait, retry, error_code)
+                    .await;
+            if let Some(failover) = &self.failover {
+                let succeeded = executed
+                    .as_ref()
+                    .map_or(false, |(res, _)| !res.status().is_server_error());
+                failover.record(endpoint, succeeded);
+            }
+            let (res, retries) = executed?;
+            log_remote_addr(&res);
+            //Parse the body content into bytes
+            let status = res.status();
+            let headers = res.headers().clone();
+            //Now make the error type into the IronOxideErr and run the resp_handler which was passed to us.
+            let server_resp = res.bytes().await.map_err(|err| {
+                //Map the generic error from reqwest to our error type.
+                IronCoreRequest::create_request_err(err.to_string(), error_code, err.status(), None)
+            })?;
+            trace::record_response(&span, status, retries, started.elapsed());
+            Ok::<_, IronOxideErr>((status, headers, server_resp))
+        });
+        let (status, headers, server_resp) =
+            match fixtures::replay(self.fixtures.as_ref(), &fixture, error_code) {
+                Some(replayed) => replayed?,
+                None => {
+                    let response = live.await?;
+                    fixtures::record(self.fixtures.as_ref(), fixture, &response, error_code)?;
+                    response
+                }
+            };
+        let (status, server_resp) = match (kept, cache) {
+            (Some((_, body)), _) if status == StatusCode::NOT_MODIFIED => (StatusCode::OK, body),
+            (_, Some((cache, url))) if status.is_success() => {
+                if let Some(etag) = headers.get(ETAG) {
+                    cache.insert(url, etag.clone(), server_resp.clone());
+                }
+                (status, server_resp)
+            }
+            _ => (status, server_resp),
+        };
         //If the status code is a 5xx, return a fixed error code message
         if status.is_server_error() || status.is_client_error() {
             Err(IronCoreRequest::request_failure_to_error(
                 status,
                 error_code,
                 &server_resp,
+                &headers,
             ))
         } else {
             resp_handler(&server_resp)
         }
     }
 
+    /// Execute `req`, resending it as `retry` allows while it fails for transient reasons and only reads
+    /// data. Returns the response and the number of retries.
+    async fn execute(
+        client: &Client,
+        mut req: Request,
+        rate_limit_wait: Option<Duration>,
+        retry: RetryConfig,
+        error_code: RequestErrorCode,
+    ) -> Result<(Response, u32), IronOxideErr> {
+        let mut retries = 0;
+        loop {
+            let resend = if retries < retry.max_retries && req.method() == Method::GET {
+                req.try_clone()
+            } else {
+                None
+            };
+            let executed =
+                IronCoreRequest::execute_rate_limited(client, req, rate_limit_wait, error_code)
+                    .await;
+            let transient = match &executed {
+                Ok((res, _)) => is_transient(res.status()),
+                Err(IronOxideErr::RequestError {
+                    http_status: None, ..
+                }) => true,
+                Err(_) => false,
+            };
+            match resend {
+                Some(resend) if transient => {
+                    retries += 1;
+                    let wait = retry.backoff * retries;
+                    log::warn!(
+                        "{} failed, resending it in {}ms ({} of {})",
+                        resend.url(),
+                        wait.as_millis(),
+                        retries,
+                        retry.max_retries
+                    );
+                    rt::sleep(wait).await;
+                    req = resend;
+                }
+                _ => return executed.map(|(res, rate_limited)| (res, retries + rate_limited)),
+            }
+        }
+    }
+
+    /// Execute `req`. If it's rate limited and the server asks us to wait no longer than `rate_limit_wait`,
+    /// wait that long and send it once more. Returns the response and the number of retries.
+    async fn execute_rate_limited(
+        client: &Client,
+        req: Request,
+        rate_limit_wait: Option<Duration>,
+        error_code: RequestErrorCode,
+    ) -> Result<(Response, u32), IronOxideErr> {
+        let retry = rate_limit_wait.and_then(|_| req.try_clone());
+        let res = client
+            .execute(req)
+            .await
+            .map_err(|e| IronOxideErr::from((e, error_code)))?;
+        let wait = retry_after(res.headers())
+            .filter(|wait| rate_limit_wait.map_or(false, |max_wait| *wait <= max_wait));
+        match (retry, wait) {
+            (Some(retry), Some(wait)) if res.status() == StatusCode::TOO_MANY_REQUESTS => {
+                log::warn!(
+                    "{} was rate limited, retrying in {}ms",
+                    res.url(),
+                    wait.as_millis()
+                );
+                rt::sleep(wait).await;
+                client
+                    .execute(retry)
+                    .await
+                    .map(|res| (res, 1))
+                    .map_err(|e| IronOxideErr::from((e, error_code)))
+            }
+            _ => Ok((res, 0)),
+        }
+    }
+
     pub async fn delete_with_no_body<B: DeserializeOwned>(
         &self,
         relative_url: &str,
@@ -717,63 +1288,127 @@ impl IronCoreRequest {
                 ),
                 error_code,
                 None,
+                None,
             )
         })
     }
 
     /// Given a request failure, determine which type of failure we have and return the appropriate error structure. Handles
     /// differentiating between 500 errors, 400 errors with an expected error body, and 404 errors without a response body.
+    /// Handles the `Retry-After` header of rate limited responses and includes any ID the server gave the request.
     fn request_failure_to_error(
         status_code: StatusCode,
         error_code: RequestErrorCode,
         body: &[u8],
+        headers: &HeaderMap,
     ) -> IronOxideErr {
+        let request_id = request_id(headers);
         if status_code.is_server_error() {
             IronCoreRequest::create_request_err(
                 "Server encountered error attempting to make request.".to_string(),
                 error_code,
                 Some(status_code),
+                request_id,
             )
         } else if status_code == StatusCode::NOT_FOUND {
             IronCoreRequest::create_request_err(
                 "Requested resource was not found.".to_string(),
                 error_code,
                 Some(status_code),
+                request_id,
             )
         } else if status_code == StatusCode::TOO_MANY_REQUESTS {
             //Handle Cloudflare rate limiting response code
-            IronCoreRequest::create_request_err(
-                "Request was denied due to rate limiting.".to_string(),
-                error_code,
-                Some(status_code),
-            )
+            IronOxideErr::RateLimited {
+                retry_after: retry_after(headers),
+                code: error_code,
+            }
         } else {
             //If the status code is an error we can try and rip off the ServerErrors which the webservice
             //returns, otherwise process it the way the user wants.
-            IronCoreRequest::deserialize_body::<Vec<ServerError>>(body, error_code).map_or_else(
-                |e| e,
-                |error_response| IronOxideErr::RequestServerErrors {
+            match IronCoreRequest::deserialize_body::<Vec<ServerError>>(body, error_code) {
+                Ok(error_response) => IronOxideErr::RequestServerErrors {
                     errors: error_response,
                     code: error_code,
                     http_status: Some(status_code.as_u16()),
+                    request_id,
                 },
-            )
+                Err(IronOxideErr::RequestError {
+                    message,
+                    code,
+                    http_status,
+                    ..
+                }) => IronOxideErr::RequestError {
+                    message,
+                    code,
+                    http_status,
+                    request_id,
+                },
+                Err(e) => e,
+            }
         }
     }
 
-    // Generic method to build up a RequestError object with the provided text, error code and optional HTTP status code
+    // Generic method to build up a RequestError object with the provided text, error code, optional HTTP status code,
+    // and optional ID the server gave the request
     fn create_request_err(
         error_text: String,
         error_code: RequestErrorCode,
         status_code: Option<StatusCode>,
+        request_id: Option<String>,
     ) -> IronOxideErr {
         IronOxideErr::RequestError {
             message: error_text,
             code: error_code,
             http_status: status_code.map(|s| s.as_u16()),
+            request_id,
+        }
+    }
+}
+/// ID the server gave a request, from the `X-Request-Id` header of its response or, failing that, the
+/// `CF-Ray` header Cloudflare adds. Include it when contacting IronCore support about a failed request.
+fn request_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("x-request-id")
+        .or_else(|| headers.get("cf-ray"))
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Whether a response with `status` means the request may succeed if it's sent again
+fn is_transient(status: StatusCode) -> bool {
+    match status {
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => {
+            true
         }
+        _ => false,
     }
 }
+
+/// How long a response asks us to wait before retrying, from its `Retry-After` header, either in seconds or
+/// as an HTTP date, or failing that from its `RateLimit-Reset` header.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let seconds = |value: &str| value.trim().parse::<u64>().ok().map(Duration::from_secs);
+    header("retry-after")
+        .and_then(|value| {
+            seconds(value).or_else(|| {
+                DateTime::parse_from_rfc2822(value).ok().map(|date| {
+                    (date.with_timezone(&Utc) - Utc::now())
+                        .to_std()
+                        .unwrap_or_default()
+                })
+            })
+        })
+        .or_else(|| header("ratelimit-reset").and_then(seconds))
+}
+
+fn log_remote_addr(res: &reqwest::Response) {
+    if let Some(addr) = res.remote_addr() {
+        log::debug!("{} answered by {}", res.url(), addr);
+    }
+}
+
 // brought this private function in from reqwest
 fn replace_headers(dst: &mut HeaderMap, src: HeaderMap) {
     // IntoIter of HeaderMap yields (Option<HeaderName>, HeaderValue).
@@ -803,6 +1438,7 @@ impl From<(serde_json::Error, RequestErrorCode)> for IronOxideErr {
             message: e.to_string(),
             code,
             http_status: None,
+            request_id: None,
         }
     }
 }
@@ -813,6 +1449,7 @@ impl From<(reqwest::Error, RequestErrorCode)> for IronOxideErr {
             message: e.to_string(),
             code,
             http_status: None,
+            request_id: None,
         }
     }
 }
@@ -823,6 +1460,7 @@ impl From<(url::ParseError, RequestErrorCode)> for IronOxideErr {
             message: e.to_string(),
             code,
             http_status: None,
+            request_id: None,
         }
     }
 }
@@ -962,6 +1600,25 @@ pub mod json {
         }
     }
 
+    impl TryFrom<EncryptedOnceValue> for recrypt::api::EncryptedValue {
+        type Error = IronOxideErr;
+
+        fn try_from(ev: EncryptedOnceValue) -> Result<Self, Self::Error> {
+            Ok(recrypt::api::EncryptedValue::EncryptedOnceValue {
+                ephemeral_public_key: internal::PublicKey::try_from(ev.ephemeral_public_key)?
+                    .into(),
+                encrypted_message: recrypt::api::EncryptedMessage::new_from_slice(
+                    &ev.encrypted_message[..],
+                )?,
+                auth_hash: recrypt::api::AuthHash::new_from_slice(&ev.auth_hash[..])?,
+                public_signing_key: recrypt::api::PublicSigningKey::new_from_slice(
+                    &ev.public_signing_key[..],
+                )?,
+                signature: recrypt::api::Ed25519Signature::new_from_slice(&ev.signature[..])?,
+            })
+        }
+    }
+
     impl TryFrom<TransformBlock> for recrypt::api::TransformBlock {
         type Error = IronOxideErr;
 
@@ -1014,7 +1671,10 @@ pub mod json {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::internal::tests::{contains, length};
+    use crate::internal::{
+        tests::{contains, length},
+        DeviceSigningKeyPair,
+    };
     use chrono::TimeZone;
     use galvanic_assert::{
         matchers::{variant::*, *},
@@ -1030,12 +1690,34 @@ mod tests {
         assert_eq!(result.len(), 2);
     }
 
+    #[test]
+    fn deserialize_error_codes_keeps_unknown_codes() {
+        let raw_string = r#"[{"message":"a","code":4},{"message":"b","code":"2"},{"message":"c","code":999},{"message":"d","code":"QUOTA"}]"#;
+        let result: Vec<ServerError> = serde_json::from_slice(raw_string.as_bytes()).unwrap();
+        let codes: Vec<_> = result.iter().map(ServerError::code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                &ApiErrorCode::GroupNotFound,
+                &ApiErrorCode::NotAuthorized,
+                &ApiErrorCode::Unknown("999".to_string()),
+                &ApiErrorCode::Unknown("QUOTA".to_string()),
+            ]
+        );
+        assert_eq!(result[0].message(), "a");
+        assert_eq!(
+            serde_json::to_string(&result).unwrap(),
+            r#"[{"message":"a","code":4},{"message":"b","code":2},{"message":"c","code":999},{"message":"d","code":"QUOTA"}]"#
+        );
+    }
+
     #[test]
     fn request_failure_to_error_server_error() {
         let server_error = IronCoreRequest::request_failure_to_error(
             StatusCode::BAD_GATEWAY,
             RequestErrorCode::DocumentList,
             &[0u8; 0],
+            &HeaderMap::new(),
         );
 
         assert_that!(&server_error, is_variant!(IronOxideErr::RequestError));
@@ -1045,37 +1727,115 @@ mod tests {
             has_structure!(IronOxideErr::RequestError {
                 message: contains("Server encountered error"),
                 code: eq(RequestErrorCode::DocumentList),
-                http_status: maybe_some(eq(StatusCode::BAD_GATEWAY.as_u16()))
+                http_status: maybe_some(eq(StatusCode::BAD_GATEWAY.as_u16())),
+                request_id: eq(None)
             })
         );
     }
 
     #[test]
     fn request_failure_to_rate_limiting_error() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("3"));
         let server_error = IronCoreRequest::request_failure_to_error(
             StatusCode::TOO_MANY_REQUESTS,
             RequestErrorCode::DocumentList,
             &[0u8; 0],
+            &headers,
         );
 
-        assert_that!(&server_error, is_variant!(IronOxideErr::RequestError));
+        assert_that!(&server_error, is_variant!(IronOxideErr::RateLimited));
 
         assert_that!(
             &server_error,
-            has_structure!(IronOxideErr::RequestError {
-                message: contains("rate limiting"),
-                code: eq(RequestErrorCode::DocumentList),
-                http_status: maybe_some(eq(StatusCode::TOO_MANY_REQUESTS.as_u16()))
+            has_structure!(IronOxideErr::RateLimited {
+                retry_after: maybe_some(eq(Duration::from_secs(3))),
+                code: eq(RequestErrorCode::DocumentList)
             })
         );
     }
 
+    #[test]
+    fn parse_headers_rejects_invalid_headers() {
+        let headers = parse_headers("headers", &[("X-Tenant".into(), "acme".into())]).unwrap();
+        assert_eq!(headers["x-tenant"], "acme");
+        for &(name, value) in &[("bad header", "acme"), ("X-Tenant", "line\nbreak")] {
+            let err = parse_headers("headers", &[(name.into(), value.into())]).unwrap_err();
+            assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+        }
+    }
+
+    #[test]
+    fn app_identifier_follows_sdk_in_user_agent() {
+        let request = IronCoreRequest::new("https://example.com/")
+            .with_app_identifier("my-app/1.2.0")
+            .unwrap();
+        assert_eq!(
+            request.user_agent.unwrap(),
+            format!("ironoxide/{} my-app/1.2.0", env!("CARGO_PKG_VERSION")).as_str()
+        );
+        assert_eq!(
+            SDK_METADATA.to_str().unwrap(),
+            format!(
+                "name=ironoxide; version={}; lang=rust",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        let err = IronCoreRequest::new("https://example.com/")
+            .with_app_identifier("my-app\n")
+            .unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+    }
+
+    #[tokio::test]
+    async fn inner_call_headers_replace_outer() {
+        let outer = parse_headers(
+            "headers",
+            &[
+                ("X-A".into(), "outer".into()),
+                ("X-B".into(), "outer".into()),
+            ],
+        )
+        .unwrap();
+        let inner = parse_headers("headers", &[("X-B".into(), "inner".into())]).unwrap();
+        let seen = with_call_headers(
+            outer,
+            with_call_headers(inner, async { CALL_HEADERS.with(HeaderMap::clone) }),
+        )
+        .await;
+        assert_eq!(seen["x-a"], "outer");
+        assert_eq!(seen["x-b"], "inner");
+    }
+
+    #[test]
+    fn retry_after_parses_seconds_dates_and_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("ratelimit-reset", HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+        headers.insert("retry-after", HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+        // a date in the past means retry now
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(0)));
+        let soon = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        headers.insert("retry-after", HeaderValue::from_str(&soon).unwrap());
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(50) && wait <= Duration::from_secs(60));
+    }
+
     #[test]
     fn request_failure_to_error_four_oh_four_error() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cf-ray", HeaderValue::from_static("6f1b2c3d4e5f6a7b-SJC"));
         let server_error = IronCoreRequest::request_failure_to_error(
             StatusCode::NOT_FOUND,
             RequestErrorCode::DocumentList,
             &[0u8; 0],
+            &headers,
         );
 
         assert_that!(&server_error, is_variant!(IronOxideErr::RequestError));
@@ -1085,7 +1845,8 @@ mod tests {
             has_structure!(IronOxideErr::RequestError {
                 message: contains("not found"),
                 code: eq(RequestErrorCode::DocumentList),
-                http_status: maybe_some(eq(StatusCode::NOT_FOUND.as_u16()))
+                http_status: maybe_some(eq(StatusCode::NOT_FOUND.as_u16())),
+                request_id: maybe_some(eq("6f1b2c3d4e5f6a7b-SJC".to_string()))
             })
         );
     }
@@ -1095,10 +1856,14 @@ mod tests {
         let error_as_bytes =
             r#"[{"message":"foo","code":2},{"message":"bar","code":3}]"#.as_bytes();
 
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", HeaderValue::from_static("req-123"));
+        headers.insert("cf-ray", HeaderValue::from_static("6f1b2c3d4e5f6a7b-SJC"));
         let server_error = IronCoreRequest::request_failure_to_error(
             StatusCode::UNPROCESSABLE_ENTITY,
             RequestErrorCode::DocumentList,
             error_as_bytes,
+            &headers,
         );
 
         assert_that!(
@@ -1111,7 +1876,8 @@ mod tests {
             has_structure!(IronOxideErr::RequestServerErrors {
                 errors: length(&2),
                 code: eq(RequestErrorCode::DocumentList),
-                http_status: maybe_some(eq(StatusCode::UNPROCESSABLE_ENTITY.as_u16()))
+                http_status: maybe_some(eq(StatusCode::UNPROCESSABLE_ENTITY.as_u16())),
+                request_id: maybe_some(eq("req-123".to_string()))
             })
         );
     }
@@ -1169,7 +1935,7 @@ mod tests {
         );
 
         // assert that the signature() implementation can be verified with the included public signing key
-        let signature = user_context.signature(&key_pair);
+        let signature = user_context.signature((&key_pair).into()).unwrap();
         let pub_signing_key: PublicSigningKey =
             PublicSigningKey::new(user_context.public_signing_key);
         assert!(pub_signing_key.verify(&payload_bytes, &Ed25519Signature::new(signature)));
@@ -1228,7 +1994,7 @@ mod tests {
             method: Method::GET,
             url: signing_url_string.clone(),
             body: Some(&fake_req_json_bytes),
-            signing_keys: &signing_keys,
+            signer: (&signing_keys).into(),
         };
 
         assert_eq!(&request_sig.payload(), &expected.to_string().into_bytes());
@@ -1236,7 +2002,7 @@ mod tests {
         // assert that the corresponding header also has the correct form
         let mut header = HeaderMap::default();
         header.append("X-IronCore-Request-Sig", "EdXNi3mkmHfEcFxhKfl3dri/Z1E0uGq6H+wbitD3N/Ooi9cq9tpmlkjoV4dnEFSKs/xxkOwlLOTwtVsM1f2lAw==".parse().unwrap());
-        assert_eq!(&request_sig.to_header(), &header);
+        assert_eq!(&request_sig.to_header().unwrap(), &header);
 
         //
         // show that no body also works
@@ -1246,7 +2012,7 @@ mod tests {
             method: Method::GET,
             url: signing_url_string.clone(),
             body: None,
-            signing_keys: &signing_keys,
+            signer: (&signing_keys).into(),
         };
 
         assert_eq!(
@@ -1257,7 +2023,7 @@ mod tests {
         // signature matches known value
         let expected_request_sig = "7zvbj5mGKir4LxrQCcHCNc6md/487MMiBokumIIq4wEk+kJEFIKP1iBRK2cX8cs9h4XrdvXju3kEh0xdJBTlBw==";
         assert_eq!(
-            base64::encode(&request_sig.signature().to_vec()),
+            base64::encode(&request_sig.signature().unwrap().to_vec()),
             expected_request_sig
         );
 
@@ -1267,7 +2033,7 @@ mod tests {
             "X-IronCore-Request-Sig",
             expected_request_sig.parse().unwrap(),
         );
-        assert_eq!(&request_sig.to_header(), &header);
+        assert_eq!(&request_sig.to_header().unwrap(), &header);
 
         // verify that the signature produced matches can be verified
         assert!(signing_keys.0.public_key().verify(
@@ -1284,12 +2050,12 @@ mod tests {
             Method::POST,
             signing_url_string,
             None,
-            &signing_keys,
+            (&signing_keys).into(),
         );
 
         let mut auth_header_expected = HeaderMap::default();
         auth_header_expected.append("authorization", "IronCore 2.CzATu+yKHO9edYZ6L27EXE4jKlk9p9hBhQsTJjj5ENFk2VhMfLp1ADKfaDQ/Q6u/Q7yHawq9L5Y1BFivdUYSCQ==".parse().unwrap());
-        assert_eq!(auth.to_auth_header(), auth_header_expected);
+        assert_eq!(auth.to_auth_header().unwrap(), auth_header_expected);
     }
 
     #[test]
@@ -0,0 +1,85 @@
+//! Revalidation of API responses that rarely change with ETags.
+//!
+//! A successful `GET` of the current user, a group, or users' public keys that carries an `ETag` is kept,
+//! and the next request for the same URL sends it in `If-None-Match`. If the service answers
+//! `304 Not Modified`, the kept body is used instead of downloading it again.
+
+use crate::internal::{take_lock, RequestErrorCode};
+use bytes::Bytes;
+use reqwest::{header::HeaderValue, Method};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// Most responses kept. The cache is cleared before storing one more.
+const MAX_ENTRIES: usize = 512;
+
+/// Responses kept by an `IronCoreRequest` and its clones, by URL, with their ETags
+#[derive(Clone, Default)]
+pub(crate) struct ResponseCache(Arc<Mutex<HashMap<String, (HeaderValue, Bytes)>>>);
+
+impl fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ResponseCache")
+    }
+}
+
+impl ResponseCache {
+    /// ETag and body of the response kept for `url`
+    pub(crate) fn get(&self, url: &str) -> Option<(HeaderValue, Bytes)> {
+        take_lock(&self.0).get(url).cloned()
+    }
+
+    pub(crate) fn insert(&self, url: String, etag: HeaderValue, body: Bytes) {
+        let mut responses = take_lock(&self.0);
+        if responses.len() >= MAX_ENTRIES && !responses.contains_key(&url) {
+            responses.clear();
+        }
+        responses.insert(url, (etag, body));
+    }
+}
+
+/// Whether responses to requests made with `method` for `error_code` are kept
+pub(crate) fn is_cacheable(method: &Method, error_code: RequestErrorCode) -> bool {
+    *method == Method::GET
+        && match error_code {
+            RequestErrorCode::UserGetCurrent
+            | RequestErrorCode::UserKeyList
+            | RequestErrorCode::GroupGet => true,
+            _ => false,
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_is_cleared_when_full() {
+        let cache = ResponseCache::default();
+        let etag = HeaderValue::from_static("\"v1\"");
+        for i in 0..MAX_ENTRIES {
+            cache.insert(format!("users/{}", i), etag.clone(), Bytes::new());
+        }
+        // replacing a kept response doesn't clear the cache
+        cache.insert("users/0".to_string(), etag.clone(), Bytes::from("{}"));
+        assert_eq!(take_lock(&cache.0).len(), MAX_ENTRIES);
+        assert_eq!(
+            cache.get("users/0"),
+            Some((etag.clone(), Bytes::from("{}")))
+        );
+
+        cache.insert("users/new".to_string(), etag, Bytes::new());
+        assert_eq!(take_lock(&cache.0).len(), 1);
+        assert!(cache.get("users/0").is_none());
+    }
+
+    #[test]
+    fn only_gets_of_keys_and_metadata_are_cacheable() {
+        assert!(is_cacheable(&Method::GET, RequestErrorCode::UserKeyList));
+        assert!(!is_cacheable(&Method::POST, RequestErrorCode::UserKeyList));
+        assert!(!is_cacheable(&Method::GET, RequestErrorCode::DocumentList));
+    }
+}
@@ -0,0 +1,189 @@
+//! Failing over to a secondary deployment of the IronCore API while the primary keeps failing.
+//!
+//! Requests go to the primary until `failure_threshold` of them in a row fail without a response or with
+//! a 5xx status. The circuit then opens and requests go to the secondary. Once `probe_interval` has
+//! passed, one request goes to the primary as a probe: if it succeeds the circuit closes, otherwise
+//! requests keep going to the secondary for another interval. A failed request is never sent again,
+//! since the service may have applied it.
+//!
+//! See [FailoverConfig](../../../config/struct.FailoverConfig.html).
+
+use crate::{
+    config::FailoverConfig,
+    internal::{take_lock, IronOxideErr},
+};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// Which deployment a request was sent to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Endpoint {
+    Primary,
+    Secondary,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    /// When the circuit opened or the primary was last probed. `None` while the circuit is closed.
+    opened_at: Option<Instant>,
+}
+
+/// Secondary endpoint and circuit breaker shared by an `IronCoreRequest` and its clones
+#[derive(Clone)]
+pub(crate) struct Failover {
+    secondary_base_url: String,
+    config: FailoverConfig,
+    state: Arc<Mutex<BreakerState>>,
+}
+
+impl fmt::Debug for Failover {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Failover")
+            .field("secondary_base_url", &self.secondary_base_url)
+            .finish()
+    }
+}
+
+impl Failover {
+    /// Closed circuit breaker for `config`
+    pub(crate) fn new(config: &FailoverConfig) -> Result<Failover, IronOxideErr> {
+        let secondary = url::Url::parse(&config.secondary_base_url).map_err(|e| {
+            IronOxideErr::ValidationError("failover.secondary_base_url".to_string(), e.to_string())
+        })?;
+        if config.failure_threshold == 0 {
+            return Err(IronOxideErr::ValidationError(
+                "failover.failure_threshold".to_string(),
+                "must be at least 1".to_string(),
+            ));
+        }
+        let secondary_base_url = if secondary.as_str().ends_with('/') {
+            secondary.to_string()
+        } else {
+            format!("{}/", secondary)
+        };
+        Ok(Failover {
+            secondary_base_url,
+            config: config.clone(),
+            state: Default::default(),
+        })
+    }
+
+    pub(crate) fn secondary_base_url(&self) -> &str {
+        &self.secondary_base_url
+    }
+
+    /// Where the next request should go. If it's time to probe the primary, this request is the probe.
+    pub(crate) fn endpoint(&self) -> Endpoint {
+        let mut state = take_lock(&self.state);
+        match state.opened_at {
+            None => Endpoint::Primary,
+            Some(opened_at) if opened_at.elapsed() >= self.config.probe_interval => {
+                // requests made while the probe is in flight still go to the secondary
+                state.opened_at = Some(Instant::now());
+                Endpoint::Primary
+            }
+            Some(_) => Endpoint::Secondary,
+        }
+    }
+
+    /// Record whether a request sent to `endpoint` got a response without a 5xx status
+    pub(crate) fn record(&self, endpoint: Endpoint, succeeded: bool) {
+        if endpoint == Endpoint::Secondary {
+            return;
+        }
+        let mut state = take_lock(&self.state);
+        if succeeded {
+            if state.opened_at.is_some() {
+                log::info!("The primary IronCore API has recovered; failing back to it");
+            }
+            *state = BreakerState::default();
+        } else {
+            state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+            if state.opened_at.is_none()
+                && state.consecutive_failures >= self.config.failure_threshold
+            {
+                log::warn!(
+                    "{} requests in a row to the primary IronCore API failed; failing over to {}",
+                    state.consecutive_failures,
+                    self.secondary_base_url
+                );
+                state.opened_at = Some(Instant::now());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::*;
+    use std::time::Duration;
+
+    fn failover(probe_interval: Duration) -> Failover {
+        Failover::new(&FailoverConfig {
+            secondary_base_url: "https://secondary.example.com/api/1".to_string(),
+            failure_threshold: 2,
+            probe_interval,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn new_validates_config() {
+        assert_eq!(
+            failover(Duration::from_secs(30)).secondary_base_url(),
+            "https://secondary.example.com/api/1/"
+        );
+        let bad_url = Failover::new(&FailoverConfig {
+            secondary_base_url: "not a url".to_string(),
+            ..Default::default()
+        });
+        assert_that!(
+            &bad_url.unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        let no_threshold = Failover::new(&FailoverConfig {
+            secondary_base_url: "https://secondary.example.com/api/1/".to_string(),
+            failure_threshold: 0,
+            ..Default::default()
+        });
+        assert_that!(
+            &no_threshold.unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let failover = failover(Duration::from_secs(30));
+        failover.record(Endpoint::Primary, false);
+        failover.record(Endpoint::Primary, true);
+        failover.record(Endpoint::Primary, false);
+        assert_eq!(failover.endpoint(), Endpoint::Primary);
+        failover.record(Endpoint::Primary, false);
+        assert_eq!(failover.endpoint(), Endpoint::Secondary);
+        // failures of the secondary don't affect the circuit
+        failover.record(Endpoint::Secondary, false);
+        assert_eq!(failover.endpoint(), Endpoint::Secondary);
+    }
+
+    #[test]
+    fn probes_primary_and_closes_when_it_recovers() {
+        let failover = failover(Duration::from_secs(0));
+        failover.record(Endpoint::Primary, false);
+        failover.record(Endpoint::Primary, false);
+        // the probe fails, so the circuit stays open
+        assert_eq!(failover.endpoint(), Endpoint::Primary);
+        failover.record(Endpoint::Primary, false);
+        assert!(take_lock(&failover.state).opened_at.is_some());
+
+        assert_eq!(failover.endpoint(), Endpoint::Primary);
+        failover.record(Endpoint::Primary, true);
+        assert!(take_lock(&failover.state).opened_at.is_none());
+        assert_eq!(take_lock(&failover.state).consecutive_failures, 0);
+    }
+}
@@ -0,0 +1,310 @@
+//! Recording requests to the IronCore API to a fixture file and replaying them, with the `test-fixtures`
+//! feature.
+//!
+//! The fixtures of an SDK instance are loaded from its `IronOxideConfig.http_fixtures` when it's
+//! initialized, and are shared with its forks.
+//!
+//! While recording, each response is written to the file with the method and URL path of its request.
+//! While replaying, requests aren't sent: each is answered with the responses recorded for its method and
+//! path, in the order they were recorded, repeating the last one once they run out. Request bodies and
+//! headers hold keys, signatures, and timestamps that differ on every run, so they aren't matched.
+//! Without the feature these are no-ops.
+//!
+//! See [HttpFixtureMode](../../../config/enum.HttpFixtureMode.html).
+
+use crate::internal::{IronOxideErr, RequestErrorCode};
+use bytes::Bytes;
+use reqwest::{header::HeaderMap, Request, StatusCode};
+use std::fmt;
+#[cfg(feature = "test-fixtures")]
+use {
+    crate::{config::HttpFixtureMode, internal::take_lock},
+    reqwest::header::{HeaderName, HeaderValue, SET_COOKIE},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::HashMap,
+        convert::TryFrom,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Status, headers, and body of a response
+pub(crate) type ResponseParts = (StatusCode, HeaderMap, Bytes);
+
+/// Method and URL path, with the query, of a request, which is what fixtures are matched on
+#[cfg(feature = "test-fixtures")]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RequestKey {
+    method: String,
+    path: String,
+}
+
+#[cfg(not(feature = "test-fixtures"))]
+pub(crate) struct RequestKey;
+
+#[cfg(feature = "test-fixtures")]
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    #[serde(flatten)]
+    request: RequestKey,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+#[cfg(feature = "test-fixtures")]
+enum Fixtures {
+    Record {
+        path: PathBuf,
+        interactions: Vec<Interaction>,
+    },
+    Replay {
+        path: PathBuf,
+        interactions: HashMap<RequestKey, Vec<Interaction>>,
+        served: HashMap<RequestKey, usize>,
+    },
+}
+
+/// Fixtures being recorded or replayed, shared with clones
+#[cfg(feature = "test-fixtures")]
+#[derive(Clone)]
+pub(crate) struct HttpFixtures(Arc<Mutex<Fixtures>>);
+
+/// Fixtures can't be recorded or replayed without the `test-fixtures` feature
+#[cfg(not(feature = "test-fixtures"))]
+#[derive(Clone)]
+pub(crate) enum HttpFixtures {}
+
+impl fmt::Debug for HttpFixtures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("HttpFixtures")
+    }
+}
+
+#[cfg(feature = "test-fixtures")]
+impl HttpFixtures {
+    /// Fixtures to record or replay as `mode` says, or `None` to send requests to the service. Fails with
+    /// `IronOxideErr::ValidationError` if the file to replay can't be read.
+    pub(crate) fn load(mode: &HttpFixtureMode) -> Result<Option<HttpFixtures>, IronOxideErr> {
+        let fixtures = match mode {
+            HttpFixtureMode::Live => return Ok(None),
+            HttpFixtureMode::Record(path) => Fixtures::Record {
+                path: path.clone(),
+                interactions: vec![],
+            },
+            HttpFixtureMode::Replay(path) => {
+                let fixture_err =
+                    |e: String| IronOxideErr::ValidationError("http_fixtures".to_string(), e);
+                let file = std::fs::read(path)
+                    .map_err(|e| fixture_err(format!("{}: {}", path.display(), e)))?;
+                let recorded: Vec<Interaction> = serde_json::from_slice(&file)
+                    .map_err(|e| fixture_err(format!("{}: {}", path.display(), e)))?;
+                let mut interactions: HashMap<RequestKey, Vec<Interaction>> = HashMap::new();
+                for interaction in recorded {
+                    interactions
+                        .entry(interaction.request.clone())
+                        .or_default()
+                        .push(interaction);
+                }
+                Fixtures::Replay {
+                    path: path.clone(),
+                    interactions,
+                    served: HashMap::new(),
+                }
+            }
+        };
+        Ok(Some(HttpFixtures(Arc::new(Mutex::new(fixtures)))))
+    }
+}
+
+#[cfg(feature = "test-fixtures")]
+pub(crate) fn request_key(req: &Request) -> RequestKey {
+    let url = req.url();
+    RequestKey {
+        method: req.method().to_string(),
+        path: match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        },
+    }
+}
+
+#[cfg(not(feature = "test-fixtures"))]
+pub(crate) fn request_key(_req: &Request) -> RequestKey {
+    RequestKey
+}
+
+/// The recorded response to the request, if `fixtures` are being replayed
+#[cfg(feature = "test-fixtures")]
+pub(crate) fn replay(
+    fixtures: Option<&HttpFixtures>,
+    key: &RequestKey,
+    error_code: RequestErrorCode,
+) -> Option<Result<ResponseParts, IronOxideErr>> {
+    match &mut *take_lock(&fixtures?.0) {
+        Fixtures::Replay {
+            path,
+            interactions,
+            served,
+        } => Some(match interactions.get(key) {
+            Some(recorded) => {
+                let count = served.entry(key.clone()).or_insert(0);
+                let interaction = &recorded[(*count).min(recorded.len() - 1)];
+                *count += 1;
+                to_response(interaction, error_code)
+            }
+            None => Err(IronOxideErr::RequestError {
+                message: format!(
+                    "{} has no response recorded for {} {}",
+                    path.display(),
+                    key.method,
+                    key.path
+                ),
+                code: error_code,
+                http_status: None,
+                request_id: None,
+            }),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "test-fixtures"))]
+pub(crate) fn replay(
+    _fixtures: Option<&HttpFixtures>,
+    _key: &RequestKey,
+    _error_code: RequestErrorCode,
+) -> Option<Result<ResponseParts, IronOxideErr>> {
+    None
+}
+
+/// Write the response to the request to the fixture file, if `fixtures` are being recorded
+#[cfg(feature = "test-fixtures")]
+pub(crate) fn record(
+    fixtures: Option<&HttpFixtures>,
+    key: RequestKey,
+    (status, headers, body): &ResponseParts,
+    error_code: RequestErrorCode,
+) -> Result<(), IronOxideErr> {
+    let fixtures = match fixtures {
+        Some(fixtures) => fixtures,
+        None => return Ok(()),
+    };
+    if let Fixtures::Record { path, interactions } = &mut *take_lock(&fixtures.0) {
+        let record_err = |e: String| IronOxideErr::RequestError {
+            message: format!("Couldn't record the response to {}: {}", path.display(), e),
+            code: error_code,
+            http_status: Some(status.as_u16()),
+            request_id: None,
+        };
+        interactions.push(Interaction {
+            request: key,
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter(|(name, _)| **name != SET_COOKIE)
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: String::from_utf8(body.to_vec()).map_err(|e| record_err(e.to_string()))?,
+        });
+        // the whole file is rewritten so it's complete whenever the test stops
+        let file =
+            serde_json::to_vec_pretty(&*interactions).map_err(|e| record_err(e.to_string()))?;
+        std::fs::write(&*path, file).map_err(|e| record_err(e.to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "test-fixtures"))]
+pub(crate) fn record(
+    _fixtures: Option<&HttpFixtures>,
+    _key: RequestKey,
+    _response: &ResponseParts,
+    _error_code: RequestErrorCode,
+) -> Result<(), IronOxideErr> {
+    Ok(())
+}
+
+#[cfg(feature = "test-fixtures")]
+fn to_response(
+    interaction: &Interaction,
+    error_code: RequestErrorCode,
+) -> Result<ResponseParts, IronOxideErr> {
+    let fixture_err = |e: String| IronOxideErr::RequestError {
+        message: format!("Recorded response is invalid: {}", e),
+        code: error_code,
+        http_status: None,
+        request_id: None,
+    };
+    let status =
+        StatusCode::from_u16(interaction.status).map_err(|e| fixture_err(e.to_string()))?;
+    let mut headers = HeaderMap::new();
+    for (name, value) in &interaction.headers {
+        headers.append(
+            HeaderName::try_from(name.as_str()).map_err(|e| fixture_err(e.to_string()))?,
+            HeaderValue::try_from(value.as_str()).map_err(|e| fixture_err(e.to_string()))?,
+        );
+    }
+    Ok((status, headers, Bytes::from(interaction.body.clone())))
+}
+
+#[cfg(all(test, feature = "test-fixtures"))]
+mod tests {
+    use super::*;
+    use reqwest::Method;
+
+    #[test]
+    fn recorded_responses_are_replayed_in_order() -> Result<(), IronOxideErr> {
+        let path =
+            std::env::temp_dir().join(format!("ironoxide-fixture-{}.json", std::process::id()));
+        let req = Request::new(
+            Method::GET,
+            "https://api.ironcorelabs.com/api/1/users/current?x=1"
+                .parse()
+                .unwrap(),
+        );
+        let code = RequestErrorCode::UserGetCurrent;
+        let response = |body: &'static str| (StatusCode::OK, HeaderMap::new(), Bytes::from(body));
+
+        assert!(HttpFixtures::load(&HttpFixtureMode::Live)?.is_none());
+        assert!(replay(None, &request_key(&req), code).is_none());
+
+        let recording = HttpFixtures::load(&HttpFixtureMode::Record(path.clone()))?;
+        assert!(replay(recording.as_ref(), &request_key(&req), code).is_none());
+        record(
+            recording.as_ref(),
+            request_key(&req),
+            &response("first"),
+            code,
+        )?;
+        record(
+            recording.as_ref(),
+            request_key(&req),
+            &response("second"),
+            code,
+        )?;
+
+        let replaying = HttpFixtures::load(&HttpFixtureMode::Replay(path.clone()))?;
+        // clones, like the requests of a forked SDK, share what has been served
+        let shared = replaying.clone();
+        let bodies: Vec<Bytes> = vec![replaying.as_ref(), shared.as_ref(), replaying.as_ref()]
+            .into_iter()
+            .map(|fixtures| {
+                replay(fixtures, &request_key(&req), code)
+                    .unwrap()
+                    .map(|(_, _, body)| body)
+            })
+            .collect::<Result<_, _>>()?;
+        assert_eq!(bodies, vec!["first", "second", "second"]);
+        let other = Request::new(Method::DELETE, req.url().clone());
+        assert!(replay(replaying.as_ref(), &request_key(&other), code)
+            .unwrap()
+            .is_err());
+
+        std::fs::remove_file(path).unwrap();
+        Ok(())
+    }
+}
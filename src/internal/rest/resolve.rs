@@ -0,0 +1,329 @@
+//! Resolution of the IronCore API's host name when the system resolver can't be relied on, the proxy
+//! requests to the API go through, the TLS implementation they use, and the pool of connections they are
+//! sent on.
+//!
+//! See [ApiResolutionConfig](../../../config/struct.ApiResolutionConfig.html) and
+//! [ApiProxyConfig](../../../config/enum.ApiProxyConfig.html).
+
+use crate::{
+    config::{ApiProxyConfig, ApiResolutionConfig, HttpTimeoutConfig},
+    internal::{take_lock, IronOxideErr, RequestErrorCode},
+};
+use reqwest::{Client, ClientBuilder, Proxy, Url};
+use std::{
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+#[cfg(feature = "fallback-resolvers")]
+use {
+    std::{net::IpAddr, time::Duration},
+    trust_dns_resolver::{
+        config::{LookupIpStrategy, NameServerConfigGroup, ResolverConfig, ResolverOpts},
+        TokioAsyncResolver,
+    },
+};
+
+/// How long to wait for an answer from a single fallback resolver
+#[cfg(feature = "fallback-resolvers")]
+const RESOLVER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// HTTP client builder using the TLS implementation chosen by the `tls-*` features. rustls is used unless
+/// one of the native TLS features is enabled, in which case native TLS is preferred even if rustls was
+/// compiled in too, so enabling `tls-native` without turning off the default features still switches.
+pub(crate) fn client_builder() -> ClientBuilder {
+    with_tls_backend(Client::builder())
+}
+
+#[cfg(feature = "tls-native")]
+fn with_tls_backend(builder: ClientBuilder) -> ClientBuilder {
+    builder.use_native_tls()
+}
+
+#[cfg(all(not(feature = "tls-native"), feature = "tls-rustls"))]
+fn with_tls_backend(builder: ClientBuilder) -> ClientBuilder {
+    builder.use_rustls_tls()
+}
+
+#[cfg(not(any(feature = "tls-native", feature = "tls-rustls")))]
+fn with_tls_backend(builder: ClientBuilder) -> ClientBuilder {
+    builder
+}
+
+/// Send the requests of `builder` through `proxy`
+fn with_proxy(builder: ClientBuilder, proxy: &ApiProxyConfig) -> reqwest::Result<ClientBuilder> {
+    match proxy {
+        // reqwest reads the proxy environment variables unless told otherwise
+        ApiProxyConfig::System => Ok(builder),
+        ApiProxyConfig::Disabled => Ok(builder.no_proxy()),
+        ApiProxyConfig::Custom { url, credentials } => {
+            let mut proxy = Proxy::all(url.as_str())?;
+            if let Some(credentials) = credentials {
+                proxy = proxy.basic_auth(&credentials.username, &credentials.password);
+            }
+            Ok(builder.proxy(proxy))
+        }
+    }
+}
+
+/// Everything a client is built from
+#[derive(Clone, PartialEq)]
+struct ClientSettings {
+    host: Option<String>,
+    timeouts: HttpTimeoutConfig,
+    proxy: ApiProxyConfig,
+    resolution: ApiResolutionConfig,
+}
+
+/// A client shared by an `IronCoreRequest` and its clones, so their requests reuse pooled connections
+/// instead of making a TLS handshake each time. The client is rebuilt if the settings it was built with
+/// change.
+#[derive(Clone, Default)]
+pub(crate) struct ClientPool(Arc<Mutex<Option<(ClientSettings, Client)>>>);
+
+impl fmt::Debug for ClientPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ClientPool")
+    }
+}
+
+/// Client for a request to `url` with `timeouts`, going through `proxy` and connecting to the addresses
+/// chosen by `resolution`. Taken from `pool` if it has a client built with the same settings, otherwise
+/// built and, if there is a pool, put in it.
+pub(crate) async fn api_client(
+    url: &Url,
+    timeouts: HttpTimeoutConfig,
+    proxy: &ApiProxyConfig,
+    resolution: &ApiResolutionConfig,
+    pool: Option<&ClientPool>,
+    error_code: RequestErrorCode,
+) -> Result<Client, IronOxideErr> {
+    let settings = ClientSettings {
+        host: url.host_str().map(str::to_string),
+        timeouts,
+        proxy: proxy.clone(),
+        resolution: resolution.clone(),
+    };
+    let pooled = pool.and_then(|pool| match &*take_lock(&pool.0) {
+        Some((built_with, client)) if *built_with == settings => Some(client.clone()),
+        _ => None,
+    });
+    if let Some(client) = pooled {
+        return Ok(client);
+    }
+    let client = build_client(url, &settings, error_code).await?;
+    if let Some(pool) = pool {
+        *take_lock(&pool.0) = Some((settings, client.clone()));
+    }
+    Ok(client)
+}
+
+async fn build_client(
+    url: &Url,
+    settings: &ClientSettings,
+    error_code: RequestErrorCode,
+) -> Result<Client, IronOxideErr> {
+    let request_err = |message| IronOxideErr::RequestError {
+        message,
+        code: error_code,
+        http_status: None,
+        request_id: None,
+    };
+    let mut builder =
+        with_proxy(client_builder(), &settings.proxy).map_err(|e| request_err(e.to_string()))?;
+    if let Some(connect_timeout) = settings.timeouts.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    if let Some(request_timeout) = settings.timeouts.request_timeout {
+        builder = builder.timeout(request_timeout);
+    }
+    let resolution = &settings.resolution;
+    let builder = match url.host_str() {
+        Some(host) if !resolution.is_empty() => {
+            let port = url.port_or_known_default().unwrap_or(443);
+            let addrs = resolve_api_addrs(resolution, host, port)
+                .await
+                .map_err(&request_err)?;
+            log::debug!("Connecting to {} using {:?}", host, addrs);
+            builder.resolve_to_addrs(host, &addrs)
+        }
+        _ => builder,
+    };
+    builder.build().map_err(|e| request_err(e.to_string()))
+}
+
+/// Addresses to connect to for `host`. Pinned IPs are used as-is. Otherwise the system resolver is asked
+/// first, then each fallback resolver in turn until one of them answers.
+async fn resolve_api_addrs(
+    resolution: &ApiResolutionConfig,
+    host: &str,
+    port: u16,
+) -> Result<Vec<SocketAddr>, String> {
+    if !resolution.pinned_ips.is_empty() {
+        return Ok(resolution
+            .pinned_ips
+            .iter()
+            .map(|ip| SocketAddr::new(*ip, port))
+            .collect());
+    }
+    resolve_with_fallback(resolution, host, port).await
+}
+
+#[cfg(feature = "fallback-resolvers")]
+async fn resolve_with_fallback(
+    resolution: &ApiResolutionConfig,
+    host: &str,
+    port: u16,
+) -> Result<Vec<SocketAddr>, String> {
+    match tokio::net::lookup_host((host, port)).await {
+        Ok(addrs) => {
+            let addrs: Vec<SocketAddr> = addrs.collect();
+            if !addrs.is_empty() {
+                return Ok(addrs);
+            }
+            log::warn!("System resolver returned no addresses for {}", host);
+        }
+        Err(e) => log::warn!("System resolver failed for {}: {}", host, e),
+    }
+    for resolver in &resolution.fallback_resolvers {
+        match query_resolver(*resolver, host).await {
+            Ok(ips) if !ips.is_empty() => {
+                log::warn!("Resolved {} with fallback resolver {}", host, resolver);
+                return Ok(ips
+                    .into_iter()
+                    .map(|ip| SocketAddr::new(ip, port))
+                    .collect());
+            }
+            Ok(_) => log::warn!(
+                "Fallback resolver {} has no addresses for {}",
+                resolver,
+                host
+            ),
+            Err(e) => log::warn!("Fallback resolver {} failed for {}: {}", resolver, host, e),
+        }
+    }
+    Err(format!(
+        "Could not resolve '{}' with the system resolver or any fallback resolver.",
+        host
+    ))
+}
+
+/// Only pinned IPs can be configured without the `fallback-resolvers` feature, so this isn't reached.
+#[cfg(not(feature = "fallback-resolvers"))]
+async fn resolve_with_fallback(
+    _resolution: &ApiResolutionConfig,
+    host: &str,
+    _port: u16,
+) -> Result<Vec<SocketAddr>, String> {
+    Err(format!("No pinned IPs for '{}'.", host))
+}
+
+/// Ask `resolver` for the IPv6 and IPv4 addresses of `host`. IPv6 addresses are listed first so that they
+/// get the head start when connecting.
+#[cfg(feature = "fallback-resolvers")]
+async fn query_resolver(resolver: SocketAddr, host: &str) -> Result<Vec<IpAddr>, String> {
+    let name_servers =
+        NameServerConfigGroup::from_ips_clear(&[resolver.ip()], resolver.port(), false);
+    let mut opts = ResolverOpts::default();
+    opts.timeout = RESOLVER_TIMEOUT;
+    opts.attempts = 1;
+    opts.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+    // the system's search domains don't apply, so the host name is looked up as given
+    let config = ResolverConfig::from_parts(None, vec![], name_servers);
+    let lookup = TokioAsyncResolver::tokio(config, opts)
+        .map_err(|e| e.to_string())?
+        .lookup_ip(host)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (mut v6, v4): (Vec<IpAddr>, Vec<IpAddr>) = lookup.iter().partition(IpAddr::is_ipv6);
+    v6.extend(v4);
+    Ok(v6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProxyCredentials;
+
+    #[test]
+    fn custom_proxy_url_must_parse() {
+        let proxy = ApiProxyConfig::Custom {
+            url: "http://proxy.example.com:3128".to_string(),
+            credentials: Some(ProxyCredentials {
+                username: "user".to_string(),
+                password: "hunter2".to_string(),
+            }),
+        };
+        assert!(with_proxy(Client::builder(), &proxy).is_ok());
+        assert!(!format!("{:?}", proxy).contains("hunter2"));
+
+        let bad = ApiProxyConfig::Custom {
+            url: "not a url".to_string(),
+            credentials: None,
+        };
+        assert!(with_proxy(Client::builder(), &bad).is_err());
+    }
+
+    #[tokio::test]
+    async fn pooled_client_is_rebuilt_when_settings_change() -> Result<(), IronOxideErr> {
+        let url = Url::parse("https://api.ironcorelabs.com/api/1/users").unwrap();
+        let pool = ClientPool::default();
+        let pooled_settings = || take_lock(&pool.0).as_ref().map(|(s, _)| s.clone());
+
+        api_client(
+            &url,
+            Default::default(),
+            &Default::default(),
+            &Default::default(),
+            Some(&pool),
+            RequestErrorCode::UserGetCurrent,
+        )
+        .await?;
+        let first = pooled_settings().unwrap();
+        assert!(first.timeouts == Default::default());
+        api_client(
+            &url,
+            Default::default(),
+            &Default::default(),
+            &Default::default(),
+            Some(&pool),
+            RequestErrorCode::UserGetCurrent,
+        )
+        .await?;
+        assert!(pooled_settings().unwrap() == first);
+
+        let timeouts = HttpTimeoutConfig {
+            connect_timeout: Some(std::time::Duration::from_secs(1)),
+            ..Default::default()
+        };
+        api_client(
+            &url,
+            timeouts,
+            &Default::default(),
+            &Default::default(),
+            Some(&pool),
+            RequestErrorCode::UserGetCurrent,
+        )
+        .await?;
+        assert!(pooled_settings().unwrap().timeouts == timeouts);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pinned_ips_skip_resolution() {
+        let resolution = ApiResolutionConfig {
+            pinned_ips: vec!["2001:db8::1".parse().unwrap(), "192.0.2.1".parse().unwrap()],
+            ..Default::default()
+        };
+        let addrs = resolve_api_addrs(&resolution, "unresolvable.invalid", 8443)
+            .await
+            .unwrap();
+        assert_eq!(
+            addrs,
+            vec![
+                "[2001:db8::1]:8443".parse::<SocketAddr>().unwrap(),
+                "192.0.2.1:8443".parse().unwrap()
+            ]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Spans for requests to the IronCore API, emitted with the `tracing` feature.
+//!
+//! Each request runs in an `ironcore_request` span with its method and URL path. Once the response has
+//! been read, its status, the number of times the request was retried, and its latency in milliseconds
+//! are recorded on the span. Query strings, headers, and bodies, which can hold keys and tokens, are
+//! never recorded. Without the feature these are no-ops.
+
+use reqwest::{Request, StatusCode};
+use std::{future::Future, time::Duration};
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone)]
+pub(crate) struct Span;
+
+/// Span for a request to `req`'s method and path
+#[cfg(feature = "tracing")]
+pub(crate) fn request_span(req: &Request) -> Span {
+    tracing::info_span!(
+        "ironcore_request",
+        method = %req.method(),
+        path = req.url().path(),
+        status = tracing::field::Empty,
+        retries = tracing::field::Empty,
+        latency_ms = tracing::field::Empty,
+    )
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn request_span(_req: &Request) -> Span {
+    Span
+}
+
+/// Record the outcome of a request on its span
+#[cfg(feature = "tracing")]
+pub(crate) fn record_response(span: &Span, status: StatusCode, retries: u32, latency: Duration) {
+    span.record("status", &status.as_u16());
+    span.record("retries", &retries);
+    span.record("latency_ms", &(latency.as_millis() as u64));
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn record_response(
+    _span: &Span,
+    _status: StatusCode,
+    _retries: u32,
+    _latency: Duration,
+) {
+}
+
+/// Run `fut` in `span`
+#[cfg(feature = "tracing")]
+pub(crate) async fn in_span<F: Future>(span: Span, fut: F) -> F::Output {
+    fut.instrument(span).await
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) async fn in_span<F: Future>(_span: Span, fut: F) -> F::Output {
+    fut.await
+}
@@ -0,0 +1,103 @@
+//! The async runtime pieces the SDK relies on: timers, spawning tasks, and the reactor its requests are
+//! made on.
+//!
+//! The SDK's HTTP client, timers, and tasks are Tokio's, so they need a Tokio runtime to run on. Under
+//! Tokio that is the caller's. With the `runtime-compat` feature, SDK calls made outside of a Tokio
+//! runtime, such as from async-std or smol, are run in the context of a background Tokio runtime that
+//! the SDK starts the first time it's needed, so they work under any executor. The futures are still
+//! polled by the caller's executor; only I/O and timers are driven by the background runtime.
+
+use std::future::Future;
+#[cfg(feature = "runtime-compat")]
+use {
+    lazy_static::lazy_static,
+    std::{
+        pin::Pin,
+        task::{Context, Poll},
+    },
+    tokio::runtime::{Builder, Handle, Runtime},
+};
+
+pub(crate) use tokio::{
+    task::JoinHandle,
+    time::{sleep, timeout, Instant},
+};
+
+#[cfg(feature = "runtime-compat")]
+lazy_static! {
+    static ref BACKGROUND: Runtime = Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("ironoxide-runtime")
+        .enable_all()
+        .build()
+        .expect("background tokio runtime failed to initialize");
+}
+
+/// Run `f` in the context of a Tokio runtime, so it can make requests and use timers whatever executor
+/// it is polled on. Every SDK call runs in one of these.
+#[cfg(feature = "runtime-compat")]
+pub(crate) fn compat<F: Future>(f: F) -> impl Future<Output = F::Output> {
+    Compat { inner: Box::pin(f) }
+}
+
+#[cfg(not(feature = "runtime-compat"))]
+pub(crate) fn compat<F: Future>(f: F) -> impl Future<Output = F::Output> {
+    f
+}
+
+/// Spawn `task` onto the caller's Tokio runtime, or the background runtime if there isn't one
+pub(crate) fn spawn<T>(task: T) -> JoinHandle<T::Output>
+where
+    T: Future + Send + 'static,
+    T::Output: Send + 'static,
+{
+    #[cfg(feature = "runtime-compat")]
+    {
+        match Handle::try_current() {
+            Ok(handle) => handle.spawn(task),
+            Err(_) => BACKGROUND.spawn(task),
+        }
+    }
+    #[cfg(not(feature = "runtime-compat"))]
+    {
+        tokio::spawn(task)
+    }
+}
+
+#[cfg(feature = "runtime-compat")]
+struct Compat<F> {
+    inner: Pin<Box<F>>,
+}
+
+#[cfg(feature = "runtime-compat")]
+impl<F: Future> Future for Compat<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // the caller's runtime is used when there is one, so nothing changes under Tokio
+        let _entered = match Handle::try_current() {
+            Ok(_) => None,
+            Err(_) => Some(BACKGROUND.enter()),
+        };
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+#[cfg(all(test, feature = "runtime-compat"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timers_and_tasks_work_outside_of_tokio() {
+        let slept = futures::executor::block_on(compat(async {
+            sleep(Duration::from_millis(10)).await;
+            timeout(Duration::from_millis(10), futures::future::pending::<()>())
+                .await
+                .is_err()
+        }));
+        assert!(slept);
+        let spawned = futures::executor::block_on(spawn(async { 42 })).unwrap();
+        assert_eq!(spawned, 42);
+    }
+}
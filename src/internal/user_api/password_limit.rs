@@ -0,0 +1,62 @@
+//! Limit on how many users' private keys are being escrowed or unlocked with a password at once.
+//!
+//! See [PasswordDerivationLimiter](../../../config/struct.PasswordDerivationLimiter.html).
+
+use crate::{
+    config::PasswordDerivationLimiter,
+    internal::{rt, IronOxideErr},
+};
+use tokio::sync::OwnedSemaphorePermit;
+
+/// Wait for a turn to derive a key from a password. The derivation should happen while the returned
+/// permit is held. Returns `None` if there is no `limiter`.
+pub(super) async fn acquire(
+    limiter: Option<&PasswordDerivationLimiter>,
+) -> Result<Option<OwnedSemaphorePermit>, IronOxideErr> {
+    match limiter {
+        None => Ok(None),
+        Some(limiter) => {
+            let queue_timeout = limiter.limit().queue_timeout;
+            let timed_out = || IronOxideErr::PasswordDerivationQueueTimeout(queue_timeout);
+            rt::timeout(queue_timeout, limiter.permits().acquire_owned())
+                .await
+                .map_err(|_| timed_out())?
+                // the semaphore is never closed
+                .map_err(|_| timed_out())
+                .map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PasswordDerivationLimit;
+    use galvanic_assert::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn excess_derivations_time_out() -> Result<(), IronOxideErr> {
+        let limiter = PasswordDerivationLimiter::new(PasswordDerivationLimit {
+            max_concurrent: 1,
+            queue_timeout: Duration::from_millis(10),
+        });
+        let shared = limiter.clone();
+        let held = acquire(Some(&limiter)).await?;
+        assert!(held.is_some());
+        let queued = acquire(Some(&shared)).await;
+        let other = PasswordDerivationLimiter::new(limiter.limit().clone());
+        let unrelated = acquire(Some(&other)).await?;
+        drop(held);
+        let after_release = acquire(Some(&shared)).await;
+
+        assert_that!(
+            &queued.unwrap_err(),
+            is_variant!(IronOxideErr::PasswordDerivationQueueTimeout)
+        );
+        assert!(unrelated.is_some());
+        assert!(after_release?.is_some());
+        assert!(acquire(None).await?.is_none());
+        Ok(())
+    }
+}
@@ -1,30 +1,30 @@
 //! User operation requests.
 //! Types and functions defined here should remain private to `user_api`
 
-use crate::{
-    crypto::aes::EncryptedMasterKey,
-    internal::{
+#[cfg(not(feature = "disable-password-escrow"))]
+use crate::crypto::aes::EncryptedMasterKey;
+use crate::internal::{
+    self,
+    rest::{
         self,
-        rest::{
-            self,
-            json::{Base64Standard, PublicKey},
-            Authorization, IronCoreRequest,
-        },
-        user_api::{DeviceName, Jwt, UserId},
-        IronOxideErr, RequestAuth, RequestErrorCode,
+        json::{Base64Standard, PublicKey},
+        Authorization, IronCoreRequest,
     },
+    user_api::{DeviceName, Jwt, UserId},
+    IronOxideErr, RequestAuth, RequestErrorCode,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::convert::TryFrom;
+use std::{convert::TryFrom, marker::PhantomData};
 
 use crate::internal::auth_v2::AuthV2Builder;
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct EncryptedPrivateKey(#[serde(with = "Base64Standard")] pub Vec<u8>);
 
+#[cfg(not(feature = "disable-password-escrow"))]
 impl From<EncryptedMasterKey> for EncryptedPrivateKey {
     fn from(enc_master_key: EncryptedMasterKey) -> Self {
-        EncryptedPrivateKey(enc_master_key.bytes().to_vec())
+        EncryptedPrivateKey(enc_master_key.bytes())
     }
 }
 
@@ -34,6 +34,7 @@ impl From<EncryptedPrivateKey> for internal::user_api::EncryptedPrivateKey {
     }
 }
 
+#[cfg(not(feature = "disable-password-escrow"))]
 impl TryFrom<EncryptedPrivateKey> for EncryptedMasterKey {
     type Error = IronOxideErr;
 
@@ -158,7 +159,82 @@ pub mod user_get {
     }
 }
 
+/// POST /users/deactivate
+pub mod user_deactivate {
+    use super::*;
+    use crate::internal::user_api::DeviceId;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct UserDeactivateResponse {
+        pub(in crate::internal) id: UserId,
+        pub(in crate::internal) deleted_device_ids: Vec<DeviceId>,
+    }
+
+    pub async fn user_deactivate(
+        jwt: &Jwt,
+        request: &IronCoreRequest,
+    ) -> Result<UserDeactivateResponse, IronOxideErr> {
+        request
+            .post_jwt_auth(
+                "users/deactivate",
+                &PhantomData::<u8>,
+                RequestErrorCode::UserDeactivate,
+                &Authorization::JwtAuth(jwt),
+            )
+            .await
+    }
+}
+
+/// GET /users?needsRotation=true
+pub mod user_list {
+    use super::*;
+    use crate::internal::user_api::UserResult;
+    use std::convert::TryInto;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct UserListItem {
+        id: UserId,
+        segment_id: usize,
+        user_master_public_key: PublicKey,
+        needs_rotation: bool,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    pub struct UserListResponse {
+        pub(in crate::internal) result: Vec<UserListItem>,
+    }
+
+    pub async fn user_list_needing_rotation(
+        jwt: &Jwt,
+        request: &IronCoreRequest,
+    ) -> Result<UserListResponse, IronOxideErr> {
+        request
+            .get_jwt_auth(
+                "users?needsRotation=true",
+                RequestErrorCode::UserList,
+                &Authorization::JwtAuth(jwt),
+            )
+            .await
+    }
+
+    impl TryFrom<UserListItem> for UserResult {
+        type Error = IronOxideErr;
+
+        fn try_from(item: UserListItem) -> Result<Self, Self::Error> {
+            Ok(UserResult {
+                account_id: item.id,
+                segment_id: item.segment_id,
+                user_public_key: item.user_master_public_key.try_into()?,
+                needs_rotation: item.needs_rotation,
+            })
+        }
+    }
+}
+
 /// PUT /users/{userId}/keys/{userKeyId}
+#[cfg(not(feature = "disable-password-escrow"))]
 pub mod user_update_private_key {
     use super::*;
     use internal::{rest::json::AugmentationFactor, user_api::UserUpdatePrivateKeyResult};
@@ -184,6 +260,7 @@ pub mod user_update_private_key {
             UserUpdatePrivateKeyResult {
                 user_master_private_key: resp.user_private_key.into(),
                 needs_rotation: resp.needs_rotation,
+                recovery_escrow: None,
             }
         }
     }
@@ -221,9 +298,9 @@ pub mod user_create {
     #[derive(Debug, PartialEq, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct UserCreateResponse {
-        id: String,
+        pub(in crate::internal) id: String,
         status: usize,
-        segment_id: usize,
+        pub(in crate::internal) segment_id: usize,
         pub user_private_key: EncryptedPrivateKey,
         pub user_master_public_key: PublicKey,
         needs_rotation: bool,
@@ -265,6 +342,7 @@ pub mod user_create {
             Ok(UserCreateResult {
                 user_public_key: resp.user_master_public_key.try_into()?,
                 needs_rotation: resp.needs_rotation,
+                recovery_escrow: None,
             })
         }
     }
@@ -307,8 +385,11 @@ pub mod user_key_list {
 pub mod device_add {
     use crate::internal::{
         rest::json::TransformKey,
-        user_api::{requests::PublicKey, DeviceAdd, DeviceId, Jwt},
+        user_api::{
+            requests::PublicKey, DeviceAdd, DeviceCreateOptsStd, DeviceId, DeviceKeyProtection, Jwt,
+        },
     };
+    use std::collections::BTreeMap;
 
     use super::*;
 
@@ -327,6 +408,11 @@ pub mod device_add {
     pub struct Device {
         pub transform_key: TransformKey,
         pub name: Option<DeviceName>,
+        pub protection: DeviceKeyProtection,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub expires: Option<DateTime<Utc>>,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        pub metadata: BTreeMap<String, String>,
     }
 
     #[derive(Debug, Deserialize)]
@@ -338,12 +424,16 @@ pub mod device_add {
         pub name: Option<DeviceName>,
         pub created: DateTime<Utc>,
         pub updated: DateTime<Utc>,
+        #[serde(default)]
+        pub expires: Option<DateTime<Utc>>,
+        #[serde(default)]
+        pub metadata: BTreeMap<String, String>,
     }
 
     pub(crate) async fn user_device_add(
         jwt: &Jwt,
         device_add: &DeviceAdd,
-        name: &Option<DeviceName>,
+        opts: &DeviceCreateOptsStd,
         request: &IronCoreRequest,
     ) -> Result<DeviceAddResponse, IronOxideErr> {
         let req_body: DeviceAddReq = DeviceAddReq {
@@ -352,7 +442,10 @@ pub mod device_add {
             signature: device_add.signature.clone().into(),
             device: Device {
                 transform_key: device_add.transform_key.clone().into(),
-                name: name.clone(),
+                name: opts.name.clone(),
+                protection: opts.protection,
+                expires: opts.expires,
+                metadata: opts.metadata.clone(),
             },
         };
         request
@@ -366,10 +459,55 @@ pub mod device_add {
     }
 }
 
+/// PUT /users/{userId}/devices/current/keys
+#[cfg(not(feature = "disable-password-escrow"))]
+pub mod device_rotate_keys {
+    use crate::internal::{
+        rest::json::TransformKey,
+        user_api::{requests::device_add::DeviceAddResponse, requests::PublicKey, DeviceAdd},
+    };
+
+    use super::*;
+
+    #[derive(Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct DeviceRotateKeysReq {
+        pub timestamp: u64,
+        pub transform_key: TransformKey,
+        #[serde(with = "Base64Standard")]
+        pub signature: Vec<u8>,
+        pub user_public_key: PublicKey,
+    }
+
+    pub(crate) async fn device_rotate_keys(
+        auth: &RequestAuth,
+        device_add: &DeviceAdd,
+    ) -> Result<DeviceAddResponse, IronOxideErr> {
+        let req_body = DeviceRotateKeysReq {
+            timestamp: device_add.signature_ts.timestamp_millis() as u64,
+            transform_key: device_add.transform_key.clone().into(),
+            signature: device_add.signature.clone().into(),
+            user_public_key: device_add.user_public_key.clone().into(),
+        };
+        auth.request
+            .put(
+                &format!(
+                    "users/{}/devices/current/keys",
+                    rest::url_encode(&auth.account_id().0)
+                ),
+                &req_body,
+                RequestErrorCode::UserDeviceKeyUpdate,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
+}
+
 pub mod device_list {
     use chrono::{DateTime, Utc};
 
-    use crate::internal::user_api::{DeviceId, DeviceName, UserDevice};
+    use crate::internal::user_api::{DeviceId, DeviceKeyProtection, DeviceName, UserDevice};
+    use std::collections::BTreeMap;
 
     use super::*;
 
@@ -382,6 +520,12 @@ pub mod device_list {
         created: DateTime<Utc>,
         updated: DateTime<Utc>,
         is_current_device: bool,
+        #[serde(default)]
+        protection: DeviceKeyProtection,
+        #[serde(default)]
+        expires: Option<DateTime<Utc>>,
+        #[serde(default)]
+        metadata: BTreeMap<String, String>,
     }
 
     #[derive(Debug, PartialEq, Deserialize)]
@@ -389,6 +533,22 @@ pub mod device_list {
         pub(in crate::internal) result: Vec<DeviceListItem>,
     }
 
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct DeviceActivityItem {
+        #[serde(rename = "id")]
+        pub(in crate::internal) device_id: DeviceId,
+        #[serde(default)]
+        pub(in crate::internal) last_used: Option<DateTime<Utc>>,
+        #[serde(with = "Base64Standard")]
+        pub(in crate::internal) public_signing_key: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    pub struct DeviceActivityResponse {
+        pub(in crate::internal) result: Vec<DeviceActivityItem>,
+    }
+
     pub async fn device_list(auth: &RequestAuth) -> Result<DeviceListResponse, IronOxideErr> {
         auth.request
             .get(
@@ -399,6 +559,22 @@ pub mod device_list {
             .await
     }
 
+    /// GET /users/{userId}/devices/activity
+    pub async fn device_activity(
+        auth: &RequestAuth,
+    ) -> Result<DeviceActivityResponse, IronOxideErr> {
+        auth.request
+            .get(
+                &format!(
+                    "users/{}/devices/activity",
+                    rest::url_encode(&auth.account_id().0)
+                ),
+                RequestErrorCode::UserDeviceList,
+                AuthV2Builder::new(auth, Utc::now()),
+            )
+            .await
+    }
+
     impl From<DeviceListItem> for UserDevice {
         fn from(resp: DeviceListItem) -> Self {
             UserDevice {
@@ -407,6 +583,11 @@ pub mod device_list {
                 created: resp.created,
                 last_updated: resp.updated,
                 is_current_device: resp.is_current_device,
+                protection: resp.protection,
+                expires: resp.expires,
+                metadata: resp.metadata,
+                last_used: None,
+                signing_key_fingerprint: None,
             }
         }
     }
@@ -0,0 +1,167 @@
+//! Validation of the IDs, names, and policy fields given to the SDK
+//!
+//! The patterns are compiled the first time they're used rather than on every call, since an ID is
+//! validated for every user and group in a grant.
+
+use crate::internal::IronOxideErr;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+const NAME_AND_ID_MAX_LEN: usize = 100;
+
+lazy_static! {
+    static ref ID_REGEX: Regex = Regex::new("^[a-zA-Z0-9_.$#|@/:;=+'-]+$").expect("regex is valid");
+    static ref SIMPLE_POLICY_FIELD_REGEX: Regex =
+        Regex::new("^[A-Za-z0-9_-]+$").expect("regex is valid");
+}
+
+/// Validate that the provided id is valid for our user/document/group IDs. Validates that the
+/// ID has a length and that it matches our restricted set of characters. Also takes the readable
+/// type of ID for usage within any resulting error messages.
+pub fn validate_id(id: &str, id_type: &str) -> Result<String, IronOxideErr> {
+    validate_matching(id, id_type, &ID_REGEX)
+}
+
+/// Validate that the provided document/group name is valid. Ensures that the length of
+/// the name is between 1-100 characters. Also takes the readable type of the name for
+/// usage within any resulting error messages.
+pub fn validate_name(name: &str, name_type: &str) -> Result<String, IronOxideErr> {
+    let trimmed_name = name.trim();
+    if trimmed_name.is_empty() || trimmed_name.len() > NAME_AND_ID_MAX_LEN {
+        Err(IronOxideErr::ValidationError(
+            name_type.to_string(),
+            format!("'{}' must have length between 1 and 100", trimmed_name),
+        ))
+    } else {
+        Ok(trimmed_name.to_string())
+    }
+}
+
+/// Validate the value of a policy's category, sensitivity, or data subject, which is more restricted
+/// than an ID. Also takes the readable type of the field for usage within any resulting error messages.
+pub fn validate_simple_policy_field_value(
+    field_id: &str,
+    field_type: &str,
+) -> Result<String, IronOxideErr> {
+    validate_matching(field_id, field_type, &SIMPLE_POLICY_FIELD_REGEX)
+}
+
+/// Validate that the trimmed value is between 1-100 characters and entirely matches `pattern`
+fn validate_matching(
+    value: &str,
+    value_type: &str,
+    pattern: &Regex,
+) -> Result<String, IronOxideErr> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() || trimmed.len() > NAME_AND_ID_MAX_LEN {
+        Err(IronOxideErr::ValidationError(
+            value_type.to_string(),
+            format!("'{}' must have length between 1 and 100", trimmed),
+        ))
+    } else if !pattern.is_match(trimmed) {
+        Err(IronOxideErr::ValidationError(
+            value_type.to_string(),
+            format!("'{}' contains invalid characters", trimmed),
+        ))
+    } else {
+        Ok(trimmed.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::tests::contains;
+    use galvanic_assert::{matchers::eq, *};
+
+    #[test]
+    fn validate_id_success() {
+        let valid_id = "abcABC012_.$#|@/:;=+'-";
+        let id = validate_id(valid_id, "id_type");
+        assert_that!(&id, is_variant!(Ok));
+        assert_that!(&id.unwrap(), eq(valid_id.to_string()))
+    }
+
+    #[test]
+    fn valid_id_whitespace() {
+        let valid_id = " abc212     ";
+        let id = validate_id(valid_id, "id_type");
+        assert_that!(&id, is_variant!(Ok));
+        assert_that!(&id.unwrap(), eq("abc212".to_string()))
+    }
+
+    #[test]
+    fn validate_id_failure() {
+        let invalid_id = "with spaces";
+        let id_type = "id_type";
+        let id = validate_id(invalid_id, id_type);
+        assert_that!(&id, is_variant!(Err));
+        let validation_error = id.unwrap_err();
+        assert_that!(
+            &validation_error,
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        assert_that!(&format!("{}", validation_error), contains(id_type));
+        assert_that!(&format!("{}", validation_error), contains(invalid_id));
+    }
+
+    #[test]
+    fn validate_id_all_whitespace() {
+        let invalid_id = "     ";
+        let id_type = "id_type";
+        let id = validate_id(invalid_id, id_type);
+        assert_that!(&id, is_variant!(Err));
+        let validation_error = id.unwrap_err();
+        assert_that!(
+            &validation_error,
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        assert_that!(&format!("{}", validation_error), contains(id_type));
+    }
+
+    #[test]
+    fn validate_name_success() {
+        let valid_name = "name with any char _.$#|@/:;=+'-";
+        let id = validate_name(valid_name, "name_type");
+        assert_that!(&id, is_variant!(Ok));
+        assert_that!(&id.unwrap(), eq(valid_name.to_string()))
+    }
+
+    #[test]
+    fn validate_name_surrounding_whitespace() {
+        let valid_name = "   a good name    ";
+        let id = validate_name(valid_name, "name_type");
+        assert_that!(&id, is_variant!(Ok));
+        assert_that!(&id.unwrap(), eq("a good name".to_string()))
+    }
+
+    #[test]
+    fn validate_name_failure() {
+        let name_type = "name_type";
+        let invalid_name = "too many chars 012345678901234567890123456789012345678901234567890123456789012345678901234567890123456789";
+        let name = validate_name(invalid_name, name_type);
+        assert_that!(&name, is_variant!(Err));
+        let validation_error = name.unwrap_err();
+        assert_that!(
+            &validation_error,
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        assert_that!(&format!("{}", validation_error), contains(invalid_name));
+        assert_that!(&format!("{}", validation_error), contains(name_type));
+    }
+
+    #[test]
+    fn validate_name_all_whitespace() {
+        let invalid_name = "        ";
+        let name_type = "name_type";
+
+        let name = validate_name(invalid_name, name_type);
+        assert_that!(&name, is_variant!(Err));
+        let validation_error = name.unwrap_err();
+        assert_that!(
+            &validation_error,
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        assert_that!(&format!("{}", validation_error), contains(name_type));
+    }
+}
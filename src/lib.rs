@@ -23,6 +23,7 @@
 //! # fn get_jwt() -> &'static str {
 //! #     unimplemented!()
 //! # }
+//! # #[cfg(not(feature = "disable-password-escrow"))]
 //! # async fn run() -> Result<(), ironoxide::IronOxideErr> {
 //! # use ironoxide::prelude::*;
 //! // Assuming an external function to get the jwt
@@ -46,13 +47,14 @@
 //! # fn get_jwt() -> &'static str {
 //! #     unimplemented!()
 //! # }
+//! # #[cfg(not(feature = "disable-password-escrow"))]
 //! # async fn run() -> Result<(), ironoxide::IronOxideErr> {
 //! # use ironoxide::prelude::*;
 //! // Assuming an external function to get the jwt
 //! let jwt_str = get_jwt();
 //! let jwt = Jwt::new(jwt_str)?;
 //! let password = "foobar";
-//! let opts = DeviceCreateOpts::new(None);
+//! let opts = DeviceCreateOpts::new(None, DeviceKeyProtection::Exportable);
 //! let device_result = IronOxide::generate_new_device(&jwt, password, &opts, None).await?;
 //! // A `DeviceAddResult` can be converted into a `DeviceContext` used to initialize the SDK
 //! let device_context: DeviceContext = device_result.into();
@@ -146,6 +148,32 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! # Async Runtimes
+//!
+//! IronOxide's requests, timers, and background tasks use Tokio, so by default its futures must be run
+//! on a Tokio runtime. To use the SDK under another executor, such as async-std or smol, enable the
+//! `runtime-compat` feature: calls made outside of a Tokio runtime then do their I/O on a small background
+//! Tokio runtime that the SDK starts when first needed. Applications that can't use async at all can use
+//! the [blocking](blocking/index.html) API instead.
+//!
+//! # Cancellation
+//!
+//! Dropping the future of an SDK call before it completes, for example when a server handler is dropped
+//! because its client disconnected, cancels the call. The SDK doesn't spawn any tasks to make a call, so
+//! nothing keeps running afterwards: requests in flight are aborted and their connections closed, no
+//! further requests or retries are made, and work that hasn't started, such as the transform keys for
+//! chunks of a large `group_add_members` that haven't been sent, is never done. Only the background tasks
+//! of a [DecryptSession](document/session/struct.DecryptSession.html) and of
+//! `secret_source::watch_device_context` outlive a call, and they stop when their session or receivers are
+//! dropped.
+//!
+//! As with a timeout, a cancelled call that changes data in the IronCore service may or may not have been
+//! applied, and one made of several requests, such as adding members to a group in chunks, may have been
+//! partly applied. Reading the data back tells which. A cancelled user private key rotation can be
+//! finished with [resume_rotation](struct.IronOxide.html#method.resume_rotation). The SDK's caches are
+//! only updated with the results of requests that succeeded, so cancelling a call never leaves them
+//! inconsistent.
 
 // required by quick_error or IronOxideErr
 #![recursion_limit = "128"]
@@ -157,69 +185,221 @@ include!(concat!(env!("OUT_DIR"), "/transform.rs"));
 
 pub mod crypto {
     pub mod aes;
+    pub mod cipher;
+    pub mod locked;
+    pub mod provider;
+    pub mod sss;
     pub mod transform;
 }
 mod internal;
 
 pub mod document;
 pub mod group;
+pub mod jwt;
+pub mod local;
+pub mod manager;
+pub mod metrics;
+pub mod offline;
 pub mod policy;
 pub mod prelude;
 pub mod user;
 
+#[cfg(feature = "bench")]
+pub mod bench;
+
 #[cfg(feature = "beta")]
 pub mod search;
 
 #[cfg(feature = "blocking")]
 pub mod blocking;
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+#[cfg(feature = "secret-sources")]
+pub mod secret_source;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
 pub use crate::internal::IronOxideErr;
 
 use crate::{
     common::{DeviceContext, DeviceSigningKeyPair, PublicKey, SdkOperation},
     config::IronOxideConfig,
-    document::UserOrGroup,
-    group::{GroupId, GroupUpdatePrivateKeyResult},
-    internal::{add_optional_timeout, WithKey},
+    document::DocumentOps,
+    group::{GroupId, GroupOps},
+    internal::add_optional_timeout,
     policy::PolicyGrant,
-    user::{UserId, UserResult, UserUpdatePrivateKeyResult},
+    user::{UserId, UserOps, UserResult},
 };
+#[cfg(not(feature = "disable-password-escrow"))]
+use crate::{group::GroupUpdatePrivateKeyResult, user::UserUpdatePrivateKeyResult};
 use dashmap::DashMap;
 use itertools::EitherOrBoth;
 use rand::{
     rngs::{adapter::ReseedingRng, OsRng},
-    SeedableRng,
+    CryptoRng, RngCore, SeedableRng,
 };
-use rand_chacha::ChaChaCore;
 use recrypt::api::{Ed25519, RandomBytes, Recrypt, Sha256};
-use std::{convert::TryInto, fmt, sync::Mutex};
+#[cfg(not(feature = "disable-password-escrow"))]
+use std::convert::TryInto;
+use std::{
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+};
 use vec1::Vec1;
 
 /// A `Result` alias where the Err case is `IronOxideErr`
 pub type Result<T> = std::result::Result<T, IronOxideErr>;
-type PolicyCache = DashMap<PolicyGrant, Vec<WithKey<UserOrGroup>>>;
+type PolicyCache = DashMap<PolicyGrant, internal::document_api::CachedPolicy>;
+type GroupKeyCache = internal::PublicKeyCache<GroupId>;
+type UserKeyCache = internal::PublicKeyCache<UserId>;
 
 // This is where we export structs that don't fit into a single module.
 // They were previously exported at the top level, but added clutter to the docs landing page.
 /// Types useful in multiple modules
 pub mod common {
+    pub use crate::internal::rest::{ApiErrorCode, ServerError};
     pub use crate::internal::{
-        DeviceContext, DeviceSigningKeyPair, PrivateKey, PublicKey, SdkOperation,
+        verify_schnorr, DeviceContext, DeviceSigningKeyPair, Environment, PrivateKey,
+        PrivateKeyProvider, PublicKey, PublicKeyFingerprint, SchnorrSignature, SdkOperation,
+        TransformKey,
     };
 }
 
 /// IronOxide SDK configuration
 pub mod config {
+    use rand::{CryptoRng, RngCore};
     use serde::{Deserialize, Serialize};
-    use std::time::Duration;
+    use std::{
+        fmt,
+        hash::{Hash, Hasher},
+        net::IpAddr,
+        sync::Arc,
+        time::Duration,
+    };
 
     /// Top-level configuration object for IronOxide
+    ///
+    /// Settings of a single SDK instance, given to [initialize](../fn.initialize.html) or
+    /// [initialize_check_rotation](../fn.initialize_check_rotation.html): the IronCore service it talks to,
+    /// how its requests are timed out and retried, and the sizes and lifetimes of its caches. Build one
+    /// with struct update syntax, starting from `IronOxideConfig::default()`. Settings that apply to the
+    /// whole process are set with the functions of this module instead.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ironoxide::{common::Environment, config::*};
+    /// # use std::time::Duration;
+    /// let config = IronOxideConfig {
+    ///     environment: Some(Environment::Staging),
+    ///     http_timeouts: HttpTimeoutConfig {
+    ///         request_timeout: Some(Duration::from_secs(10)),
+    ///         ..Default::default()
+    ///     },
+    ///     retry: RetryConfig {
+    ///         max_retries: 2,
+    ///         ..Default::default()
+    ///     },
+    ///     policy_caching: PolicyCachingConfig {
+    ///         ttl: Some(Duration::from_secs(600)),
+    ///         ..Default::default()
+    ///     },
+    ///     api_proxy: ApiProxyConfig::Custom {
+    ///         url: "http://proxy.example.com:3128".to_string(),
+    ///         credentials: None,
+    ///     },
+    ///     ..Default::default()
+    /// };
+    /// ```
     #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
     pub struct IronOxideConfig {
         /// See [PolicyCachingConfig](struct.PolicyCachingConfig.html)
         pub policy_caching: PolicyCachingConfig,
         /// Timeout for all SDK methods. Will return IronOxideErr::OperationTimedOut on timeout.
         pub sdk_operation_timeout: Option<Duration>,
+        /// IronCore service to make requests to. `None`, the default, keeps the service of the
+        /// `DeviceContext`, which is chosen with the `IRONCORE_ENV` environment variable unless it was set
+        /// with [DeviceContext::with_environment](../common/struct.DeviceContext.html#method.with_environment).
+        /// Not serialized.
+        #[serde(skip)]
+        pub environment: Option<crate::common::Environment>,
+        /// See [HttpTimeoutConfig](struct.HttpTimeoutConfig.html)
+        pub http_timeouts: HttpTimeoutConfig,
+        /// See [RetryConfig](struct.RetryConfig.html)
+        pub retry: RetryConfig,
+        /// Headers added to every request to the IronCore service, such as a tenant identifier, as
+        /// `(name, value)` pairs. They never replace a header the SDK sets itself. To add headers to the
+        /// requests of a single call, see [with_request_headers](../fn.with_request_headers.html).
+        pub extra_headers: Vec<(String, String)>,
+        /// Name and version of the application, such as `"my-app/1.2.0"`, added after the SDK's own in the
+        /// User-Agent of every request so the service's logs can tell applications apart. Requests always
+        /// name the SDK and its version in the User-Agent and the `X-IronCore-SDK` header.
+        pub app_identifier: Option<String>,
+        /// A secondary API to fail over to. `None`, the default, sends every request to the API of the
+        /// `DeviceContext`. See [FailoverConfig](struct.FailoverConfig.html).
+        pub failover: Option<FailoverConfig>,
+        /// Proxy that requests to the IronCore service go through. Requests fail if a `Custom` proxy URL
+        /// can't be parsed. See [ApiProxyConfig](enum.ApiProxyConfig.html).
+        pub api_proxy: ApiProxyConfig,
+        /// See [ApiResolutionConfig](struct.ApiResolutionConfig.html)
+        pub api_resolution: ApiResolutionConfig,
+        /// See [DecryptionConfig](struct.DecryptionConfig.html)
+        pub decryption: DecryptionConfig,
+        /// See [GroupKeyCachingConfig](struct.GroupKeyCachingConfig.html)
+        pub group_key_caching: GroupKeyCachingConfig,
+        /// See [UserKeyCachingConfig](struct.UserKeyCachingConfig.html)
+        pub user_key_caching: UserKeyCachingConfig,
+        /// See [DocumentKeyCachingConfig](struct.DocumentKeyCachingConfig.html)
+        pub document_key_caching: DocumentKeyCachingConfig,
+        /// See [UniqueEncryptionConfig](struct.UniqueEncryptionConfig.html)
+        pub unique_encryption: UniqueEncryptionConfig,
+        /// See [RequestBatchingConfig](struct.RequestBatchingConfig.html)
+        pub request_batching: RequestBatchingConfig,
+        /// If `true`, operations that would change data in the IronCore service fail with
+        /// `IronOxideErr::ReadOnlyMode` before any request is made.
+        pub read_only: bool,
+        /// If `true`, document grants and revokes, document and group name updates, and group description,
+        /// attribute, and ownership updates that fail because the IronCore service couldn't be reached are
+        /// queued to be replayed later. See [OfflineOps](../offline/trait.OfflineOps.html). The queue is held in memory, so
+        /// mutations still queued when the SDK is dropped are lost.
+        pub offline_queue: bool,
+        /// Random number generators used for keys, nonces, and IDs. `None` uses a ChaCha20 generator that
+        /// is seeded and periodically reseeded from the operating system. Not serialized.
+        #[serde(skip)]
+        pub rng_source: Option<RngSource>,
+        /// Whether this SDK instance's requests are sent, recorded, or replayed. See
+        /// [HttpFixtureMode](enum.HttpFixtureMode.html). Not serialized.
+        #[cfg(feature = "test-fixtures")]
+        #[serde(skip)]
+        pub http_fixtures: HttpFixtureMode,
+        /// Sink that the metrics of this SDK instance's operations are reported to. `None`, the default,
+        /// doesn't report them. See the [metrics](../metrics/index.html) module. Not serialized.
+        #[serde(skip)]
+        pub metrics_sink: Option<crate::metrics::SharedMetricsSink>,
+        /// Limit on concurrent password key derivations made by this SDK instance. `None`, the default, is
+        /// unlimited. See [PasswordDerivationLimiter](struct.PasswordDerivationLimiter.html). Not serialized.
+        #[cfg(not(feature = "disable-password-escrow"))]
+        #[serde(skip)]
+        pub password_derivation_limiter: Option<PasswordDerivationLimiter>,
+        /// Function, such as PBKDF2 with a raised iteration count, that this SDK instance escrows the user's
+        /// rotated private key with. `None`, the default, keeps the function and parameters the key was
+        /// escrowed with. Keys escrowed under more than one password always keep theirs. The function is
+        /// recorded with each escrowed key, so keys escrowed with a different one can still be unlocked.
+        #[cfg(not(feature = "disable-password-escrow"))]
+        pub password_kdf: Option<PasswordKdf>,
+        /// How long this SDK instance keeps a key derived from the user's password after using it to unlock
+        /// their private key. `None`, the default, keeps no keys. Flows that unlock the same escrowed key more
+        /// than once, such as verifying the password and then rotating the user's private key, only pay for
+        /// the derivation once. A cached key is only used for the same password, and only keys that unlocked a
+        /// private key are cached.
+        #[cfg(not(feature = "disable-password-escrow"))]
+        pub derived_key_cache_ttl: Option<Duration>,
     }
 
     impl Default for IronOxideConfig {
@@ -227,6 +407,143 @@ pub mod config {
             IronOxideConfig {
                 policy_caching: PolicyCachingConfig::default(),
                 sdk_operation_timeout: Some(Duration::from_secs(30)),
+                environment: None,
+                http_timeouts: HttpTimeoutConfig::default(),
+                retry: RetryConfig::default(),
+                extra_headers: vec![],
+                app_identifier: None,
+                failover: None,
+                api_proxy: ApiProxyConfig::default(),
+                api_resolution: ApiResolutionConfig::default(),
+                decryption: DecryptionConfig::default(),
+                group_key_caching: GroupKeyCachingConfig::default(),
+                user_key_caching: UserKeyCachingConfig::default(),
+                document_key_caching: DocumentKeyCachingConfig::default(),
+                unique_encryption: UniqueEncryptionConfig::default(),
+                request_batching: RequestBatchingConfig::default(),
+                read_only: false,
+                offline_queue: false,
+                rng_source: None,
+                #[cfg(feature = "test-fixtures")]
+                http_fixtures: HttpFixtureMode::Live,
+                metrics_sink: None,
+                #[cfg(not(feature = "disable-password-escrow"))]
+                password_derivation_limiter: None,
+                #[cfg(not(feature = "disable-password-escrow"))]
+                password_kdf: None,
+                #[cfg(not(feature = "disable-password-escrow"))]
+                derived_key_cache_ttl: None,
+            }
+        }
+    }
+
+    /// Makes the random number generators an `IronOxide` uses.
+    ///
+    /// The function is called when the SDK is initialized and again by
+    /// [fork_for_core](../struct.IronOxide.html#method.fork_for_core), and must return an independently
+    /// seeded generator each time. Use it to supply a platform RNG where the operating system's isn't
+    /// available, or a seeded generator in tests. Sources are only equal if they are clones of each other.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ironoxide::config::{IronOxideConfig, RngSource};
+    /// # use rand::SeedableRng;
+    /// let config = IronOxideConfig {
+    ///     rng_source: Some(RngSource::new(rand_chacha::ChaChaRng::from_entropy)),
+    ///     ..Default::default()
+    /// };
+    /// ```
+    #[derive(Clone)]
+    pub struct RngSource(Arc<dyn Fn() -> crate::SdkRng + Send + Sync>);
+
+    impl RngSource {
+        /// Create a source that calls `new_rng` for each generator
+        pub fn new<F, R>(new_rng: F) -> RngSource
+        where
+            F: Fn() -> R + Send + Sync + 'static,
+            R: RngCore + CryptoRng + Send + 'static,
+        {
+            RngSource(Arc::new(move || crate::SdkRng(Box::new(new_rng()))))
+        }
+
+        pub(crate) fn new_rng(&self) -> crate::SdkRng {
+            (self.0)()
+        }
+
+        fn addr(&self) -> usize {
+            &*self.0 as *const (dyn Fn() -> crate::SdkRng + Send + Sync) as *const u8 as usize
+        }
+    }
+
+    impl fmt::Debug for RngSource {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("RngSource")
+        }
+    }
+
+    impl PartialEq for RngSource {
+        fn eq(&self, other: &RngSource) -> bool {
+            self.addr() == other.addr()
+        }
+    }
+
+    impl Eq for RngSource {}
+
+    impl Hash for RngSource {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.addr().hash(state)
+        }
+    }
+
+    /// Path through which the calling user can gain access to a document's symmetric key.
+    ///
+    /// These are the only kinds of EDEK the IronCore service issues; it has no delegated keys, so there is
+    /// no path for them.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub enum DecryptKeyPath {
+        /// The document was encrypted to, or directly granted to, the calling user.
+        User,
+        /// The document was granted to a group the calling user is a member of.
+        Group,
+    }
+
+    /// Document decryption config
+    ///
+    /// Controls which key paths the SDK is allowed to use when decrypting a document, and the order in
+    /// which they are tried.
+    ///
+    /// For unmanaged documents, the EDEKs are filtered down to each key path in turn and the first path
+    /// that can be transformed for the calling user is used. Each path tried costs a request, and the next
+    /// path is only tried if the webservice refuses the previous one as unauthorized or not found; other
+    /// failures are returned right away. The default config leaves the choice to the webservice and makes a
+    /// single request. For managed documents, the webservice
+    /// chooses the key path, and decryption will fail with
+    /// `IronOxideErr::DecryptKeyPathNotAllowed` if the chosen path is not in `key_paths`.
+    #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct DecryptionConfig {
+        /// Key paths to try, in order. An empty list will cause all decryption to fail.
+        pub key_paths: Vec<DecryptKeyPath>,
+    }
+
+    impl DecryptionConfig {
+        /// Strict mode. Only documents that were encrypted or granted directly to the calling user
+        /// can be decrypted; group access is never used.
+        pub fn strict() -> DecryptionConfig {
+            DecryptionConfig {
+                key_paths: vec![DecryptKeyPath::User],
+            }
+        }
+
+        /// Returns true if `key_path` is allowed by this config.
+        pub fn allows(&self, key_path: DecryptKeyPath) -> bool {
+            self.key_paths.contains(&key_path)
+        }
+    }
+
+    impl Default for DecryptionConfig {
+        fn default() -> Self {
+            DecryptionConfig {
+                key_paths: vec![DecryptKeyPath::User, DecryptKeyPath::Group],
             }
         }
     }
@@ -236,18 +553,502 @@ pub mod config {
     /// The lifetime of the cache is the lifetime of the `IronOxide` struct.
     ///
     /// Since policies are evaluated by the webservice, caching the result can greatly speed
-    /// up encrypting a document with a [PolicyGrant](../policy/struct.PolicyGrant.html). There is no expiration of the cache, so
-    /// if you want to clear it at runtime, call [IronOxide::clear_policy_cache](../struct.IronOxide.html#method.clear_policy_cache).
+    /// up encrypting a document with a [PolicyGrant](../policy/struct.PolicyGrant.html). By default cached
+    /// evaluations don't expire, so changes to a policy or to the groups it names aren't seen until the
+    /// cache is cleared; set a `ttl` to have them evaluated again. To clear the cache at runtime, call
+    /// [IronOxide::clear_policy_cache](../struct.IronOxide.html#method.clear_policy_cache).
     #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
     pub struct PolicyCachingConfig {
         /// maximum number of policy evaluations that will be cached by the SDK.
         /// If the maximum number is exceeded, the cache will be cleared prior to storing the next entry
         pub max_entries: usize,
+        /// How long a cached policy evaluation will be used before the policy is evaluated again. `None`,
+        /// the default, uses it until the cache is cleared.
+        #[serde(default)]
+        pub ttl: Option<Duration>,
     }
 
     impl Default for PolicyCachingConfig {
         fn default() -> Self {
-            PolicyCachingConfig { max_entries: 128 }
+            PolicyCachingConfig {
+                max_entries: 128,
+                ttl: None,
+            }
+        }
+    }
+
+    /// Group public key caching config
+    ///
+    /// The cache lives as long as the `IronOxide` that created it. It is shared with the instances
+    /// created by [fork_for_core](../struct.IronOxide.html#method.fork_for_core), and an
+    /// [IronOxideManager](../manager/struct.IronOxideManager.html) shares one cache between the users of
+    /// each segment.
+    ///
+    /// Group public keys fetched while encrypting a document are cached, so encrypting many documents
+    /// to the same group doesn't fetch its key every time. A group's public key doesn't change when its
+    /// private key is rotated, but a deleted group can still be encrypted to until its entry expires. To
+    /// clear the cache at runtime, call [IronOxide::clear_group_key_cache](../struct.IronOxide.html#method.clear_group_key_cache).
+    #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct GroupKeyCachingConfig {
+        /// How long a cached group public key will be used before it is fetched again.
+        /// A `ttl` of zero disables the cache.
+        pub ttl: Duration,
+        /// maximum number of group public keys that will be cached by the SDK.
+        /// If the maximum number is exceeded, the cache will be cleared prior to storing the next entry
+        pub max_entries: usize,
+    }
+
+    impl Default for GroupKeyCachingConfig {
+        fn default() -> Self {
+            GroupKeyCachingConfig {
+                ttl: Duration::from_secs(300),
+                max_entries: 128,
+            }
+        }
+    }
+
+    /// User public key caching config
+    ///
+    /// The cache lives as long as the `IronOxide` that created it. It is shared with the instances
+    /// created by [fork_for_core](../struct.IronOxide.html#method.fork_for_core), and an
+    /// [IronOxideManager](../manager/struct.IronOxideManager.html) shares one cache between the users of
+    /// each segment.
+    ///
+    /// User public keys fetched while encrypting a document are cached, so encrypting many documents
+    /// to the same users doesn't fetch their keys every time. A user's public key doesn't change when
+    /// their private key is rotated, but a deactivated user can still be encrypted to until their entry
+    /// expires. To drop entries at runtime, call [IronOxide::clear_user_key_cache](../struct.IronOxide.html#method.clear_user_key_cache)
+    /// or [IronOxide::invalidate_user_key](../struct.IronOxide.html#method.invalidate_user_key).
+    #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct UserKeyCachingConfig {
+        /// How long a cached user public key will be used before it is fetched again.
+        /// A `ttl` of zero disables the cache.
+        pub ttl: Duration,
+        /// maximum number of user public keys that will be cached by the SDK.
+        /// If the maximum number is exceeded, the cache will be cleared prior to storing the next entry
+        pub max_entries: usize,
+    }
+
+    impl Default for UserKeyCachingConfig {
+        fn default() -> Self {
+            UserKeyCachingConfig {
+                ttl: Duration::from_secs(300),
+                max_entries: 512,
+            }
+        }
+    }
+
+    /// Document key caching config
+    ///
+    /// The lifetime of the cache is the lifetime of the `IronOxide` struct.
+    ///
+    /// When enabled, the symmetric key of each document decrypted by
+    /// [document_decrypt](../document/trait.DocumentOps.html#tymethod.document_decrypt) is cached, so
+    /// decrypting the same document again doesn't fetch and transform its key. Cached keys are held in
+    /// memory unencrypted, and a document whose access was revoked from the calling user can still be
+    /// decrypted until its entry expires. The name and updated time returned with a cached key are those
+    /// of when it was cached, unless the name was updated by the same `IronOxide`. To drop entries at
+    /// runtime, call
+    /// [IronOxide::clear_document_key_cache](../struct.IronOxide.html#method.clear_document_key_cache)
+    /// or [IronOxide::invalidate_document_key](../struct.IronOxide.html#method.invalidate_document_key).
+    #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct DocumentKeyCachingConfig {
+        /// How long a cached document key will be used before it is fetched again.
+        /// A `ttl` of zero disables the cache.
+        pub ttl: Duration,
+        /// maximum number of document keys that will be cached by the SDK. When the cache is full, the
+        /// least recently used key is evicted to store the next one. The default of zero disables the cache.
+        pub max_entries: usize,
+    }
+
+    impl Default for DocumentKeyCachingConfig {
+        fn default() -> Self {
+            DocumentKeyCachingConfig {
+                ttl: Duration::from_secs(60),
+                max_entries: 0,
+            }
+        }
+    }
+
+    /// Index used by `document_encrypt_unique`
+    ///
+    /// [document_encrypt_unique](../document/trait.DocumentOps.html#tymethod.document_encrypt_unique)
+    /// remembers the ID of each document it encrypts, keyed by a hash of the document bytes and their
+    /// audience. The index holds at most `max_entries` entries, evicting the least recently used one to
+    /// store the next, and an entry older than `ttl` is no longer used. To drop entries at runtime, call
+    /// [IronOxide::clear_unique_encryption_index](../struct.IronOxide.html#method.clear_unique_encryption_index)
+    /// or [IronOxide::invalidate_unique_encryption](../struct.IronOxide.html#method.invalidate_unique_encryption).
+    #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct UniqueEncryptionConfig {
+        /// How long an encrypted document is recognized as a duplicate. A `ttl` of zero disables the index.
+        pub ttl: Duration,
+        /// maximum number of documents held in the index. A `max_entries` of zero disables the index.
+        pub max_entries: usize,
+    }
+
+    impl Default for UniqueEncryptionConfig {
+        fn default() -> Self {
+            UniqueEncryptionConfig {
+                ttl: Duration::from_secs(60 * 60),
+                max_entries: 10_000,
+            }
+        }
+    }
+
+    /// Request batching config
+    ///
+    /// Operations on large lists of users, groups, or documents are split into several requests, with at
+    /// most `max_concurrency` of them in flight at once, and their results are merged:
+    /// - [document_grant_access](../document/trait.DocumentOps.html#tymethod.document_grant_access) sends
+    ///   the grants in chunks of at most `chunk_size` users and groups. The users and groups of a chunk whose
+    ///   request fails are reported as failed with its error.
+    /// - Public key lookups for users, such as those made by document encryption, grants, and group
+    ///   operations, send the user IDs in chunks of at most `max_query_len` URL-encoded characters. The
+    ///   lookup fails if any of its requests does.
+    /// - [DecryptSession::preload](../document/struct.DecryptSession.html#method.preload) fetches the keys of
+    ///   its documents.
+    ///
+    /// Values of zero are treated as one.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct RequestBatchingConfig {
+        /// maximum number of users and groups granted access in a single request
+        pub chunk_size: usize,
+        /// maximum number of requests in flight at once for a single operation
+        pub max_concurrency: usize,
+        /// maximum length of the URL-encoded user IDs sent in a single public key lookup
+        pub max_query_len: usize,
+    }
+
+    impl Default for RequestBatchingConfig {
+        fn default() -> Self {
+            RequestBatchingConfig {
+                chunk_size: 250,
+                max_concurrency: 4,
+                max_query_len: 4096,
+            }
+        }
+    }
+
+    /// Timeouts for each HTTP request the SDK makes to the IronCore API
+    ///
+    /// An SDK method can make several requests, so these bound each one, while
+    /// `IronOxideConfig.sdk_operation_timeout` bounds the whole method. A request that times out fails
+    /// with `IronOxideErr::RequestError`. `None` leaves the request unbounded, which is the default.
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct HttpTimeoutConfig {
+        /// Time allowed to connect to the API, including the TLS handshake
+        pub connect_timeout: Option<Duration>,
+        /// Time allowed for a whole request, from connecting until the response body has been read
+        pub request_timeout: Option<Duration>,
+        /// If a request is rate limited and the server asks us to retry after no longer than this, wait and
+        /// send it once more instead of failing with `IronOxideErr::RateLimited`. `None`, the default,
+        /// never retries.
+        pub rate_limit_wait: Option<Duration>,
+    }
+
+    /// Resending requests to the IronCore API that failed for reasons that are likely to pass
+    ///
+    /// Only requests that read data are resent, since a failed request that changes data may have been
+    /// applied. They are resent if they fail without a response, such as when connecting times out, or
+    /// with a 502, 503, or 504 status. Before the `n`th resend the SDK waits `backoff` times `n`.
+    /// Rate limited requests are handled by `HttpTimeoutConfig.rate_limit_wait` instead.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct RetryConfig {
+        /// Number of times a request may be resent. Zero, the default, never resends.
+        pub max_retries: u32,
+        /// How long to wait before the first resend
+        pub backoff: Duration,
+    }
+
+    impl Default for RetryConfig {
+        fn default() -> Self {
+            RetryConfig {
+                max_retries: 0,
+                backoff: Duration::from_millis(250),
+            }
+        }
+    }
+
+    /// A secondary deployment of the IronCore API to fail over to while the primary keeps failing
+    ///
+    /// Requests go to the primary, the API of the `DeviceContext`, until `failure_threshold` of them in a
+    /// row fail without a response or with a 5xx status. The SDK then stops sending requests to the
+    /// primary and sends them to the secondary. Every `probe_interval`, one request is sent to the primary
+    /// instead, and if it succeeds requests go back to the primary. Failed requests are not resent, so the
+    /// requests that open the circuit, and failed probes, still fail. The state is shared by an `IronOxide`
+    /// and its forks.
+    #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct FailoverConfig {
+        /// Base URL of the secondary's API, for example `https://ironcore-dr.example.com/api/1/`
+        pub secondary_base_url: String,
+        /// Number of consecutive failed requests to the primary that make the SDK fail over. Must be at
+        /// least 1.
+        pub failure_threshold: u32,
+        /// How long to wait after failing over, or after a failed probe, before probing the primary
+        pub probe_interval: Duration,
+    }
+
+    impl Default for FailoverConfig {
+        fn default() -> Self {
+            FailoverConfig {
+                secondary_base_url: String::new(),
+                failure_threshold: 5,
+                probe_interval: Duration::from_secs(30),
+            }
+        }
+    }
+
+    /// How the SDK finds the network addresses of the IronCore API
+    ///
+    /// For environments where DNS is broken or filtered. Set per SDK instance with
+    /// [IronOxideConfig.api_resolution](struct.IronOxideConfig.html#structfield.api_resolution). Calls
+    /// made without an SDK instance, such as `user_create` and `user_verify`, use the system resolver.
+    ///
+    /// When the API's host name resolves to both IPv6 and IPv4 addresses, connections are attempted in
+    /// the "happy eyeballs" style: the family of the first address is tried first, and the other family
+    /// is tried if that hasn't connected within 300 milliseconds. Which addresses were used is logged at
+    /// the `debug` level, and falling back to a resolver is logged at the `warn` level.
+    #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct ApiResolutionConfig {
+        /// IP addresses to connect to instead of resolving the API's host name. The port is taken
+        /// from the API URL. TLS certificates are still verified against the host name.
+        pub pinned_ips: Vec<IpAddr>,
+        /// DNS servers, such as `1.1.1.1:53`, that are asked for the API's addresses in order if the
+        /// system resolver fails or has no addresses. Ignored if `pinned_ips` is not empty.
+        ///
+        /// # Optional
+        /// This requires the optional `fallback-resolvers` feature to be enabled.
+        #[cfg(feature = "fallback-resolvers")]
+        pub fallback_resolvers: Vec<std::net::SocketAddr>,
+    }
+
+    impl ApiResolutionConfig {
+        /// Returns true if neither pinned IPs nor fallback resolvers are configured, in which case
+        /// only the system resolver is used.
+        pub fn is_empty(&self) -> bool {
+            #[cfg(feature = "fallback-resolvers")]
+            let no_fallback_resolvers = self.fallback_resolvers.is_empty();
+            #[cfg(not(feature = "fallback-resolvers"))]
+            let no_fallback_resolvers = true;
+            self.pinned_ips.is_empty() && no_fallback_resolvers
+        }
+    }
+
+    /// Proxy that requests to the IronCore API go through
+    ///
+    /// Set per SDK instance with [IronOxideConfig.api_proxy](struct.IronOxideConfig.html#structfield.api_proxy).
+    /// Calls made without an SDK instance, such as `user_create` and `user_verify`, use `System`. Through
+    /// a proxy, the proxy resolves the API's host name, so pinned IPs and fallback resolvers only apply to
+    /// hosts the proxy environment variables exclude.
+    #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub enum ApiProxyConfig {
+        /// The proxy named by the standard environment variables (`HTTPS_PROXY`, `HTTP_PROXY`, and their
+        /// lowercase forms), if any. The default.
+        System,
+        /// Connect directly, ignoring the proxy environment variables
+        Disabled,
+        /// Send all requests through the proxy at `url`, such as `http://proxy.example.com:3128`
+        Custom {
+            url: String,
+            /// Basic authentication for proxies that require it
+            credentials: Option<ProxyCredentials>,
+        },
+    }
+
+    impl Default for ApiProxyConfig {
+        fn default() -> Self {
+            ApiProxyConfig::System
+        }
+    }
+
+    /// Username and password for an authenticated proxy. The password is left out of `Debug` output.
+    #[derive(Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct ProxyCredentials {
+        pub username: String,
+        pub password: String,
+    }
+
+    impl fmt::Debug for ProxyCredentials {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("ProxyCredentials")
+                .field("username", &self.username)
+                .finish()
+        }
+    }
+
+    /// Limit on concurrent password key derivations
+    ///
+    /// Escrowing or unlocking a user's private key derives a key from their password with at least 250,000
+    /// iterations of PBKDF2, which keeps a core busy for a noticeable time. A burst of user creations,
+    /// device generations, or user private key rotations can occupy every core and starve latency
+    /// sensitive work running alongside the SDK. With a limit set, derivations beyond `max_concurrent`
+    /// wait for a turn, and fail with `IronOxideErr::PasswordDerivationQueueTimeout` if they haven't
+    /// started within `queue_timeout`.
+    ///
+    /// Enforced by a [PasswordDerivationLimiter](struct.PasswordDerivationLimiter.html).
+    #[cfg(not(feature = "disable-password-escrow"))]
+    #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub struct PasswordDerivationLimit {
+        /// Maximum number of derivations that run at the same time. Zero is treated as one.
+        pub max_concurrent: usize,
+        /// How long a derivation waits for a turn before failing
+        pub queue_timeout: Duration,
+    }
+
+    /// Function used to derive a key from a user's password when their private key is escrowed
+    ///
+    /// The function and its parameters are recorded with each escrowed key, so a key can always be unlocked
+    /// regardless of which function it was escrowed with. Keys escrowed with Argon2id can only be unlocked by
+    /// SDKs that support it.
+    #[cfg(not(feature = "disable-password-escrow"))]
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+    pub enum PasswordKdf {
+        /// PBKDF2-HMAC-SHA256
+        Pbkdf2Sha256 {
+            /// Number of iterations. At least 250,000, the default.
+            iterations: u32,
+        },
+        /// Argon2id (version 1.3), a memory-hard function. Each derivation holds `memory_kib` of memory,
+        /// so consider a [PasswordDerivationLimit](struct.PasswordDerivationLimit.html).
+        /// Requires the `argon2id` feature, which can't be used with the `fips` feature.
+        Argon2id {
+            /// Memory used by a derivation, in KiB. Between 19,456 (19 MiB) and 1,048,576 (1 GiB).
+            memory_kib: u32,
+            /// Number of passes over the memory. At least 2.
+            iterations: u32,
+            /// Number of lanes. Between 1 and 16.
+            parallelism: u32,
+        },
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    impl PasswordKdf {
+        /// Argon2id with 64 MiB of memory, 3 iterations, and 4 lanes, as recommended by RFC 9106
+        pub fn argon2id() -> PasswordKdf {
+            PasswordKdf::Argon2id {
+                memory_kib: 65_536,
+                iterations: 3,
+                parallelism: 4,
+            }
+        }
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    impl Default for PasswordKdf {
+        /// PBKDF2-HMAC-SHA256, which every IronCore SDK can unlock
+        fn default() -> Self {
+            PasswordKdf::Pbkdf2Sha256 {
+                iterations: crate::crypto::aes::PBKDF2_MIN_ITERATIONS,
+            }
+        }
+    }
+
+    /// Enforces a [PasswordDerivationLimit](struct.PasswordDerivationLimit.html) on the derivations it is
+    /// given to
+    ///
+    /// Clones share their turns, so a single limiter can be given to several SDK instances with
+    /// [IronOxideConfig.password_derivation_limiter](struct.IronOxideConfig.html#structfield.password_derivation_limiter)
+    /// and to calls made without an SDK instance with
+    /// [UserCreateOpts](../user/struct.UserCreateOpts.html#method.with_password_derivation_limiter) and
+    /// [DeviceCreateOpts](../user/struct.DeviceCreateOpts.html#method.with_password_derivation_limiter).
+    ///
+    /// # Examples
+    /// ```
+    /// # use ironoxide::config::{IronOxideConfig, PasswordDerivationLimit, PasswordDerivationLimiter};
+    /// let limiter = PasswordDerivationLimiter::new(PasswordDerivationLimit {
+    ///     max_concurrent: 2,
+    ///     queue_timeout: std::time::Duration::from_secs(5),
+    /// });
+    /// let config = IronOxideConfig {
+    ///     password_derivation_limiter: Some(limiter.clone()),
+    ///     ..Default::default()
+    /// };
+    /// ```
+    #[cfg(not(feature = "disable-password-escrow"))]
+    #[derive(Clone)]
+    pub struct PasswordDerivationLimiter {
+        limit: PasswordDerivationLimit,
+        permits: Arc<tokio::sync::Semaphore>,
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    impl PasswordDerivationLimiter {
+        /// Create a limiter with turns of its own
+        pub fn new(limit: PasswordDerivationLimit) -> PasswordDerivationLimiter {
+            PasswordDerivationLimiter {
+                permits: Arc::new(tokio::sync::Semaphore::new(limit.max_concurrent.max(1))),
+                limit,
+            }
+        }
+
+        /// The limit this limiter enforces
+        pub fn limit(&self) -> &PasswordDerivationLimit {
+            &self.limit
+        }
+
+        pub(crate) fn permits(&self) -> Arc<tokio::sync::Semaphore> {
+            self.permits.clone()
+        }
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    impl fmt::Debug for PasswordDerivationLimiter {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_tuple("PasswordDerivationLimiter")
+                .field(&self.limit)
+                .finish()
+        }
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    impl PartialEq for PasswordDerivationLimiter {
+        fn eq(&self, other: &PasswordDerivationLimiter) -> bool {
+            Arc::ptr_eq(&self.permits, &other.permits)
+        }
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    impl Eq for PasswordDerivationLimiter {}
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    impl Hash for PasswordDerivationLimiter {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            (Arc::as_ptr(&self.permits) as usize).hash(state)
+        }
+    }
+
+    /// Whether requests to the IronCore API are sent, or recorded to or replayed from a fixture file
+    ///
+    /// Recording against a real IronCore service once, then replaying, lets integration tests of an
+    /// application run deterministically without the service or credentials. Responses are matched to
+    /// requests by method and URL path, and are replayed in the order they were recorded for each path,
+    /// so a replaying test must make the same requests as the recording one, with the same IDs and the
+    /// same `DeviceContext`. Fixture files hold the service's responses, such as encrypted keys, but no
+    /// request bodies or headers.
+    ///
+    /// Set per SDK instance with
+    /// [IronOxideConfig.http_fixtures](struct.IronOxideConfig.html#structfield.http_fixtures). The file is
+    /// read, or recorded from scratch, when the instance is initialized, and is shared with its forks.
+    /// Calls made without an SDK instance, such as `user_create`, are always sent to the service.
+    ///
+    /// # Optional
+    /// This requires the optional `test-fixtures` feature to be enabled.
+    #[cfg(feature = "test-fixtures")]
+    #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+    pub enum HttpFixtureMode {
+        /// Send requests to the service. The default.
+        Live,
+        /// Send requests to the service and write their responses to the file, replacing it
+        Record(std::path::PathBuf),
+        /// Answer requests with the responses in the file, without sending them. A request with no
+        /// recorded response fails with `IronOxideErr::RequestError`.
+        Replay(std::path::PathBuf),
+    }
+
+    #[cfg(feature = "test-fixtures")]
+    impl Default for HttpFixtureMode {
+        fn default() -> Self {
+            HttpFixtureMode::Live
         }
     }
 }
@@ -257,17 +1058,43 @@ pub mod config {
 /// Struct that is used to make authenticated requests to the IronCore API. Instantiated with the details
 /// of an account's various ids, device, and signing keys. Once instantiated all operations will be
 /// performed in the context of the account provided.
+///
+/// Requests are sent on a pool of connections that is kept for the life of the instance. Responses that
+/// rarely change, such as users' public keys, are kept and revalidated with their ETags so they are only
+/// downloaded again when they have changed.
+///
+/// The configuration, caches, and pending rotation are shared with instances created by
+/// [fork_for_core](struct.IronOxide.html#method.fork_for_core); the random number generators and
+/// connection pool are not.
+///
+/// Every operation takes `&self`, and `IronOxide` is `Send` and `Sync`, so a single instance can be
+/// shared by the threads and tasks of a server in an `Arc` without a lock around it. Its mutable state,
+/// the random number generators and caches, is synchronized internally.
 pub struct IronOxide {
-    pub(crate) config: IronOxideConfig,
-    pub(crate) recrypt: Recrypt<Sha256, Ed25519, RandomBytes<recrypt::api::DefaultRng>>,
+    pub(crate) config: Arc<IronOxideConfig>,
+    pub(crate) recrypt: Recrypt<Sha256, Ed25519, RandomBytes<SdkRng>>,
     /// Master public key for the user identified by `account_id`
     pub(crate) user_master_pub_key: PublicKey,
     pub(crate) device: DeviceContext,
-    pub(crate) rng: Mutex<ReseedingRng<ChaChaCore, OsRng>>,
-    pub(crate) policy_eval_cache: PolicyCache,
+    pub(crate) rng: Mutex<SdkRng>,
+    pub(crate) policy_eval_cache: Arc<PolicyCache>,
+    pub(crate) group_key_cache: Arc<GroupKeyCache>,
+    pub(crate) user_key_cache: Arc<UserKeyCache>,
+    /// Symmetric keys of recently decrypted documents, when `IronOxideConfig.document_key_caching` is enabled
+    pub(crate) document_key_cache: Arc<internal::document_api::DocumentKeyCache>,
+    /// Keyed hashes of documents encrypted with `document_encrypt_unique`, mapped to their IDs
+    pub(crate) content_hash_index: Arc<internal::document_api::ContentHashIndex>,
+    /// User private key rotation that has not been confirmed by the webservice, encrypted to the device
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub(crate) pending_user_rotation: Arc<Mutex<Option<Vec<u8>>>>,
+    /// Keys derived from the user's password, when `IronOxideConfig.derived_key_cache_ttl` is set
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub(crate) derived_key_cache: Option<Arc<crypto::aes::DerivedKeyCache>>,
+    /// Mutations that couldn't reach the webservice, when `IronOxideConfig.offline_queue` is set
+    pub(crate) offline_queue: Arc<offline::OfflineQueue>,
 }
 
-/// Manual implementation of Debug without the `recrypt` or `rng` fields
+/// Manual implementation of Debug that only includes the config, public key, device, and policy cache
 impl fmt::Debug for IronOxide {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("IronOxide")
@@ -279,6 +1106,22 @@ impl fmt::Debug for IronOxide {
     }
 }
 
+/// The document, group, and user operations of an `IronOxide`, as one trait that can be used as a trait
+/// object.
+///
+/// Code that takes a `&dyn IronOxideOps` or `Arc<dyn IronOxideOps>` instead of an `IronOxide` can be unit
+/// tested with a [MockIronOxide](testing/struct.MockIronOxide.html), from the `testing` feature. The
+/// `UserOps` functions that don't take `&self`, such as `user_create`, can't be called on a trait object.
+pub trait IronOxideOps: DocumentOps + GroupOps + UserOps + Send + Sync {}
+
+impl<T: DocumentOps + GroupOps + UserOps + Send + Sync> IronOxideOps for T {}
+
+// fails to compile if a change to IronOxide's state makes it unsafe to share between threads
+const _: fn() = || {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<IronOxide>();
+};
+
 /// Result of calling `initialize_check_rotation`
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum InitAndRotationCheck<T> {
@@ -311,6 +1154,43 @@ impl<T> InitAndRotationCheck<T> {
 /// number of bytes that can be read from `IronOxide.rng` before it is reseeded. 1 MB
 const BYTES_BEFORE_RESEEDING: u64 = 1024 * 1024;
 
+/// Object-safe combination of `RngCore` and `CryptoRng`
+trait CryptoRngCore: RngCore + CryptoRng + Send {}
+impl<R: RngCore + CryptoRng + Send> CryptoRngCore for R {}
+
+/// Random number generator of an `IronOxide` instance, made by the configured `RngSource`
+pub(crate) struct SdkRng(Box<dyn CryptoRngCore>);
+
+impl RngCore for SdkRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> std::result::Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for SdkRng {}
+
+/// Make a new RNG for an `IronOxide` instance from `rng_source`. Without a source, this seeds a reseeding
+/// RNG, which depends on the system having enough entropy.
+fn new_rng(rng_source: &Option<config::RngSource>) -> SdkRng {
+    match rng_source {
+        Some(source) => source.new_rng(),
+        None => SdkRng(Box::new(ReseedingRng::new(
+            rand_chacha::ChaChaCore::from_entropy(),
+            BYTES_BEFORE_RESEEDING,
+            OsRng::default(),
+        ))),
+    }
+}
+
 /// Provides soft rotation capabilities for user and group keys
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct PrivateKeyRotationCheckResult {
@@ -333,6 +1213,41 @@ impl PrivateKeyRotationCheckResult {
     }
 }
 
+/// Apply the HTTP settings of `config` to a copy of `device_context`
+fn configure_device(
+    device_context: &DeviceContext,
+    config: &IronOxideConfig,
+) -> Result<DeviceContext> {
+    let extra_headers = internal::rest::parse_headers("extra_headers", &config.extra_headers)?;
+    // the environment replaces the device's requests, so it comes before the other settings
+    let device_context = match &config.environment {
+        Some(environment) => device_context.clone().with_environment(environment.clone()),
+        None => device_context.clone(),
+    };
+    let device_context = device_context
+        .with_http_timeouts(config.http_timeouts)
+        .with_retry(config.retry)
+        .with_request_batching(config.request_batching)
+        .with_request_headers(extra_headers)
+        .with_api_proxy(config.api_proxy.clone())
+        .with_api_resolution(config.api_resolution.clone())
+        .with_connection_pool()
+        .with_response_cache();
+    let device_context = match &config.app_identifier {
+        Some(app_identifier) => device_context.with_app_identifier(app_identifier)?,
+        None => device_context,
+    };
+    #[cfg(feature = "test-fixtures")]
+    let device_context = match internal::rest::HttpFixtures::load(&config.http_fixtures)? {
+        Some(fixtures) => device_context.with_http_fixtures(fixtures),
+        None => device_context,
+    };
+    match &config.failover {
+        Some(failover) => device_context.with_failover(failover),
+        None => Ok(device_context),
+    }
+}
+
 /// Initializes the IronOxide SDK with a device.
 ///
 /// Verifies that the provided user/segment exists and the provided device keys are valid and
@@ -341,10 +1256,12 @@ pub async fn initialize(
     device_context: &DeviceContext,
     config: &IronOxideConfig,
 ) -> Result<IronOxide> {
+    let device_context = &configure_device(device_context, config)?;
     internal::add_optional_timeout(
         internal::user_api::user_get_current(device_context.auth()),
         config.sdk_operation_timeout,
         SdkOperation::InitializeSdk,
+        config.metrics_sink.as_ref(),
     )
     .await?
     .map(|current_user| IronOxide::create(&current_user, device_context, config))
@@ -387,6 +1304,7 @@ pub async fn initialize_check_rotation(
     device_context: &DeviceContext,
     config: &IronOxideConfig,
 ) -> Result<InitAndRotationCheck<IronOxide>> {
+    let device_context = &configure_device(device_context, config)?;
     let (curr_user, group_list_result) = add_optional_timeout(
         futures::future::try_join(
             internal::user_api::user_get_current(device_context.auth()),
@@ -394,6 +1312,7 @@ pub async fn initialize_check_rotation(
         ),
         config.sdk_operation_timeout,
         SdkOperation::InitializeSdkCheckRotation,
+        config.metrics_sink.as_ref(),
     )
     .await??;
 
@@ -408,6 +1327,84 @@ pub async fn initialize_check_rotation(
     ))
 }
 
+/// Runs `sdk_call`, adding `headers` to each request it makes to the IronCore service.
+///
+/// Use it to attach per-call headers such as an `X-Request-Id` that correlates the SDK's requests with
+/// the caller's own request. Headers given here take precedence over
+/// [IronOxideConfig.extra_headers](config/struct.IronOxideConfig.html#structfield.extra_headers) and those
+/// of an enclosing `with_request_headers`, but never replace a header the SDK sets itself. Work that
+/// `sdk_call` spawns onto other tasks doesn't get the headers.
+///
+/// Fails with `IronOxideErr::ValidationError` without running `sdk_call` if any header is invalid.
+///
+/// # Examples
+/// ```no_run
+/// # async fn run(sdk: &ironoxide::IronOxide) -> Result<(), ironoxide::IronOxideErr> {
+/// use ironoxide::prelude::*;
+/// let headers = [("X-Request-Id".to_string(), "4f6e0b5c".to_string())];
+/// let _documents = ironoxide::with_request_headers(&headers, sdk.document_list()).await??;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn with_request_headers<F: Future>(
+    headers: &[(String, String)],
+    sdk_call: F,
+) -> Result<F::Output> {
+    let headers = internal::rest::parse_headers("headers", headers)?;
+    Ok(internal::rest::with_call_headers(headers, sdk_call).await)
+}
+
+/// Runs `sdk_call`, failing each SDK operation it runs with `IronOxideErr::OperationTimedOut` if it hasn't
+/// finished within `timeout`.
+///
+/// The timeout bounds the whole operation, including retries and every request it makes, such as the
+/// several round trips of `generate_new_device`. It applies along with
+/// [IronOxideConfig.sdk_operation_timeout](config/struct.IronOxideConfig.html#structfield.sdk_operation_timeout)
+/// and the timeout of an enclosing `with_timeout`, whichever runs out first. Operations run one after
+/// another in `sdk_call` share the same deadline. Work that `sdk_call` spawns onto other tasks doesn't get
+/// the timeout.
+///
+/// # Examples
+/// ```no_run
+/// # async fn run(sdk: &ironoxide::IronOxide) -> Result<(), ironoxide::IronOxideErr> {
+/// use ironoxide::prelude::*;
+/// use std::time::Duration;
+/// let _documents = ironoxide::with_timeout(Duration::from_secs(2), sdk.document_list()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn with_timeout<F: Future>(timeout: std::time::Duration, sdk_call: F) -> F::Output {
+    internal::with_call_timeout(timeout, sdk_call).await
+}
+
+/// Runs `sdk_call` as a dry run: each document encryption, group creation, document grant, or group member
+/// addition it makes runs its client-side validation and key lookups, then stops before the request that
+/// would change anything in the IronCore service.
+///
+/// A dry run that would have succeeded fails with `IronOxideErr::ValidateOnly`. One that would have left out
+/// some of the users or groups, such as users that don't exist, fails with a `ValidationError` naming them,
+/// and one that would have failed outright fails with the error it would have returned. Other operations
+/// are run as usual. The same check can be set on [DocumentEncryptOpts](document/struct.DocumentEncryptOpts.html#method.with_validate_only)
+/// and [GroupCreateOpts](group/struct.GroupCreateOpts.html#method.with_validate_only).
+///
+/// # Examples
+/// ```no_run
+/// # async fn run(sdk: &ironoxide::IronOxide, id: &ironoxide::document::DocumentId) -> Result<(), ironoxide::IronOxideErr> {
+/// use ironoxide::prelude::*;
+/// let grants = vec![UserOrGroup::User {
+///     id: UserId::unsafe_from_string("bob".to_string()),
+/// }];
+/// match ironoxide::validate_only(sdk.document_grant_access(id, &grants)).await {
+///     Err(IronOxideErr::ValidateOnly(_)) => println!("grant would succeed"),
+///     other => println!("grant would not succeed: {:?}", other),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn validate_only<F: Future>(sdk_call: F) -> F::Output {
+    internal::with_validate_only(true, sdk_call).await
+}
+
 impl IronOxide {
     /// DeviceContext that was used to create this SDK instance
     pub fn device(&self) -> &DeviceContext {
@@ -423,6 +1420,110 @@ impl IronOxide {
         size
     }
 
+    /// Clears all entries from the group public key cache.
+    ///
+    /// Returns the number of entries cleared from the cache.
+    pub fn clear_group_key_cache(&self) -> usize {
+        self.group_key_cache.clear()
+    }
+
+    /// Clears all entries from the user public key cache.
+    ///
+    /// Returns the number of entries cleared from the cache.
+    pub fn clear_user_key_cache(&self) -> usize {
+        self.user_key_cache.clear()
+    }
+
+    /// Removes a user's public key from the user public key cache, so it is fetched again the next time
+    /// a document is encrypted to them.
+    ///
+    /// Returns `true` if the user's key was cached.
+    pub fn invalidate_user_key(&self, user: &UserId) -> bool {
+        self.user_key_cache.remove(user)
+    }
+
+    /// Clears all entries from the document key cache.
+    ///
+    /// Returns the number of entries cleared from the cache.
+    pub fn clear_document_key_cache(&self) -> usize {
+        self.document_key_cache.clear()
+    }
+
+    /// Removes a document's key from the document key cache, so it is fetched and transformed again the
+    /// next time the document is decrypted.
+    ///
+    /// Returns `true` if the document's key was cached.
+    pub fn invalidate_document_key(&self, id: &document::DocumentId) -> bool {
+        self.document_key_cache.remove(id)
+    }
+
+    /// Clears the index used by
+    /// [document_encrypt_unique](document/trait.DocumentOps.html#tymethod.document_encrypt_unique), so
+    /// documents encrypted before the call are encrypted again.
+    ///
+    /// Returns the number of entries cleared from the index.
+    pub fn clear_unique_encryption_index(&self) -> usize {
+        self.content_hash_index.clear()
+    }
+
+    /// Removes a document from the index used by
+    /// [document_encrypt_unique](document/trait.DocumentOps.html#tymethod.document_encrypt_unique), for
+    /// example after it was deleted, so the same bytes are encrypted again the next time.
+    ///
+    /// Returns `true` if the document was in the index.
+    pub fn invalidate_unique_encryption(&self, id: &document::DocumentId) -> bool {
+        self.content_hash_index.remove(id)
+    }
+
+    /// Exports the policy cache so it can be reloaded by a later process.
+    ///
+    /// The returned bytes are encrypted with a key derived from this device's private key, so they
+    /// can be written to disk and only an SDK initialized with the same `DeviceContext` can load
+    /// them with [import_policy_cache](struct.IronOxide.html#method.import_policy_cache).
+    pub fn export_policy_cache(&self) -> Result<Vec<u8>> {
+        internal::document_api::export_policy_cache(
+            &self.policy_eval_cache,
+            self.device.device_key().local()?,
+            &self.rng,
+        )
+    }
+
+    /// Loads a policy cache produced by [export_policy_cache](struct.IronOxide.html#method.export_policy_cache).
+    ///
+    /// Entries cached more than `ttl` ago are discarded, and loading stops once the cache reaches
+    /// `PolicyCachingConfig.max_entries`. Returns the number of entries loaded into the cache.
+    ///
+    /// # Arguments
+    /// - `exported` - Bytes returned by `export_policy_cache` on the same device
+    /// - `ttl` - Maximum age of an entry to be loaded
+    pub fn import_policy_cache(&self, exported: &[u8], ttl: std::time::Duration) -> Result<usize> {
+        internal::document_api::import_policy_cache(
+            &self.config.policy_caching,
+            &self.policy_eval_cache,
+            self.device.device_key().local()?,
+            exported,
+            ttl,
+        )
+    }
+
+    /// How this instance derives keys from the user's password
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub(crate) fn password_derivation(&self) -> internal::user_api::PasswordDerivation<'_> {
+        internal::user_api::PasswordDerivation {
+            limiter: self.config.password_derivation_limiter.as_ref(),
+            cache: self.derived_key_cache.as_deref(),
+        }
+    }
+
+    /// Fail with `IronOxideErr::ReadOnlyMode` if `op` is mutating and the SDK is read-only.
+    pub(crate) fn check_writable(&self, op: SdkOperation) -> Result<()> {
+        if self.config.read_only && op.is_mutating() {
+            Err(IronOxideErr::ReadOnlyMode(op))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Create an IronOxide instance. Depends on the system having enough entropy to seed a RNG.
     fn create(
         curr_user: &UserResult,
@@ -430,19 +1531,148 @@ impl IronOxide {
         config: &IronOxideConfig,
     ) -> IronOxide {
         IronOxide {
-            config: config.clone(),
-            recrypt: Recrypt::new(),
+            config: Arc::new(config.clone()),
+            recrypt: Recrypt::new_with_rand(new_rng(&config.rng_source)),
             device: device_context.clone(),
             user_master_pub_key: curr_user.user_public_key().to_owned(),
-            rng: Mutex::new(ReseedingRng::new(
-                rand_chacha::ChaChaCore::from_entropy(),
-                BYTES_BEFORE_RESEEDING,
-                OsRng::default(),
-            )),
-            policy_eval_cache: DashMap::new(),
+            rng: Mutex::new(new_rng(&config.rng_source)),
+            policy_eval_cache: Arc::new(DashMap::new()),
+            group_key_cache: Arc::new(Default::default()),
+            user_key_cache: Arc::new(Default::default()),
+            document_key_cache: Arc::new(Default::default()),
+            content_hash_index: Arc::new(Default::default()),
+            #[cfg(not(feature = "disable-password-escrow"))]
+            pending_user_rotation: Arc::new(Mutex::new(None)),
+            #[cfg(not(feature = "disable-password-escrow"))]
+            derived_key_cache: config
+                .derived_key_cache_ttl
+                .map(|ttl| Arc::new(crypto::aes::DerivedKeyCache::new(ttl))),
+            offline_queue: Arc::new(Default::default()),
+        }
+    }
+
+    /// Creates another `IronOxide` for the same device, to be used on a different core of a
+    /// thread-per-core runtime.
+    ///
+    /// The new instance shares this instance's configuration and all of its caches and other state, so
+    /// work done on one core is visible to the others. It has its own random number generators, so
+    /// encrypting on one core never waits on a lock held by another, and its own pool of connections to
+    /// the webservice.
+    ///
+    /// Unlike [initialize](fn.initialize.html), this does not make any requests to the webservice.
+    pub fn fork_for_core(&self) -> IronOxide {
+        IronOxide {
+            config: self.config.clone(),
+            recrypt: Recrypt::new_with_rand(new_rng(&self.config.rng_source)),
+            device: self.device.clone().with_connection_pool(),
+            user_master_pub_key: self.user_master_pub_key.clone(),
+            rng: Mutex::new(new_rng(&self.config.rng_source)),
+            policy_eval_cache: self.policy_eval_cache.clone(),
+            group_key_cache: self.group_key_cache.clone(),
+            user_key_cache: self.user_key_cache.clone(),
+            document_key_cache: self.document_key_cache.clone(),
+            content_hash_index: self.content_hash_index.clone(),
+            #[cfg(not(feature = "disable-password-escrow"))]
+            pending_user_rotation: self.pending_user_rotation.clone(),
+            #[cfg(not(feature = "disable-password-escrow"))]
+            derived_key_cache: self.derived_key_cache.clone(),
+            offline_queue: self.offline_queue.clone(),
+        }
+    }
+
+    /// Rotate the calling user's private key, keeping the augmented key until the webservice has
+    /// confirmed the update so it can be retried with `resume_rotation`.
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub(crate) async fn rotate_user_private_key(
+        &self,
+        password: internal::Password,
+        recovery_public_key: Option<&PublicKey>,
+    ) -> Result<UserUpdatePrivateKeyResult> {
+        let auth = self.device.auth();
+        let pending = internal::user_api::prepare_user_rotation(
+            &self.recrypt,
+            &self.rng,
+            password,
+            recovery_public_key,
+            self.config.password_kdf,
+            self.password_derivation(),
+            auth,
+        )
+        .await?;
+        let encrypted = pending.encrypt(self.device.device_key().local()?, &self.rng)?;
+        *internal::take_lock(&self.pending_user_rotation) = Some(encrypted);
+        let result = internal::user_api::apply_user_rotation(auth, &pending).await?;
+        *internal::take_lock(&self.pending_user_rotation) = None;
+        Ok(result)
+    }
+
+    /// Retries a user private key rotation that did not complete.
+    ///
+    /// [user_rotate_private_key](user\/trait.UserOps.html#tymethod.user_rotate_private_key) and
+    /// [rotate_all](struct.IronOxide.html#method.rotate_all) keep the augmented private key, encrypted
+    /// to this device, until the webservice confirms the update. If that request failed or timed out,
+    /// this sends the same update again instead of computing a new one, and reports success if the
+    /// earlier request turns out to have been applied.
+    ///
+    /// Returns `None` if there is no pending rotation. If the user's private key was changed some
+    /// other way in the meantime, the pending rotation is discarded and this fails with
+    /// `IronOxideErr::UserPrivateKeyRotationError`.
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub async fn resume_rotation(&self) -> Result<Option<UserUpdatePrivateKeyResult>> {
+        self.check_writable(SdkOperation::UserResumeRotation)?;
+        let encrypted = match internal::take_lock(&self.pending_user_rotation).clone() {
+            Some(encrypted) => encrypted,
+            None => return Ok(None),
+        };
+        let pending = internal::user_api::PendingUserRotation::decrypt(
+            &encrypted,
+            self.device.device_key().local()?,
+        )?;
+        let resumed = add_optional_timeout(
+            internal::user_api::resume_user_rotation(self.device.auth(), &pending),
+            self.config.sdk_operation_timeout,
+            SdkOperation::UserResumeRotation,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await??;
+        *internal::take_lock(&self.pending_user_rotation) = None;
+        match resumed {
+            internal::user_api::ResumedUserRotation::Applied(result) => Ok(Some(result)),
+            internal::user_api::ResumedUserRotation::Superseded => {
+                Err(IronOxideErr::UserPrivateKeyRotationError(
+                    "The user's private key changed after the pending rotation was started".into(),
+                ))
+            }
         }
     }
 
+    /// Exports the pending user private key rotation, if there is one.
+    ///
+    /// The returned bytes are encrypted with a key derived from this device's private key. Writing
+    /// them to disk lets a later process finish the rotation by loading them with
+    /// [import_pending_rotation](struct.IronOxide.html#method.import_pending_rotation) and calling
+    /// [resume_rotation](struct.IronOxide.html#method.resume_rotation).
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn export_pending_rotation(&self) -> Option<Vec<u8>> {
+        internal::take_lock(&self.pending_user_rotation).clone()
+    }
+
+    /// Loads a pending rotation produced by
+    /// [export_pending_rotation](struct.IronOxide.html#method.export_pending_rotation) on the same
+    /// device, replacing any rotation already pending in this SDK.
+    ///
+    /// # Arguments
+    /// - `exported` - Bytes returned by `export_pending_rotation`
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn import_pending_rotation(&self, exported: &[u8]) -> Result<()> {
+        internal::user_api::PendingUserRotation::decrypt(
+            exported,
+            self.device.device_key().local()?,
+        )?;
+        *internal::take_lock(&self.pending_user_rotation) = Some(exported.to_vec());
+        Ok(())
+    }
+
     /// Rotate the private key of the calling user and all groups they are an administrator of where needs_rotation is true.
     /// Note that this function has the potential to take much longer than other functions, as rotation will be done
     /// individually on each user/group. If rotation is only needed for a specific group, it is strongly recommended
@@ -453,6 +1683,7 @@ impl IronOxide {
     /// - `password` - Password to unlock the current user's user master key
     /// - `timeout` - timeout for rotate_all. This is a separate timeout from the SDK-wide timeout as it is
     /// expected that this operation might take significantly longer than other operations.
+    #[cfg(not(feature = "disable-password-escrow"))]
     pub async fn rotate_all(
         &self,
         rotations: &PrivateKeyRotationCheckResult,
@@ -462,14 +1693,11 @@ impl IronOxide {
         Option<UserUpdatePrivateKeyResult>,
         Option<Vec<GroupUpdatePrivateKeyResult>>,
     )> {
+        self.check_writable(SdkOperation::RotateAll)?;
         let valid_password: internal::Password = password.try_into()?;
-        let user_future = rotations.user_rotation_needed().map(|_| {
-            internal::user_api::user_rotate_private_key(
-                &self.recrypt,
-                valid_password,
-                self.device().auth(),
-            )
-        });
+        let user_future = rotations
+            .user_rotation_needed()
+            .map(|_| self.rotate_user_private_key(valid_password, None));
         let group_futures = rotations.group_rotation_needed().map(|groups| {
             let group_futures = groups
                 .into_iter()
@@ -478,7 +1706,7 @@ impl IronOxide {
                         &self.recrypt,
                         self.device().auth(),
                         group_id,
-                        self.device().device_private_key(),
+                        self.device().device_key(),
                     )
                 })
                 .collect::<Vec<_>>();
@@ -490,6 +1718,7 @@ impl IronOxide {
             futures::future::join(user_opt_future, group_opt_future),
             timeout,
             SdkOperation::RotateAll,
+            self.config.metrics_sink.as_ref(),
         )
         .await?;
         let group_opt_result_vec = group_opt_vec_result.map(|g| g.into_iter().collect());
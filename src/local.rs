@@ -0,0 +1,52 @@
+//! Local-only document API
+//!
+//! Functions that work entirely on the bytes they're given, without an initialized SDK, a `DeviceContext`,
+//! or any network configuration, for pipelines that process encrypted documents where the IronCore service
+//! can't be reached. They never make a request.
+//!
+//! Decrypting unmanaged documents with known EDEKs is out of scope for this module: an EDEK is encrypted to a
+//! user or group, and only the service can transform it to the caller's device, so decryption always needs a
+//! request. What can be done locally is inspecting encrypted documents and unmanaged EDEKs, for example to
+//! route each document to a service that has access to it. The AES utilities in [crypto::aes](../crypto/aes/index.html) are also
+//! local.
+
+pub use crate::internal::document_api::DocumentHeaderInfo;
+use crate::{
+    document::{DocumentId, UserOrGroup},
+    internal::document_api,
+    Result,
+};
+
+/// Returns the ID of an encrypted document. Same as
+/// [document_get_id_from_bytes](../document/trait.DocumentOps.html#tymethod.document_get_id_from_bytes).
+///
+/// # Arguments
+/// - `encrypted_document` - Bytes of the encrypted document, or at least its header
+pub fn document_get_id_from_bytes(encrypted_document: &[u8]) -> Result<DocumentId> {
+    document_api::get_id_from_bytes(encrypted_document)
+}
+
+/// Returns the header of an encrypted document: its ID, its segment, and how its data was encrypted.
+///
+/// # Arguments
+/// - `encrypted_document` - Bytes of the encrypted document, or at least its header
+pub fn document_get_header(encrypted_document: &[u8]) -> Result<DocumentHeaderInfo> {
+    document_api::get_header_from_bytes(encrypted_document)
+}
+
+/// Returns the users and groups that the EDEKs of an unmanaged document were encrypted to, which are those
+/// that can decrypt it.
+///
+/// Fails with `IronOxideErr::UnmanagedDecryptionError` if `encrypted_document` is given and the EDEKs are
+/// for a different document.
+///
+/// # Arguments
+/// - `encrypted_deks` - EDEKs returned from
+///   [document_encrypt_unmanaged](../document/advanced/trait.DocumentAdvancedOps.html#tymethod.document_encrypt_unmanaged)
+/// - `encrypted_document` - Bytes of the encrypted document the EDEKs should be for, or at least its header
+pub fn unmanaged_edek_recipients(
+    encrypted_deks: &[u8],
+    encrypted_document: Option<&[u8]>,
+) -> Result<Vec<UserOrGroup>> {
+    document_api::get_edek_recipients(encrypted_deks, encrypted_document)
+}
@@ -0,0 +1,138 @@
+//! Multi-identity SDK manager
+//!
+//! A backend that acts for many users, such as one service account per tenant, can hold a device for each
+//! of them in an [IronOxideManager](struct.IronOxideManager.html) instead of initializing and keeping track of
+//! an `IronOxide` per user. All of the manager's users send their requests on one pool of connections to
+//! the IronCore service, and users in the same segment share group and user public key caches, so a key
+//! fetched for one tenant isn't fetched again for the next. Everything that depends on who the user is,
+//! such as the policy cache and pending user key rotation, is kept per user.
+
+use crate::{
+    common::DeviceContext,
+    config::IronOxideConfig,
+    internal::{rest::IronCoreRequest, take_lock},
+    user::UserId,
+    GroupKeyCache, IronOxide, Result, UserKeyCache,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Public key caches shared by the users of a segment
+#[derive(Default)]
+struct SegmentCaches {
+    group_key_cache: Arc<GroupKeyCache>,
+    user_key_cache: Arc<UserKeyCache>,
+}
+
+/// SDK instances for multiple users, keyed by their `UserId`
+///
+/// Users are added with [add_device](#method.add_device), which initializes the SDK for their device, and
+/// used through the handle returned by [as_user](#method.as_user), which has all of the operations of an
+/// `IronOxide`. Every user is initialized with the manager's `IronOxideConfig`.
+///
+/// # Examples
+/// ```no_run
+/// # use ironoxide::prelude::*;
+/// # async fn run(tenant_a: DeviceContext, tenant_b: DeviceContext) -> Result<(), IronOxideErr> {
+/// let manager = IronOxideManager::new(&IronOxideConfig::default());
+/// let tenant_a_id = manager.add_device(&tenant_a).await?.device().account_id().clone();
+/// manager.add_device(&tenant_b).await?;
+/// if let Some(sdk) = manager.as_user(&tenant_a_id) {
+///     sdk.document_encrypt(b"secret", &Default::default()).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct IronOxideManager {
+    config: IronOxideConfig,
+    /// Request whose pool of connections every user's requests are sent on
+    connections: IronCoreRequest,
+    segment_caches: Mutex<HashMap<usize, SegmentCaches>>,
+    users: Mutex<HashMap<UserId, Arc<IronOxide>>>,
+}
+
+impl IronOxideManager {
+    /// Creates a manager without any users. Users added to it are initialized with `config`.
+    pub fn new(config: &IronOxideConfig) -> IronOxideManager {
+        IronOxideManager {
+            config: config.clone(),
+            connections: IronCoreRequest::default().with_connection_pool(),
+            segment_caches: Mutex::new(HashMap::new()),
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Configuration every user is initialized with
+    pub fn config(&self) -> &IronOxideConfig {
+        &self.config
+    }
+
+    /// Initializes the SDK for the user of `device_context` and adds it to the manager, replacing the
+    /// user's previous device if they had one. See [initialize](../fn.initialize.html).
+    ///
+    /// Returns the handle for the user, which is also returned by `as_user`.
+    pub async fn add_device(&self, device_context: &DeviceContext) -> Result<Arc<IronOxide>> {
+        let sdk = crate::initialize(device_context, &self.config).await?;
+        Ok(self.add(sdk))
+    }
+
+    /// Handle for `user`, if they have been added to the manager
+    pub fn as_user(&self, user: &UserId) -> Option<Arc<IronOxide>> {
+        take_lock(&self.users).get(user).cloned()
+    }
+
+    /// Removes `user` from the manager, returning their handle if they had been added. Handles that are
+    /// still held keep working.
+    pub fn remove_user(&self, user: &UserId) -> Option<Arc<IronOxide>> {
+        take_lock(&self.users).remove(user)
+    }
+
+    /// IDs of the users that have been added to the manager
+    pub fn users(&self) -> Vec<UserId> {
+        take_lock(&self.users).keys().cloned().collect()
+    }
+
+    /// Share the manager's connections and the caches of `sdk`'s segment with `sdk`, and add it
+    fn add(&self, mut sdk: IronOxide) -> Arc<IronOxide> {
+        sdk.device = sdk.device.with_connection_pool_of(&self.connections);
+        {
+            let mut segment_caches = take_lock(&self.segment_caches);
+            let caches = segment_caches.entry(sdk.device.segment_id()).or_default();
+            sdk.group_key_cache = caches.group_key_cache.clone();
+            sdk.user_key_cache = caches.user_key_cache.clone();
+        }
+        let sdk = Arc::new(sdk);
+        take_lock(&self.users).insert(sdk.device.account_id().clone(), sdk.clone());
+        sdk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::tests::create_test_sdk;
+
+    #[test]
+    fn added_users_share_segment_caches() -> Result<()> {
+        let manager = IronOxideManager::new(&Default::default());
+        let first = manager.add(create_test_sdk()?);
+        let user = first.device().account_id().clone();
+        assert_eq!(manager.users(), vec![user.clone()]);
+
+        // the same user again replaces their handle, in the same segment
+        let second = manager.add(create_test_sdk()?);
+        assert!(Arc::ptr_eq(&manager.as_user(&user).unwrap(), &second));
+        assert!(Arc::ptr_eq(&first.group_key_cache, &second.group_key_cache));
+        assert!(Arc::ptr_eq(&first.user_key_cache, &second.user_key_cache));
+        assert!(!Arc::ptr_eq(
+            &first.policy_eval_cache,
+            &second.policy_eval_cache
+        ));
+
+        assert!(manager.remove_user(&user).is_some());
+        assert!(manager.as_user(&user).is_none());
+        Ok(())
+    }
+}
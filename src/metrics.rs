@@ -0,0 +1,229 @@
+//! SDK metrics API
+//!
+//! Every operation of an SDK instance reports which operation it was, how long it took, and whether it
+//! succeeded to the [MetricsSink](trait.MetricsSink.html) in its
+//! [IronOxideConfig.metrics_sink](../config/struct.IronOxideConfig.html#structfield.metrics_sink), so an
+//! application can ship them to Prometheus, StatsD, or another metrics system without parsing the SDK's
+//! logs. Encryption operations also report how many bytes they encrypted. Operations made without an SDK
+//! instance, such as `user_create`, aren't reported.
+//!
+//! # Examples
+//! ```
+//! # use ironoxide::{config::IronOxideConfig, metrics::*};
+//! # use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+//! #[derive(Default)]
+//! struct BytesEncrypted(AtomicUsize);
+//!
+//! impl MetricsSink for BytesEncrypted {
+//!     fn record(&self, metrics: &OperationMetrics) {
+//!         if let Some(bytes) = metrics.bytes_encrypted() {
+//!             self.0.fetch_add(bytes, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! let bytes_encrypted = Arc::new(BytesEncrypted::default());
+//! let config = IronOxideConfig {
+//!     metrics_sink: Some(SharedMetricsSink::new(bytes_encrypted.clone())),
+//!     ..Default::default()
+//! };
+//! ```
+
+use crate::{common::SdkOperation, IronOxideErr};
+use std::{
+    fmt,
+    future::Future,
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+tokio::task_local! {
+    /// Size of the data encrypted by the SDK operation run by `with_bytes_encrypted`
+    static BYTES_ENCRYPTED: usize;
+}
+
+/// Receives the metrics of every operation of the SDK instances it is configured for.
+///
+/// `record` is called on the task that made the operation, once it finishes, so it should hand the metrics
+/// off or update counters rather than block.
+pub trait MetricsSink: Send + Sync {
+    fn record(&self, metrics: &OperationMetrics);
+}
+
+/// A [MetricsSink](trait.MetricsSink.html) that can be put in an `IronOxideConfig`. Clones report to the
+/// same sink, and are equal to each other.
+#[derive(Clone)]
+pub struct SharedMetricsSink(Arc<dyn MetricsSink>);
+
+impl SharedMetricsSink {
+    pub fn new(sink: Arc<dyn MetricsSink>) -> SharedMetricsSink {
+        SharedMetricsSink(sink)
+    }
+
+    fn addr(&self) -> usize {
+        &*self.0 as *const dyn MetricsSink as *const u8 as usize
+    }
+}
+
+impl fmt::Debug for SharedMetricsSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SharedMetricsSink")
+    }
+}
+
+impl PartialEq for SharedMetricsSink {
+    fn eq(&self, other: &SharedMetricsSink) -> bool {
+        self.addr() == other.addr()
+    }
+}
+
+impl Eq for SharedMetricsSink {}
+
+impl Hash for SharedMetricsSink {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state)
+    }
+}
+
+/// Metrics of a single SDK operation
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OperationMetrics {
+    operation: SdkOperation,
+    duration: Duration,
+    succeeded: bool,
+    bytes_encrypted: Option<usize>,
+}
+
+impl OperationMetrics {
+    /// The operation. Its `Display` form is a name suitable for a metric label.
+    pub fn operation(&self) -> SdkOperation {
+        self.operation
+    }
+    /// How long the operation took, including its requests, retries, and any time spent waiting to be
+    /// polled
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+    /// Whether the operation succeeded. An operation that timed out or returned an error didn't.
+    pub fn succeeded(&self) -> bool {
+        self.succeeded
+    }
+    /// Size of the plaintext the operation encrypted, for operations that encrypt data
+    pub fn bytes_encrypted(&self) -> Option<usize> {
+        self.bytes_encrypted
+    }
+}
+
+/// Result of an SDK operation, which tells whether it succeeded
+pub(crate) trait Outcome {
+    fn succeeded(&self) -> bool;
+}
+
+impl<T, E> Outcome for std::result::Result<T, E> {
+    fn succeeded(&self) -> bool {
+        self.is_ok()
+    }
+}
+
+// operations made of several requests whose results are kept apart, like rotate_all, succeed if every
+// request that was made did
+
+impl<T: Outcome> Outcome for Option<T> {
+    fn succeeded(&self) -> bool {
+        self.as_ref().map_or(true, Outcome::succeeded)
+    }
+}
+
+impl<T: Outcome> Outcome for Vec<T> {
+    fn succeeded(&self) -> bool {
+        self.iter().all(Outcome::succeeded)
+    }
+}
+
+impl<A: Outcome, B: Outcome> Outcome for (A, B) {
+    fn succeeded(&self) -> bool {
+        self.0.succeeded() && self.1.succeeded()
+    }
+}
+
+/// Run `f`, reporting `bytes` as the size of the data encrypted by the SDK operation it runs
+pub(crate) async fn with_bytes_encrypted<F: Future>(bytes: usize, f: F) -> F::Output {
+    BYTES_ENCRYPTED.scope(bytes, f).await
+}
+
+/// Report the metrics of a finished operation to `sink`, if there is one
+pub(crate) fn record<T: Outcome>(
+    operation: SdkOperation,
+    duration: Duration,
+    result: &std::result::Result<T, IronOxideErr>,
+    sink: Option<&SharedMetricsSink>,
+) {
+    if let Some(SharedMetricsSink(sink)) = sink {
+        sink.record(&OperationMetrics {
+            operation,
+            duration,
+            succeeded: result.as_ref().map_or(false, Outcome::succeeded),
+            bytes_encrypted: BYTES_ENCRYPTED.try_with(|bytes| *bytes).ok(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::add_optional_timeout;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorded(Mutex<Vec<OperationMetrics>>);
+
+    impl MetricsSink for Recorded {
+        fn record(&self, metrics: &OperationMetrics) {
+            self.0.lock().unwrap().push(metrics.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn sdk_operations_are_recorded() {
+        let sink = Arc::new(Recorded::default());
+        let shared = SharedMetricsSink::new(sink.clone());
+        let _ = with_bytes_encrypted(
+            5,
+            add_optional_timeout(
+                async { Ok::<_, IronOxideErr>(()) },
+                None,
+                SdkOperation::DocumentEncrypt,
+                Some(&shared),
+            ),
+        )
+        .await;
+        let _ = add_optional_timeout(
+            futures::future::pending::<Result<(), IronOxideErr>>(),
+            Some(Duration::from_millis(1)),
+            SdkOperation::DocumentList,
+            Some(&shared),
+        )
+        .await;
+        let _ = add_optional_timeout(
+            async { Ok::<_, IronOxideErr>(()) },
+            None,
+            SdkOperation::GroupList,
+            None,
+        )
+        .await;
+
+        let recorded = sink.0.lock().unwrap();
+        let ops: Vec<_> = recorded
+            .iter()
+            .map(|m| (m.operation(), m.succeeded(), m.bytes_encrypted()))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (SdkOperation::DocumentEncrypt, true, Some(5)),
+                (SdkOperation::DocumentList, false, None)
+            ]
+        );
+    }
+}
@@ -0,0 +1,380 @@
+//! In-process mock of the IronCore webservice
+//!
+//! [MockServer](struct.MockServer.html) answers the requests made by user verification, device
+//! creation, SDK initialization, user public key lookup, document creation, and group creation with
+//! responses shaped like the real webservice's. All cryptography still happens in the SDK, so the
+//! calls exercised against the mock are the same ones an application makes against production.
+//! This is what the runnable documentation examples are built on.
+//!
+//! The mock keeps no state beyond a single user, so operations that read back what was written
+//! (document decrypt, group get, etc.) are answered with `404 Not Found`.
+//!
+//! # Optional
+//! This requires the optional `mock` feature to be enabled.
+
+#[cfg(not(feature = "disable-password-escrow"))]
+use crate::{
+    common::Environment,
+    config::{IronOxideConfig, PasswordKdf},
+    crypto::aes,
+    internal::{rest::IronCoreRequest, user_api},
+    user::{DeviceCreateOpts, Jwt},
+    IronOxide,
+};
+use crate::{IronOxideErr, Result};
+use chrono::Utc;
+use recrypt::prelude::*;
+use serde_json::{json, Value};
+#[cfg(not(feature = "disable-password-escrow"))]
+use std::convert::TryInto;
+#[cfg(not(feature = "disable-password-escrow"))]
+use std::sync::Mutex;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+const MOCK_USER_ID: &str = "mock-user";
+const MOCK_SEGMENT_ID: usize = 1;
+
+/// Local HTTP server that stands in for the IronCore webservice.
+///
+/// The server runs on the current Tokio runtime until it is dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    url: String,
+    #[cfg(not(feature = "disable-password-escrow"))]
+    password: String,
+    server: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Start a mock server with a single user whose private key is escrowed with `password`.
+    ///
+    /// The mock doesn't change the process's environment. SDKs from
+    /// [initialize_sdk](#method.initialize_sdk) send their requests to it, and other SDKs can be pointed
+    /// at [url](#method.url) with `IronOxideConfig::environment`. Static `UserOps` calls always use the
+    /// service named by `IRONCORE_ENV`.
+    pub async fn start(password: &str) -> Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| IronOxideErr::InitializeError(e.to_string()))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| IronOxideErr::InitializeError(e.to_string()))?;
+        let url = format!("http://{}/api/1/", addr);
+        let state = Arc::new(MockState::new(password)?);
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    // a failed connection only affects the request that was made on it
+                    let _ = handle_connection(stream, &state).await;
+                });
+            }
+        });
+        Ok(MockServer {
+            url,
+            #[cfg(not(feature = "disable-password-escrow"))]
+            password: password.to_string(),
+            server,
+        })
+    }
+
+    /// Base URL the mock is listening on
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// JWT for the mock user. The mock does not check signatures, so this is not signed.
+    pub fn jwt(&self) -> String {
+        let encode =
+            |value: Value| base64::encode_config(value.to_string(), base64::URL_SAFE_NO_PAD);
+        let iat = Utc::now().timestamp() as u64;
+        format!(
+            "{}.{}.{}",
+            encode(json!({ "alg": "ES256", "typ": "JWT" })),
+            encode(json!({
+                "sub": MOCK_USER_ID,
+                "iat": iat,
+                "exp": iat + 120,
+                "pid": 1,
+                "sid": "mock-segment",
+                "kid": 1
+            })),
+            base64::encode_config(b"unsigned", base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    /// Environment that sends requests to the mock
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn environment(&self) -> Result<Environment> {
+        url::Url::parse(&self.url)
+            .map(Environment::Custom)
+            .map_err(|e| IronOxideErr::InitializeError(e.to_string()))
+    }
+
+    /// Generate a device for the mock user and initialize an SDK with it.
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub async fn initialize_sdk(&self) -> Result<IronOxide> {
+        let jwt = Jwt::new(&self.jwt())?;
+        let signing_ts = std::time::SystemTime::now().into();
+        let device_opts = DeviceCreateOpts::default().standardize(signing_ts)?;
+        let device = user_api::generate_device_key(
+            &recrypt::api::Recrypt::new(),
+            &jwt,
+            self.password.as_str().try_into()?,
+            device_opts,
+            &signing_ts,
+            None,
+            &IronCoreRequest::with_base_url(self.url.clone()),
+        )
+        .await?;
+        let config = IronOxideConfig {
+            environment: Some(self.environment()?),
+            ..Default::default()
+        };
+        crate::initialize(&device.into(), &config).await
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// The mock user and counters used to assign ids.
+struct MockState {
+    encrypted_private_key: String,
+    public_key: Value,
+    next_id: AtomicU64,
+}
+
+impl MockState {
+    fn new(password: &str) -> Result<MockState> {
+        let recrypt = recrypt::api::Recrypt::new();
+        let (private_key, public_key) = recrypt.generate_key_pair()?;
+        let (x, y) = public_key.bytes_x_y();
+        Ok(MockState {
+            encrypted_private_key: escrow_private_key(password, &private_key)?,
+            public_key: json!({ "x": base64::encode(x), "y": base64::encode(y) }),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn user(&self) -> Value {
+        json!({
+            "id": MOCK_USER_ID,
+            "status": 1,
+            "segmentId": MOCK_SEGMENT_ID,
+            "userPrivateKey": self.encrypted_private_key,
+            "userMasterPublicKey": self.public_key,
+            "needsRotation": false,
+        })
+    }
+
+    /// Produce the status line and JSON body for a request.
+    fn respond(&self, method: &str, path: &str, query: &str, body: &Value) -> (&str, Value) {
+        let now = Utc::now();
+        match (method, path) {
+            ("GET", "users/verify") => ("200 OK", self.user()),
+            ("GET", "users/current") => {
+                let mut user = self.user();
+                user["currentKeyId"] = json!(1);
+                user["groupsNeedingRotation"] = json!([]);
+                ("200 OK", user)
+            }
+            ("GET", "users") if query.split('&').any(|param| param == "needsRotation=true") => {
+                // the mock user never needs rotation
+                ("200 OK", json!({ "result": [] }))
+            }
+            ("GET", "users") => {
+                let requested = query
+                    .split('&')
+                    .filter(|param| param.starts_with("id="))
+                    .map(|param| param.trim_start_matches("id="))
+                    .flat_map(|ids| {
+                        percent_encoding::percent_decode_str(ids)
+                            .decode_utf8_lossy()
+                            .split(',')
+                            .map(str::to_string)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                let result: Vec<Value> = requested
+                    .iter()
+                    .filter(|id| id.as_str() == MOCK_USER_ID)
+                    .map(|id| json!({ "id": id, "userMasterPublicKey": self.public_key }))
+                    .collect();
+                ("200 OK", json!({ "result": result }))
+            }
+            ("POST", "users/devices") => {
+                let metadata = match &body["device"]["metadata"] {
+                    Value::Null => json!({}),
+                    metadata => metadata.clone(),
+                };
+                (
+                    "201 Created",
+                    json!({
+                        "id": self.next_id(),
+                        "devicePublicKey": self.public_key,
+                        "name": body["device"]["name"],
+                        "created": now,
+                        "updated": now,
+                        "expires": body["device"]["expires"],
+                        "metadata": metadata,
+                    }),
+                )
+            }
+            ("POST", "documents") => (
+                "201 Created",
+                json!({
+                    "id": body["id"],
+                    "name": body["value"]["name"],
+                    "sharedWith": body["value"]["sharedWith"],
+                    "created": now,
+                    "updated": now,
+                }),
+            ),
+            ("POST", "groups") => {
+                let id = match &body["id"] {
+                    Value::Null => json!(format!("mock-group-{}", self.next_id())),
+                    id => id.clone(),
+                };
+                let owner = match &body["owner"] {
+                    Value::Null => json!(MOCK_USER_ID),
+                    owner => owner.clone(),
+                };
+                let admin_ids: Vec<&Value> = body["admins"]
+                    .as_array()
+                    .map(|admins| admins.iter().map(|a| &a["user"]["userId"]).collect())
+                    .unwrap_or_default();
+                let member_ids: Vec<&Value> = body["members"]
+                    .as_array()
+                    .map(|members| members.iter().map(|m| &m["userId"]).collect())
+                    .unwrap_or_default();
+                let mut permissions = vec![];
+                if admin_ids.iter().any(|a| *a == MOCK_USER_ID) {
+                    permissions.push("admin");
+                }
+                if member_ids.iter().any(|m| *m == MOCK_USER_ID) {
+                    permissions.push("member");
+                }
+                (
+                    "201 Created",
+                    json!({
+                        "id": id,
+                        "name": body["name"],
+                        "permissions": permissions,
+                        "created": now,
+                        "updated": now,
+                        "owner": owner,
+                        "adminIds": admin_ids,
+                        "memberIds": member_ids,
+                        "groupMasterPublicKey": body["groupPublicKey"],
+                        "needsRotation": body["needsRotation"],
+                    }),
+                )
+            }
+            _ => (
+                "404 Not Found",
+                json!([{
+                    "message": format!("MockServer does not support {} {}", method, path),
+                    "code": 0
+                }]),
+            ),
+        }
+    }
+}
+
+/// Escrow the mock user's private key the same way `user_create` does.
+#[cfg(not(feature = "disable-password-escrow"))]
+fn escrow_private_key(password: &str, private_key: &recrypt::api::PrivateKey) -> Result<String> {
+    let encrypted = aes::encrypt_user_master_key(
+        &Mutex::new(rand::thread_rng()),
+        password,
+        private_key.bytes(),
+        PasswordKdf::default(),
+    )?;
+    Ok(base64::encode(&encrypted.bytes()[..]))
+}
+
+/// Without password escrow the SDK never unlocks the mock user's private key, so only its size matters.
+#[cfg(feature = "disable-password-escrow")]
+fn escrow_private_key(_: &str, _: &recrypt::api::PrivateKey) -> Result<String> {
+    Ok(base64::encode(&[0u8; 92][..]))
+}
+
+/// Read a single HTTP/1.1 request from `stream`, answer it, and close the connection.
+async fn handle_connection(mut stream: TcpStream, state: &MockState) -> std::io::Result<()> {
+    let mut request = vec![];
+    let mut chunk = [0u8; 4096];
+    let (head_len, content_len) = loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&chunk[..read]);
+        if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&request[..pos]);
+            let content_len = head
+                .lines()
+                .filter_map(|line| {
+                    let mut header = line.splitn(2, ':');
+                    match (header.next(), header.next()) {
+                        (Some(name), Some(value))
+                            if name.trim().eq_ignore_ascii_case("content-length") =>
+                        {
+                            value.trim().parse::<usize>().ok()
+                        }
+                        _ => None,
+                    }
+                })
+                .next()
+                .unwrap_or(0);
+            break (pos + 4, content_len);
+        }
+    };
+    while request.len() < head_len + content_len {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&chunk[..read]);
+    }
+
+    let head = String::from_utf8_lossy(&request[..head_len]).into_owned();
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let mut target = target.splitn(2, '?');
+    let path = target.next().unwrap_or_default();
+    let query = target.next().unwrap_or_default();
+    let path = path.trim_start_matches("/api/1/");
+    let body = serde_json::from_slice(&request[head_len..]).unwrap_or(Value::Null);
+
+    let (status, response) = state.respond(method, path, query, &body);
+    let response = response.to_string();
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await
+}
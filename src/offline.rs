@@ -0,0 +1,446 @@
+//! Offline mutation queue API
+//!
+//! With [IronOxideConfig.offline_queue](../config/struct.IronOxideConfig.html#structfield.offline_queue)
+//! set, document grants and revokes, document and group name updates, and group description, attribute,
+//! and ownership updates that fail because the IronCore service couldn't be reached are queued instead of
+//! lost, and fail with `IronOxideErr::MutationQueued`.
+//! See [OfflineOps](trait.OfflineOps.html) for functions to replay them once the service is reachable.
+//!
+//! A failure is treated as the service being unreachable if no HTTP response was received or the
+//! operation timed out, in which case the mutation may or may not have been applied. All of the queued
+//! mutations are idempotent, so replaying one that was applied is harmless.
+//!
+//! A queued mutation isn't replayed if its document or group changed after it was first attempted,
+//! either by a later call on this SDK or (going by the `last_updated` time the service reports) by
+//! anyone else. Replaying it could undo that change, such as restoring access that was revoked since,
+//! so it is reported as a conflict instead.
+//!
+//! The queue is only held in memory. Mutations still queued when the `IronOxide` and its forks are
+//! dropped are lost; call [offline_queue](trait.OfflineOps.html#tymethod.offline_queue) before shutting
+//! down to find out which ones weren't applied.
+
+use crate::{
+    common::SdkOperation,
+    document::{self, DocumentId, DocumentName, UserOrGroup},
+    group::{self, GroupId, GroupName},
+    internal::rt,
+    IronOxide, IronOxideErr, Result,
+};
+#[cfg(feature = "unstable-group-endpoints")]
+use crate::{
+    group::{GroupAttributes, GroupDescription},
+    user::UserId,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{collections::VecDeque, future::Future};
+
+/// A mutation that failed because the IronCore service couldn't be reached
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum QueuedMutation {
+    DocumentGrantAccess {
+        id: DocumentId,
+        grants: Vec<UserOrGroup>,
+    },
+    DocumentRevokeAccess {
+        id: DocumentId,
+        revokes: Vec<UserOrGroup>,
+    },
+    DocumentUpdateName {
+        id: DocumentId,
+        name: Option<DocumentName>,
+    },
+    GroupUpdateName {
+        id: GroupId,
+        name: Option<GroupName>,
+    },
+    #[cfg(feature = "unstable-group-endpoints")]
+    GroupUpdateDescription {
+        id: GroupId,
+        description: Option<GroupDescription>,
+    },
+    #[cfg(feature = "unstable-group-endpoints")]
+    GroupUpdateAttributes {
+        id: GroupId,
+        attributes: GroupAttributes,
+    },
+    #[cfg(feature = "unstable-group-endpoints")]
+    GroupTransferOwnership { id: GroupId, new_owner: UserId },
+}
+
+impl QueuedMutation {
+    /// Whether `self` and `other` change the same document or group
+    fn same_target(&self, other: &QueuedMutation) -> bool {
+        match (self.document_id(), other.document_id()) {
+            (Some(id), Some(other_id)) => id == other_id,
+            _ => match (self.group_id(), other.group_id()) {
+                (Some(id), Some(other_id)) => id == other_id,
+                _ => false,
+            },
+        }
+    }
+
+    fn document_id(&self) -> Option<&DocumentId> {
+        match self {
+            QueuedMutation::DocumentGrantAccess { id, .. }
+            | QueuedMutation::DocumentRevokeAccess { id, .. }
+            | QueuedMutation::DocumentUpdateName { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+
+    fn group_id(&self) -> Option<&GroupId> {
+        match self {
+            QueuedMutation::GroupUpdateName { id, .. } => Some(id),
+            #[cfg(feature = "unstable-group-endpoints")]
+            QueuedMutation::GroupUpdateDescription { id, .. }
+            | QueuedMutation::GroupUpdateAttributes { id, .. }
+            | QueuedMutation::GroupTransferOwnership { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// A queued mutation that the IronCore service rejected, in whole or in part, when it was replayed, or
+/// that wasn't replayed because its document or group changed after it was queued
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MutationConflict {
+    mutation: QueuedMutation,
+    err: String,
+}
+
+impl MutationConflict {
+    /// The mutation that was replayed. It has been removed from the queue.
+    pub fn mutation(&self) -> &QueuedMutation {
+        &self.mutation
+    }
+    /// Why the service rejected it, or why it wasn't replayed
+    pub fn err(&self) -> &str {
+        &self.err
+    }
+}
+
+/// Result of replaying the offline queue
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FlushResult {
+    applied: Vec<QueuedMutation>,
+    conflicts: Vec<MutationConflict>,
+    remaining: usize,
+}
+
+impl FlushResult {
+    /// Mutations that were replayed successfully, in the order they were queued
+    pub fn applied(&self) -> &[QueuedMutation] {
+        &self.applied
+    }
+    /// Mutations that the service rejected, in the order they were queued
+    pub fn conflicts(&self) -> &[MutationConflict] {
+        &self.conflicts
+    }
+    /// Number of mutations still queued because the service couldn't be reached again
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+/// IronOxide Offline Queue Operations
+#[async_trait]
+pub trait OfflineOps {
+    /// Replays queued mutations, oldest first.
+    ///
+    /// Each mutation is removed from the queue once the service has answered it, either as applied
+    /// or as a conflict. Replaying stops at the first mutation that still can't reach the service,
+    /// leaving it and those after it queued. Mutations queued while this runs are replayed too.
+    ///
+    /// A mutation whose document or group changed after it was first attempted is reported as a
+    /// conflict without being replayed.
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// let flushed = sdk.flush().await?;
+    /// for conflict in flushed.conflicts() {
+    ///     println!("{:?} was rejected: {}", conflict.mutation(), conflict.err());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn flush(&self) -> Result<FlushResult>;
+
+    /// Mutations waiting to be replayed, oldest first
+    async fn offline_queue(&self) -> Vec<QueuedMutation>;
+}
+
+#[async_trait]
+impl OfflineOps for IronOxide {
+    async fn flush(&self) -> Result<FlushResult> {
+        // holding the queue keeps concurrent flushes from replaying the same mutation
+        let mut queue = self.offline_queue.lock().await;
+        let mut result = FlushResult::default();
+        while let Some(entry) = queue.front().cloned() {
+            let err = match rt::compat(replay_unless_changed(self, &entry)).await {
+                Err(e) if is_unreachable(&e) => break,
+                Err(e) => Some(e.to_string()),
+                Ok(failures) if failures.is_empty() => None,
+                Ok(failures) => Some(failures.join("; ")),
+            };
+            queue.pop_front();
+            let mutation = entry.mutation;
+            match err {
+                None => result.applied.push(mutation),
+                Some(err) => result.conflicts.push(MutationConflict { mutation, err }),
+            }
+        }
+        result.remaining = queue.len();
+        Ok(result)
+    }
+
+    async fn offline_queue(&self) -> Vec<QueuedMutation> {
+        self.offline_queue
+            .lock()
+            .await
+            .iter()
+            .map(|entry| entry.mutation.clone())
+            .collect()
+    }
+}
+
+impl IronOxide {
+    /// Run `attempt`, which makes the mutation described by `mutation`. If it fails because the service
+    /// couldn't be reached and the offline queue is enabled, queue the mutation and fail with
+    /// `IronOxideErr::MutationQueued` instead. If it succeeds, mutations already queued for the same
+    /// document or group won't be replayed, since they could undo it.
+    pub(crate) async fn queue_if_unreachable<T, Fut, F>(
+        &self,
+        attempt: Fut,
+        operation: SdkOperation,
+        mutation: F,
+    ) -> Result<T>
+    where
+        Fut: Future<Output = Result<T>>,
+        F: FnOnce() -> QueuedMutation,
+    {
+        let attempted_at = Utc::now();
+        let result = attempt.await;
+        if !self.config.offline_queue {
+            return result;
+        }
+        match result {
+            Err(e) if is_unreachable(&e) => {
+                self.offline_queue.lock().await.push_back(QueuedEntry {
+                    mutation: mutation(),
+                    attempted_at,
+                    superseded: false,
+                });
+                Err(IronOxideErr::MutationQueued {
+                    operation,
+                    cause: e.to_string(),
+                })
+            }
+            Ok(value) => {
+                let mutation = mutation();
+                self.offline_queue
+                    .lock()
+                    .await
+                    .iter_mut()
+                    .filter(|entry| entry.mutation.same_target(&mutation))
+                    .for_each(|entry| entry.superseded = true);
+                Ok(value)
+            }
+            result => result,
+        }
+    }
+}
+
+/// A queued mutation, with when it was first attempted
+#[derive(Clone, Debug)]
+pub(crate) struct QueuedEntry {
+    mutation: QueuedMutation,
+    attempted_at: DateTime<Utc>,
+    /// whether a later call on this SDK changed the same document or group
+    superseded: bool,
+}
+
+/// Mutations waiting to be replayed, shared by an `IronOxide` and its forks
+pub(crate) type OfflineQueue = tokio::sync::Mutex<VecDeque<QueuedEntry>>;
+
+/// Whether `err` means the IronCore service couldn't be reached
+fn is_unreachable(err: &IronOxideErr) -> bool {
+    match err {
+        IronOxideErr::RequestError {
+            http_status: None, ..
+        }
+        | IronOxideErr::OperationTimedOut { .. } => true,
+        _ => false,
+    }
+}
+
+/// Replay `entry` unless its document or group changed after it was first attempted. Returns why it
+/// wasn't replayed, or the users and groups whose access couldn't be changed, with why.
+async fn replay_unless_changed(sdk: &IronOxide, entry: &QueuedEntry) -> Result<Vec<String>> {
+    if entry.superseded {
+        return Ok(vec![
+            "it was changed by this SDK after the mutation was queued".to_string(),
+        ]);
+    }
+    let last_updated = match &entry.mutation {
+        QueuedMutation::DocumentGrantAccess { id, .. }
+        | QueuedMutation::DocumentRevokeAccess { id, .. }
+        | QueuedMutation::DocumentUpdateName { id, .. } => {
+            *document::DocumentOps::document_get_metadata(sdk, id)
+                .await?
+                .last_updated()
+        }
+        QueuedMutation::GroupUpdateName { id, .. } => *group::GroupOps::group_get_metadata(sdk, id)
+            .await?
+            .last_updated(),
+        #[cfg(feature = "unstable-group-endpoints")]
+        QueuedMutation::GroupUpdateDescription { id, .. }
+        | QueuedMutation::GroupUpdateAttributes { id, .. }
+        | QueuedMutation::GroupTransferOwnership { id, .. } => {
+            *group::GroupOps::group_get_metadata(sdk, id)
+                .await?
+                .last_updated()
+        }
+    };
+    if last_updated > entry.attempted_at {
+        Ok(vec![format!(
+            "it was changed at {} after the mutation was queued",
+            last_updated
+        )])
+    } else {
+        replay(sdk, &entry.mutation).await
+    }
+}
+
+/// Apply `mutation` without queueing it again. Returns the users and groups whose access couldn't be
+/// changed, with why.
+async fn replay(sdk: &IronOxide, mutation: &QueuedMutation) -> Result<Vec<String>> {
+    let access_failures = |result: document::DocumentAccessResult| {
+        result
+            .failed()
+            .iter()
+            .map(|failed| format!("{:?}: {}", failed.user_or_group, failed.err))
+            .collect()
+    };
+    match mutation {
+        QueuedMutation::DocumentGrantAccess { id, grants } => {
+            document::grant_access(sdk, id, grants)
+                .await
+                .map(access_failures)
+        }
+        QueuedMutation::DocumentRevokeAccess { id, revokes } => {
+            document::revoke_access(sdk, id, revokes)
+                .await
+                .map(access_failures)
+        }
+        QueuedMutation::DocumentUpdateName { id, name } => {
+            document::update_name(sdk, id, name.as_ref())
+                .await
+                .map(|_| vec![])
+        }
+        QueuedMutation::GroupUpdateName { id, name } => group::update_name(sdk, id, name.as_ref())
+            .await
+            .map(|_| vec![]),
+        #[cfg(feature = "unstable-group-endpoints")]
+        QueuedMutation::GroupUpdateDescription { id, description } => {
+            group::update_description(sdk, id, description.as_ref())
+                .await
+                .map(|_| vec![])
+        }
+        #[cfg(feature = "unstable-group-endpoints")]
+        QueuedMutation::GroupUpdateAttributes { id, attributes } => {
+            group::update_attributes(sdk, id, attributes)
+                .await
+                .map(|_| vec![])
+        }
+        #[cfg(feature = "unstable-group-endpoints")]
+        QueuedMutation::GroupTransferOwnership { id, new_owner } => {
+            group::transfer_ownership(sdk, id, new_owner)
+                .await
+                .map(|_| vec![])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::RequestErrorCode;
+    use galvanic_assert::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn only_failures_without_a_response_are_unreachable() {
+        let request_err = |http_status| IronOxideErr::RequestError {
+            message: "".to_string(),
+            code: RequestErrorCode::DocumentGrantAccess,
+            http_status,
+            request_id: None,
+        };
+        assert!(is_unreachable(&request_err(None)));
+        assert!(is_unreachable(&IronOxideErr::OperationTimedOut {
+            operation: SdkOperation::DocumentGrantAccess,
+            duration: std::time::Duration::from_secs(30),
+        }));
+        assert!(!is_unreachable(&request_err(Some(404))));
+        assert!(!is_unreachable(&IronOxideErr::ValidationError(
+            "id".to_string(),
+            "".to_string()
+        )));
+    }
+
+    #[test]
+    fn mutations_for_the_same_document_or_group_share_a_target() -> Result<()> {
+        let doc = |id: &str| -> Result<QueuedMutation> {
+            Ok(QueuedMutation::DocumentGrantAccess {
+                id: DocumentId::try_from(id)?,
+                grants: vec![],
+            })
+        };
+        let revoke = QueuedMutation::DocumentRevokeAccess {
+            id: DocumentId::try_from("doc1")?,
+            revokes: vec![],
+        };
+        let group = QueuedMutation::GroupUpdateName {
+            id: GroupId::try_from("doc1")?,
+            name: None,
+        };
+        assert!(doc("doc1")?.same_target(&revoke));
+        assert!(!doc("doc2")?.same_target(&revoke));
+        // a group with the same ID as a document is a different target
+        assert!(!group.same_target(&revoke));
+        assert!(group.same_target(&group));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unreachable_mutations_are_queued_and_left_queued_while_unreachable() -> Result<()> {
+        let sdk =
+            crate::internal::tests::create_unreachable_test_sdk(&crate::config::IronOxideConfig {
+                offline_queue: true,
+                sdk_operation_timeout: Some(std::time::Duration::from_secs(5)),
+                ..Default::default()
+            })?;
+        let id = DocumentId::try_from("doc1")?;
+        let name = DocumentName::try_from("renamed")?;
+        let err = document::DocumentOps::document_update_name(&sdk, &id, Some(&name))
+            .await
+            .unwrap_err();
+        assert_that!(&err, is_variant!(IronOxideErr::MutationQueued));
+        assert_eq!(
+            sdk.offline_queue().await,
+            vec![QueuedMutation::DocumentUpdateName {
+                id,
+                name: Some(name)
+            }]
+        );
+
+        let flushed = sdk.flush().await?;
+        assert!(flushed.applied().is_empty() && flushed.conflicts().is_empty());
+        assert_eq!(flushed.remaining(), 1);
+        Ok(())
+    }
+}
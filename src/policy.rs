@@ -84,8 +84,11 @@
 //! The PolicyGrant `PolicyGrant::new(None, None, None, None)` will match the last rule in the example and will return
 //! the group \[data_recovery\]
 //!
-use crate::{internal::user_api::UserId, IronOxideErr, Result};
-use regex::Regex;
+use crate::{
+    internal::{user_api::UserId, validation::validate_simple_policy_field_value},
+    IronOxideErr, Result,
+};
+use serde::{Deserialize, Serialize};
 use std::convert::{TryFrom, TryInto};
 
 /// Document access granted by a policy.
@@ -96,7 +99,7 @@ use std::convert::{TryFrom, TryInto};
 /// rule may generate any number of users/groups.
 ///
 /// `substitute_user` replaces `%USER%` in a matched policy rule.
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct PolicyGrant {
     category: Option<Category>,
     sensitivity: Option<Sensitivity>,
@@ -147,7 +150,7 @@ impl Default for PolicyGrant {
 
 macro_rules! policy_field {
     ($t: ident, $l: literal) => {
-        #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+        #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
         pub struct $t(pub(crate) String);
 
         impl TryFrom<&str> for $t {
@@ -176,25 +179,6 @@ policy_field!(Category, "category");
 policy_field!(DataSubject, "dataSubject");
 policy_field!(Sensitivity, "sensitivity");
 
-const NAME_AND_ID_MAX_LEN: usize = 100;
-
-fn validate_simple_policy_field_value(field_id: &str, field_type: &str) -> Result<String> {
-    let simple_policy_field_regex = Regex::new("^[A-Za-z0-9_-]+$").expect("regex is valid");
-    let trimmed_id = field_id.trim();
-    if trimmed_id.is_empty() || trimmed_id.len() > NAME_AND_ID_MAX_LEN {
-        Err(IronOxideErr::ValidationError(
-            field_type.to_string(),
-            format!("'{}' must have length between 1 and 100", trimmed_id),
-        ))
-    } else if !simple_policy_field_regex.is_match(trimmed_id) {
-        Err(IronOxideErr::ValidationError(
-            field_type.to_string(),
-            format!("'{}' contains invalid characters", trimmed_id),
-        ))
-    } else {
-        Ok(trimmed_id.to_string())
-    }
-}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,8 +2,9 @@
 
 #[doc(no_inline)]
 pub use crate::{
-    common::*, config::*, document::advanced::*, document::*, group::*, policy::*, user::*,
-    InitAndRotationCheck, IronOxide, IronOxideErr, PrivateKeyRotationCheckResult,
+    common::*, config::*, document::advanced::*, document::audit::*, document::*, group::*, jwt::*,
+    manager::*, offline::*, policy::*, user::*, InitAndRotationCheck, IronOxide, IronOxideErr,
+    IronOxideOps, PrivateKeyRotationCheckResult,
 };
 
 #[cfg(feature = "blocking")]
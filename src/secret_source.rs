@@ -0,0 +1,234 @@
+//! Loading a `DeviceContext` from a secret store
+//!
+//! Deployments usually keep the device an SDK runs as in a secret store rather than on disk next to
+//! the application. [DeviceContextSource](trait.DeviceContextSource.html) reads a
+//! [DeviceContext](../common/struct.DeviceContext.html) from such a store. Reference implementations
+//! are provided for a Kubernetes secret mounted as a volume ([KubernetesSecretSource](struct.KubernetesSecretSource.html))
+//! and a HashiCorp Vault KV version 2 secret ([VaultSecretSource](struct.VaultSecretSource.html)).
+//! Other stores can be supported by implementing the trait.
+//!
+//! In both reference implementations the secret holds the JSON produced by serializing a `DeviceContext`.
+//!
+//! [initialize_from_source](fn.initialize_from_source.html) loads the device and initializes the SDK with it,
+//! and [watch_device_context](fn.watch_device_context.html) re-reads the source periodically so a rotated
+//! device is picked up without restarting the process.
+//!
+//! # Optional
+//! This requires the optional `secret-sources` feature to be enabled.
+
+use crate::{
+    common::DeviceContext, config::IronOxideConfig, internal::rt, IronOxide, IronOxideErr, Result,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::{fmt, path::PathBuf, time::Duration};
+use tokio::sync::watch;
+
+/// Store that a `DeviceContext` can be read from.
+#[async_trait]
+pub trait DeviceContextSource: Send + Sync {
+    /// Read the device context currently held by the store.
+    async fn load(&self) -> Result<DeviceContext>;
+}
+
+/// Device context held in a Kubernetes secret that is mounted into the pod as a volume.
+///
+/// The kubelet replaces the mounted file when the secret is updated, so each `load` sees the current
+/// value of the secret.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct KubernetesSecretSource {
+    path: PathBuf,
+}
+
+impl KubernetesSecretSource {
+    /// # Arguments
+    /// - `path` - Path of the file for the secret's key, e.g. `/var/run/secrets/ironoxide/device.json`
+    pub fn new<P: Into<PathBuf>>(path: P) -> KubernetesSecretSource {
+        KubernetesSecretSource { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl DeviceContextSource for KubernetesSecretSource {
+    async fn load(&self) -> Result<DeviceContext> {
+        let bytes = tokio::fs::read(&self.path).await.map_err(|e| {
+            IronOxideErr::DeviceContextSourceError(format!("{}: {}", self.path.display(), e))
+        })?;
+        parse_device_context(&bytes)
+    }
+}
+
+/// Device context held in a field of a HashiCorp Vault KV version 2 secret.
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub struct VaultSecretSource {
+    address: String,
+    token: String,
+    path: String,
+    field: String,
+}
+
+impl VaultSecretSource {
+    /// # Arguments
+    /// - `address` - Base URL of the Vault server, e.g. `https://vault.example.com:8200`
+    /// - `token` - Vault token with read access to the secret
+    /// - `path` - API path of the secret, including the mount and `data` segment, e.g. `secret/data/ironoxide`
+    /// - `field` - Field of the secret that holds the device context JSON
+    pub fn new(address: &str, token: &str, path: &str, field: &str) -> VaultSecretSource {
+        VaultSecretSource {
+            address: address.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            path: path.trim_start_matches('/').to_string(),
+            field: field.to_string(),
+        }
+    }
+}
+
+/// Leaves out the token, so it doesn't end up in logs
+impl fmt::Debug for VaultSecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VaultSecretSource")
+            .field("address", &self.address)
+            .field("path", &self.path)
+            .field("field", &self.field)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl DeviceContextSource for VaultSecretSource {
+    async fn load(&self) -> Result<DeviceContext> {
+        let url = format!("{}/v1/{}", self.address, self.path);
+        let source_err =
+            |e: reqwest::Error| IronOxideErr::DeviceContextSourceError(format!("{}: {}", url, e));
+        let response: Value = crate::internal::rest::client_builder()
+            .build()
+            .map_err(source_err)?
+            .get(&url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(source_err)?
+            .json()
+            .await
+            .map_err(source_err)?;
+        parse_vault_secret(&response, &self.field)
+    }
+}
+
+/// Pull the device context out of `field` of a Vault KV version 2 read response.
+fn parse_vault_secret(response: &Value, field: &str) -> Result<DeviceContext> {
+    match &response["data"]["data"][field] {
+        Value::String(device_json) => parse_device_context(device_json.as_bytes()),
+        Value::Null => Err(IronOxideErr::DeviceContextSourceError(format!(
+            "Vault secret has no field '{}'",
+            field
+        ))),
+        device_json => serde_json::from_value(device_json.clone())
+            .map_err(|e| IronOxideErr::DeviceContextSourceError(e.to_string())),
+    }
+}
+
+fn parse_device_context(bytes: &[u8]) -> Result<DeviceContext> {
+    serde_json::from_slice(bytes).map_err(|e| IronOxideErr::DeviceContextSourceError(e.to_string()))
+}
+
+/// Loads a device from `source` and initializes the SDK with it.
+///
+/// See [initialize](../fn.initialize.html).
+pub async fn initialize_from_source<S: DeviceContextSource + ?Sized>(
+    source: &S,
+    config: &IronOxideConfig,
+) -> Result<IronOxide> {
+    rt::compat(async { crate::initialize(&source.load().await?, config).await }).await
+}
+
+/// Loads a device from `source`, then re-reads it every `interval` on a background task.
+///
+/// The returned receiver always holds the most recently loaded device, and is notified when the
+/// device in the store changes. Re-initialize the SDK with the new device when that happens. Failed
+/// re-reads are logged and the previous device is kept. The background task stops once every receiver
+/// has been dropped.
+///
+/// Must be called from within a Tokio runtime, unless the `runtime-compat` feature is enabled.
+pub async fn watch_device_context<S: DeviceContextSource + 'static>(
+    source: S,
+    interval: Duration,
+) -> Result<watch::Receiver<DeviceContext>> {
+    let initial = rt::compat(source.load()).await?;
+    let (sender, receiver) = watch::channel(initial);
+    rt::spawn(async move {
+        loop {
+            rt::sleep(interval).await;
+            if sender.is_closed() {
+                break;
+            }
+            match source.load().await {
+                Ok(device) if device != *sender.borrow() => {
+                    if sender.send(device).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => log::warn!("Keeping the current device after a failed re-read: {}", e),
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::*;
+    use serde_json::json;
+
+    const DEVICE_JSON: &str = r#"{"accountId":"account_id","segmentId":22,"signingPrivateKey":"AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQGKiOPddAnxlf1S2y08ul1yymcJvx2UEhvzdIgBtA9vXA==","devicePrivateKey":"bzb0Rlg0u7gx9wDuk1ppRI77OH/0ferXleenJ3Ag6Jg="}"#;
+
+    #[test]
+    fn vault_source_debug_leaves_out_token() {
+        let source = VaultSecretSource::new(
+            "https://vault.example.com:8200/",
+            "s.secret-token",
+            "secret/data/ironoxide",
+            "device",
+        );
+        let debug = format!("{:?}", source);
+        assert!(debug.contains("https://vault.example.com:8200"));
+        assert!(!debug.contains("s.secret-token"));
+    }
+
+    #[test]
+    fn parse_vault_secret_string_or_object() -> Result<()> {
+        let expected: DeviceContext = serde_json::from_str(DEVICE_JSON).unwrap();
+        let as_string = json!({ "data": { "data": { "device": DEVICE_JSON } } });
+        assert_eq!(parse_vault_secret(&as_string, "device")?, expected);
+
+        let device_value: Value = serde_json::from_str(DEVICE_JSON).unwrap();
+        let as_object = json!({ "data": { "data": { "device": device_value } } });
+        assert_eq!(parse_vault_secret(&as_object, "device")?, expected);
+
+        assert_that!(
+            &parse_vault_secret(&as_string, "other").unwrap_err(),
+            is_variant!(IronOxideErr::DeviceContextSourceError)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn kubernetes_source_rereads_file() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("ironoxide-device-{}.json", uuid::Uuid::new_v4()));
+        let source = KubernetesSecretSource::new(&path);
+        assert_that!(
+            &source.load().await.unwrap_err(),
+            is_variant!(IronOxideErr::DeviceContextSourceError)
+        );
+
+        std::fs::write(&path, DEVICE_JSON).unwrap();
+        let loaded = source.load().await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded?, serde_json::from_str(DEVICE_JSON).unwrap());
+        Ok(())
+    }
+}
@@ -0,0 +1,544 @@
+//! Test double for code that uses the SDK
+//!
+//! [MockIronOxide](struct.MockIronOxide.html) implements the document, group, and user operations
+//! without network access or keys. Write application code against
+//! [IronOxideOps](../trait.IronOxideOps.html) trait objects, then give it a `MockIronOxide` in unit tests
+//! and an `IronOxide` in production. With this feature, `DocumentEncryptResult::new` and
+//! `DocumentDecryptResult::new` build results for the mock to return.
+//!
+//! # Optional
+//! This requires the optional `testing` feature to be enabled.
+
+#[cfg(feature = "unstable-group-endpoints")]
+use crate::group::{
+    GroupAttributes, GroupDescription, GroupDocumentListResult, GroupInviteListResult,
+};
+use crate::{
+    common::{DeviceContext, PrivateKey, PublicKey, SdkOperation},
+    document::{
+        DocumentAccessResult, DocumentAccessViaResult, DocumentDecryptResult, DocumentEncryptOpts,
+        DocumentEncryptResult, DocumentEncryptUniqueResult, DocumentId, DocumentListResult,
+        DocumentMetadataResult, DocumentName, DocumentOps, UserOrGroup,
+    },
+    group::{
+        GroupAccessEditResult, GroupAdminEditResult, GroupAdminRemovalApproval, GroupCreateOpts,
+        GroupCreateResult, GroupEffectiveMembersResult, GroupGetResult, GroupId, GroupImportResult,
+        GroupListResult, GroupMemberGroupsEditResult, GroupMetaResult, GroupName, GroupOps,
+        GroupSnapshot, GroupUpdatePrivateKeyResult,
+    },
+    internal::{document_api, take_lock},
+    user::{
+        DeviceAddResult, DeviceCreateOpts, DeviceId, DeviceListOpts, Jwt, KeyPair,
+        MasterKeyWrapper, RecoveryEscrow, UserCreateOpts, UserCreateResult, UserDeactivateResult,
+        UserDeviceListResult, UserId, UserListResult, UserOps, UserResult,
+        UserUpdatePrivateKeyResult,
+    },
+    Result,
+};
+use async_trait::async_trait;
+use std::{
+    any::{type_name, Any},
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+/// Stand-in for an `IronOxide` that returns responses queued by the test.
+///
+/// Each call takes the oldest response queued for its operation with
+/// [respond](#method.respond), and panics if there isn't one or it has the wrong type, so a test fails
+/// loudly when the code under test makes a call it didn't expect. Calls are recorded, in order, for
+/// [calls](#method.calls).
+///
+/// `document_get_id_from_bytes` reads the ID from the bytes, as `IronOxide` does, and
+/// `document_encrypt_unique` and `group_import` are answered by the calls `IronOxide` would make:
+/// `document_encrypt`, and `group_create` followed by `group_add_admins`, `group_add_members`, and
+/// `group_add_member_groups` (with the `unstable-group-endpoints` feature) for those in the snapshot. The
+/// `UserOps` functions that don't take `&self` can't be mocked and panic.
+///
+/// # Examples
+/// ```
+/// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+/// use ironoxide::{prelude::*, testing::MockIronOxide};
+/// use std::convert::TryFrom;
+///
+/// async fn is_missing(sdk: &dyn IronOxideOps, id: &GroupId) -> bool {
+///     sdk.group_get_metadata(id).await.is_err()
+/// }
+///
+/// let mock = MockIronOxide::new();
+/// mock.respond::<GroupGetResult>(
+///     SdkOperation::GroupGetMetadata,
+///     Err(IronOxideErr::ValidationError("id".to_string(), "not found".to_string())),
+/// );
+/// assert!(is_missing(&mock, &GroupId::try_from("group1")?).await);
+/// assert_eq!(mock.calls(), vec![SdkOperation::GroupGetMetadata]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct MockIronOxide {
+    responses: Mutex<HashMap<SdkOperation, VecDeque<Box<dyn Any + Send>>>>,
+    calls: Mutex<Vec<SdkOperation>>,
+}
+
+impl MockIronOxide {
+    pub fn new() -> MockIronOxide {
+        MockIronOxide::default()
+    }
+
+    /// Queue `response` to be returned by a call of `operation`. `T` must be the type the operation
+    /// returns on success.
+    pub fn respond<T: Send + 'static>(&self, operation: SdkOperation, response: Result<T>) {
+        take_lock(&self.responses)
+            .entry(operation)
+            .or_insert_with(VecDeque::new)
+            .push_back(Box::new(response));
+    }
+
+    /// Operations called so far, oldest first
+    pub fn calls(&self) -> Vec<SdkOperation> {
+        take_lock(&self.calls).clone()
+    }
+
+    /// Responses queued with `respond` that haven't been returned yet, by operation
+    pub fn pending(&self) -> HashMap<SdkOperation, usize> {
+        take_lock(&self.responses)
+            .iter()
+            .filter(|(_, queued)| !queued.is_empty())
+            .map(|(operation, queued)| (*operation, queued.len()))
+            .collect()
+    }
+
+    /// Record a call of `operation` and return its oldest queued response
+    fn next<T: 'static>(&self, operation: SdkOperation) -> Result<T> {
+        take_lock(&self.calls).push(operation);
+        let response = take_lock(&self.responses)
+            .get_mut(&operation)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| panic!("MockIronOxide has no response queued for {}", operation));
+        *response.downcast::<Result<T>>().unwrap_or_else(|_| {
+            panic!(
+                "the response queued for {} is not a Result<{}>",
+                operation,
+                type_name::<T>()
+            )
+        })
+    }
+}
+
+fn unmockable(function: &str) -> ! {
+    panic!(
+        "MockIronOxide can't mock {}, since it doesn't take &self; call it on IronOxide",
+        function
+    )
+}
+
+#[async_trait]
+impl DocumentOps for MockIronOxide {
+    async fn document_encrypt(
+        &self,
+        _document_data: &[u8],
+        _encrypt_opts: &DocumentEncryptOpts,
+    ) -> Result<DocumentEncryptResult> {
+        self.next(SdkOperation::DocumentEncrypt)
+    }
+
+    async fn document_encrypt_unique(
+        &self,
+        document_data: &[u8],
+        encrypt_opts: &DocumentEncryptOpts,
+    ) -> Result<DocumentEncryptUniqueResult> {
+        self.document_encrypt(document_data, encrypt_opts)
+            .await
+            .map(DocumentEncryptUniqueResult::Encrypted)
+    }
+
+    async fn document_decrypt(&self, _encrypted_document: &[u8]) -> Result<DocumentDecryptResult> {
+        self.next(SdkOperation::DocumentDecrypt)
+    }
+
+    async fn document_verify(&self, _encrypted_document: &[u8]) -> Result<bool> {
+        self.next(SdkOperation::DocumentVerify)
+    }
+
+    async fn document_list(&self) -> Result<DocumentListResult> {
+        self.next(SdkOperation::DocumentList)
+    }
+
+    async fn document_get_metadata(&self, _id: &DocumentId) -> Result<DocumentMetadataResult> {
+        self.next(SdkOperation::DocumentGetMetadata)
+    }
+
+    async fn document_access_via(&self, _id: &DocumentId) -> Result<DocumentAccessViaResult> {
+        self.next(SdkOperation::DocumentAccessVia)
+    }
+
+    fn document_get_id_from_bytes(&self, encrypted_document: &[u8]) -> Result<DocumentId> {
+        document_api::get_id_from_bytes(encrypted_document)
+    }
+
+    async fn document_update_bytes(
+        &self,
+        _id: &DocumentId,
+        _new_document_data: &[u8],
+    ) -> Result<DocumentEncryptResult> {
+        self.next(SdkOperation::DocumentUpdateBytes)
+    }
+
+    async fn document_update_name(
+        &self,
+        _id: &DocumentId,
+        _name: Option<&DocumentName>,
+    ) -> Result<DocumentMetadataResult> {
+        self.next(SdkOperation::DocumentUpdateName)
+    }
+
+    async fn document_grant_access(
+        &self,
+        _document_id: &DocumentId,
+        _grant_list: &Vec<UserOrGroup>,
+    ) -> Result<DocumentAccessResult> {
+        self.next(SdkOperation::DocumentGrantAccess)
+    }
+
+    async fn document_revoke_access(
+        &self,
+        _document_id: &DocumentId,
+        _revoke_list: &Vec<UserOrGroup>,
+    ) -> Result<DocumentAccessResult> {
+        self.next(SdkOperation::DocumentRevokeAccess)
+    }
+}
+
+#[async_trait]
+impl GroupOps for MockIronOxide {
+    async fn group_create(
+        &self,
+        _group_create_opts: &GroupCreateOpts,
+    ) -> Result<GroupCreateResult> {
+        self.next(SdkOperation::GroupCreate)
+    }
+
+    async fn group_get_metadata(&self, _id: &GroupId) -> Result<GroupGetResult> {
+        self.next(SdkOperation::GroupGetMetadata)
+    }
+
+    async fn group_list(&self) -> Result<GroupListResult> {
+        self.next(SdkOperation::GroupList)
+    }
+
+    async fn group_update_name(
+        &self,
+        _id: &GroupId,
+        _name: Option<&GroupName>,
+    ) -> Result<GroupMetaResult> {
+        self.next(SdkOperation::GroupUpdateName)
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_update_description(
+        &self,
+        _id: &GroupId,
+        _description: Option<&GroupDescription>,
+    ) -> Result<GroupMetaResult> {
+        self.next(SdkOperation::GroupUpdateDescription)
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_update_attributes(
+        &self,
+        _id: &GroupId,
+        _attributes: &GroupAttributes,
+    ) -> Result<GroupMetaResult> {
+        self.next(SdkOperation::GroupUpdateAttributes)
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_transfer_ownership(
+        &self,
+        _id: &GroupId,
+        _new_owner: &UserId,
+    ) -> Result<GroupMetaResult> {
+        self.next(SdkOperation::GroupTransferOwnership)
+    }
+
+    async fn group_rotate_private_key(&self, _id: &GroupId) -> Result<GroupUpdatePrivateKeyResult> {
+        self.next(SdkOperation::GroupRotatePrivateKey)
+    }
+
+    async fn group_add_members(
+        &self,
+        _id: &GroupId,
+        _users: &[UserId],
+    ) -> Result<GroupAccessEditResult> {
+        self.next(SdkOperation::GroupAddMembers)
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_invite_members(
+        &self,
+        _id: &GroupId,
+        _users: &[UserId],
+    ) -> Result<GroupAccessEditResult> {
+        self.next(SdkOperation::GroupInviteMembers)
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_list_invites(&self) -> Result<GroupInviteListResult> {
+        self.next(SdkOperation::GroupListInvites)
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_accept_invite(&self, _id: &GroupId) -> Result<GroupMetaResult> {
+        self.next(SdkOperation::GroupAcceptInvite)
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_add_member_groups(
+        &self,
+        _id: &GroupId,
+        _groups: &[GroupId],
+    ) -> Result<GroupMemberGroupsEditResult> {
+        self.next(SdkOperation::GroupAddMemberGroups)
+    }
+
+    async fn group_effective_members(&self, _id: &GroupId) -> Result<GroupEffectiveMembersResult> {
+        self.next(SdkOperation::GroupEffectiveMembers)
+    }
+
+    async fn group_remove_members(
+        &self,
+        _id: &GroupId,
+        _revoke_list: &[UserId],
+    ) -> Result<GroupAccessEditResult> {
+        self.next(SdkOperation::GroupRemoveMembers)
+    }
+
+    async fn group_add_admins(
+        &self,
+        _id: &GroupId,
+        _users: &[UserId],
+    ) -> Result<GroupAdminEditResult> {
+        self.next(SdkOperation::GroupAddAdmins)
+    }
+
+    async fn group_remove_admins(
+        &self,
+        _id: &GroupId,
+        _revoke_list: &[UserId],
+    ) -> Result<GroupAdminEditResult> {
+        self.next(SdkOperation::GroupRemoveAdmins)
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn group_advisory_approve_admin_removal(
+        &self,
+        _password: &str,
+        _id: &GroupId,
+        _users: &[UserId],
+    ) -> Result<GroupAdminRemovalApproval> {
+        self.next(SdkOperation::GroupAdvisoryApproveAdminRemoval)
+    }
+
+    async fn group_advisory_remove_admins(
+        &self,
+        _id: &GroupId,
+        _revoke_list: &[UserId],
+        _approval: &GroupAdminRemovalApproval,
+    ) -> Result<GroupAdminEditResult> {
+        self.next(SdkOperation::GroupAdvisoryRemoveAdmins)
+    }
+
+    async fn group_delete(&self, _id: &GroupId) -> Result<GroupId> {
+        self.next(SdkOperation::GroupDelete)
+    }
+
+    async fn group_export(&self, _id: &GroupId) -> Result<GroupSnapshot> {
+        self.next(SdkOperation::GroupExport)
+    }
+
+    #[cfg(feature = "unstable-group-endpoints")]
+    async fn group_list_documents(&self, _id: &GroupId) -> Result<GroupDocumentListResult> {
+        self.next(SdkOperation::GroupListDocuments)
+    }
+
+    async fn group_import(&self, snapshot: &GroupSnapshot) -> Result<GroupImportResult> {
+        crate::group::import_group(self, snapshot).await
+    }
+}
+
+#[async_trait]
+impl UserOps for MockIronOxide {
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_create(
+        _jwt: &Jwt,
+        _password: &str,
+        _user_create_opts: &UserCreateOpts,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<UserCreateResult> {
+        unmockable("user_create")
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_create_with_device(
+        _jwt: &Jwt,
+        _password: &str,
+        _user_create_opts: &UserCreateOpts,
+        _device_create_options: &DeviceCreateOpts,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<(UserCreateResult, DeviceContext)> {
+        unmockable("user_create_with_device")
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn generate_new_device(
+        _jwt: &Jwt,
+        _password: &str,
+        _device_create_options: &DeviceCreateOpts,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult> {
+        unmockable("generate_new_device")
+    }
+
+    async fn user_create_with_master_key(
+        _jwt: &Jwt,
+        _key_pair: Option<&KeyPair>,
+        _wrapper: &dyn MasterKeyWrapper,
+        _user_create_opts: &UserCreateOpts,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<UserCreateResult> {
+        unmockable("user_create_with_master_key")
+    }
+
+    async fn generate_new_device_with_master_key(
+        _jwt: &Jwt,
+        _wrapper: &dyn MasterKeyWrapper,
+        _device_create_options: &DeviceCreateOpts,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult> {
+        unmockable("generate_new_device_with_master_key")
+    }
+
+    async fn generate_new_device_with_recovery(
+        _jwt: &Jwt,
+        _escrow: &RecoveryEscrow,
+        _recovery_private_key: &PrivateKey,
+        _device_create_options: &DeviceCreateOpts,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult> {
+        unmockable("generate_new_device_with_recovery")
+    }
+
+    async fn user_verify(
+        _jwt: &Jwt,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<Option<UserResult>> {
+        unmockable("user_verify")
+    }
+
+    async fn user_deactivate(
+        _jwt: &Jwt,
+        _user_id: &UserId,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<UserDeactivateResult> {
+        unmockable("user_deactivate")
+    }
+
+    async fn user_list_needing_rotation(
+        _jwt: &Jwt,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<UserListResult> {
+        unmockable("user_list_needing_rotation")
+    }
+
+    async fn user_list_devices(&self) -> Result<UserDeviceListResult> {
+        self.next(SdkOperation::UserListDevices)
+    }
+
+    async fn user_list_devices_with_opts(
+        &self,
+        _opts: &DeviceListOpts,
+    ) -> Result<UserDeviceListResult> {
+        self.next(SdkOperation::UserListDevices)
+    }
+
+    async fn user_get_public_key(&self, _users: &[UserId]) -> Result<HashMap<UserId, PublicKey>> {
+        self.next(SdkOperation::UserGetPublicKey)
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_rotate_private_key(&self, _password: &str) -> Result<UserUpdatePrivateKeyResult> {
+        self.next(SdkOperation::UserRotatePrivateKey)
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_verify_password(&self, _password: &str) -> Result<bool> {
+        self.next(SdkOperation::UserVerifyPassword)
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_rotate_private_key_with_recovery(
+        &self,
+        _password: &str,
+        _recovery_public_key: &PublicKey,
+    ) -> Result<UserUpdatePrivateKeyResult> {
+        self.next(SdkOperation::UserRotatePrivateKey)
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn device_rotate_keys(&self, _password: &str) -> Result<DeviceAddResult> {
+        self.next(SdkOperation::DeviceRotateKeys)
+    }
+
+    async fn user_delete_device(&self, _device_id: Option<&DeviceId>) -> Result<DeviceId> {
+        self.next(SdkOperation::UserDeleteDevice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IronOxideErr, IronOxideOps};
+    use std::convert::TryFrom;
+
+    async fn delete(sdk: &dyn IronOxideOps, id: &GroupId) -> Result<GroupId> {
+        sdk.group_delete(id).await
+    }
+
+    #[tokio::test]
+    async fn queued_responses_are_returned_in_order() -> Result<()> {
+        let id = GroupId::try_from("group1")?;
+        let mock = MockIronOxide::new();
+        mock.respond(SdkOperation::GroupDelete, Ok(id.clone()));
+        mock.respond::<GroupId>(
+            SdkOperation::GroupDelete,
+            Err(IronOxideErr::ValidationError("id".into(), "".into())),
+        );
+        mock.respond::<GroupListResult>(
+            SdkOperation::GroupList,
+            Err(IronOxideErr::ValidationError("".into(), "".into())),
+        );
+        assert_eq!(delete(&mock, &id).await?, id);
+        assert!(delete(&mock, &id).await.is_err());
+        assert_eq!(mock.pending().get(&SdkOperation::GroupList), Some(&1));
+        assert_eq!(
+            mock.calls(),
+            vec![SdkOperation::GroupDelete, SdkOperation::GroupDelete]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "no response queued")]
+    async fn unexpected_calls_panic() {
+        let _ = MockIronOxide::new().document_list().await;
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "is not a Result")]
+    async fn responses_of_the_wrong_type_panic() {
+        let mock = MockIronOxide::new();
+        mock.respond(SdkOperation::GroupDelete, Ok(true));
+        let _ = mock.group_delete(&GroupId::try_from("g").unwrap()).await;
+    }
+}
@@ -3,18 +3,28 @@
 //! See [UserOps](trait.UserOps.html) for user functions and key terms.
 
 pub use crate::internal::user_api::{
-    DeviceAddResult, DeviceId, DeviceName, EncryptedPrivateKey, Jwt, JwtClaims, KeyPair,
-    UserCreateResult, UserDevice, UserDeviceListResult, UserId, UserResult,
+    DeviceAddResult, DeviceId, DeviceKeyProtection, DeviceName, EncryptedPrivateKey, Jwt,
+    JwtClaims, KeyPair, MasterKeyWrapper, RecoveryEscrow, UserCreateResult, UserDeactivateResult,
+    UserDevice, UserDeviceListResult, UserId, UserListResult, UserResult,
     UserUpdatePrivateKeyResult,
 };
+#[cfg(not(feature = "disable-password-escrow"))]
 use crate::{
-    common::{PublicKey, SdkOperation},
+    common::DeviceContext,
+    config::{PasswordDerivationLimiter, PasswordKdf},
+};
+use crate::{
+    common::{PrivateKey, PublicKey, SdkOperation},
     internal::{add_optional_timeout, user_api, OUR_REQUEST},
     IronOxide, Result,
 };
+use crate::{internal::user_api::DeviceCreateOptsStd, IronOxideErr};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use recrypt::api::Recrypt;
-use std::{collections::HashMap, convert::TryInto};
+use std::collections::{BTreeMap, HashMap};
+#[cfg(not(feature = "disable-password-escrow"))]
+use std::convert::TryInto;
 
 /// Options for device creation.
 ///
@@ -22,22 +32,212 @@ use std::{collections::HashMap, convert::TryInto};
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct DeviceCreateOpts {
     device_name: Option<DeviceName>,
+    protection: DeviceKeyProtection,
+    expires: Option<DateTime<Utc>>,
+    metadata: BTreeMap<String, String>,
+    #[cfg(not(feature = "disable-password-escrow"))]
+    password_derivation_limiter: Option<PasswordDerivationLimiter>,
 }
 impl DeviceCreateOpts {
     /// # Arguments
-    /// - device_name
+    /// - `device_name`
     ///   - `None` (default) - The device will be created with no name.
     ///   - `Some` - The provided name will be used as the device's name.
-    pub fn new(device_name: Option<DeviceName>) -> DeviceCreateOpts {
-        DeviceCreateOpts { device_name }
+    /// - `protection` - How the device keys may be handled. See [DeviceKeyProtection](enum.DeviceKeyProtection.html).
+    pub fn new(
+        device_name: Option<DeviceName>,
+        protection: DeviceKeyProtection,
+    ) -> DeviceCreateOpts {
+        DeviceCreateOpts {
+            device_name,
+            protection,
+            expires: None,
+            metadata: BTreeMap::new(),
+            #[cfg(not(feature = "disable-password-escrow"))]
+            password_derivation_limiter: None,
+        }
+    }
+
+    /// Name the device will be created with, or `None` for no name.
+    pub fn with_device_name(mut self, device_name: Option<DeviceName>) -> DeviceCreateOpts {
+        self.device_name = device_name;
+        self
+    }
+
+    /// How the device keys may be handled. See [DeviceKeyProtection](enum.DeviceKeyProtection.html).
+    pub fn with_protection(mut self, protection: DeviceKeyProtection) -> DeviceCreateOpts {
+        self.protection = protection;
+        self
+    }
+
+    /// Time after which the IronCore service revokes the device, or `None` (default) for a device that
+    /// lasts until it is deleted. Must be in the future when the device is created.
+    pub fn with_expires(mut self, expires: Option<DateTime<Utc>>) -> DeviceCreateOpts {
+        self.expires = expires;
+        self
+    }
+
+    /// Arbitrary key/value pairs stored with the device and returned when listing devices,
+    /// such as the host or application that created it.
+    pub fn with_metadata(mut self, metadata: BTreeMap<String, String>) -> DeviceCreateOpts {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Wait for a turn from `limiter` before unlocking the user's private key with their password, or
+    /// `None` (default) to not wait. See [PasswordDerivationLimiter](../config/struct.PasswordDerivationLimiter.html).
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn with_password_derivation_limiter(
+        mut self,
+        limiter: Option<PasswordDerivationLimiter>,
+    ) -> DeviceCreateOpts {
+        self.password_derivation_limiter = limiter;
+        self
+    }
+
+    /// Validate the options for a device being created at `now`.
+    pub(crate) fn standardize(self, now: DateTime<Utc>) -> Result<DeviceCreateOptsStd> {
+        match self.expires {
+            Some(expires) if expires <= now => Err(IronOxideErr::ValidationError(
+                "expires".to_string(),
+                format!("'{}' is not in the future", expires.to_rfc3339()),
+            )),
+            _ => Ok(DeviceCreateOptsStd {
+                name: self.device_name,
+                protection: self.protection,
+                expires: self.expires,
+                metadata: self.metadata,
+            }),
+        }
     }
 }
 impl Default for DeviceCreateOpts {
     /// Default `DeviceCreateOpts` for common use cases.
     ///
-    /// The device will be created with no name.
+    /// The device will be created with no name and exportable keys.
+    fn default() -> Self {
+        DeviceCreateOpts::new(None, DeviceKeyProtection::Exportable)
+    }
+}
+
+/// Order of the devices returned by [user_list_devices_with_opts](trait.UserOps.html#tymethod.user_list_devices_with_opts).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DeviceListSort {
+    /// By device ID (default)
+    Id,
+    /// By device name. Devices without a name come first.
+    Name,
+    /// By the time the device was created
+    Created,
+}
+impl Default for DeviceListSort {
     fn default() -> Self {
-        DeviceCreateOpts::new(None)
+        DeviceListSort::Id
+    }
+}
+
+/// Options for listing a user's devices.
+///
+/// The IronCore service returns all of a user's devices, which are then filtered, sorted, and paged
+/// by the SDK. Default values are provided with [DeviceListOpts::default()](#method.default), which
+/// lists every device in order of ID.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DeviceListOpts {
+    sort: DeviceListSort,
+    descending: bool,
+    name_contains: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    current_device_only: bool,
+    offset: usize,
+    limit: Option<usize>,
+}
+impl DeviceListOpts {
+    /// Order of the devices, and whether it is reversed. Ties are broken by device ID.
+    pub fn with_sort(mut self, sort: DeviceListSort, descending: bool) -> DeviceListOpts {
+        self.sort = sort;
+        self.descending = descending;
+        self
+    }
+
+    /// Only list devices whose name contains `name_contains`. Devices without a name are excluded.
+    pub fn with_name_contains(mut self, name_contains: Option<String>) -> DeviceListOpts {
+        self.name_contains = name_contains;
+        self
+    }
+
+    /// Only list devices created at or after `created_after`, and before `created_before`.
+    pub fn with_created_between(
+        mut self,
+        created_after: Option<DateTime<Utc>>,
+        created_before: Option<DateTime<Utc>>,
+    ) -> DeviceListOpts {
+        self.created_after = created_after;
+        self.created_before = created_before;
+        self
+    }
+
+    /// Only list the device making the request.
+    pub fn with_current_device_only(mut self, current_device_only: bool) -> DeviceListOpts {
+        self.current_device_only = current_device_only;
+        self
+    }
+
+    /// Skip the first `offset` matching devices and return at most `limit` of the rest.
+    /// `UserDeviceListResult::total_count` reports how many devices matched in all.
+    pub fn with_page(mut self, offset: usize, limit: Option<usize>) -> DeviceListOpts {
+        self.offset = offset;
+        self.limit = limit;
+        self
+    }
+
+    fn matches(&self, device: &UserDevice) -> bool {
+        let name_matches = match &self.name_contains {
+            Some(needle) => device
+                .name()
+                .map_or(false, |name| name.name().contains(needle.as_str())),
+            None => true,
+        };
+        name_matches
+            && self
+                .created_after
+                .map_or(true, |after| *device.created() >= after)
+            && self
+                .created_before
+                .map_or(true, |before| *device.created() < before)
+            && (!self.current_device_only || device.is_current_device())
+    }
+
+    /// Filter, sort, and page `devices`.
+    fn apply(&self, devices: &[UserDevice]) -> UserDeviceListResult {
+        let mut matching: Vec<UserDevice> = devices
+            .iter()
+            .filter(|device| self.matches(device))
+            .cloned()
+            .collect();
+        let by_id = |a: &UserDevice, b: &UserDevice| a.id().id().cmp(b.id().id());
+        match self.sort {
+            DeviceListSort::Id => matching.sort_by(by_id),
+            DeviceListSort::Name => matching.sort_by(|a, b| {
+                a.name()
+                    .map(DeviceName::name)
+                    .cmp(&b.name().map(DeviceName::name))
+                    .then_with(|| by_id(a, b))
+            }),
+            DeviceListSort::Created => {
+                matching.sort_by(|a, b| a.created().cmp(b.created()).then_with(|| by_id(a, b)))
+            }
+        }
+        if self.descending {
+            matching.reverse();
+        }
+        let total_count = matching.len();
+        let page = matching
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        UserDeviceListResult::new(page, total_count)
     }
 }
 
@@ -47,13 +247,55 @@ impl Default for DeviceCreateOpts {
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct UserCreateOpts {
     needs_rotation: bool,
+    recovery_public_key: Option<PublicKey>,
+    #[cfg(not(feature = "disable-password-escrow"))]
+    password_kdf: PasswordKdf,
+    #[cfg(not(feature = "disable-password-escrow"))]
+    password_derivation_limiter: Option<PasswordDerivationLimiter>,
 }
 
 impl UserCreateOpts {
     /// # Arguments
     /// - `needs_rotation` - `true` if the private key for this user marked for rotation
     pub fn new(needs_rotation: bool) -> UserCreateOpts {
-        UserCreateOpts { needs_rotation }
+        UserCreateOpts {
+            needs_rotation,
+            recovery_public_key: None,
+            #[cfg(not(feature = "disable-password-escrow"))]
+            password_kdf: PasswordKdf::default(),
+            #[cfg(not(feature = "disable-password-escrow"))]
+            password_derivation_limiter: None,
+        }
+    }
+
+    /// Also encrypt the user's private key to an organization's recovery public key. The resulting
+    /// [RecoveryEscrow](struct.RecoveryEscrow.html) is returned in the `UserCreateResult` and is not sent to IronCore.
+    pub fn with_recovery_public_key(
+        mut self,
+        recovery_public_key: Option<PublicKey>,
+    ) -> UserCreateOpts {
+        self.recovery_public_key = recovery_public_key;
+        self
+    }
+
+    /// Derive the key that escrows the user's private key from their password with `password_kdf`.
+    /// Defaults to PBKDF2. Rotations of the user's private key keep the function it was escrowed with,
+    /// unless the SDK instance sets [IronOxideConfig.password_kdf](../config/struct.IronOxideConfig.html#structfield.password_kdf).
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn with_password_kdf(mut self, password_kdf: PasswordKdf) -> UserCreateOpts {
+        self.password_kdf = password_kdf;
+        self
+    }
+
+    /// Wait for a turn from `limiter` before escrowing the user's private key with their password, or
+    /// `None` (default) to not wait. See [PasswordDerivationLimiter](../config/struct.PasswordDerivationLimiter.html).
+    #[cfg(not(feature = "disable-password-escrow"))]
+    pub fn with_password_derivation_limiter(
+        mut self,
+        limiter: Option<PasswordDerivationLimiter>,
+    ) -> UserCreateOpts {
+        self.password_derivation_limiter = limiter;
+        self
     }
 }
 
@@ -98,12 +340,62 @@ pub trait UserOps {
     /// # Ok(())
     /// # }
     /// ```
+    #[cfg(not(feature = "disable-password-escrow"))]
     async fn user_create(
         jwt: &Jwt,
         password: &str,
         user_create_opts: &UserCreateOpts,
         timeout: Option<std::time::Duration>,
-    ) -> Result<UserCreateResult>;
+    ) -> Result<UserCreateResult>
+    where
+        Self: Sized;
+
+    /// Creates a user and generates their first device.
+    ///
+    /// Equivalent to [user_create](trait.UserOps.html#tymethod.user_create) followed by
+    /// [generate_new_device](trait.UserOps.html#tymethod.generate_new_device), but the device is added
+    /// with the keys generated for the user instead of fetching and decrypting them again. Signup flows
+    /// can use the JWT once and initialize the SDK with the returned `DeviceContext` right away.
+    ///
+    /// If the user is created but the device can't be added, the error is returned and the user remains.
+    /// Use `generate_new_device` to add their device.
+    ///
+    /// # Arguments
+    /// - `jwt`                   - Valid IronCore or Auth0 JWT
+    /// - `password`              - Password used to encrypt and escrow the user's private master key
+    /// - `user_create_opts`      - User creation parameters
+    /// - `device_create_options` - Device creation parameters
+    /// - `timeout`               - Timeout for this operation or `None` for no timeout
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let jwt_str = "";
+    /// let jwt = Jwt::new(jwt_str)?;
+    /// let password = "foobar";
+    /// let (user_result, device) = IronOxide::user_create_with_device(
+    ///     &jwt,
+    ///     password,
+    ///     &UserCreateOpts::default(),
+    ///     &DeviceCreateOpts::default(),
+    ///     None,
+    /// )
+    /// .await?;
+    /// let sdk = ironoxide::initialize(&device, &IronOxideConfig::default()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_create_with_device(
+        jwt: &Jwt,
+        password: &str,
+        user_create_opts: &UserCreateOpts,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(UserCreateResult, DeviceContext)>
+    where
+        Self: Sized;
 
     /// Generates a new device for the user specified in the JWT.
     ///
@@ -119,25 +411,125 @@ pub trait UserOps {
     ///
     /// # Examples
     /// ```
-    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # async fn run(jwt_str: &str) -> Result<(), ironoxide::IronOxideErr> {
     /// # use ironoxide::prelude::*;
     /// # use std::convert::TryFrom;
-    /// # let jwt_str = "";
     /// let jwt = Jwt::new(jwt_str)?;
     /// let password = "foobar";
     /// let device_name = DeviceName::try_from("primary_device")?;
-    /// let opts = DeviceCreateOpts::new(Some(device_name));
+    /// let mut metadata = std::collections::BTreeMap::new();
+    /// metadata.insert("host".to_string(), "worker-1".to_string());
+    /// let opts = DeviceCreateOpts::new(Some(device_name), DeviceKeyProtection::Exportable)
+    ///     .with_expires(Some(chrono::Utc::now() + chrono::Duration::days(30)))
+    ///     .with_metadata(metadata);
     /// let device_result = IronOxide::generate_new_device(&jwt, password, &opts, None).await?;
     /// let device_id: &DeviceId = device_result.device_id();
+    /// # assert_eq!(device_result.name().map(DeviceName::name), Some(&"primary_device".to_string()));
+    /// # assert!(device_result.expires().is_some());
+    /// # assert_eq!(device_result.metadata().get("host"), Some(&"worker-1".to_string()));
     /// # Ok(())
     /// # }
+    /// # #[cfg(all(feature = "mock", not(feature = "disable-password-escrow")))]
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), ironoxide::IronOxideErr> {
+    /// #     let mock = ironoxide::mock::MockServer::start("foobar").await?;
+    /// #     std::env::set_var("IRONCORE_ENV", mock.url());
+    /// #     run(&mock.jwt()).await
+    /// # }
+    /// # #[cfg(not(all(feature = "mock", not(feature = "disable-password-escrow"))))]
+    /// # fn main() {}
     /// ```
+    #[cfg(not(feature = "disable-password-escrow"))]
     async fn generate_new_device(
         jwt: &Jwt,
         password: &str,
         device_create_options: &DeviceCreateOpts,
         timeout: Option<std::time::Duration>,
-    ) -> Result<DeviceAddResult>;
+    ) -> Result<DeviceAddResult>
+    where
+        Self: Sized;
+
+    /// Creates a user whose master private key is protected by `wrapper` rather than escrowed with a password.
+    ///
+    /// Intended for deployments that can't derive the escrow key from a password, such as those where
+    /// the user's master key is held by a KMS. The private key is passed to
+    /// [MasterKeyWrapper::wrap](trait.MasterKeyWrapper.html#tymethod.wrap) and the wrapped bytes are stored
+    /// by the IronCore service. Devices for the user must be added with
+    /// [generate_new_device_with_master_key](trait.UserOps.html#tymethod.generate_new_device_with_master_key);
+    /// password-based operations such as `generate_new_device` and `user_rotate_private_key` will fail.
+    ///
+    /// Available even when the `disable-password-escrow` feature is enabled.
+    ///
+    /// # Arguments
+    /// - `jwt`              - Valid IronCore or Auth0 JWT
+    /// - `key_pair`         - Master key pair to use for the user, or `None` to generate one
+    /// - `wrapper`          - Protects the user's master private key
+    /// - `user_create_opts` - User creation parameters
+    /// - `timeout`          - Timeout for this operation or `None` for no timeout
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run(wrapper: &dyn ironoxide::user::MasterKeyWrapper) -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let jwt_str = "";
+    /// let jwt = Jwt::new(jwt_str)?;
+    /// let opts = UserCreateOpts::default();
+    /// let user_result =
+    ///     IronOxide::user_create_with_master_key(&jwt, None, wrapper, &opts, None).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn user_create_with_master_key(
+        jwt: &Jwt,
+        key_pair: Option<&KeyPair>,
+        wrapper: &dyn MasterKeyWrapper,
+        user_create_opts: &UserCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserCreateResult>
+    where
+        Self: Sized;
+
+    /// Generates a new device for a user created with
+    /// [user_create_with_master_key](trait.UserOps.html#tymethod.user_create_with_master_key).
+    ///
+    /// The user's wrapped master private key is fetched and passed to
+    /// [MasterKeyWrapper::unwrap](trait.MasterKeyWrapper.html#tymethod.unwrap) to authorize the device.
+    ///
+    /// # Arguments
+    /// - `jwt`                   - Valid IronCore or Auth0 JWT
+    /// - `wrapper`               - Recovers the user's master private key
+    /// - `device_create_options` - Device creation parameters
+    /// - `timeout`               - Timeout for this operation or `None` for no timeout
+    async fn generate_new_device_with_master_key(
+        jwt: &Jwt,
+        wrapper: &dyn MasterKeyWrapper,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult>
+    where
+        Self: Sized;
+
+    /// Generates a new device for the user specified in the JWT, using their master key recovered from a
+    /// [RecoveryEscrow](struct.RecoveryEscrow.html).
+    ///
+    /// Lets an organization holding the recovery private key restore access for a user who has forgotten
+    /// their password. The recovery private key is only used locally; it is never sent to IronCore.
+    ///
+    /// # Arguments
+    /// - `jwt`                   - Valid IronCore or Auth0 JWT for the user the escrow belongs to
+    /// - `escrow`                - Most recent recovery escrow of the user's private key
+    /// - `recovery_private_key`  - Private key corresponding to the recovery public key
+    /// - `device_create_options` - Device creation parameters
+    /// - `timeout`               - Timeout for this operation or `None` for no timeout
+    async fn generate_new_device_with_recovery(
+        jwt: &Jwt,
+        escrow: &RecoveryEscrow,
+        recovery_private_key: &PrivateKey,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult>
+    where
+        Self: Sized;
 
     /// Verifies the existence of a user using a JWT to identify their user record.
     ///
@@ -161,7 +553,78 @@ pub trait UserOps {
     async fn user_verify(
         jwt: &Jwt,
         timeout: Option<std::time::Duration>,
-    ) -> Result<Option<UserResult>>;
+    ) -> Result<Option<UserResult>>
+    where
+        Self: Sized;
+
+    /// Deactivates a user, deleting all of their devices and preventing documents and groups from being
+    /// shared with them in the future.
+    ///
+    /// Intended for offboarding workflows run by a backend that can mint JWTs for the segment's users.
+    /// The JWT identifies the user to deactivate, and must be for `user_id`. A deactivated user can no
+    /// longer be found by [user_verify](trait.UserOps.html#tymethod.user_verify) or
+    /// [user_get_public_key](trait.UserOps.html#tymethod.user_get_public_key).
+    ///
+    /// # Arguments
+    /// - `jwt`     - Valid IronCore or Auth0 JWT for the user to deactivate
+    /// - `user_id` - ID of the user to deactivate
+    /// - `timeout` - Timeout for this operation or `None` for no timeout
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let jwt_str = "";
+    /// # use std::convert::TryFrom;
+    /// let jwt = Jwt::new(jwt_str)?;
+    /// let user_id = UserId::try_from("colt")?;
+    /// let deactivated = IronOxide::user_deactivate(&jwt, &user_id, None).await?;
+    /// let deleted_devices: Vec<DeviceId> = deactivated.deleted_devices().to_vec();
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails with a `ValidationError` if the JWT is not for `user_id`.
+    async fn user_deactivate(
+        jwt: &Jwt,
+        user_id: &UserId,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserDeactivateResult>
+    where
+        Self: Sized;
+
+    /// Lists the users in a segment whose private keys are marked for rotation.
+    ///
+    /// Intended for backends that drive key rotation campaigns, e.g. by prompting each returned user to call
+    /// [user_rotate_private_key](trait.UserOps.html#tymethod.user_rotate_private_key) on their next login.
+    /// The segment is the one the JWT was issued for.
+    ///
+    /// # Arguments
+    /// - `jwt`     - Valid IronCore or Auth0 JWT for a user in the segment
+    /// - `timeout` - Timeout for this operation or `None` for no timeout
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let jwt_str = "";
+    /// let jwt = Jwt::new(jwt_str)?;
+    /// let needing_rotation = IronOxide::user_list_needing_rotation(&jwt, None).await?;
+    /// let user_ids: Vec<&UserId> = needing_rotation
+    ///     .result()
+    ///     .iter()
+    ///     .map(UserResult::account_id)
+    ///     .collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn user_list_needing_rotation(
+        jwt: &Jwt,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserListResult>
+    where
+        Self: Sized;
 
     /// Lists all of the devices for the current user.
     ///
@@ -177,6 +640,29 @@ pub trait UserOps {
     /// ```
     async fn user_list_devices(&self) -> Result<UserDeviceListResult>;
 
+    /// Lists a filtered, sorted page of the devices for the current user.
+    ///
+    /// # Arguments
+    /// - `opts` - Filters, order, and page of the devices to list. See [DeviceListOpts](struct.DeviceListOpts.html).
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// let opts = DeviceListOpts::default()
+    ///     .with_sort(DeviceListSort::Created, true)
+    ///     .with_page(0, Some(20));
+    /// let newest = sdk.user_list_devices_with_opts(&opts).await?;
+    /// let has_more = newest.total_count() > newest.result().len();
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn user_list_devices_with_opts(
+        &self,
+        opts: &DeviceListOpts,
+    ) -> Result<UserDeviceListResult>;
+
     /// Gets users' public keys given their IDs.
     ///
     /// Allows discovery of which user IDs have keys in the IronCore system to help determine if they can be added to groups
@@ -213,6 +699,9 @@ pub trait UserOps {
     /// In this situation, a user's cryptographic identity can be generated by a third party, like a server process, and then
     /// the user can take control of their keys by rotating their private key.
     ///
+    /// If the update is not confirmed by the webservice, the new private key is kept so the same
+    /// rotation can be retried with [IronOxide::resume_rotation](../struct.IronOxide.html#method.resume_rotation).
+    ///
     /// # Arguments
     /// `password` - Password to unlock the current user's private key
     ///
@@ -226,8 +715,75 @@ pub trait UserOps {
     /// # Ok(())
     /// # }
     /// ```
+    #[cfg(not(feature = "disable-password-escrow"))]
     async fn user_rotate_private_key(&self, password: &str) -> Result<UserUpdatePrivateKeyResult>;
 
+    /// Checks whether `password` unlocks the current user's escrowed private key, without changing anything.
+    ///
+    /// Lets a UI confirm a password before starting a rotation or adding a device. Returns `false` if the
+    /// password is wrong. Fails if the password is empty, if the user's private key isn't escrowed
+    /// with a password, or if the key can't be derived from the password, for example because it was
+    /// escrowed with a key derivation function this build doesn't support.
+    ///
+    /// # Arguments
+    /// `password` - Password to check
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// let password = "foobar";
+    /// if sdk.user_verify_password(password).await? {
+    ///     sdk.user_rotate_private_key(password).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_verify_password(&self, password: &str) -> Result<bool>;
+
+    /// Rotates the current user's private key like
+    /// [user_rotate_private_key](trait.UserOps.html#tymethod.user_rotate_private_key), and also encrypts
+    /// the new private key to an organization's recovery public key.
+    ///
+    /// The [RecoveryEscrow](struct.RecoveryEscrow.html) is returned in the result and is not sent to IronCore.
+    /// Escrows made before the rotation hold the old private key and should be replaced with this one.
+    ///
+    /// # Arguments
+    /// - `password`            - Password to unlock the current user's private key
+    /// - `recovery_public_key` - Public key to escrow the rotated private key to
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_rotate_private_key_with_recovery(
+        &self,
+        password: &str,
+        recovery_public_key: &PublicKey,
+    ) -> Result<UserUpdatePrivateKeyResult>;
+
+    /// Replaces the keys of the current device while keeping its `DeviceId`.
+    ///
+    /// New device and signing keys are generated along with a transform key from the user's private key,
+    /// so long-lived devices can rotate their keys without being deleted and re-added. The device's
+    /// previous keys stop working once the rotation succeeds, so the SDK must be re-initialized with the
+    /// returned keys before further use.
+    ///
+    /// # Arguments
+    /// `password` - Password to unlock the current user's private key
+    ///
+    /// # Examples
+    /// ```
+    /// # async fn run() -> Result<(), ironoxide::IronOxideErr> {
+    /// # use ironoxide::prelude::*;
+    /// # let sdk: IronOxide = unimplemented!();
+    /// let password = "foobar";
+    /// let rotated = sdk.device_rotate_keys(password).await?;
+    /// let sdk = ironoxide::initialize(&rotated.into(), &IronOxideConfig::default()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn device_rotate_keys(&self, password: &str) -> Result<DeviceAddResult>;
+
     /// Deletes a device.
     ///
     /// If deleting the currently signed-in device, the SDK will need to be
@@ -254,6 +810,7 @@ pub trait UserOps {
 
 #[async_trait]
 impl UserOps for IronOxide {
+    #[cfg(not(feature = "disable-password-escrow"))]
     async fn user_create(
         jwt: &Jwt,
         password: &str,
@@ -266,15 +823,52 @@ impl UserOps for IronOxide {
                 &recrypt,
                 jwt,
                 password.try_into()?,
+                user_create_opts.password_kdf,
                 user_create_opts.needs_rotation,
-                *OUR_REQUEST,
+                user_create_opts.recovery_public_key.as_ref(),
+                user_create_opts.password_derivation_limiter.as_ref(),
+                OUR_REQUEST.clone(),
             ),
             timeout,
             SdkOperation::UserCreate,
+            None,
         )
         .await?
     }
 
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_create_with_device(
+        jwt: &Jwt,
+        password: &str,
+        user_create_opts: &UserCreateOpts,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(UserCreateResult, DeviceContext)> {
+        let recrypt = Recrypt::new();
+        let signing_ts = std::time::SystemTime::now().into();
+        let device_opts = device_create_options.clone().standardize(signing_ts)?;
+        let (user, device) = add_optional_timeout(
+            user_api::user_create_with_device(
+                &recrypt,
+                jwt,
+                password.try_into()?,
+                user_create_opts.password_kdf,
+                user_create_opts.needs_rotation,
+                user_create_opts.recovery_public_key.as_ref(),
+                device_opts,
+                &signing_ts,
+                user_create_opts.password_derivation_limiter.as_ref(),
+                &OUR_REQUEST,
+            ),
+            timeout,
+            SdkOperation::UserCreateWithDevice,
+            None,
+        )
+        .await??;
+        Ok((user, device.into()))
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
     async fn generate_new_device(
         jwt: &Jwt,
         password: &str,
@@ -282,20 +876,101 @@ impl UserOps for IronOxide {
         timeout: Option<std::time::Duration>,
     ) -> Result<DeviceAddResult> {
         let recrypt = Recrypt::new();
-
-        let device_create_options = device_create_options.clone();
+        let signing_ts = std::time::SystemTime::now().into();
+        let device_opts = device_create_options.clone().standardize(signing_ts)?;
 
         add_optional_timeout(
             user_api::generate_device_key(
                 &recrypt,
                 jwt,
                 password.try_into()?,
-                device_create_options.device_name,
-                &std::time::SystemTime::now().into(),
+                device_opts,
+                &signing_ts,
+                device_create_options.password_derivation_limiter.as_ref(),
                 &OUR_REQUEST,
             ),
             timeout,
             SdkOperation::GenerateNewDevice,
+            None,
+        )
+        .await?
+    }
+
+    async fn user_create_with_master_key(
+        jwt: &Jwt,
+        key_pair: Option<&KeyPair>,
+        wrapper: &dyn MasterKeyWrapper,
+        user_create_opts: &UserCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserCreateResult> {
+        let recrypt = Recrypt::new();
+        add_optional_timeout(
+            user_api::user_create_with_master_key(
+                &recrypt,
+                jwt,
+                key_pair.cloned(),
+                wrapper,
+                user_create_opts.needs_rotation,
+                user_create_opts.recovery_public_key.as_ref(),
+                OUR_REQUEST.clone(),
+            ),
+            timeout,
+            SdkOperation::UserCreateWithMasterKey,
+            None,
+        )
+        .await?
+    }
+
+    async fn generate_new_device_with_master_key(
+        jwt: &Jwt,
+        wrapper: &dyn MasterKeyWrapper,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult> {
+        let recrypt = Recrypt::new();
+        let signing_ts = std::time::SystemTime::now().into();
+        let device_opts = device_create_options.clone().standardize(signing_ts)?;
+
+        add_optional_timeout(
+            user_api::generate_device_key_with_master_key(
+                &recrypt,
+                jwt,
+                wrapper,
+                device_opts,
+                &signing_ts,
+                &OUR_REQUEST,
+            ),
+            timeout,
+            SdkOperation::GenerateNewDeviceWithMasterKey,
+            None,
+        )
+        .await?
+    }
+
+    async fn generate_new_device_with_recovery(
+        jwt: &Jwt,
+        escrow: &RecoveryEscrow,
+        recovery_private_key: &PrivateKey,
+        device_create_options: &DeviceCreateOpts,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<DeviceAddResult> {
+        let recrypt = Recrypt::new();
+        let signing_ts = std::time::SystemTime::now().into();
+        let device_opts = device_create_options.clone().standardize(signing_ts)?;
+
+        add_optional_timeout(
+            user_api::generate_device_key_with_recovery(
+                &recrypt,
+                jwt,
+                escrow,
+                recovery_private_key,
+                device_opts,
+                &signing_ts,
+                &OUR_REQUEST,
+            ),
+            timeout,
+            SdkOperation::GenerateNewDeviceWithRecovery,
+            None,
         )
         .await?
     }
@@ -305,9 +980,37 @@ impl UserOps for IronOxide {
         timeout: Option<std::time::Duration>,
     ) -> Result<Option<UserResult>> {
         add_optional_timeout(
-            user_api::user_verify(jwt, *OUR_REQUEST),
+            user_api::user_verify(jwt, OUR_REQUEST.clone()),
             timeout,
             SdkOperation::UserVerify,
+            None,
+        )
+        .await?
+    }
+
+    async fn user_deactivate(
+        jwt: &Jwt,
+        user_id: &UserId,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserDeactivateResult> {
+        add_optional_timeout(
+            user_api::user_deactivate(jwt, user_id, &OUR_REQUEST),
+            timeout,
+            SdkOperation::UserDeactivate,
+            None,
+        )
+        .await?
+    }
+
+    async fn user_list_needing_rotation(
+        jwt: &Jwt,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<UserListResult> {
+        add_optional_timeout(
+            user_api::user_list_needing_rotation(jwt, &OUR_REQUEST),
+            timeout,
+            SdkOperation::UserListNeedingRotation,
+            None,
         )
         .await?
     }
@@ -317,37 +1020,98 @@ impl UserOps for IronOxide {
             user_api::device_list(self.device.auth()),
             self.config.sdk_operation_timeout,
             SdkOperation::UserListDevices,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
 
+    async fn user_list_devices_with_opts(
+        &self,
+        opts: &DeviceListOpts,
+    ) -> Result<UserDeviceListResult> {
+        let devices = self.user_list_devices().await?;
+        Ok(opts.apply(devices.result()))
+    }
+
     async fn user_get_public_key(&self, users: &[UserId]) -> Result<HashMap<UserId, PublicKey>> {
         add_optional_timeout(
             user_api::user_key_list(self.device.auth(), &users.to_vec()),
             self.config.sdk_operation_timeout,
             SdkOperation::UserGetPublicKey,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
 
+    #[cfg(not(feature = "disable-password-escrow"))]
     async fn user_rotate_private_key(&self, password: &str) -> Result<UserUpdatePrivateKeyResult> {
+        self.check_writable(SdkOperation::UserRotatePrivateKey)?;
         add_optional_timeout(
-            user_api::user_rotate_private_key(
-                &self.recrypt,
+            self.rotate_user_private_key(password.try_into()?, None),
+            self.config.sdk_operation_timeout,
+            SdkOperation::UserRotatePrivateKey,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_verify_password(&self, password: &str) -> Result<bool> {
+        add_optional_timeout(
+            user_api::user_verify_password(
                 password.try_into()?,
-                self.device().auth(),
+                self.password_derivation(),
+                self.device.auth(),
             ),
             self.config.sdk_operation_timeout,
+            SdkOperation::UserVerifyPassword,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn user_rotate_private_key_with_recovery(
+        &self,
+        password: &str,
+        recovery_public_key: &PublicKey,
+    ) -> Result<UserUpdatePrivateKeyResult> {
+        self.check_writable(SdkOperation::UserRotatePrivateKey)?;
+        add_optional_timeout(
+            self.rotate_user_private_key(password.try_into()?, Some(recovery_public_key)),
+            self.config.sdk_operation_timeout,
             SdkOperation::UserRotatePrivateKey,
+            self.config.metrics_sink.as_ref(),
+        )
+        .await?
+    }
+
+    #[cfg(not(feature = "disable-password-escrow"))]
+    async fn device_rotate_keys(&self, password: &str) -> Result<DeviceAddResult> {
+        self.check_writable(SdkOperation::DeviceRotateKeys)?;
+        add_optional_timeout(
+            user_api::device_rotate_keys(
+                &self.recrypt,
+                self.device.auth(),
+                password.try_into()?,
+                self.device.protection(),
+                &std::time::SystemTime::now().into(),
+                self.password_derivation(),
+            ),
+            self.config.sdk_operation_timeout,
+            SdkOperation::DeviceRotateKeys,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
 
     async fn user_delete_device(&self, device_id: Option<&DeviceId>) -> Result<DeviceId> {
+        self.check_writable(SdkOperation::UserDeleteDevice)?;
         add_optional_timeout(
             user_api::device_delete(self.device.auth(), device_id),
             self.config.sdk_operation_timeout,
             SdkOperation::UserDeleteDevice,
+            self.config.metrics_sink.as_ref(),
         )
         .await?
     }
@@ -378,4 +1142,28 @@ mod tests {
             })
         )
     }
+    #[cfg(not(feature = "disable-password-escrow"))]
+    #[test]
+    fn device_create_opts_rejects_past_expiration() -> Result<()> {
+        let now = Utc::now();
+        let metadata: BTreeMap<String, String> = vec![("app".to_string(), "cli".to_string())]
+            .into_iter()
+            .collect();
+        let opts = DeviceCreateOpts::default()
+            .with_protection(DeviceKeyProtection::NonExportable)
+            .with_expires(Some(now + chrono::Duration::hours(1)))
+            .with_metadata(metadata.clone())
+            .standardize(now)?;
+        assert_eq!(opts.protection, DeviceKeyProtection::NonExportable);
+        assert_eq!(opts.metadata, metadata);
+
+        let expired = DeviceCreateOpts::default()
+            .with_expires(Some(now))
+            .standardize(now);
+        assert_that!(
+            &expired.unwrap_err(),
+            is_variant!(IronOxideErr::ValidationError)
+        );
+        Ok(())
+    }
 }
@@ -6,7 +6,10 @@ use galvanic_assert::{
     matchers::{collection::contains_in_any_order, eq},
     *,
 };
-use ironoxide::prelude::*;
+use ironoxide::{
+    config::{DocumentKeyCachingConfig, HttpTimeoutConfig, RequestBatchingConfig},
+    prelude::*,
+};
 use itertools::EitherOrBoth;
 use std::convert::{TryFrom, TryInto};
 
@@ -25,6 +28,28 @@ async fn doc_list() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn doc_encrypt_caches_user_keys() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let (other_user, _) = init_sdk_get_user().await;
+    let opts =
+        DocumentEncryptOpts::with_explicit_grants(None, None, false, vec![(&other_user).into()]);
+    sdk.document_encrypt(b"first", &opts).await?;
+    let second = sdk.document_encrypt(b"second", &opts).await?;
+    assert_eq!(
+        second.grants(),
+        &[UserOrGroup::User {
+            id: other_user.clone()
+        }]
+    );
+
+    assert!(sdk.invalidate_user_key(&other_user));
+    assert!(!sdk.invalidate_user_key(&other_user));
+    sdk.document_encrypt(b"third", &opts).await?;
+    assert_eq!(sdk.clear_user_key_cache(), 1);
+    Ok(())
+}
+
 #[tokio::test]
 async fn doc_roundtrip_empty_data() -> Result<(), IronOxideErr> {
     let sdk = initialize_sdk().await?;
@@ -586,6 +611,71 @@ async fn doc_encrypt_decrypt_roundtrip() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn doc_verify_detects_corruption() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let encrypted_doc = sdk
+        .document_encrypt(&[43u8; 64], &Default::default())
+        .await?;
+    let mut encrypted_data = encrypted_doc.encrypted_data().to_vec();
+    assert!(sdk.document_verify(&encrypted_data).await?);
+
+    let last = encrypted_data.len() - 1;
+    encrypted_data[last] ^= 1;
+    assert!(!sdk.document_verify(&encrypted_data).await?);
+    assert!(!sdk.document_verify(&encrypted_data[..10]).await?);
+    Ok(())
+}
+
+#[cfg(not(feature = "fips"))]
+#[tokio::test]
+async fn doc_encrypt_decrypt_with_xchacha20_poly1305() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let encrypt_opts =
+        DocumentEncryptOpts::default().with_cipher_suite(CipherSuite::XChaCha20Poly1305);
+    let doc = [7u8; 64];
+
+    let encrypted_doc = sdk.document_encrypt(&doc, &encrypt_opts).await?;
+    let decrypted = sdk
+        .document_decrypt(&encrypted_doc.encrypted_data())
+        .await?;
+    assert_eq!(doc.to_vec(), decrypted.decrypted_data());
+
+    let unmanaged = sdk.document_encrypt_unmanaged(&doc, &encrypt_opts).await?;
+    let decrypted = sdk
+        .document_decrypt_unmanaged(&unmanaged.encrypted_data(), &unmanaged.encrypted_deks())
+        .await?;
+    assert_eq!(&doc[..], decrypted.decrypted_data());
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_encrypt_decrypt_with_header_aad() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let doc = [8u8; 64];
+    for &cipher_suite in &[
+        CipherSuite::Aes256Gcm,
+        CipherSuite::XChaCha20Poly1305,
+        CipherSuite::Aes256GcmSiv,
+    ] {
+        let encrypt_opts = DocumentEncryptOpts::default()
+            .with_cipher_suite(cipher_suite)
+            .with_header_aad(true);
+        let encrypted_doc = sdk.document_encrypt(&doc, &encrypt_opts).await?;
+        let decrypted = sdk
+            .document_decrypt(&encrypted_doc.encrypted_data())
+            .await?;
+        assert_eq!(doc.to_vec(), decrypted.decrypted_data());
+
+        let unmanaged = sdk.document_encrypt_unmanaged(&doc, &encrypt_opts).await?;
+        let decrypted = sdk
+            .document_decrypt_unmanaged(&unmanaged.encrypted_data(), &unmanaged.encrypted_deks())
+            .await?;
+        assert_eq!(&doc[..], decrypted.decrypted_data());
+    }
+    Ok(())
+}
+
 #[tokio::test]
 async fn doc_decrypt_unmanaged_no_access() -> Result<(), IronOxideErr> {
     use std::borrow::Borrow;
@@ -656,6 +746,188 @@ async fn doc_encrypt_decrypt_unmanaged_roundtrip() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn doc_access_via_direct_and_group() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let group = sdk.group_create(&Default::default()).await?;
+    let encrypt_opts =
+        DocumentEncryptOpts::with_explicit_grants(None, None, true, vec![group.id().into()]);
+    let encrypted_doc = sdk.document_encrypt(&[0u8; 42], &encrypt_opts).await?;
+
+    let access = sdk.document_access_via(encrypted_doc.id()).await?;
+    assert_eq!(access.id(), encrypted_doc.id());
+    assert!(access.has_direct_access());
+    let group_ids: Vec<&GroupId> = access.groups().iter().map(|g| g.id()).collect();
+    assert_eq!(group_ids, vec![group.id()]);
+
+    let group_only_opts =
+        DocumentEncryptOpts::with_explicit_grants(None, None, false, vec![group.id().into()]);
+    let group_only_doc = sdk.document_encrypt(&[0u8; 42], &group_only_opts).await?;
+    let access = sdk.document_access_via(group_only_doc.id()).await?;
+    assert!(!access.has_direct_access());
+    assert_eq!(access.association_type(), &AssociationType::FromGroup);
+    assert_eq!(access.groups().len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_access_restore_undoes_grant() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let second_user = create_second_user().await;
+    let encrypted_doc = sdk
+        .document_encrypt(&[0u8; 42], &Default::default())
+        .await?;
+    let snapshot = sdk.document_access_snapshot(encrypted_doc.id()).await?;
+    assert_eq!(snapshot.users().len(), 1);
+
+    sdk.document_grant_access(encrypted_doc.id(), &vec![second_user.account_id().into()])
+        .await?;
+    let restore_result = sdk.document_access_restore(&snapshot).await?;
+    assert!(restore_result.granted().succeeded().is_empty());
+    assert_eq!(
+        restore_result.revoked().succeeded(),
+        &[UserOrGroup::User {
+            id: second_user.account_id().clone()
+        }]
+    );
+
+    let doc_meta = sdk.document_get_metadata(encrypted_doc.id()).await?;
+    assert_eq!(doc_meta.visible_to_users().len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_decrypt_session_reuses_keys() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let doc = [43u8; 64];
+    let first = sdk.document_encrypt(&doc, &Default::default()).await?;
+    let second = sdk.document_encrypt(&doc, &Default::default()).await?;
+
+    let session = sdk
+        .open_decrypt_session(std::time::Duration::from_secs(60))
+        .await;
+    session.preload(&[first.id().clone()]).await?;
+    assert_eq!(session.len(), 1);
+    let decrypted = session.document_decrypt(first.encrypted_data()).await?;
+    assert_eq!(decrypted.decrypted_data(), &doc[..]);
+    let decrypted = session.document_decrypt(second.encrypted_data()).await?;
+    assert_eq!(decrypted.id(), second.id());
+    assert_eq!(session.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_decrypt_session_preloads_many_keys() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let doc = [44u8; 64];
+    let encrypted = futures::future::try_join_all(
+        (0..8).map(|_| sdk.document_encrypt(&doc, &Default::default())),
+    )
+    .await?;
+    let ids: Vec<DocumentId> = encrypted.iter().map(|e| e.id().clone()).collect();
+
+    let session = sdk
+        .open_decrypt_session(std::time::Duration::from_secs(60))
+        .await;
+    session.preload(&ids).await?;
+    assert_eq!(session.len(), ids.len());
+    for e in &encrypted {
+        let decrypted = session.document_decrypt(e.encrypted_data()).await?;
+        assert_eq!(decrypted.decrypted_data(), &doc[..]);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_encrypt_read_only_mode() -> Result<(), IronOxideErr> {
+    let sdk = init_sdk_with_config(&IronOxideConfig {
+        read_only: true,
+        ..Default::default()
+    })
+    .await?;
+    let doc = [0u8; 42];
+
+    let result = sdk.document_encrypt(&doc, &Default::default()).await;
+    assert_that!(
+        &result.unwrap_err(),
+        has_structure!(IronOxideErr::ReadOnlyMode[eq(SdkOperation::DocumentEncrypt)])
+    );
+
+    // reads and unmanaged encryption still work
+    sdk.document_list().await?;
+    sdk.document_encrypt_unmanaged(&doc, &Default::default())
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_encrypt_with_rng_source() -> Result<(), IronOxideErr> {
+    use rand::SeedableRng;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let rngs_made = Arc::new(AtomicUsize::new(0));
+    let counter = rngs_made.clone();
+    let sdk = init_sdk_with_config(&IronOxideConfig {
+        rng_source: Some(RngSource::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            rand_chacha::ChaChaRng::from_entropy()
+        })),
+        ..Default::default()
+    })
+    .await?;
+    let made_on_init = rngs_made.load(Ordering::SeqCst);
+    assert!(made_on_init > 0);
+
+    let doc = [7u8; 42];
+    let encrypted = sdk.document_encrypt(&doc, &Default::default()).await?;
+    let decrypted = sdk.document_decrypt(encrypted.encrypted_data()).await?;
+    assert_eq!(decrypted.decrypted_data(), &doc[..]);
+
+    sdk.fork_for_core();
+    assert_eq!(rngs_made.load(Ordering::SeqCst), 2 * made_on_init);
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_decrypt_strict_mode_rejects_group_access() -> Result<(), IronOxideErr> {
+    let sdk = init_sdk_with_config(&IronOxideConfig {
+        decryption: DecryptionConfig::strict(),
+        ..Default::default()
+    })
+    .await?;
+    let group = sdk.group_create(&Default::default()).await?;
+    let encrypt_opts =
+        DocumentEncryptOpts::with_explicit_grants(None, None, false, vec![group.id().into()]);
+    let doc = [0u8; 42];
+
+    let encrypted_doc = sdk.document_encrypt(&doc, &encrypt_opts).await?;
+    let decrypt_err = sdk
+        .document_decrypt(encrypted_doc.encrypted_data())
+        .await
+        .unwrap_err();
+    assert_that!(
+        &decrypt_err,
+        is_variant!(IronOxideErr::DecryptKeyPathNotAllowed)
+    );
+
+    let unmanaged_doc = sdk.document_encrypt_unmanaged(&doc, &encrypt_opts).await?;
+    let decrypt_err = sdk
+        .document_decrypt_unmanaged(
+            unmanaged_doc.encrypted_data(),
+            unmanaged_doc.encrypted_deks(),
+        )
+        .await
+        .unwrap_err();
+    assert_that!(
+        &decrypt_err,
+        is_variant!(IronOxideErr::DecryptKeyPathNotAllowed)
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn doc_encrypt_update_and_decrypt() -> Result<(), IronOxideErr> {
     let sdk = initialize_sdk().await?;
@@ -670,10 +942,10 @@ async fn doc_encrypt_update_and_decrypt() -> Result<(), IronOxideErr> {
     let updated_encrypted_doc = sdk.document_update_bytes(doc_id, &doc2).await?;
 
     let decrypted = sdk
-        .document_decrypt(&updated_encrypted_doc.encrypted_data())
+        .document_decrypt(&updated_encrypted_doc.into_encrypted_data())
         .await?;
 
-    assert_eq!(doc2.to_vec(), decrypted.decrypted_data());
+    assert_eq!(doc2.to_vec(), decrypted.into_decrypted_data());
     Ok(())
 }
 
@@ -730,6 +1002,69 @@ async fn doc_grant_access() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn doc_grant_access_in_chunks() -> Result<(), IronOxideErr> {
+    let sdk = init_sdk_with_config(&IronOxideConfig {
+        request_batching: RequestBatchingConfig {
+            chunk_size: 1,
+            max_concurrency: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await?;
+    let doc_result = sdk
+        .document_encrypt(&[0u8; 64], &Default::default())
+        .await?;
+    let user = create_second_user().await;
+    let group_result = sdk.group_create(&Default::default()).await?;
+
+    let grants = sdk
+        .document_grant_access(
+            doc_result.id(),
+            &vec![
+                UserOrGroup::User {
+                    id: user.account_id().clone(),
+                },
+                UserOrGroup::Group {
+                    id: group_result.id().clone(),
+                },
+                UserOrGroup::User {
+                    id: create_id_all_classes("bad-user-id").try_into()?,
+                },
+            ],
+        )
+        .await?;
+    assert_eq!(2, grants.succeeded().len());
+    assert_eq!(1, grants.failed().len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_decrypt_with_cached_key() -> Result<(), IronOxideErr> {
+    let sdk = init_sdk_with_config(&IronOxideConfig {
+        document_key_caching: DocumentKeyCachingConfig {
+            max_entries: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await?;
+    let doc = [1u8; 64];
+    let first = sdk.document_encrypt(&doc, &Default::default()).await?;
+    let second = sdk.document_encrypt(&doc, &Default::default()).await?;
+
+    sdk.document_decrypt(first.encrypted_data()).await?;
+    let decrypted = sdk.document_decrypt(first.encrypted_data()).await?;
+    assert_eq!(decrypted.decrypted_data(), &doc[..]);
+    // decrypting the second document evicts the first one's key
+    sdk.document_decrypt(second.encrypted_data()).await?;
+    assert!(!sdk.invalidate_document_key(first.id()));
+    assert!(sdk.invalidate_document_key(second.id()));
+    assert_eq!(sdk.clear_document_key_cache(), 0);
+    Ok(())
+}
+
 #[tokio::test]
 async fn doc_revoke_access() -> Result<(), IronOxideErr> {
     let sdk = initialize_sdk().await?;
@@ -794,6 +1129,56 @@ async fn doc_revoke_access() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn doc_verify_access_log() -> Result<(), IronOxideErr> {
+    use std::borrow::Borrow;
+
+    let sdk = initialize_sdk().await?;
+    let user2 = create_second_user().await;
+    let author: UserOrGroup = sdk.device().account_id().into();
+    let user2: UserOrGroup = user2.account_id().borrow().into();
+
+    let doc = sdk
+        .document_encrypt(&[42u8; 10], &Default::default())
+        .await?;
+    let doc_id = doc.id().clone();
+    sdk.document_grant_access(&doc_id, &vec![user2.clone()])
+        .await?;
+
+    let mut log = vec![AccessLogEntry::new(
+        doc_id.clone(),
+        author.clone(),
+        AccessLogOp::Grant,
+    )];
+    // the grant to user2 was never recorded
+    assert_eq!(
+        sdk.document_verify_access_log(&log).await?,
+        vec![AccessDivergence::Unrecorded {
+            document_id: doc_id.clone(),
+            user_or_group: user2.clone(),
+        }]
+    );
+
+    log.push(AccessLogEntry::new(
+        doc_id.clone(),
+        user2.clone(),
+        AccessLogOp::Grant,
+    ));
+    assert!(sdk.document_verify_access_log(&log).await?.is_empty());
+
+    // revoke without recording it
+    sdk.document_revoke_access(&doc_id, &vec![user2.clone()])
+        .await?;
+    assert_eq!(
+        sdk.document_verify_access_log(&log).await?,
+        vec![AccessDivergence::Missing {
+            document_id: doc_id,
+            user_or_group: user2,
+        }]
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn sdk_init_with_timeout() -> Result<(), IronOxideErr> {
     let result = init_sdk_with_config(&IronOxideConfig {
@@ -815,29 +1200,113 @@ async fn sdk_init_with_timeout() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
-//#[tokio::test]
-//async fn doc_encrypt_concurrent() -> Result<(), IronOxideErr> {
-//    let sdk = Arc::new(initialize_sdk()?);
-//    let doc = [43u8; 64];
-//    let _encrypted_doc = sdk.document_encrypt(&doc, &Default::default()).await?;
-//
-//    let mut threads = vec![];
-//    for _i in 0..10 {
-//        let sdk_ref = sdk.clone();
-//        threads.push(std::thread::spawn(move || {
-//            let _result = sdk_ref.document_encrypt(&doc, &Default::default()).unwrap();
-//        }));
-//    }
-//
-//    let mut joined_count = 0;
-//    for t in threads {
-//        t.join().expect("couldn't join");
-//        joined_count += 1;
-//    }
-//
-//    assert_eq!(joined_count, 10);
-//    Ok(())
-//}
+#[tokio::test]
+async fn sdk_init_with_request_timeout() -> Result<(), IronOxideErr> {
+    let result = init_sdk_with_config(&IronOxideConfig {
+        http_timeouts: HttpTimeoutConfig {
+            connect_timeout: None,
+            request_timeout: Some(std::time::Duration::from_nanos(1)),
+            rate_limit_wait: None,
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let err_result = result.unwrap_err();
+    assert_that!(&err_result, is_variant!(IronOxideErr::InitializeError));
+    Ok(())
+}
+
+#[tokio::test]
+async fn sdk_init_with_invalid_extra_headers() -> Result<(), IronOxideErr> {
+    let result = init_sdk_with_config(&IronOxideConfig {
+        extra_headers: vec![("X-Tenant".to_string(), "line\nbreak".to_string())],
+        ..Default::default()
+    })
+    .await;
+
+    assert_that!(
+        &result.unwrap_err(),
+        is_variant!(IronOxideErr::ValidationError)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn sdk_init_with_failover_sends_requests_to_working_primary() -> Result<(), IronOxideErr> {
+    // nothing listens on the secondary, so these only succeed if the requests go to the primary
+    let sdk = init_sdk_with_config(&IronOxideConfig {
+        failover: Some(FailoverConfig {
+            secondary_base_url: "http://127.0.0.1:1/api/1/".to_string(),
+            failure_threshold: 1,
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+    .await?;
+    sdk.document_list().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_list_with_app_identifier() -> Result<(), IronOxideErr> {
+    let sdk = init_sdk_with_config(&IronOxideConfig {
+        app_identifier: Some("ironoxide-integration-tests/1.0".to_string()),
+        ..Default::default()
+    })
+    .await?;
+    sdk.document_list().await?;
+
+    let result = init_sdk_with_config(&IronOxideConfig {
+        app_identifier: Some("line\nbreak".to_string()),
+        ..Default::default()
+    })
+    .await;
+    assert_that!(
+        &result.unwrap_err(),
+        is_variant!(IronOxideErr::ValidationError)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn doc_list_with_request_headers() -> Result<(), IronOxideErr> {
+    let sdk = init_sdk_with_config(&IronOxideConfig {
+        extra_headers: vec![("X-Tenant".to_string(), "integration-tests".to_string())],
+        ..Default::default()
+    })
+    .await?;
+    let headers = [("X-Request-Id".to_string(), "doc-list-1".to_string())];
+    let list = ironoxide::with_request_headers(&headers, sdk.document_list()).await??;
+    assert_eq!(list.result().len(), 0);
+
+    let bad = [("bad header".to_string(), "x".to_string())];
+    let err = ironoxide::with_request_headers(&bad, sdk.document_list())
+        .await
+        .unwrap_err();
+    assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn doc_encrypt_concurrent() -> Result<(), IronOxideErr> {
+    let sdk = std::sync::Arc::new(initialize_sdk().await?);
+    let doc = [43u8; 64];
+
+    let tasks: Vec<_> = (0..10)
+        .map(|_| {
+            let sdk = sdk.clone();
+            tokio::spawn(async move { sdk.document_encrypt(&doc, &Default::default()).await })
+        })
+        .collect();
+    let mut ids = std::collections::HashSet::new();
+    for task in tasks {
+        let encrypted = task.await.expect("couldn't join")?;
+        ids.insert(encrypted.id().clone());
+    }
+    assert_eq!(ids.len(), 10);
+    Ok(())
+}
 
 trait WithGrantsAndErrs {
     fn grants(&self) -> Vec<UserOrGroup>;
@@ -44,6 +44,30 @@ async fn group_create_with_defaults() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn group_create_validate_only() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let group_id: GroupId = create_id_all_classes("").try_into()?;
+    let opts = GroupCreateOpts::default()
+        .with_id(Some(group_id.clone()))
+        .with_validate_only(true);
+
+    let result = sdk.group_create(&opts).await;
+    assert_that!(
+        &result.unwrap_err(),
+        is_variant!(IronOxideErr::ValidateOnly)
+    );
+    assert!(sdk.group_get_metadata(&group_id).await.is_err());
+
+    let missing_member = opts.with_members(vec![create_id_all_classes("").try_into()?]);
+    let result = sdk.group_create(&missing_member).await;
+    assert_that!(
+        &result.unwrap_err(),
+        is_variant!(IronOxideErr::UserDoesNotExist)
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn group_init_and_rotation_check() -> Result<(), IronOxideErr> {
     use ironoxide::InitAndRotationCheck;
@@ -334,6 +358,60 @@ async fn group_update_name() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn group_description_and_attributes() -> Result<(), IronOxideErr> {
+    use std::collections::BTreeMap;
+
+    let sdk = initialize_sdk().await?;
+    let mut attribute_map = BTreeMap::new();
+    attribute_map.insert("team".to_string(), "payments".to_string());
+    let attributes: GroupAttributes = attribute_map.try_into()?;
+    let description: GroupDescription = "first description".try_into()?;
+
+    let group_result = sdk
+        .group_create(
+            &GroupCreateOpts::default()
+                .with_description(Some(description.clone()))
+                .with_attributes(attributes.clone()),
+        )
+        .await?;
+
+    let group_get = sdk.group_get_metadata(group_result.id()).await?;
+    assert_eq!(group_get.description(), Some(&description));
+    assert_eq!(group_get.attributes(), &attributes);
+    Ok(())
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+#[tokio::test]
+async fn group_update_description_and_attributes() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let group_result = sdk.group_create(&Default::default()).await?;
+
+    let updated_group = sdk
+        .group_update_description(group_result.id(), Some(&"new description".try_into()?))
+        .await?;
+    assert_eq!(
+        updated_group.description(),
+        Some(
+            &"new description"
+                .try_into()
+                .expect("this description is valid")
+        )
+    );
+
+    let cleared_description = sdk
+        .group_update_description(group_result.id(), None)
+        .await?;
+    assert!(cleared_description.description().is_none());
+
+    let cleared_attributes = sdk
+        .group_update_attributes(group_result.id(), &Default::default())
+        .await?;
+    assert!(cleared_attributes.attributes().attributes().is_empty());
+    Ok(())
+}
+
 #[tokio::test]
 async fn group_add_member() -> Result<(), IronOxideErr> {
     let (account_id, sdk) = init_sdk_get_user().await;
@@ -457,6 +535,57 @@ async fn group_add_admin_on_create() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[cfg(feature = "unstable-group-endpoints")]
+#[tokio::test]
+async fn group_transfer_ownership() -> Result<(), IronOxideErr> {
+    let (account_id, sdk) = init_sdk_get_user().await;
+    let (second_account_id, second_sdk) = init_sdk_get_user().await;
+
+    let group = sdk
+        .group_create(&GroupCreateOpts::new(
+            None,
+            None,
+            true,
+            true,
+            None,
+            vec![second_account_id.clone()],
+            vec![],
+            false,
+        ))
+        .await?;
+    assert_eq!(group.owner(), &account_id);
+
+    sdk.group_transfer_ownership(group.id(), &second_account_id)
+        .await?;
+    let group_get = sdk.group_get_metadata(group.id()).await?;
+    assert_eq!(group_get.owner(), Some(&second_account_id));
+
+    // the previous owner can now be removed as an admin by the new owner
+    let remove_result = second_sdk
+        .group_remove_admins(group.id(), &[account_id])
+        .await?;
+    assert_eq!(remove_result.failed().len(), 0);
+    Ok(())
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+#[tokio::test]
+async fn group_transfer_ownership_to_non_admin() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let (second_account_id, _) = init_sdk_get_user().await;
+
+    let group = sdk.group_create(&Default::default()).await?;
+    let transfer_result = sdk
+        .group_transfer_ownership(group.id(), &second_account_id)
+        .await;
+
+    assert_that!(
+        &transfer_result.unwrap_err(),
+        is_variant!(IronOxideErr::ValidationError)
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn group_add_admin_invalid_ids() -> Result<(), IronOxideErr> {
     let sdk = initialize_sdk().await?;
@@ -520,6 +649,40 @@ async fn group_list() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn group_needs_rotation_surfaced_in_get_and_list() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+
+    let rotation_group = sdk
+        .group_create(&GroupCreateOpts::new(
+            None,
+            None,
+            true,
+            true,
+            None,
+            vec![],
+            vec![],
+            true,
+        ))
+        .await?;
+    let default_group = sdk.group_create(&Default::default()).await?;
+
+    let get_result = sdk.group_get_metadata(rotation_group.id()).await?;
+    assert_eq!(get_result.needs_rotation(), Some(true));
+
+    let list_result = sdk.group_list().await?;
+    let listed_rotation = |id: &GroupId| {
+        list_result
+            .result()
+            .iter()
+            .find(|group| group.id() == id)
+            .and_then(|group| group.needs_rotation())
+    };
+    assert_eq!(listed_rotation(rotation_group.id()), Some(true));
+    assert_eq!(listed_rotation(default_group.id()), Some(false));
+    Ok(())
+}
+
 #[tokio::test]
 async fn group_remove_member() -> Result<(), IronOxideErr> {
     let sdk = initialize_sdk().await?;
@@ -582,6 +745,14 @@ async fn group_add_admin() -> Result<(), IronOxideErr> {
     assert_eq!(add_member_res.succeeded()[0], second_account_id);
     assert_eq!(add_member_res.failed().len(), 1);
     assert_eq!(add_member_res.failed()[0].user(), &account_id);
+    assert_eq!(
+        add_member_res.failed()[0].kind(),
+        GroupAccessEditErrKind::AlreadyAdmin
+    );
+    assert_eq!(
+        add_member_res.admins(),
+        &vec![account_id.clone(), second_account_id.clone()]
+    );
     Ok(())
 }
 
@@ -606,6 +777,49 @@ async fn group_remove_admin() -> Result<(), IronOxideErr> {
     assert_eq!(remove_member_res.succeeded().len(), 1);
     assert_eq!(remove_member_res.succeeded()[0], second_account_id);
     assert_eq!(remove_member_res.failed().len(), 0);
+    assert!(!remove_member_res.caller_role_changed());
+    assert!(!remove_member_res.admins().contains(&second_account_id));
+    //Removing them again fails without reaching the service.
+    let remove_again_res = sdk
+        .group_remove_admins(&group_id, &[second_account_id.clone()])
+        .await?;
+    assert_eq!(
+        remove_again_res.failed()[0].kind(),
+        GroupAccessEditErrKind::NotAMember
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn group_remove_last_admin_would_orphan() -> Result<(), IronOxideErr> {
+    let (account_id, sdk) = init_sdk_get_user().await;
+    let group_result = sdk.group_create(&GroupCreateOpts::default()).await?;
+
+    let remove_result = sdk
+        .group_remove_admins(group_result.id(), &[account_id])
+        .await;
+    match remove_result {
+        Err(IronOxideErr::WouldOrphanGroup(id)) => assert_eq!(&id, group_result.id()),
+        other => panic!("expected WouldOrphanGroup, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "unstable-group-endpoints")]
+#[tokio::test]
+async fn group_list_documents_shared_to_group() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let group = sdk.group_create(&Default::default()).await?;
+    let encrypt_opts =
+        DocumentEncryptOpts::with_explicit_grants(None, None, true, vec![group.id().into()]);
+    let shared = sdk.document_encrypt(&[0u8; 42], &encrypt_opts).await?;
+    sdk.document_encrypt(&[1u8; 42], &Default::default())
+        .await?;
+
+    let documents = sdk.group_list_documents(group.id()).await?;
+    assert_eq!(documents.group_id(), group.id());
+    let document_ids: Vec<&DocumentId> = documents.result().iter().map(|doc| doc.id()).collect();
+    assert_eq!(document_ids, vec![shared.id()]);
     Ok(())
 }
 
@@ -3,7 +3,10 @@ mod common;
 use common::{create_id_all_classes, gen_jwt, initialize_sdk};
 use galvanic_assert::{matchers::*, *};
 use ironoxide::prelude::*;
-use std::{convert::TryInto, default::Default};
+use std::{
+    convert::{TryFrom, TryInto},
+    default::Default,
+};
 use uuid::Uuid;
 #[tokio::test]
 async fn user_verify_non_existing_user() -> Result<(), IronOxideErr> {
@@ -48,6 +51,48 @@ async fn user_verify_after_create_with_needs_rotation() -> Result<(), IronOxideE
     assert!(verify_resp.needs_rotation());
     Ok(())
 }
+#[tokio::test]
+async fn user_get_public_key_for_many_users() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let current_user = sdk.device().account_id().clone();
+    let mut users = (0..250)
+        .map(|_| create_id_all_classes("").try_into())
+        .collect::<Result<Vec<UserId>, _>>()?;
+    users.push(current_user.clone());
+
+    let public_keys = sdk.user_get_public_key(&users).await?;
+    assert_eq!(public_keys.len(), 1);
+    assert!(public_keys.contains_key(&current_user));
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_deactivate_deletes_devices() -> Result<(), IronOxideErr> {
+    let (account_id, sdk) = common::init_sdk_get_user().await;
+    let devices = sdk.user_list_devices().await?;
+    let jwt = gen_jwt(Some(account_id.id())).0;
+
+    let other_user: UserId = Uuid::new_v4().to_string().try_into()?;
+    let mismatched = IronOxide::user_deactivate(&jwt, &other_user, None).await;
+    assert_that!(
+        &mismatched.unwrap_err(),
+        is_variant!(IronOxideErr::ValidationError)
+    );
+
+    let deactivated = IronOxide::user_deactivate(&jwt, &account_id, None).await?;
+    assert_eq!(deactivated.account_id(), &account_id);
+    assert_eq!(
+        deactivated.deleted_devices(),
+        &devices
+            .result()
+            .iter()
+            .map(|d| d.id().clone())
+            .collect::<Vec<_>>()
+    );
+    assert!(IronOxide::user_verify(&jwt, None).await?.is_none());
+    Ok(())
+}
+
 #[tokio::test]
 async fn user_create_good_with_devices() -> Result<(), IronOxideErr> {
     let account_id: UserId = Uuid::new_v4().to_string().try_into()?;
@@ -61,11 +106,16 @@ async fn user_create_good_with_devices() -> Result<(), IronOxideErr> {
     let device: DeviceContext = IronOxide::generate_new_device(
         &gen_jwt(Some(account_id.id())).0,
         "foo",
-        &DeviceCreateOpts::new(Some("myDevice".try_into()?)),
+        &DeviceCreateOpts::new(
+            Some("myDevice".try_into()?),
+            DeviceKeyProtection::NonExportable,
+        ),
         None,
     )
     .await?
     .into();
+    assert_eq!(device.protection(), DeviceKeyProtection::NonExportable);
+    assert!(serde_json::to_string(&device).is_err());
     let sdk = ironoxide::initialize(&device, &Default::default()).await?;
     let device_list = sdk.user_list_devices().await?;
 
@@ -77,6 +127,279 @@ async fn user_create_good_with_devices() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn user_list_devices_with_opts_filters_and_pages() -> Result<(), IronOxideErr> {
+    let account_id: UserId = create_id_all_classes("").try_into()?;
+    let jwt = gen_jwt(Some(account_id.id())).0;
+    IronOxide::user_create(&jwt, common::USER_PASSWORD, &Default::default(), None).await?;
+    let mut devices = vec![];
+    for name in &["laptop-b", "laptop-a", "phone"] {
+        let opts = DeviceCreateOpts::default().with_device_name(Some((*name).try_into()?));
+        devices.push(
+            IronOxide::generate_new_device(
+                &gen_jwt(Some(account_id.id())).0,
+                common::USER_PASSWORD,
+                &opts,
+                None,
+            )
+            .await?,
+        );
+    }
+    let sdk = ironoxide::initialize(&devices[2].clone().into(), &Default::default()).await?;
+
+    let laptops = sdk
+        .user_list_devices_with_opts(
+            &DeviceListOpts::default()
+                .with_name_contains(Some("laptop".to_string()))
+                .with_sort(DeviceListSort::Name, false),
+        )
+        .await?;
+    let names: Vec<&str> = laptops
+        .result()
+        .iter()
+        .map(|d| d.name().unwrap().name().as_str())
+        .collect();
+    assert_eq!(names, vec!["laptop-a", "laptop-b"]);
+
+    let newest = sdk
+        .user_list_devices_with_opts(
+            &DeviceListOpts::default()
+                .with_sort(DeviceListSort::Created, true)
+                .with_page(1, Some(1)),
+        )
+        .await?;
+    assert_eq!(newest.total_count(), 3);
+    assert_eq!(newest.result().len(), 1);
+    assert_eq!(newest.result()[0].id(), devices[1].device_id());
+
+    let current = sdk
+        .user_list_devices_with_opts(&DeviceListOpts::default().with_current_device_only(true))
+        .await?;
+    assert_eq!(current.result().len(), 1);
+    assert_eq!(current.result()[0].id(), devices[2].device_id());
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_create_with_device_initializes() -> Result<(), IronOxideErr> {
+    let account_id: UserId = create_id_all_classes("").try_into()?;
+    let device_name: DeviceName = "signup".try_into()?;
+    let (user_result, device) = IronOxide::user_create_with_device(
+        &gen_jwt(Some(account_id.id())).0,
+        common::USER_PASSWORD,
+        &UserCreateOpts::new(true),
+        &DeviceCreateOpts::new(Some(device_name.clone()), Default::default()),
+        None,
+    )
+    .await?;
+    assert!(user_result.needs_rotation());
+    assert_eq!(device.account_id(), &account_id);
+
+    let sdk = ironoxide::initialize(&device, &Default::default()).await?;
+    let devices = sdk.user_list_devices().await?;
+    assert_eq!(devices.result().len(), 1);
+    assert_eq!(devices.result()[0].name(), Some(&device_name));
+    Ok(())
+}
+
+#[cfg(all(feature = "argon2id", not(feature = "fips")))]
+#[tokio::test]
+async fn user_create_with_argon2id_unlocks_after_rotation() -> Result<(), IronOxideErr> {
+    let account_id: UserId = create_id_all_classes("").try_into()?;
+    let jwt = gen_jwt(Some(account_id.id())).0;
+    let opts = UserCreateOpts::new(true).with_password_kdf(PasswordKdf::argon2id());
+    IronOxide::user_create(&jwt, common::USER_PASSWORD, &opts, None).await?;
+    let device: DeviceContext =
+        IronOxide::generate_new_device(&jwt, common::USER_PASSWORD, &Default::default(), None)
+            .await?
+            .into();
+
+    let sdk = ironoxide::initialize(&device, &Default::default()).await?;
+    sdk.user_rotate_private_key(common::USER_PASSWORD).await?;
+    assert!(sdk.user_verify_password(common::USER_PASSWORD).await?);
+    assert!(!sdk.user_verify_password("not the password").await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn generate_device_with_expiration_and_metadata() -> Result<(), IronOxideErr> {
+    let account_id: UserId = create_id_all_classes("").try_into()?;
+    let jwt = gen_jwt(Some(account_id.id())).0;
+    IronOxide::user_create(&jwt, common::USER_PASSWORD, &Default::default(), None).await?;
+    let expires = chrono::Utc::now() + chrono::Duration::days(1);
+    let metadata: std::collections::BTreeMap<String, String> =
+        vec![("host".to_string(), "build-agent-7".to_string())]
+            .into_iter()
+            .collect();
+    let opts = DeviceCreateOpts::default()
+        .with_expires(Some(expires))
+        .with_metadata(metadata.clone());
+    let device: DeviceContext =
+        IronOxide::generate_new_device(&jwt, common::USER_PASSWORD, &opts, None)
+            .await?
+            .into();
+
+    let sdk = ironoxide::initialize(&device, &Default::default()).await?;
+    let devices = sdk.user_list_devices().await?;
+    assert_eq!(devices.result()[0].metadata(), &metadata);
+    assert_eq!(
+        devices.result()[0].expires().map(|e| e.timestamp()),
+        Some(expires.timestamp())
+    );
+
+    let expired = DeviceCreateOpts::default().with_expires(Some(chrono::Utc::now()));
+    let err = IronOxide::generate_new_device(&jwt, common::USER_PASSWORD, &expired, None)
+        .await
+        .unwrap_err();
+    assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+    Ok(())
+}
+
+struct XorWrapper(u8);
+#[async_trait::async_trait]
+impl MasterKeyWrapper for XorWrapper {
+    async fn wrap(&self, private_key: &PrivateKey) -> Result<Vec<u8>, IronOxideErr> {
+        Ok(private_key.as_bytes().iter().map(|b| b ^ self.0).collect())
+    }
+    async fn unwrap(&self, wrapped: &[u8]) -> Result<PrivateKey, IronOxideErr> {
+        let bytes: Vec<u8> = wrapped.iter().map(|b| b ^ self.0).collect();
+        PrivateKey::try_from(&bytes[..])
+    }
+}
+
+#[tokio::test]
+async fn user_create_and_add_device_with_master_key() -> Result<(), IronOxideErr> {
+    let account_id: UserId = create_id_all_classes("").try_into()?;
+    let jwt = gen_jwt(Some(account_id.id())).0;
+    let wrapper = XorWrapper(0x5a);
+    let created =
+        IronOxide::user_create_with_master_key(&jwt, None, &wrapper, &Default::default(), None)
+            .await?;
+    let device: DeviceContext =
+        IronOxide::generate_new_device_with_master_key(&jwt, &wrapper, &Default::default(), None)
+            .await?
+            .into();
+
+    let sdk = ironoxide::initialize(&device, &Default::default()).await?;
+    let public_keys = sdk.user_get_public_key(&[account_id.clone()]).await?;
+    assert_eq!(
+        public_keys.get(&account_id),
+        Some(created.user_public_key())
+    );
+    let doc = [1u8, 2, 3];
+    let encrypted = sdk.document_encrypt(&doc, &Default::default()).await?;
+    let decrypted = sdk.document_decrypt(encrypted.encrypted_data()).await?;
+    assert_eq!(&doc, decrypted.decrypted_data());
+
+    // the key isn't escrowed with a password, so password-based operations can't unlock it
+    let password_device =
+        IronOxide::generate_new_device(&jwt, common::USER_PASSWORD, &Default::default(), None)
+            .await;
+    assert!(password_device.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_recovery_escrow_adds_device_after_rotation() -> Result<(), IronOxideErr> {
+    use recrypt::prelude::*;
+    let (recovery_priv, recovery_pub) = Recrypt::new().generate_key_pair()?;
+    let (recovery_priv, recovery_pub): (PrivateKey, PublicKey) =
+        (recovery_priv.into(), recovery_pub.into());
+    let account_id: UserId = create_id_all_classes("").try_into()?;
+    let jwt = gen_jwt(Some(account_id.id())).0;
+    let created = IronOxide::user_create(
+        &jwt,
+        common::USER_PASSWORD,
+        &UserCreateOpts::default().with_recovery_public_key(Some(recovery_pub.clone())),
+        None,
+    )
+    .await?;
+    let escrow = created.recovery_escrow().expect("escrow was requested");
+    assert_eq!(&escrow.user_public_key()?, created.user_public_key());
+
+    let device: DeviceContext =
+        IronOxide::generate_new_device(&jwt, common::USER_PASSWORD, &Default::default(), None)
+            .await?
+            .into();
+    let sdk = ironoxide::initialize(&device, &Default::default()).await?;
+    let doc = [4u8, 5, 6];
+    let encrypted = sdk.document_encrypt(&doc, &Default::default()).await?;
+    let rotated = sdk
+        .user_rotate_private_key_with_recovery(common::USER_PASSWORD, &recovery_pub)
+        .await?;
+    let escrow = rotated.recovery_escrow().expect("escrow was requested");
+
+    let recovered_device: DeviceContext = IronOxide::generate_new_device_with_recovery(
+        &jwt,
+        escrow,
+        &recovery_priv,
+        &Default::default(),
+        None,
+    )
+    .await?
+    .into();
+    let recovered_sdk = ironoxide::initialize(&recovered_device, &Default::default()).await?;
+    let decrypted = recovered_sdk
+        .document_decrypt(encrypted.encrypted_data())
+        .await?;
+    assert_eq!(&doc, decrypted.decrypted_data());
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_list_needing_rotation_includes_new_user() -> Result<(), IronOxideErr> {
+    let account_id: UserId = create_id_all_classes("").try_into()?;
+    let jwt = gen_jwt(Some(account_id.id())).0;
+    IronOxide::user_create(
+        &jwt,
+        common::USER_PASSWORD,
+        &UserCreateOpts::new(true),
+        None,
+    )
+    .await?;
+
+    let listed = IronOxide::user_list_needing_rotation(&jwt, None).await?;
+    assert!(listed.result().iter().all(UserResult::needs_rotation));
+    assert!(listed
+        .result()
+        .iter()
+        .any(|user| user.account_id() == &account_id));
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_verify_password_checks_escrowed_key() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    assert!(sdk.user_verify_password(common::USER_PASSWORD).await?);
+    assert!(!sdk.user_verify_password("not the password").await?);
+    let err = sdk.user_verify_password("").await.unwrap_err();
+    assert_that!(&err, is_variant!(IronOxideErr::ValidationError));
+    Ok(())
+}
+
+#[tokio::test]
+async fn user_list_devices_reports_activity() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let devices = sdk.user_list_devices().await?;
+    let current = devices
+        .result()
+        .iter()
+        .find(|device| device.is_current_device())
+        .expect("current device is listed");
+
+    assert!(current.last_used().is_some());
+    assert_eq!(
+        current.signing_key_fingerprint(),
+        Some(
+            sdk.device()
+                .signing_private_key()
+                .public_key_fingerprint()
+                .as_str()
+        )
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn user_private_key_rotation() -> Result<(), IronOxideErr> {
     let io = initialize_sdk().await?;
@@ -93,6 +416,32 @@ async fn user_private_key_rotation() -> Result<(), IronOxideErr> {
     Ok(())
 }
 
+#[tokio::test]
+async fn device_rotate_keys_keeps_device_id() -> Result<(), IronOxideErr> {
+    let sdk = initialize_sdk().await?;
+    let encrypted = sdk
+        .document_encrypt(&[1u8, 2, 3], &Default::default())
+        .await?;
+    let device_id = sdk.user_list_devices().await?.result()[0].id().clone();
+
+    let rotated = sdk.device_rotate_keys(common::USER_PASSWORD).await?;
+    assert_eq!(rotated.device_id(), &device_id);
+    assert_ne!(
+        rotated.signing_private_key(),
+        sdk.device().signing_private_key()
+    );
+
+    let rotated_sdk = ironoxide::initialize(&rotated.into(), &Default::default()).await?;
+    let devices = rotated_sdk.user_list_devices().await?;
+    assert_eq!(devices.result().len(), 1);
+    assert_eq!(devices.result()[0].id(), &device_id);
+    let decrypted = rotated_sdk
+        .document_decrypt(encrypted.encrypted_data())
+        .await?;
+    assert_eq!(decrypted.decrypted_data(), &[1u8, 2, 3]);
+    Ok(())
+}
+
 #[tokio::test]
 async fn sdk_init_with_private_key_rotation() -> Result<(), IronOxideErr> {
     use ironoxide::InitAndRotationCheck;